@@ -1,31 +1,40 @@
-use idle_monitor::IdleNotification;
 use idle_monitor::run_idle_monitor;
+use idle_monitor::IdleEvent;
 use std::sync::mpsc::channel;
 use std::time::Duration;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting idle monitor example, avoid using your mouse and keyboard for 5 seconds...");
 
-    // Create a channel for receiving idle notifications
-    let (tx, rx) = channel::<IdleNotification>();
+    // Create a channel for receiving idle events
+    let (tx, rx) = channel::<IdleEvent>();
 
     // Spawn the idle monitor in a background thread (5 second timeout)
-    run_idle_monitor(
+    let (_thread, handle) = run_idle_monitor(
         move |i| {
             tx.send(i).unwrap();
         },
         Duration::from_secs(5),
     );
 
-    // Listen for idle notifications
+    // Listen for idle events until the monitor finishes
     for notification in rx {
+        println!("Idle state: {}", handle.is_idle());
         match notification {
-            IdleNotification::Idle => {
-                println!("💤 User is idle!");
+            IdleEvent::Idle(at) => {
+                println!("💤 User is idle! ({:?})", at);
             }
-            IdleNotification::Resumed => {
-                println!("✅ User activity resumed!");
+            IdleEvent::Resumed { at, idle_duration } => {
+                println!(
+                    "✅ User activity resumed after {:?} idle! ({:?})",
+                    idle_duration, at
+                );
             }
+            IdleEvent::Error(e) => {
+                println!("❌ Idle monitor failed: {}", e);
+                break;
+            }
+            IdleEvent::Stopped => break,
         }
     }
 
@@ -1,50 +1,198 @@
+use smithay_client_toolkit::reexports::calloop::EventLoop;
+use smithay_client_toolkit::reexports::calloop_wayland_source::WaylandSource;
 use smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat;
 use smithay_client_toolkit::reexports::client::Connection;
 use smithay_client_toolkit::reexports::client::Dispatch;
 use smithay_client_toolkit::reexports::client::QueueHandle;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
 use std::thread::JoinHandle;
 use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
 use wayland_client::protocol::wl_registry;
+use wayland_client::Proxy;
 use wayland_protocols::ext::idle_notify::v1::client::ext_idle_notification_v1::ExtIdleNotificationV1;
 use wayland_protocols::ext::idle_notify::v1::client::ext_idle_notifier_v1::ExtIdleNotifierV1;
 
-pub enum IdleNotification {
-    Idle,
-    Resumed,
+/// Events of the idle monitor, delivered to its callback
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdleEvent {
+    /// User idled, at the time the compositor's notification was received
+    Idle(SystemTime),
+    /// User resumed, at the time the compositor's notification was received.
+    ///
+    /// `idle_duration` is the time since the idle notification, zero if the
+    /// user was not idle. The user was inactive for the timeout before that.
+    Resumed {
+        at: SystemTime,
+        idle_duration: Duration,
+    },
+    /// Monitor failed and finished, e.g. the compositor does not support idle
+    /// notifications or the connection was lost
+    Error(String),
+    /// Monitor finished after `stop` was set, or `IdleMonitorHandle::stop`
+    /// was called
+    Stopped,
+}
+
+/// How often the stop flag is checked
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Thread of the idle monitor, finishes when stopped or failed
+pub type IdleMonitorThread = JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>>;
+
+/// Latest idle state of a running idle monitor, can be cloned and queried at
+/// any time
+#[derive(Debug, Clone, Default)]
+pub struct IdleMonitorHandle {
+    idle: Arc<AtomicBool>,
+    // Timeout to switch to on the next dispatch of the monitor
+    requested_timeout: Arc<Mutex<Option<Duration>>>,
+    // Set by `stop`, checked like the stop flag of the monitor
+    stopped: Arc<AtomicBool>,
+}
+
+impl IdleMonitorHandle {
+    /// True if the user idled and has not resumed since
+    pub fn is_idle(&self) -> bool {
+        self.idle.load(Ordering::SeqCst)
+    }
+
+    /// Changes the idle timeout of the running monitor.
+    ///
+    /// The notification is recreated with the new timeout on the next
+    /// dispatch, and `IdleEvent::Resumed` is sent as the idle time starts
+    /// over.
+    ///
+    /// A zero timeout is not a valid notification timeout, use `stop` to
+    /// disable idle monitoring.
+    pub fn set_timeout(&self, timeout: Duration) {
+        *self.requested_timeout.lock().unwrap() = Some(timeout);
+    }
+
+    /// Stops the running monitor like its stop flag, e.g. when idle
+    /// monitoring is disabled while the rest of the application runs
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    /// True once `stop` was called
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    fn take_requested_timeout(&self) -> Option<Duration> {
+        self.requested_timeout.lock().unwrap().take()
+    }
+
+    fn set_idle(&self, idle: bool) {
+        self.idle.store(idle, Ordering::SeqCst);
+    }
 }
 
 pub fn run_idle_monitor(
-    callback: impl Fn(IdleNotification) + Send + Sync + 'static,
+    callback: impl Fn(IdleEvent) + Send + Sync + 'static,
     timeout: Duration,
-) -> JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
-    std::thread::spawn(move || {
-        let conn = Connection::connect_to_env()?;
-        let mut event_queue = conn.new_event_queue();
-        let qh = event_queue.handle();
-
-        let _registry = conn.display().get_registry(&qh, ());
-
-        let mut state = IdleMonitorState {
-            idle_notifier: None,
-            seat: None,
-            idle_notification: None,
-            callback: Box::new(callback),
-            timeout,
-        };
+) -> (IdleMonitorThread, IdleMonitorHandle) {
+    run_idle_monitor_until_stopped(callback, timeout, Arc::new(AtomicBool::new(false)))
+}
+
+/// Runs the idle monitor until `stop` is set, the Wayland connection is closed
+/// when the thread finishes.
+pub fn run_idle_monitor_until_stopped(
+    callback: impl Fn(IdleEvent) + Send + Sync + 'static,
+    timeout: Duration,
+    stop: Arc<AtomicBool>,
+) -> (IdleMonitorThread, IdleMonitorHandle) {
+    run_idle_monitor_on_seat(callback, timeout, None, stop)
+}
 
-        // Main event loop
-        loop {
-            event_queue.blocking_dispatch(&mut state)?;
+/// Runs the idle monitor for the seat with the given name (e.g. "seat1"),
+/// or the first seat if `None`, until `stop` is set.
+///
+/// The callback gets `IdleEvent::Error` if the monitor fails, or
+/// `IdleEvent::Stopped` when it finishes, the thread returns the same result.
+/// The handle tells the latest idle state without waiting for the events.
+///
+/// Activity on other seats does not count, e.g. a remote session on a second
+/// seat does not keep the physical seat from idling.
+pub fn run_idle_monitor_on_seat(
+    callback: impl Fn(IdleEvent) + Send + Sync + 'static,
+    timeout: Duration,
+    seat_name: Option<String>,
+    stop: Arc<AtomicBool>,
+) -> (IdleMonitorThread, IdleMonitorHandle) {
+    let callback: Arc<dyn Fn(IdleEvent) + Send + Sync> = Arc::new(callback);
+    let handle = IdleMonitorHandle::default();
+    let handle_ = handle.clone();
+    let thread = std::thread::spawn(move || {
+        let result = monitor_idle(callback.clone(), timeout, seat_name, stop, handle_);
+        match &result {
+            Ok(()) => callback(IdleEvent::Stopped),
+            Err(e) => callback(IdleEvent::Error(e.to_string())),
         }
-    })
+        result
+    });
+    (thread, handle)
+}
+
+fn monitor_idle(
+    callback: Arc<dyn Fn(IdleEvent) + Send + Sync>,
+    timeout: Duration,
+    seat_name: Option<String>,
+    stop: Arc<AtomicBool>,
+    handle: IdleMonitorHandle,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let conn = Connection::connect_to_env()?;
+    let event_queue = conn.new_event_queue();
+    let qh = event_queue.handle();
+
+    let _registry = conn.display().get_registry(&qh, ());
+
+    let mut state = IdleMonitorState {
+        idle_notifier: None,
+        seat_name,
+        seats: Vec::new(),
+        seat_warned: false,
+        idle_notification: None,
+        idled_at: None,
+        callback,
+        timeout,
+        handle,
+    };
+
+    let mut event_loop = EventLoop::<IdleMonitorState>::try_new()?;
+    WaylandSource::new(conn, event_queue)
+        .insert(event_loop.handle())
+        .map_err(|e| e.error)?;
+
+    // Main event loop
+    while !stop.load(Ordering::SeqCst) && !state.handle.is_stopped() {
+        event_loop.dispatch(Some(STOP_POLL_INTERVAL), &mut state)?;
+        if let Some(timeout) = state.handle.take_requested_timeout() {
+            state.change_timeout(timeout, &qh);
+        }
+    }
+    Ok(())
 }
 
 struct IdleMonitorState {
     idle_notifier: Option<ExtIdleNotifierV1>,
-    seat: Option<WlSeat>,
+    // Seat to monitor, first seat if `None`
+    seat_name: Option<String>,
+    // Bound seats with their names, once received
+    seats: Vec<(WlSeat, Option<String>)>,
+    // True once a missing `seat_name` was warned about
+    seat_warned: bool,
     idle_notification: Option<ExtIdleNotificationV1>,
-    callback: Box<dyn Fn(IdleNotification) + Send + Sync + 'static>,
+    // When the last idle notification was received, until resumed
+    idled_at: Option<Instant>,
+    callback: Arc<dyn Fn(IdleEvent) + Send + Sync>,
     timeout: Duration,
+    handle: IdleMonitorHandle,
 }
 
 impl Dispatch<wl_registry::WlRegistry, ()> for IdleMonitorState {
@@ -63,36 +211,166 @@ impl Dispatch<wl_registry::WlRegistry, ()> for IdleMonitorState {
         } = event
         {
             if interface == "wl_seat" {
+                let version = bind_version(&interface, version, WlSeat::interface().version);
                 let seat = registry.bind::<WlSeat, _, _>(name, version, qh, ());
-                state.seat = Some(seat);
+                state.seats.push((seat, None));
             } else if interface == "ext_idle_notifier_v1" {
+                let version =
+                    bind_version(&interface, version, ExtIdleNotifierV1::interface().version);
                 let notifier = registry.bind::<ExtIdleNotifierV1, _, _>(name, version, qh, ());
                 state.idle_notifier = Some(notifier);
             }
-            // If we have both notifier and seat available, create a notification object
-            if state.idle_notification.is_none() {
-                if let (Some(notifier), Some(seat)) =
-                    (state.idle_notifier.as_ref(), state.seat.as_ref())
-                {
-                    let timeout_ms = state.timeout.as_millis().min(u32::MAX as u128) as u32;
-                    let notification =
-                        notifier.get_input_idle_notification(timeout_ms, seat, qh, ());
-                    state.idle_notification = Some(notification);
+            state.create_notification(qh);
+        }
+    }
+}
+
+/// Version to bind a global at, the offered version clamped to the one the
+/// crate was built against
+fn bind_version(interface: &str, offered: u32, supported: u32) -> u32 {
+    if offered != supported {
+        log::info!(
+            "Compositor offers {} version {}, built against version {}",
+            interface,
+            offered,
+            supported
+        );
+    }
+    offered.min(supported)
+}
+
+/// Request the idle notification is created with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NotificationRequest {
+    /// `get_idle_notification`, idle inhibitors keep the user from idling
+    Idle,
+    /// `get_input_idle_notification`, only input counts, since version 2
+    InputIdle,
+}
+
+/// Request to create the notification with on the notifier bound at the
+/// version, version 1 has no input idle notifications
+fn notification_request(notifier_version: u32) -> NotificationRequest {
+    if notifier_version < 2 {
+        NotificationRequest::Idle
+    } else {
+        NotificationRequest::InputIdle
+    }
+}
+
+/// True if the seat is configured but none of the seats, once all are named,
+/// has its name
+fn is_unmatched_seat(seat_name: Option<&str>, names: &[Option<String>]) -> bool {
+    seat_name.is_some_and(|seat_name| {
+        names.iter().all(Option::is_some)
+            && !names.iter().any(|name| name.as_deref() == Some(seat_name))
+    })
+}
+
+impl IdleMonitorState {
+    /// Creates the notification object once the notifier and the seat are
+    /// available
+    fn create_notification(&mut self, qh: &QueueHandle<Self>) {
+        if self.idle_notification.is_some() {
+            return;
+        }
+        let seat = match &self.seat_name {
+            Some(seat_name) => self
+                .seats
+                .iter()
+                .find(|(_, name)| name.as_ref() == Some(seat_name)),
+            None => self.seats.first(),
+        };
+        if let (Some(notifier), Some((seat, _))) = (self.idle_notifier.as_ref(), seat) {
+            let timeout_ms = self.timeout.as_millis().min(u32::MAX as u128) as u32;
+            let notification = match notification_request(notifier.version()) {
+                NotificationRequest::Idle => {
+                    notifier.get_idle_notification(timeout_ms, seat, qh, ())
                 }
-            }
+                NotificationRequest::InputIdle => {
+                    notifier.get_input_idle_notification(timeout_ms, seat, qh, ())
+                }
+            };
+            self.idle_notification = Some(notification);
+        }
+    }
+
+    /// Warns once if the configured seat is not among the named seats, idle
+    /// is not monitored then
+    fn warn_unmatched_seat(&mut self) {
+        let names = self
+            .seats
+            .iter()
+            .map(|(_, name)| name.clone())
+            .collect::<Vec<_>>();
+        if !self.seat_warned && is_unmatched_seat(self.seat_name.as_deref(), &names) {
+            self.seat_warned = true;
+            log::warn!(
+                "Idle seat {:?} matches none of the seats {:?}, idle is not monitored",
+                self.seat_name,
+                names.into_iter().flatten().collect::<Vec<_>>()
+            );
+        }
+    }
+
+    /// Recreates the notification with the new timeout
+    fn change_timeout(&mut self, timeout: Duration, qh: &QueueHandle<Self>) {
+        if timeout == self.timeout {
+            return;
+        }
+        log::info!(
+            "Changing idle timeout from {:?} to {:?}",
+            self.timeout,
+            timeout
+        );
+        self.timeout = timeout;
+        if let Some(notification) = self.idle_notification.take() {
+            notification.destroy();
         }
+        self.create_notification(qh);
+
+        // New notification starts counting from now, the user is not idle
+        // until it says so
+        self.resumed();
+    }
+
+    fn idled(&mut self) {
+        self.idled_at = Some(Instant::now());
+        self.handle.set_idle(true);
+        (self.callback)(IdleEvent::Idle(SystemTime::now()));
+    }
+
+    fn resumed(&mut self) {
+        let idle_duration = self
+            .idled_at
+            .take()
+            .map(|idled_at| idled_at.elapsed())
+            .unwrap_or_default();
+        self.handle.set_idle(false);
+        (self.callback)(IdleEvent::Resumed {
+            at: SystemTime::now(),
+            idle_duration,
+        });
     }
 }
 
 impl Dispatch<WlSeat, ()> for IdleMonitorState {
     fn event(
-        _state: &mut Self,
-        _proxy: &WlSeat,
-        _event: <WlSeat as wayland_client::Proxy>::Event,
+        state: &mut Self,
+        proxy: &WlSeat,
+        event: <WlSeat as wayland_client::Proxy>::Event,
         _data: &(),
         _conn: &Connection,
-        _qh: &QueueHandle<Self>,
+        qh: &QueueHandle<Self>,
     ) {
+        use smithay_client_toolkit::reexports::client::protocol::wl_seat::Event;
+        if let Event::Name { name } = event {
+            if let Some((_, seat_name)) = state.seats.iter_mut().find(|(seat, _)| seat == proxy) {
+                *seat_name = Some(name);
+            }
+            state.create_notification(qh);
+            state.warn_unmatched_seat();
+        }
     }
 }
 
@@ -119,13 +397,78 @@ impl Dispatch<ExtIdleNotificationV1, ()> for IdleMonitorState {
     ) {
         use wayland_protocols::ext::idle_notify::v1::client::ext_idle_notification_v1::Event;
         match event {
-            Event::Idled => {
-                (state.callback)(IdleNotification::Idle);
-            }
-            Event::Resumed => {
-                (state.callback)(IdleNotification::Resumed);
-            }
+            Event::Idled => state.idled(),
+            Event::Resumed => state.resumed(),
             _ => {}
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_version_is_clamped_to_supported() {
+        assert_eq!(bind_version("ext_idle_notifier_v1", 3, 2), 2);
+        assert_eq!(bind_version("ext_idle_notifier_v1", 2, 2), 2);
+        assert_eq!(bind_version("ext_idle_notifier_v1", 1, 2), 1);
+    }
+
+    #[test]
+    fn version_1_notifier_gets_idle_notifications() {
+        assert_eq!(notification_request(1), NotificationRequest::Idle);
+        assert_eq!(notification_request(2), NotificationRequest::InputIdle);
+        assert_eq!(
+            notification_request(bind_version("ext_idle_notifier_v1", 1, 2)),
+            NotificationRequest::Idle
+        );
+    }
+
+    #[test]
+    fn unmatched_seat_is_detected_once_all_are_named() {
+        let named = [Some("seat0".to_string()), Some("seat1".to_string())];
+        assert!(!is_unmatched_seat(None, &named));
+        assert!(!is_unmatched_seat(Some("seat1"), &named));
+        assert!(is_unmatched_seat(Some("seat2"), &named));
+        // Name of the second seat has not arrived yet
+        assert!(!is_unmatched_seat(
+            Some("seat2"),
+            &[Some("seat0".to_string()), None]
+        ));
+    }
+
+    #[test]
+    fn handle_reflects_latest_idle_state() {
+        let handle = IdleMonitorHandle::default();
+        let queried = handle.clone();
+        assert!(!queried.is_idle());
+        handle.set_idle(true);
+        assert!(queried.is_idle());
+        handle.set_idle(false);
+        assert!(!queried.is_idle());
+    }
+
+    #[test]
+    fn requested_timeout_is_taken_once() {
+        let handle = IdleMonitorHandle::default();
+        let monitor = handle.clone();
+        assert_eq!(monitor.take_requested_timeout(), None);
+        handle.set_timeout(Duration::from_secs(60));
+        handle.set_timeout(Duration::from_secs(120));
+        assert_eq!(
+            monitor.take_requested_timeout(),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(monitor.take_requested_timeout(), None);
+    }
+
+    #[test]
+    fn stop_is_seen_by_the_monitor() {
+        let handle = IdleMonitorHandle::default();
+        let monitor = handle.clone();
+        assert!(!monitor.is_stopped());
+        handle.stop();
+        assert!(monitor.is_stopped());
+    }
+}
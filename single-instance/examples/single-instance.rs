@@ -4,11 +4,14 @@ use std::time::Duration;
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let bus_name = "org.example.MyApp";
 
-    match only_single_instance(bus_name, || {
+    match only_single_instance(bus_name, |args| {
         println!("\n⚡ Activation signal received from secondary instance!");
+        if !args.is_empty() {
+            println!("   Arguments: {:?}", args);
+        }
         println!("   (This is where you could bring your window to front, etc.)");
     }) {
-        Ok(_) => {
+        Ok(_guard) => {
             println!("✓ This is the primary instance");
             println!("  Press Ctrl+C to exit.\n");
             std::thread::sleep(Duration::from_secs(99999999));
@@ -0,0 +1,519 @@
+use crate::activation_args;
+use crate::name_hash;
+use crate::validate_prefix;
+use crate::Error;
+use crate::Handlers;
+use futures::executor::block_on;
+use futures::Stream;
+use futures::StreamExt;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use zbus::interface;
+use zbus::Connection;
+
+impl From<zbus::Error> for Error {
+    fn from(e: zbus::Error) -> Self {
+        Error::DBus(e.to_string())
+    }
+}
+
+impl From<zbus::fdo::Error> for Error {
+    fn from(e: zbus::fdo::Error) -> Self {
+        Error::DBus(e.to_string())
+    }
+}
+
+const OBJECT_PATH: &str = "/org/example/SingleInstance";
+const INTERFACE: &str = "org.example.SingleInstance";
+const UNKNOWN_METHOD: &str = "org.freedesktop.DBus.Error.UnknownMethod";
+
+/// Make unique D-Bus compatible bus name from arbitrary string, `prefix` is
+/// a valid D-Bus name, see `validate_prefix`
+fn sanitize_bus_name(prefix: &str, input: &str) -> String {
+    format!("{}.SingleInstance{:x}", prefix, name_hash(input))
+}
+
+/// Make the D-Bus name of a role shared by instances of any unique name
+fn role_bus_name(prefix: &str, role: &str) -> String {
+    format!("{}.SingleInstance.Role{:x}", prefix, name_hash(role))
+}
+
+/// Ownership change of a D-Bus name of this connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NameEvent {
+    Acquired(String),
+    Lost(String),
+}
+
+/// Whether the role is held, starting with `holds` and changing on the events
+/// of its bus name
+fn holder_changes(
+    bus_name: String,
+    holds: bool,
+    events: impl Stream<Item = NameEvent>,
+) -> impl Stream<Item = bool> {
+    let changes = events.filter_map(move |event| {
+        let change = match event {
+            NameEvent::Acquired(name) if name == bus_name => Some(true),
+            NameEvent::Lost(name) if name == bus_name => Some(false),
+            _ => None,
+        };
+        async move { change }
+    });
+    let mut previous = None;
+    futures::stream::once(async move { holds })
+        .chain(changes)
+        .filter(move |holds| {
+            let changed = previous != Some(*holds);
+            previous = Some(*holds);
+            async move { changed }
+        })
+}
+
+/// Claims a role shared by all instances in the session, whatever their
+/// unique names, e.g. the one writing to a shared resource, until `stop`
+/// resolves
+///
+/// Instances queue for the role and the next one takes over when the holder
+/// exits. `on_change` is called with true when this instance holds the role
+/// and with false while it waits. Instances sharing the role use the same
+/// prefix, see `only_single_instance_with_prefix`.
+pub fn claim_role(
+    prefix: &str,
+    role: &str,
+    on_change: impl Fn(bool) + Send + Sync + 'static,
+    stop: impl Future<Output = ()> + Send + 'static,
+) -> Result<JoinHandle<()>, Error> {
+    validate_prefix(prefix)?;
+    let bus_name = role_bus_name(prefix, role);
+    let (dbus, acquired, lost, holds) = block_on(async {
+        let connection = Connection::session().await?;
+        let dbus = zbus::fdo::DBusProxy::new(&connection).await?;
+        // Subscribed before requesting, so that a change right after the
+        // request is not missed
+        let acquired = dbus.receive_name_acquired().await?;
+        let lost = dbus.receive_name_lost().await?;
+        // Without `DoNotQueue` the request waits in the queue of the name
+        let reply = dbus
+            .request_name(
+                zbus::names::WellKnownName::from_string_unchecked(bus_name.clone()),
+                zbus::fdo::RequestNameFlags::AllowReplacement.into(),
+            )
+            .await?;
+        let holds = matches!(
+            reply,
+            zbus::fdo::RequestNameReply::PrimaryOwner | zbus::fdo::RequestNameReply::AlreadyOwner
+        );
+        Ok::<_, Error>((dbus, acquired, lost, holds))
+    })?;
+
+    let handle = std::thread::spawn(move || {
+        block_on(async {
+            let events = futures::stream::select(
+                acquired.filter_map(|signal| async move {
+                    let name = signal.args().ok()?.name().to_string();
+                    Some(NameEvent::Acquired(name))
+                }),
+                lost.filter_map(|signal| async move {
+                    let name = signal.args().ok()?.name().to_string();
+                    Some(NameEvent::Lost(name))
+                }),
+            );
+            let changes = holder_changes(bus_name.clone(), holds, events).for_each(|holds| {
+                on_change(holds);
+                futures::future::ready(())
+            });
+            futures::future::select(std::pin::pin!(changes), std::pin::pin!(stop)).await;
+
+            let _ = dbus
+                .release_name(zbus::names::WellKnownName::from_string_unchecked(
+                    bus_name.clone(),
+                ))
+                .await;
+        })
+    });
+
+    Ok(handle)
+}
+
+/// Runs the single instance checker until `stop` resolves, the primary
+/// instance performs the `handlers` for secondary instances
+///
+/// The names are prefixed like in `only_single_instance_with_prefix`.
+pub fn only_single_instance_with_handlers(
+    prefix: &str,
+    unique_name: &str,
+    callback: impl Fn(Vec<String>) + Send + Sync + 'static,
+    handlers: Handlers,
+    stop: impl Future<Output = ()> + Send + 'static,
+) -> Result<JoinHandle<()>, Error> {
+    validate_prefix(prefix)?;
+    let bus_name = sanitize_bus_name(prefix, unique_name);
+    // First check if we can acquire the name
+    let can_acquire = block_on(async {
+        let connection = Connection::session().await?;
+        let reply = zbus::fdo::DBusProxy::new(&connection)
+            .await?
+            .request_name(
+                zbus::names::WellKnownName::from_string_unchecked(bus_name.clone()),
+                zbus::fdo::RequestNameFlags::DoNotQueue.into(),
+            )
+            .await?;
+
+        match reply {
+            zbus::fdo::RequestNameReply::PrimaryOwner => {
+                // Release the name so the thread can acquire it
+                zbus::fdo::DBusProxy::new(&connection)
+                    .await?
+                    .release_name(zbus::names::WellKnownName::from_string_unchecked(
+                        bus_name.clone(),
+                    ))
+                    .await?;
+                Ok(true)
+            }
+            zbus::fdo::RequestNameReply::Exists => Ok(false),
+            _ => Err(Error::DBus(
+                "Unexpected reply when requesting name".to_string(),
+            )),
+        }
+    })?;
+
+    if !can_acquire {
+        // Signal the primary instance
+        signal_primary_instance(bus_name)?;
+        return Err(Error::AlreadyRunning);
+    }
+
+    // Spawn the monitoring thread
+    let handle = std::thread::spawn(move || {
+        block_on(async {
+            let connection = Connection::session().await.unwrap();
+
+            // Acquire the D-Bus name (should succeed since we just checked)
+            zbus::fdo::DBusProxy::new(&connection)
+                .await
+                .unwrap()
+                .request_name(
+                    zbus::names::WellKnownName::from_string_unchecked(bus_name.clone()),
+                    zbus::fdo::RequestNameFlags::DoNotQueue.into(),
+                )
+                .await
+                .unwrap();
+
+            // Register the D-Bus service
+            let service = SingleInstanceService {
+                callback: Arc::new(Mutex::new(callback)),
+                handlers,
+            };
+
+            connection
+                .object_server()
+                .at(OBJECT_PATH, service)
+                .await
+                .unwrap();
+
+            // Keep the connection alive until stopped
+            stop.await;
+
+            let _ = zbus::fdo::DBusProxy::new(&connection)
+                .await
+                .unwrap()
+                .release_name(zbus::names::WellKnownName::from_string_unchecked(
+                    bus_name.clone(),
+                ))
+                .await;
+        })
+    });
+
+    Ok(handle)
+}
+
+fn signal_primary_instance(bus_name: impl Into<String>) -> Result<(), Error> {
+    let bus_name = bus_name.into();
+
+    block_on(async {
+        let connection = Connection::session().await?;
+
+        let proxy = zbus::Proxy::new(&connection, bus_name, OBJECT_PATH, INTERFACE).await?;
+
+        // Primary of an older version has only the parameterless method
+        match proxy
+            .call_method("ActivateWithArgs", &(activation_args(),))
+            .await
+        {
+            Err(e) if is_unknown_method(&e) => {
+                proxy.call_method("Activate", &()).await?;
+            }
+            result => {
+                result?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Asks the primary instance to perform an action, returns the reply of its
+/// handler. The primary is found by the prefix and the unique name it was
+/// started with.
+///
+/// Fails with `Error::UnknownAction` if the primary has no handler for the
+/// action, and with `Error::DBus` if no primary instance is running.
+pub fn send_to_primary(
+    prefix: &str,
+    unique_name: &str,
+    action: &str,
+    payload: &str,
+) -> Result<String, Error> {
+    validate_prefix(prefix)?;
+    let bus_name = sanitize_bus_name(prefix, unique_name);
+    block_on(async {
+        let connection = Connection::session().await?;
+        invoke(&connection, Some(bus_name.as_str()), action, payload).await
+    })
+}
+
+/// Calls `Invoke` of the service, `destination` is `None` on peer-to-peer
+/// connections
+async fn invoke(
+    connection: &Connection,
+    destination: Option<&str>,
+    action: &str,
+    payload: &str,
+) -> Result<String, Error> {
+    let reply = connection
+        .call_method(
+            destination,
+            OBJECT_PATH,
+            Some(INTERFACE),
+            "Invoke",
+            &(action, payload),
+        )
+        .await;
+    match reply {
+        Ok(reply) => Ok(reply.body().deserialize::<String>()?),
+        Err(e) if is_unknown_method(&e) => Err(Error::UnknownAction(action.to_string())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// True if the called method does not exist, as a method error reply or as
+/// the parsed `fdo` error
+fn is_unknown_method(error: &zbus::Error) -> bool {
+    match error {
+        zbus::Error::MethodError(name, _, _) => name.as_str() == UNKNOWN_METHOD,
+        zbus::Error::FDO(e) => matches!(**e, zbus::fdo::Error::UnknownMethod(_)),
+        _ => false,
+    }
+}
+
+struct SingleInstanceService {
+    callback: Arc<Mutex<dyn Fn(Vec<String>) + Send + Sync + 'static>>,
+    handlers: Handlers,
+}
+
+#[interface(name = "org.example.SingleInstance")]
+impl SingleInstanceService {
+    /// Called when a secondary instance of an older version tries to start,
+    /// like `activate_with_args` without arguments
+    fn activate(&self) {
+        self.activate_with_args(Vec::new());
+    }
+
+    /// Called when a secondary instance tries to start, with its arguments,
+    /// empty if it has none
+    fn activate_with_args(&self, args: Vec<String>) {
+        let callback = self.callback.lock().unwrap();
+        callback(args);
+    }
+
+    /// Performs a named action for a secondary instance, one method for all
+    /// actions keeps the interface stable
+    fn invoke(&self, action: String, payload: String) -> zbus::fdo::Result<String> {
+        self.handlers
+            .invoke(&action, &payload)
+            .ok_or_else(|| zbus::fdo::Error::UnknownMethod(format!("Unknown action: {}", action)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_PREFIX;
+    use std::os::unix::net::UnixStream;
+    use zbus::connection::Builder;
+
+    /// Primary serving the handlers and a secondary connected to it
+    async fn connect(handlers: Handlers) -> (Connection, Connection) {
+        connect_with_callback(handlers, |_| {}).await
+    }
+
+    async fn connect_with_callback(
+        handlers: Handlers,
+        callback: impl Fn(Vec<String>) + Send + Sync + 'static,
+    ) -> (Connection, Connection) {
+        let (primary, secondary) = UnixStream::pair().unwrap();
+        let service = SingleInstanceService {
+            callback: Arc::new(Mutex::new(callback)),
+            handlers,
+        };
+        futures::try_join!(
+            Builder::unix_stream(primary)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(OBJECT_PATH, service)
+                .unwrap()
+                .build(),
+            Builder::unix_stream(secondary).p2p().build(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn routes_actions_to_handlers() {
+        block_on(async {
+            let handlers = Handlers::new()
+                .on("flush", |payload| format!("flushed {}", payload))
+                .on("quit", |_| "bye".to_string());
+            let (_primary, secondary) = connect(handlers).await;
+
+            let reply = invoke(&secondary, None, "flush", "now").await.unwrap();
+            assert_eq!(reply, "flushed now");
+            let reply = invoke(&secondary, None, "quit", "").await.unwrap();
+            assert_eq!(reply, "bye");
+        });
+    }
+
+    #[test]
+    fn activation_carries_the_args() {
+        block_on(async {
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let received_ = received.clone();
+            let (_primary, secondary) = connect_with_callback(Handlers::new(), move |args| {
+                received_.lock().unwrap().push(args);
+            })
+            .await;
+
+            for args in [vec!["--desktop".to_string(), "2".to_string()], vec![]] {
+                secondary
+                    .call_method(
+                        None,
+                        OBJECT_PATH,
+                        Some(INTERFACE),
+                        "ActivateWithArgs",
+                        &(args,),
+                    )
+                    .await
+                    .unwrap();
+            }
+            // Parameterless method of the older versions
+            secondary
+                .call_method(None, OBJECT_PATH, Some(INTERFACE), "Activate", &())
+                .await
+                .unwrap();
+            assert_eq!(
+                *received.lock().unwrap(),
+                vec![
+                    vec!["--desktop".to_string(), "2".to_string()],
+                    vec![],
+                    vec![]
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn role_is_taken_over_when_holder_exits() {
+        block_on(async {
+            let bus_name = role_bus_name(DEFAULT_PREFIX, "desktop-writer");
+            // Name of the versions before the prefix
+            assert_eq!(
+                bus_name,
+                format!(
+                    "org.example.SingleInstance.Role{:x}",
+                    name_hash("desktop-writer")
+                )
+            );
+            assert_ne!(bus_name, role_bus_name(DEFAULT_PREFIX, "other"));
+            assert_ne!(
+                bus_name,
+                role_bus_name("com.example.Other", "desktop-writer")
+            );
+            assert_ne!(
+                bus_name,
+                sanitize_bus_name(DEFAULT_PREFIX, "desktop-writer")
+            );
+
+            // Waiting in the queue, acquired when the holder releases it
+            let events = futures::stream::iter([
+                NameEvent::Acquired(":1.42".to_string()),
+                NameEvent::Acquired(sanitize_bus_name(DEFAULT_PREFIX, "desktop-writer")),
+                NameEvent::Acquired(bus_name.clone()),
+                NameEvent::Acquired(bus_name.clone()),
+                NameEvent::Lost(bus_name.clone()),
+            ]);
+            let changes = holder_changes(bus_name, false, events)
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(changes, vec![false, true, false]);
+        });
+    }
+
+    #[test]
+    fn unknown_action_is_an_error() {
+        block_on(async {
+            let handlers = Handlers::new().on("flush", |_| String::new());
+            let (_primary, secondary) = connect(handlers).await;
+
+            let result = invoke(&secondary, None, "show-stats", "").await;
+            assert!(matches!(result, Err(Error::UnknownAction(action)) if action == "show-stats"));
+        });
+    }
+
+    #[test]
+    fn bus_names_are_stable_and_distinct() {
+        let names: Vec<String> = (0..1000)
+            .map(|i| format!("/home/user/timings-{}.db", i))
+            .collect();
+        let bus_names: std::collections::HashSet<String> = names
+            .iter()
+            .map(|name| sanitize_bus_name("com.example.Timings", name))
+            .collect();
+        assert_eq!(bus_names.len(), names.len());
+        for name in &names {
+            assert_eq!(
+                sanitize_bus_name("com.example.Timings", name),
+                sanitize_bus_name("com.example.Timings", name)
+            );
+        }
+        assert!(bus_names
+            .iter()
+            .all(|bus_name| bus_name.starts_with("com.example.Timings.SingleInstance")));
+        assert_ne!(
+            sanitize_bus_name("com.example.Timings", "timings.db"),
+            sanitize_bus_name(DEFAULT_PREFIX, "timings.db")
+        );
+    }
+
+    #[test]
+    fn guard_releases_the_name_on_drop() {
+        // Needs the session bus, e.g. not available in a container
+        if block_on(Connection::session()).is_err() {
+            return;
+        }
+        let unique_name = format!("single-instance-guard-test-{}", std::process::id());
+
+        let guard = crate::only_single_instance(&unique_name, |_| {}).unwrap();
+        assert!(matches!(
+            crate::only_single_instance(&unique_name, |_| {}),
+            Err(Error::AlreadyRunning)
+        ));
+        drop(guard);
+
+        let guard = crate::only_single_instance(&unique_name, |_| {});
+        assert!(guard.is_ok(), "Not primary after the drop");
+    }
+}
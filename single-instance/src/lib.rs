@@ -1,161 +1,254 @@
-use futures::executor::block_on;
+//! Ensures that only a single instance of an application runs, e.g. per
+//! database file.
+//!
+//! The first instance becomes the primary. Later instances fail to start with
+//! `Error::AlreadyRunning` after activating the primary, and may ask it to
+//! perform named actions with `send_to_primary`.
+//!
+//! The mechanism depends on the platform:
+//!
+//! - Linux and other Unix-likes: a well-known D-Bus name on the session bus,
+//!   actions are calls to its `Invoke` method.
+//! - Windows: a named mutex marks the primary, requests go through a named
+//!   pipe.
+//! - macOS: a Unix socket in the user temp dir marks the primary and carries
+//!   the requests.
+
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::future::Future;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::sync::Arc;
-use std::sync::Mutex;
 use std::thread::JoinHandle;
-use zbus::interface;
-use zbus::Connection;
+
+#[cfg(not(any(windows, target_os = "macos")))]
+mod dbus;
+#[cfg(windows)]
+mod named_pipe;
+#[cfg(any(windows, target_os = "macos"))]
+mod protocol;
+#[cfg(target_os = "macos")]
+mod unix_socket;
+#[cfg(not(any(windows, target_os = "macos")))]
+pub use dbus::*;
+#[cfg(windows)]
+pub use named_pipe::*;
+#[cfg(target_os = "macos")]
+pub use unix_socket::*;
 
 /// Errors that can occur when starting the single instance monitor
 #[derive(Debug)]
 pub enum Error {
     AlreadyRunning,
+    /// Primary instance has no handler for the action
+    UnknownAction(String),
     DBus(String),
+    /// Socket or pipe error, on platforms without D-Bus
+    Io(String),
+    /// Prefix is not a valid D-Bus name, see `only_single_instance_with_prefix`
+    InvalidPrefix(String),
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::AlreadyRunning => write!(f, "Another instance is already running"),
+            Error::UnknownAction(action) => write!(f, "Unknown action: {}", action),
             Error::DBus(e) => write!(f, "D-Bus error: {}", e),
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::InvalidPrefix(prefix) => write!(f, "Invalid name prefix: {}", prefix),
         }
     }
 }
 
 impl std::error::Error for Error {}
 
-impl From<zbus::Error> for Error {
-    fn from(e: zbus::Error) -> Self {
-        Error::DBus(e.to_string())
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e.to_string())
     }
 }
 
-impl From<zbus::fdo::Error> for Error {
-    fn from(e: zbus::fdo::Error) -> Self {
-        Error::DBus(e.to_string())
+type Handler = Arc<dyn Fn(&str) -> String + Send + Sync + 'static>;
+
+/// Named actions the primary instance performs for secondary instances, see
+/// `send_to_primary`
+#[derive(Clone, Default)]
+pub struct Handlers {
+    handlers: HashMap<String, Handler>,
+}
+
+impl Handlers {
+    pub fn new() -> Self {
+        Self::default()
     }
+
+    /// Registers the handler of an action, it gets the payload sent with the
+    /// action and its return value is sent back as the reply
+    pub fn on(
+        mut self,
+        action: &str,
+        handler: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.handlers.insert(action.to_string(), Arc::new(handler));
+        self
+    }
+
+    pub(crate) fn invoke(&self, action: &str, payload: &str) -> Option<String> {
+        self.handlers.get(action).map(|handler| handler(payload))
+    }
+}
+
+/// Arguments a secondary instance sends to the primary, without the program
+/// name. Arguments which are not valid Unicode are converted lossily rather
+/// than failing the activation.
+fn activation_args() -> Vec<String> {
+    std::env::args_os()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
 }
 
-/// Make unique D-Bus compatible bus name from arbitrary string
-fn sanitize_bus_name(input: &str) -> String {
+/// Prefix of the bus, mutex, pipe and socket names if none is given. The
+/// names with it are the same as before the prefix could be given.
+pub const DEFAULT_PREFIX: &str = "org.example";
+
+/// Longest prefix, the D-Bus name with the hash appended must fit in 255
+/// characters
+const MAX_PREFIX_LEN: usize = 200;
+
+/// Checks that the prefix is a valid D-Bus name, e.g. `com.example.Timings`:
+/// dot separated elements of ASCII letters, digits, `_` and `-`, none of them
+/// empty or starting with a digit
+fn validate_prefix(prefix: &str) -> Result<(), Error> {
+    let valid_element = |element: &str| {
+        !element.is_empty()
+            && !element.starts_with(|c: char| c.is_ascii_digit())
+            && element
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    };
+    if prefix.len() > MAX_PREFIX_LEN || !prefix.split('.').all(valid_element) {
+        return Err(Error::InvalidPrefix(prefix.to_string()));
+    }
+    Ok(())
+}
+
+/// Hash of the unique name, used in bus, mutex, pipe and socket names
+fn name_hash(unique_name: &str) -> u64 {
     let mut hasher = DefaultHasher::new();
-    input.hash(&mut hasher);
-    let hash = hasher.finish();
+    unique_name.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Primary instance monitor of `only_single_instance`
+///
+/// Dropping the guard releases the D-Bus name, mutex or socket and waits for
+/// the monitor thread to finish, after which a new instance with the same
+/// name becomes the primary.
+pub struct InstanceGuard {
+    stop: Option<futures::channel::oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl InstanceGuard {
+    fn start(
+        start: impl FnOnce(futures::channel::oneshot::Receiver<()>) -> Result<JoinHandle<()>, Error>,
+    ) -> Result<Self, Error> {
+        let (stop, stopped) = futures::channel::oneshot::channel();
+        let thread = start(stopped)?;
+        Ok(InstanceGuard {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
 
-    format!("org.example.SingleInstance{:x}", hash)
+impl Drop for InstanceGuard {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
 }
 
-/// Runs the single instance checker
+/// Runs the single instance checker until the returned guard is dropped
 ///
 /// - `unique_name`: Unique name to identify the instance (e.g. database path)
 /// - `callback`: The callback to invoke when a secondary instance tries to
-///   start (this is used in primary instance only)
+///   start, with the command line arguments of the secondary instance without
+///   the program name (this is used in primary instance only)
 pub fn only_single_instance(
     unique_name: &str,
-    callback: impl Fn() + Send + Sync + 'static,
-) -> Result<JoinHandle<()>, Error> {
-    let bus_name = sanitize_bus_name(unique_name);
-    // First check if we can acquire the name
-    let can_acquire = block_on(async {
-        let connection = Connection::session().await?;
-        let reply = zbus::fdo::DBusProxy::new(&connection)
-            .await?
-            .request_name(
-                zbus::names::WellKnownName::from_string_unchecked(bus_name.clone()),
-                zbus::fdo::RequestNameFlags::DoNotQueue.into(),
-            )
-            .await?;
-
-        match reply {
-            zbus::fdo::RequestNameReply::PrimaryOwner => {
-                // Release the name so the thread can acquire it
-                zbus::fdo::DBusProxy::new(&connection)
-                    .await?
-                    .release_name(zbus::names::WellKnownName::from_string_unchecked(
-                        bus_name.clone(),
-                    ))
-                    .await?;
-                Ok(true)
-            }
-            zbus::fdo::RequestNameReply::Exists => Ok(false),
-            _ => Err(Error::DBus(
-                "Unexpected reply when requesting name".to_string(),
-            )),
-        }
-    })?;
-
-    if !can_acquire {
-        // Signal the primary instance
-        signal_primary_instance(bus_name)?;
-        return Err(Error::AlreadyRunning);
-    }
-
-    // Spawn the monitoring thread
-    let handle = std::thread::spawn(move || {
-        block_on(async {
-            let connection = Connection::session().await.unwrap();
-
-            // Acquire the D-Bus name (should succeed since we just checked)
-            zbus::fdo::DBusProxy::new(&connection)
-                .await
-                .unwrap()
-                .request_name(
-                    zbus::names::WellKnownName::from_string_unchecked(bus_name.clone()),
-                    zbus::fdo::RequestNameFlags::DoNotQueue.into(),
-                )
-                .await
-                .unwrap();
-
-            // Register the D-Bus service
-            let service = SingleInstanceService {
-                callback: Arc::new(Mutex::new(callback)),
-            };
-
-            connection
-                .object_server()
-                .at("/org/example/SingleInstance", service)
-                .await
-                .unwrap();
-
-            // Keep the connection alive
-            futures::future::pending::<()>().await;
-        })
-    });
-
-    Ok(handle)
+    callback: impl Fn(Vec<String>) + Send + Sync + 'static,
+) -> Result<InstanceGuard, Error> {
+    only_single_instance_with_prefix(DEFAULT_PREFIX, unique_name, callback)
 }
 
-fn signal_primary_instance(bus_name: impl Into<String>) -> Result<(), Error> {
-    let bus_name = bus_name.into();
-
-    block_on(async {
-        let connection = Connection::session().await?;
-
-        let proxy = zbus::Proxy::new(
-            &connection,
-            bus_name,
-            "/org/example/SingleInstance",
-            "org.example.SingleInstance",
+/// Runs the single instance checker with the names prefixed by `prefix`
+/// instead of `DEFAULT_PREFIX`, e.g. the reverse domain name of the
+/// application, so that unrelated applications using this crate don't clash
+///
+/// Fails with `Error::InvalidPrefix` if `prefix` is not a valid D-Bus name.
+/// Secondary instances must use the same prefix to find the primary.
+pub fn only_single_instance_with_prefix(
+    prefix: &str,
+    unique_name: &str,
+    callback: impl Fn(Vec<String>) + Send + Sync + 'static,
+) -> Result<InstanceGuard, Error> {
+    InstanceGuard::start(|stopped| {
+        only_single_instance_with_handlers(
+            prefix,
+            unique_name,
+            callback,
+            Handlers::new(),
+            async move {
+                let _ = stopped.await;
+            },
         )
-        .await?;
-
-        proxy.call_method("Activate", &()).await?;
-
-        Ok(())
     })
 }
 
-struct SingleInstanceService {
-    callback: Arc<Mutex<dyn Fn() + Send + Sync + 'static>>,
+/// Runs the single instance checker until `stop` resolves, the names
+/// prefixed like in `only_single_instance_with_prefix`
+///
+/// The D-Bus name, mutex or socket is released when `stop` resolves, and the
+/// returned thread finishes.
+pub fn only_single_instance_until(
+    prefix: &str,
+    unique_name: &str,
+    callback: impl Fn(Vec<String>) + Send + Sync + 'static,
+    stop: impl Future<Output = ()> + Send + 'static,
+) -> Result<JoinHandle<()>, Error> {
+    only_single_instance_with_handlers(prefix, unique_name, callback, Handlers::new(), stop)
 }
 
-#[interface(name = "org.example.SingleInstance")]
-impl SingleInstanceService {
-    /// Called when a secondary instance tries to start
-    fn activate(&self) {
-        let callback = self.callback.lock().unwrap();
-        callback();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_must_be_a_dbus_name() {
+        assert!(validate_prefix(DEFAULT_PREFIX).is_ok());
+        assert!(validate_prefix("com.example.my-app_2").is_ok());
+        assert!(validate_prefix("timings").is_ok());
+        for prefix in [
+            "",
+            "com..example",
+            "com.example.",
+            "com.2example",
+            "com.ex ample",
+        ] {
+            assert!(
+                matches!(validate_prefix(prefix), Err(Error::InvalidPrefix(_))),
+                "{:?}",
+                prefix
+            );
+        }
+        assert!(validate_prefix(&"a".repeat(MAX_PREFIX_LEN + 1)).is_err());
     }
 }
@@ -0,0 +1,214 @@
+use crate::activation_args;
+use crate::name_hash;
+use crate::protocol::encode_args;
+use crate::protocol::prefixed_name;
+use crate::protocol::send_request;
+use crate::protocol::serve_request;
+use crate::protocol::ACTIVATE;
+use crate::validate_prefix;
+use crate::Error;
+use crate::Handlers;
+use futures::executor::block_on;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::os::windows::io::FromRawHandle;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use windows_sys::Win32::Foundation::CloseHandle;
+use windows_sys::Win32::Foundation::GetLastError;
+use windows_sys::Win32::Foundation::ERROR_ALREADY_EXISTS;
+use windows_sys::Win32::Foundation::ERROR_PIPE_BUSY;
+use windows_sys::Win32::Foundation::ERROR_PIPE_CONNECTED;
+use windows_sys::Win32::Foundation::HANDLE;
+use windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE;
+use windows_sys::Win32::Storage::FileSystem::PIPE_ACCESS_DUPLEX;
+use windows_sys::Win32::System::Pipes::ConnectNamedPipe;
+use windows_sys::Win32::System::Pipes::CreateNamedPipeW;
+use windows_sys::Win32::System::Pipes::DisconnectNamedPipe;
+use windows_sys::Win32::System::Pipes::PIPE_READMODE_BYTE;
+use windows_sys::Win32::System::Pipes::PIPE_TYPE_BYTE;
+use windows_sys::Win32::System::Pipes::PIPE_UNLIMITED_INSTANCES;
+use windows_sys::Win32::System::Pipes::PIPE_WAIT;
+use windows_sys::Win32::System::Threading::CreateMutexW;
+
+/// How long a secondary instance waits for the pipe of the primary, which
+/// may be starting or busy with another request
+const CONNECT_ATTEMPTS: u32 = 50;
+const CONNECT_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Named mutex held by the primary instance, released when dropped
+struct InstanceMutex(HANDLE);
+
+// The handle is only closed, which is allowed from any thread
+unsafe impl Send for InstanceMutex {}
+
+impl Drop for InstanceMutex {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+fn wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(Some(0)).collect()
+}
+
+fn mutex_name(prefix: &str, unique_name: &str) -> String {
+    let name = format!("SingleInstance{:x}", name_hash(unique_name));
+    format!("Local\\{}", prefixed_name(prefix, name))
+}
+
+fn pipe_name(prefix: &str, unique_name: &str) -> String {
+    let name = format!("SingleInstance{:x}", name_hash(unique_name));
+    format!("\\\\.\\pipe\\{}", prefixed_name(prefix, name))
+}
+
+/// Runs the single instance checker until `stop` resolves, the primary
+/// instance performs the `handlers` for secondary instances
+///
+/// The names are prefixed like in `only_single_instance_with_prefix`.
+pub fn only_single_instance_with_handlers(
+    prefix: &str,
+    unique_name: &str,
+    callback: impl Fn(Vec<String>) + Send + Sync + 'static,
+    handlers: Handlers,
+    stop: impl Future<Output = ()> + Send + 'static,
+) -> Result<JoinHandle<()>, Error> {
+    validate_prefix(prefix)?;
+    let name = wide(&mutex_name(prefix, unique_name));
+    let handle = unsafe { CreateMutexW(std::ptr::null(), 0, name.as_ptr()) };
+    if handle.is_null() {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let mutex = InstanceMutex(handle);
+    if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+        drop(mutex);
+        // Signal the primary instance
+        send_request(
+            connect(prefix, unique_name)?,
+            ACTIVATE,
+            &encode_args(&activation_args()),
+        )?;
+        return Err(Error::AlreadyRunning);
+    }
+
+    let pipe_name = pipe_name(prefix, unique_name);
+    let handle = std::thread::spawn(move || {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_ = stopped.clone();
+        let pipe_name_ = pipe_name.clone();
+        let server = std::thread::spawn(move || {
+            let name = wide(&pipe_name_);
+            while !stopped_.load(Ordering::SeqCst) {
+                match accept(&name) {
+                    Ok(pipe) => {
+                        if stopped_.load(Ordering::SeqCst) {
+                            break;
+                        }
+                        let _ = serve_request(&pipe, &callback, &handlers);
+                        disconnect(pipe);
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        block_on(stop);
+
+        // Wake the server blocked in accepting
+        stopped.store(true, Ordering::SeqCst);
+        let _ = OpenOptions::new().read(true).write(true).open(&pipe_name);
+        let _ = server.join();
+        drop(mutex);
+    });
+
+    Ok(handle)
+}
+
+/// Creates a pipe instance and waits for a client to connect to it
+fn accept(name: &[u16]) -> std::io::Result<File> {
+    let handle = unsafe {
+        CreateNamedPipeW(
+            name.as_ptr(),
+            PIPE_ACCESS_DUPLEX,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT,
+            PIPE_UNLIMITED_INSTANCES,
+            4096,
+            4096,
+            0,
+            std::ptr::null(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(std::io::Error::last_os_error());
+    }
+    let pipe = unsafe { File::from_raw_handle(handle as _) };
+    // Client may connect between creating and waiting
+    if unsafe { ConnectNamedPipe(handle, std::ptr::null_mut()) } == 0
+        && unsafe { GetLastError() } != ERROR_PIPE_CONNECTED
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(pipe)
+}
+
+/// Waits for the client to read the reply before closing the pipe instance
+fn disconnect(pipe: File) {
+    use std::os::windows::io::AsRawHandle;
+
+    let _ = pipe.sync_all();
+    unsafe {
+        DisconnectNamedPipe(pipe.as_raw_handle() as HANDLE);
+    }
+}
+
+/// Connects to the pipe of the primary instance
+fn connect(prefix: &str, unique_name: &str) -> Result<File, Error> {
+    let name = pipe_name(prefix, unique_name);
+    let mut attempts = 0;
+    loop {
+        match OpenOptions::new().read(true).write(true).open(&name) {
+            Ok(pipe) => return Ok(pipe),
+            Err(e)
+                if attempts < CONNECT_ATTEMPTS
+                    && (e.kind() == std::io::ErrorKind::NotFound
+                        || e.raw_os_error() == Some(ERROR_PIPE_BUSY as i32)) =>
+            {
+                attempts += 1;
+                std::thread::sleep(CONNECT_INTERVAL);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Asks the primary instance to perform an action, returns the reply of its
+/// handler. The primary is found by the prefix and the unique name it was
+/// started with.
+///
+/// Fails with `Error::UnknownAction` if the primary has no handler for the
+/// action, and with `Error::Io` if no primary instance is running.
+pub fn send_to_primary(
+    prefix: &str,
+    unique_name: &str,
+    action: &str,
+    payload: &str,
+) -> Result<String, Error> {
+    validate_prefix(prefix)?;
+    send_request(connect(prefix, unique_name)?, action, payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::assert_backend_routes_actions;
+
+    #[test]
+    fn routes_actions_over_named_pipe() {
+        assert_backend_routes_actions();
+    }
+}
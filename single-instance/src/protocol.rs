@@ -0,0 +1,219 @@
+//! Requests of the socket and pipe backends
+//!
+//! A request is the action and the payload, a reply is a status byte and the
+//! reply of the handler. Strings are UTF-8 prefixed with their length as a
+//! little-endian `u32`.
+
+use crate::Error;
+use crate::Handlers;
+use crate::DEFAULT_PREFIX;
+use std::io::Read;
+use std::io::Write;
+
+/// Mutex, pipe or socket name with the prefix. Names of `DEFAULT_PREFIX`
+/// are not prefixed, they are the names of the versions before the prefix,
+/// which then still find each other.
+pub(crate) fn prefixed_name(prefix: &str, name: String) -> String {
+    if prefix == DEFAULT_PREFIX {
+        return name;
+    }
+    format!("{}.{}", prefix, name)
+}
+
+/// Action of a secondary instance trying to start, handled by the callback.
+/// The payload is the arguments, see `encode_args`
+pub(crate) const ACTIVATE: &str = "";
+
+/// Separates the arguments in the payload of `ACTIVATE`, arguments can't
+/// contain it
+const ARG_SEPARATOR: char = '\0';
+
+/// Payload of `ACTIVATE`, empty if there are no arguments
+pub(crate) fn encode_args(args: &[String]) -> String {
+    args.join(&ARG_SEPARATOR.to_string())
+}
+
+fn decode_args(payload: &str) -> Vec<String> {
+    if payload.is_empty() {
+        return Vec::new();
+    }
+    payload.split(ARG_SEPARATOR).map(str::to_string).collect()
+}
+
+/// Longest string read, so that a length sent by a broken client is not
+/// allocated
+const MAX_STRING_LEN: usize = 1 << 20;
+
+const STATUS_OK: u8 = 0;
+const STATUS_UNKNOWN_ACTION: u8 = 1;
+
+fn write_string(writer: &mut impl Write, value: &str) -> std::io::Result<()> {
+    writer.write_all(&(value.len() as u32).to_le_bytes())?;
+    writer.write_all(value.as_bytes())
+}
+
+fn read_string(reader: &mut impl Read) -> std::io::Result<String> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_STRING_LEN {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("String of {} bytes is too long", len),
+        ));
+    }
+    let mut value = vec![0; len];
+    reader.read_exact(&mut value)?;
+    String::from_utf8(value).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Sends a request to the primary instance and reads its reply
+pub(crate) fn send_request(
+    mut stream: impl Read + Write,
+    action: &str,
+    payload: &str,
+) -> Result<String, Error> {
+    write_string(&mut stream, action)?;
+    write_string(&mut stream, payload)?;
+    stream.flush()?;
+
+    let mut status = [0; 1];
+    stream.read_exact(&mut status)?;
+    let reply = read_string(&mut stream)?;
+    match status[0] {
+        STATUS_OK => Ok(reply),
+        STATUS_UNKNOWN_ACTION => Err(Error::UnknownAction(action.to_string())),
+        status => Err(Error::Io(format!("Unexpected reply status {}", status))),
+    }
+}
+
+/// Reads a request of a secondary instance and replies to it
+pub(crate) fn serve_request(
+    mut stream: impl Read + Write,
+    callback: &(dyn Fn(Vec<String>) + Send + Sync),
+    handlers: &Handlers,
+) -> std::io::Result<()> {
+    let action = read_string(&mut stream)?;
+    let payload = read_string(&mut stream)?;
+    let (status, reply) = if action == ACTIVATE {
+        callback(decode_args(&payload));
+        (STATUS_OK, String::new())
+    } else {
+        match handlers.invoke(&action, &payload) {
+            Some(reply) => (STATUS_OK, reply),
+            None => (STATUS_UNKNOWN_ACTION, String::new()),
+        }
+    };
+    stream.write_all(&[status])?;
+    write_string(&mut stream, &reply)?;
+    stream.flush()
+}
+
+/// Exercises a backend through its public API, run by the test of each
+/// backend on its own platform
+#[cfg(test)]
+pub(crate) fn assert_backend_routes_actions() {
+    use crate::only_single_instance;
+    use crate::only_single_instance_with_handlers;
+    use crate::only_single_instance_with_prefix;
+    use crate::send_to_primary;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    let unique_name = format!("single-instance-test-{}", std::process::id());
+    let activations = Arc::new(AtomicUsize::new(0));
+    let activations_ = activations.clone();
+    let (stop, stopped) = futures::channel::oneshot::channel::<()>();
+    let primary = only_single_instance_with_handlers(
+        DEFAULT_PREFIX,
+        &unique_name,
+        move |_| {
+            activations_.fetch_add(1, Ordering::SeqCst);
+        },
+        Handlers::new().on("flush", |payload| format!("flushed {}", payload)),
+        async move {
+            let _ = stopped.await;
+        },
+    )
+    .unwrap();
+
+    assert!(matches!(
+        only_single_instance(&unique_name, |_| {}),
+        Err(Error::AlreadyRunning)
+    ));
+    assert_eq!(activations.load(Ordering::SeqCst), 1);
+    assert_eq!(
+        send_to_primary(DEFAULT_PREFIX, &unique_name, "flush", "now").unwrap(),
+        "flushed now"
+    );
+    assert!(matches!(
+        send_to_primary(DEFAULT_PREFIX, &unique_name, "quit", ""),
+        Err(Error::UnknownAction(action)) if action == "quit"
+    ));
+    // Primary of another prefix is another instance
+    assert!(matches!(
+        send_to_primary("com.example.Other", &unique_name, "flush", ""),
+        Err(Error::Io(_))
+    ));
+    let other = only_single_instance_with_prefix("com.example.Other", &unique_name, |_| {});
+    assert!(other.is_ok());
+    drop(other);
+
+    // Released on stop, the next instance becomes the primary
+    stop.send(()).unwrap();
+    primary.join().unwrap();
+    let (stop, stopped) = futures::channel::oneshot::channel::<()>();
+    let primary =
+        crate::only_single_instance_until(DEFAULT_PREFIX, &unique_name, |_| {}, async move {
+            let _ = stopped.await;
+        })
+        .unwrap();
+    stop.send(()).unwrap();
+    primary.join().unwrap();
+
+    // Released when the guard is dropped
+    let guard = only_single_instance(&unique_name, |_| {}).unwrap();
+    assert!(matches!(
+        only_single_instance(&unique_name, |_| {}),
+        Err(Error::AlreadyRunning)
+    ));
+    drop(guard);
+    assert!(only_single_instance(&unique_name, |_| {}).is_ok());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_prefix_keeps_the_names() {
+        assert_eq!(
+            prefixed_name(DEFAULT_PREFIX, "SingleInstance1f".to_string()),
+            "SingleInstance1f"
+        );
+        assert_eq!(
+            prefixed_name("com.example.Timings", "SingleInstance1f".to_string()),
+            "com.example.Timings.SingleInstance1f"
+        );
+    }
+
+    #[test]
+    fn too_long_string_is_refused() {
+        let mut request = (u32::MAX).to_le_bytes().to_vec();
+        request.extend_from_slice(b"flush");
+        let error = read_string(&mut request.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut request = Vec::new();
+        write_string(&mut request, "flush").unwrap();
+        assert_eq!(read_string(&mut request.as_slice()).unwrap(), "flush");
+    }
+
+    #[test]
+    fn args_round_trip() {
+        let args = vec!["--desktop".to_string(), "a b".to_string(), String::new()];
+        assert_eq!(decode_args(&encode_args(&args)), args);
+        assert_eq!(decode_args(&encode_args(&[])), Vec::<String>::new());
+    }
+}
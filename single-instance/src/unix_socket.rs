@@ -0,0 +1,133 @@
+use crate::activation_args;
+use crate::name_hash;
+use crate::protocol::encode_args;
+use crate::protocol::prefixed_name;
+use crate::protocol::send_request;
+use crate::protocol::serve_request;
+use crate::protocol::ACTIVATE;
+use crate::validate_prefix;
+use crate::Error;
+use crate::Handlers;
+use futures::executor::block_on;
+use std::fs::File;
+use std::future::Future;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long the server waits for a request of a connected client, a stalled
+/// client must not block the server
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Socket of the primary instance in the user temp dir
+fn socket_path(prefix: &str, unique_name: &str) -> PathBuf {
+    let name = format!("single-instance-{:x}.sock", name_hash(unique_name));
+    std::env::temp_dir().join(prefixed_name(prefix, name))
+}
+
+/// Locks the lock file next to the socket, waiting for other instances
+/// starting or stopping. The socket is only unlinked while holding it, so an
+/// instance starting at the same time can't unlink the socket of the
+/// primary. The lock is released when the file is dropped.
+fn lock_socket(socket: &Path) -> std::io::Result<File> {
+    let lock = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(socket.with_extension("lock"))?;
+    lock.lock()?;
+    Ok(lock)
+}
+
+/// Runs the single instance checker until `stop` resolves, the primary
+/// instance performs the `handlers` for secondary instances
+///
+/// The names are prefixed like in `only_single_instance_with_prefix`.
+pub fn only_single_instance_with_handlers(
+    prefix: &str,
+    unique_name: &str,
+    callback: impl Fn(Vec<String>) + Send + Sync + 'static,
+    handlers: Handlers,
+    stop: impl Future<Output = ()> + Send + 'static,
+) -> Result<JoinHandle<()>, Error> {
+    validate_prefix(prefix)?;
+    let path = socket_path(prefix, unique_name);
+    let lock = lock_socket(&path)?;
+    if let Ok(stream) = UnixStream::connect(&path) {
+        drop(lock);
+        // Signal the primary instance
+        send_request(stream, ACTIVATE, &encode_args(&activation_args()))?;
+        return Err(Error::AlreadyRunning);
+    }
+
+    // Nobody listens, the socket is left over from a crashed instance
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    drop(lock);
+
+    let handle = std::thread::spawn(move || {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_ = stopped.clone();
+        let server = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped_.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    if stream.set_read_timeout(Some(REQUEST_TIMEOUT)).is_ok() {
+                        let _ = serve_request(stream, &callback, &handlers);
+                    }
+                }
+            }
+        });
+
+        block_on(stop);
+
+        // Locked before the listener is closed, an instance starting
+        // meanwhile binds its socket only after this one is unlinked
+        let lock = lock_socket(&path);
+
+        // Wake the server blocked in accepting
+        stopped.store(true, Ordering::SeqCst);
+        let _ = UnixStream::connect(&path);
+        let _ = server.join();
+        if lock.is_ok() {
+            let _ = std::fs::remove_file(&path);
+        }
+    });
+
+    Ok(handle)
+}
+
+/// Asks the primary instance to perform an action, returns the reply of its
+/// handler. The primary is found by the prefix and the unique name it was
+/// started with.
+///
+/// Fails with `Error::UnknownAction` if the primary has no handler for the
+/// action, and with `Error::Io` if no primary instance is running.
+pub fn send_to_primary(
+    prefix: &str,
+    unique_name: &str,
+    action: &str,
+    payload: &str,
+) -> Result<String, Error> {
+    validate_prefix(prefix)?;
+    let stream = UnixStream::connect(socket_path(prefix, unique_name))?;
+    send_request(stream, action, payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::assert_backend_routes_actions;
+
+    #[test]
+    fn routes_actions_over_unix_socket() {
+        assert_backend_routes_actions();
+    }
+}
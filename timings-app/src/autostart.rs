@@ -0,0 +1,98 @@
+use crate::config::expand_tilde;
+use std::path::Path;
+use std::path::PathBuf;
+
+const AUTOSTART_FILE: &str = "~/.config/autostart/timings-app.desktop";
+
+pub fn autostart_path() -> PathBuf {
+    expand_tilde(AUTOSTART_FILE)
+}
+
+/// Writes the autostart entry starting `exec` with the given arguments
+pub fn install_autostart(exec: &Path, args: &[String]) -> Result<PathBuf, std::io::Error> {
+    let path = autostart_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, desktop_entry(exec, args))?;
+    Ok(path)
+}
+
+/// Removes the autostart entry, returns false if it did not exist
+pub fn uninstall_autostart() -> Result<bool, std::io::Error> {
+    let path = autostart_path();
+    if !path.exists() {
+        return Ok(false);
+    }
+    std::fs::remove_file(&path)?;
+    Ok(true)
+}
+
+/// Contents of the XDG autostart `.desktop` file
+pub fn desktop_entry(exec: &Path, args: &[String]) -> String {
+    let exec_line = std::iter::once(exec.to_string_lossy().to_string())
+        .chain(args.iter().cloned())
+        .map(|arg| quote_exec_arg(&arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "[Desktop Entry]\nType=Application\nName=Project Timings\nComment=Virtual desktop timings \
+         tracker\nExec={}\nTerminal=false\nX-GNOME-Autostart-enabled=true\n",
+        exec_line
+    )
+}
+
+/// Quotes an argument of the `Exec` key as described in the Desktop Entry
+/// specification
+fn quote_exec_arg(arg: &str) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(',
+        ')', '`',
+    ];
+    if !arg.is_empty() && !arg.contains(RESERVED) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        if matches!(c, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    // Backslash is also an escape character of the string value itself
+    quoted.replace('\\', "\\\\")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desktop_entry_contents() {
+        let entry = desktop_entry(
+            Path::new("/home/user/.config/timings/timings-app"),
+            &[
+                "--database".to_string(),
+                "/home/user/.config/timings/timings.db".to_string(),
+                "--config".to_string(),
+                "/home/user/My Config/config.toml".to_string(),
+            ],
+        );
+        assert_eq!(
+            entry,
+            "[Desktop Entry]\nType=Application\nName=Project Timings\nComment=Virtual desktop \
+             timings tracker\nExec=/home/user/.config/timings/timings-app --database \
+             /home/user/.config/timings/timings.db --config \"/home/user/My \
+             Config/config.toml\"\nTerminal=false\nX-GNOME-Autostart-enabled=true\n"
+        );
+    }
+
+    #[test]
+    fn quote_exec_arg_escapes() {
+        assert_eq!(quote_exec_arg("plain"), "plain");
+        assert_eq!(quote_exec_arg(""), "\"\"");
+        assert_eq!(quote_exec_arg("a$b"), "\"a\\\\$b\"");
+    }
+}
@@ -0,0 +1,440 @@
+use crate::quiet::QuietWindow;
+use chrono::NaiveTime;
+use chrono::Weekday;
+use serde::Deserialize;
+use serde::Deserializer;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::path::PathBuf;
+use timings::DailyTarget;
+use timings::Locale;
+use timings::normalize_name;
+
+pub const DEFAULT_CONFIG: &str = "~/.config/timings/config.toml";
+pub const DEFAULT_IDLE_TIMEOUT_SECONDS: u64 = 180;
+
+/// Application configuration, read from a TOML file
+///
+/// All fields are optional, a missing file means default configuration.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub weekly_report: Option<WeeklyReportConfig>,
+    pub overlay_on: OverlayOn,
+    /// Scheduled quiet mode windows, e.g. `quiet = ["09:00-10:00"]`
+    pub quiet: Vec<QuietWindow>,
+    /// How often the overlay totals are refreshed in seconds, defaults to 1
+    pub overlay_refresh_seconds: Option<u64>,
+    /// Overlay size in pixels, defaults to 350 x 200
+    pub overlay_width: Option<u32>,
+    pub overlay_height: Option<u32>,
+    /// Seconds before the overlay hides itself after being shown, 0 keeps
+    /// it open until hidden manually. Defaults to 3
+    pub overlay_hide_delay_secs: Option<u64>,
+    /// Weekly hour cap, the overlay shows this week's progress towards it
+    /// and warns when this week goes over
+    pub weekly_target_hours: Option<f64>,
+    /// Daily hour targets of projects, shown in the overlay
+    pub project_targets: Vec<ProjectTargetConfig>,
+    /// Number and date formatting in reports, overlay and tray
+    pub locale: Locale,
+    /// Read-only HTTP API served in-process, disabled if not set
+    pub http_server: Option<HttpServerConfig>,
+    /// Today's total in the tray tooltip is rounded to minutes, defaults to 1
+    pub tooltip_rounding: Option<u32>,
+    /// Commit pending overlay edits when it loses keyboard focus instead of
+    /// waiting for the debounce, defaults to true
+    pub commit_on_focus_loss: Option<bool>,
+    /// Record desktops without a project as "(unassigned)"
+    pub track_unassigned: bool,
+    /// Include unassigned timings in reports, also set with
+    /// `--include-unassigned`
+    pub include_unassigned: bool,
+    /// Seconds before the user is considered idle, `--idle-timeout`
+    /// overrides this. 0 disables idle monitoring
+    pub idle_timeout_seconds: Option<u64>,
+    /// Seconds the user must idle before the timing is stopped, the idle
+    /// time is then deducted. Defaults to 0, stopping when the idle timeout
+    /// elapses
+    pub min_idle_to_stop_seconds: Option<u64>,
+    /// Wayland seat watched for idling, e.g. "seat0", defaults to the first
+    /// seat
+    pub idle_seat: Option<String>,
+    /// Record time per foreground application of each timing, KDE only
+    pub track_applications: bool,
+    /// Show the overlay only while a key is held, the compositor must call
+    /// the D-Bus `Peek` method on press and release of the key
+    pub peek_mode: bool,
+    /// Tag recorded with each timing, e.g. the hostname, so reports can be
+    /// filtered by device. Timings are not tagged if not set
+    pub source: Option<String>,
+    /// Daily work hours, e.g. `work_hours = "08:00-16:00"`. The overlay
+    /// offers to fill the latest untracked gap of them with the running
+    /// project. Gaps are not looked for if not set
+    pub work_hours: Option<QuietWindow>,
+    /// Timings of a project separated by at most this many seconds are
+    /// merged once a day, keeping the recorded time. Timings are not
+    /// compacted if not set
+    pub compact_gap_seconds: Option<u64>,
+}
+
+/// Which outputs (monitors) the overlay is shown on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlayOn {
+    /// First output only
+    #[default]
+    Primary,
+    /// Same overlay on every output, keyboard input goes to one of them
+    All,
+    /// Output the compositor focuses, the overlay follows it when shown
+    /// again or when outputs come and go
+    Active,
+}
+
+/// Weekly report schedule, e.g.
+///
+/// ```toml
+/// weekly_report = { weekday = "Fri", time = "16:00", dir = "~/Reports" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WeeklyReportConfig {
+    pub weekday: Weekday,
+    #[serde(deserialize_with = "deserialize_hh_mm")]
+    pub time: NaiveTime,
+    pub dir: String,
+}
+
+/// Daily hour target of a project on weekdays, e.g.
+///
+/// ```toml
+/// project_targets = [{ client = "Acme", project = "Website", daily_hours = 2, rollover = true }]
+/// ```
+///
+/// With `rollover` the unmet or exceeded hours move to the next day's target
+/// within the week.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ProjectTargetConfig {
+    pub client: String,
+    pub project: String,
+    pub daily_hours: f64,
+    #[serde(default)]
+    pub rollover: bool,
+}
+
+/// Read-only HTTP API, e.g.
+///
+/// ```toml
+/// http_server = { address = "0.0.0.0:8787", token = "secret" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HttpServerConfig {
+    pub address: SocketAddr,
+    /// Required as `Authorization: Bearer <token>` header if set
+    pub token: Option<String>,
+}
+
+impl WeeklyReportConfig {
+    pub fn dir(&self) -> PathBuf {
+        expand_tilde(&self.dir)
+    }
+}
+
+impl Config {
+    pub fn weekly_target(&self) -> Option<chrono::Duration> {
+        self.weekly_target_hours
+            .map(|hours| chrono::Duration::seconds((hours * 3600.0) as i64))
+    }
+
+    /// Daily target of the project, `None` if not configured
+    pub fn project_target(&self, client: &str, project: &str) -> Option<DailyTarget> {
+        let (client, project) = (normalize_name(client), normalize_name(project));
+        self.project_targets
+            .iter()
+            .find(|t| normalize_name(&t.client) == client && normalize_name(&t.project) == project)
+            .map(|t| DailyTarget {
+                daily: chrono::Duration::seconds((t.daily_hours * 3600.0) as i64),
+                rollover: t.rollover,
+            })
+    }
+
+    pub fn commit_on_focus_loss(&self) -> bool {
+        self.commit_on_focus_loss.unwrap_or(true)
+    }
+
+    pub fn tooltip_rounding_minutes(&self) -> u32 {
+        self.tooltip_rounding.unwrap_or(1).max(1)
+    }
+
+    pub fn min_idle_to_stop(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.min_idle_to_stop_seconds.unwrap_or(0) as i64)
+    }
+
+    /// Delay of hiding the overlay automatically, `None` if disabled
+    pub fn overlay_hide_delay(&self) -> Option<std::time::Duration> {
+        match self.overlay_hide_delay_secs.unwrap_or(3) {
+            0 => None,
+            secs => Some(std::time::Duration::from_secs(secs)),
+        }
+    }
+
+    pub fn overlay_size(&self) -> (u32, u32) {
+        (
+            self.overlay_width.unwrap_or(350),
+            self.overlay_height.unwrap_or(200),
+        )
+    }
+
+    pub fn idle_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(
+            self.idle_timeout_seconds
+                .unwrap_or(DEFAULT_IDLE_TIMEOUT_SECONDS),
+        )
+    }
+
+    pub fn overlay_refresh_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.overlay_refresh_seconds.unwrap_or(1).max(1))
+    }
+
+    /// Loads the configuration from the given path, returns default
+    /// configuration if the file does not exist.
+    pub fn load(path: &str) -> Result<Config, Box<dyn std::error::Error>> {
+        let path = expand_tilde(path);
+        if !path.exists() {
+            log::info!("Config file {:?} does not exist, using defaults", path);
+            return Ok(Config::default());
+        }
+        Self::load_from_file(&path)
+    }
+
+    fn load_from_file(path: &Path) -> Result<Config, Box<dyn std::error::Error>> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&contents)?)
+    }
+
+    pub fn parse(contents: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(contents)
+    }
+}
+
+/// Expands `~` to the home directory
+pub fn expand_tilde(path: &str) -> PathBuf {
+    if path.starts_with("~") {
+        if let Some(home) = std::env::var_os("HOME") {
+            return PathBuf::from(home).join(path.strip_prefix("~/").unwrap_or(&path[1..]));
+        }
+    }
+    PathBuf::from(path)
+}
+
+fn deserialize_hh_mm<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&value, "%H:%M").map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_weekly_report() {
+        let config = Config::parse(
+            r#"weekly_report = { weekday = "Fri", time = "16:00", dir = "~/Reports" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.weekly_report,
+            Some(WeeklyReportConfig {
+                weekday: Weekday::Fri,
+                time: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+                dir: "~/Reports".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_overlay_on() {
+        let config = Config::parse(r#"overlay_on = "all""#).unwrap();
+        assert_eq!(config.overlay_on, OverlayOn::All);
+    }
+
+    #[test]
+    fn parse_quiet() {
+        let config = Config::parse(r#"quiet = ["09:00-10:00", "22:00-06:00"]"#).unwrap();
+        assert_eq!(config.quiet.len(), 2);
+        assert!(Config::parse(r#"quiet = ["09:00"]"#).is_err());
+    }
+
+    #[test]
+    fn parse_min_idle_to_stop_seconds() {
+        let config = Config::parse("min_idle_to_stop_seconds = 600").unwrap();
+        assert_eq!(config.min_idle_to_stop(), chrono::Duration::minutes(10));
+        assert_eq!(
+            Config::default().min_idle_to_stop(),
+            chrono::Duration::zero()
+        );
+    }
+
+    #[test]
+    fn parse_overlay_hide_delay_secs() {
+        let config = Config::parse("overlay_hide_delay_secs = 10").unwrap();
+        assert_eq!(
+            config.overlay_hide_delay(),
+            Some(std::time::Duration::from_secs(10))
+        );
+        assert_eq!(
+            Config::default().overlay_hide_delay(),
+            Some(std::time::Duration::from_secs(3))
+        );
+
+        // Zero disables auto-hide
+        let config = Config::parse("overlay_hide_delay_secs = 0").unwrap();
+        assert_eq!(config.overlay_hide_delay(), None);
+    }
+
+    #[test]
+    fn parse_project_targets() {
+        let config = Config::parse(
+            r#"project_targets = [
+                { client = "Acme", project = "Website", daily_hours = 2, rollover = true },
+                { client = "Acme", project = "Backend", daily_hours = 1.5 },
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.project_target("Acme", "Website"),
+            Some(DailyTarget {
+                daily: chrono::Duration::hours(2),
+                rollover: true,
+            })
+        );
+        assert_eq!(
+            config.project_target("Acme", "Backend"),
+            Some(DailyTarget {
+                daily: chrono::Duration::minutes(90),
+                rollover: false,
+            })
+        );
+        assert_eq!(config.project_target("Acme", "Docs"), None);
+    }
+
+    #[test]
+    fn parse_overlay_refresh_seconds() {
+        let config = Config::parse("overlay_refresh_seconds = 5").unwrap();
+        assert_eq!(config.overlay_refresh_interval().as_secs(), 5);
+        assert_eq!(Config::default().overlay_refresh_interval().as_secs(), 1);
+    }
+
+    #[test]
+    fn parse_weekly_target_hours() {
+        let config = Config::parse("weekly_target_hours = 37.5").unwrap();
+        assert_eq!(
+            config.weekly_target(),
+            Some(chrono::Duration::minutes(37 * 60 + 30))
+        );
+        assert_eq!(Config::default().weekly_target(), None);
+    }
+
+    #[test]
+    fn parse_locale() {
+        let config = Config::parse(
+            r#"
+            [locale]
+            decimal_separator = ","
+            date_format = "%d.%m.%Y"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.locale.decimal_separator, ',');
+        assert_eq!(config.locale.date_format, "%d.%m.%Y");
+        assert_eq!(config.locale.week_format, Locale::default().week_format);
+
+        // Formats which can't format a date are rejected
+        assert!(Config::parse("[locale]\ndate_format = \"%H\"").is_err());
+        assert!(Config::parse("[locale]\nweek_format = \"%Q\"").is_err());
+    }
+
+    #[test]
+    fn parse_http_server() {
+        let config = Config::parse(r#"http_server = { address = "127.0.0.1:8787" }"#).unwrap();
+        assert_eq!(
+            config.http_server,
+            Some(HttpServerConfig {
+                address: "127.0.0.1:8787".parse().unwrap(),
+                token: None,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_tooltip_rounding() {
+        let config = Config::parse("tooltip_rounding = 15").unwrap();
+        assert_eq!(config.tooltip_rounding_minutes(), 15);
+        assert_eq!(Config::default().tooltip_rounding_minutes(), 1);
+    }
+
+    #[test]
+    fn parse_track_unassigned() {
+        let config = Config::parse("track_unassigned = true").unwrap();
+        assert!(config.track_unassigned);
+        assert!(!config.include_unassigned);
+    }
+
+    #[test]
+    fn parse_idle_seat() {
+        let config = Config::parse(r#"idle_seat = "seat1""#).unwrap();
+        assert_eq!(config.idle_seat.as_deref(), Some("seat1"));
+    }
+
+    #[test]
+    fn parse_source() {
+        let config = Config::parse(r#"source = "laptop""#).unwrap();
+        assert_eq!(config.source.as_deref(), Some("laptop"));
+        assert_eq!(Config::default().source, None);
+    }
+
+    #[test]
+    fn parse_work_hours() {
+        let config = Config::parse(r#"work_hours = "08:00-16:00""#).unwrap();
+        let work_hours = config.work_hours.unwrap();
+        assert_eq!(work_hours.start, NaiveTime::from_hms_opt(8, 0, 0).unwrap());
+        assert_eq!(work_hours.end, NaiveTime::from_hms_opt(16, 0, 0).unwrap());
+        assert!(Config::parse(r#"work_hours = "08:00""#).is_err());
+    }
+
+    #[test]
+    fn parse_compact_gap_seconds() {
+        let config = Config::parse("compact_gap_seconds = 120").unwrap();
+        assert_eq!(config.compact_gap_seconds, Some(120));
+        assert_eq!(Config::default().compact_gap_seconds, None);
+    }
+
+    #[test]
+    fn parse_track_applications() {
+        let config = Config::parse("track_applications = true").unwrap();
+        assert!(config.track_applications);
+        assert!(!Config::default().track_applications);
+    }
+
+    #[test]
+    fn parse_peek_mode() {
+        let config = Config::parse("peek_mode = true").unwrap();
+        assert!(config.peek_mode);
+        assert!(!Config::default().peek_mode);
+    }
+
+    #[test]
+    fn parse_commit_on_focus_loss() {
+        let config = Config::parse("commit_on_focus_loss = false").unwrap();
+        assert!(!config.commit_on_focus_loss());
+        assert!(Config::default().commit_on_focus_loss());
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::config::Config;
+
+/// Where the configuration is read from, and the command line options
+/// applied over it, so that reloading gives the same result as starting
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigSource {
+    pub path: String,
+    /// `--include-unassigned`
+    pub include_unassigned: bool,
+    /// `--idle-timeout`, overrides the configuration if given
+    pub idle_timeout_seconds: Option<u64>,
+}
+
+impl ConfigSource {
+    pub fn load(&self) -> Result<Config, Box<dyn std::error::Error>> {
+        let mut config = Config::load(&self.path)?;
+        self.apply(&mut config);
+        Ok(config)
+    }
+
+    fn apply(&self, config: &mut Config) {
+        config.include_unassigned |= self.include_unassigned;
+        if let Some(seconds) = self.idle_timeout_seconds {
+            config.idle_timeout_seconds = Some(seconds);
+        }
+    }
+}
+
+/// Changes of a reloaded configuration which are applied live, the rest of
+/// the settings are read from the configuration when used
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigChanges {
+    /// New overlay size, a shown overlay is shown again
+    pub overlay_size: Option<(u32, u32)>,
+    /// Overlay settings read when it's shown, e.g. the refresh interval
+    pub overlay: bool,
+    /// New idle timeout of the running idle monitor, zero stops it
+    pub idle_timeout: Option<std::time::Duration>,
+    /// Changed settings which take effect after a restart
+    pub needs_restart: Vec<&'static str>,
+}
+
+pub fn config_changes(old: &Config, new: &Config) -> ConfigChanges {
+    let mut needs_restart = Vec::new();
+    if old.http_server != new.http_server {
+        needs_restart.push("http_server");
+    }
+    if old.track_applications != new.track_applications {
+        needs_restart.push("track_applications");
+    }
+    // Schedule changes are read when checking, the checking starts only if
+    // a report is configured
+    if old.weekly_report.is_none() && new.weekly_report.is_some() {
+        needs_restart.push("weekly_report");
+    }
+    // Likewise the gap is read when compacting
+    if old.compact_gap_seconds.is_none() && new.compact_gap_seconds.is_some() {
+        needs_restart.push("compact_gap_seconds");
+    }
+    if old.idle_seat != new.idle_seat {
+        needs_restart.push("idle_seat");
+    }
+    if old.min_idle_to_stop_seconds != new.min_idle_to_stop_seconds {
+        needs_restart.push("min_idle_to_stop_seconds");
+    }
+    ConfigChanges {
+        overlay_size: (old.overlay_size() != new.overlay_size()).then(|| new.overlay_size()),
+        overlay: old.overlay_on != new.overlay_on
+            || old.overlay_refresh_interval() != new.overlay_refresh_interval(),
+        idle_timeout: (old.idle_timeout_seconds != new.idle_timeout_seconds)
+            .then(|| new.idle_timeout()),
+        needs_restart,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> ConfigSource {
+        ConfigSource {
+            path: String::new(),
+            include_unassigned: false,
+            idle_timeout_seconds: None,
+        }
+    }
+
+    fn reload(old: &str, new: &str) -> ConfigChanges {
+        reload_with(&source(), old, new)
+    }
+
+    fn reload_with(source: &ConfigSource, old: &str, new: &str) -> ConfigChanges {
+        let (mut old, mut new) = (Config::parse(old).unwrap(), Config::parse(new).unwrap());
+        source.apply(&mut old);
+        source.apply(&mut new);
+        config_changes(&old, &new)
+    }
+
+    #[test]
+    fn changed_overlay_size_is_applied() {
+        let changes = reload("", "overlay_width = 400\noverlay_height = 250");
+        assert_eq!(changes.overlay_size, Some((400, 250)));
+        assert!(changes.needs_restart.is_empty());
+
+        let changes = reload("overlay_width = 400", "overlay_width = 400");
+        assert_eq!(changes, ConfigChanges::default());
+    }
+
+    #[test]
+    fn changed_idle_timeout_is_applied() {
+        let changes = reload("", "idle_timeout_seconds = 60");
+        assert_eq!(
+            changes.idle_timeout,
+            Some(std::time::Duration::from_secs(60))
+        );
+
+        let changes = reload("idle_timeout_seconds = 60", "");
+        assert_eq!(
+            changes.idle_timeout,
+            Some(std::time::Duration::from_secs(180))
+        );
+
+        let changes = reload("", "idle_timeout_seconds = 0");
+        assert_eq!(changes.idle_timeout, Some(std::time::Duration::ZERO));
+    }
+
+    #[test]
+    fn command_line_idle_timeout_overrides_the_config() {
+        let source = ConfigSource {
+            idle_timeout_seconds: Some(30),
+            ..source()
+        };
+        let changes = reload_with(&source, "", "idle_timeout_seconds = 60");
+        assert_eq!(changes.idle_timeout, None);
+
+        let mut config = Config::parse("idle_timeout_seconds = 60").unwrap();
+        source.apply(&mut config);
+        assert_eq!(config.idle_timeout(), std::time::Duration::from_secs(30));
+    }
+
+    #[test]
+    fn restart_is_needed_for_listeners() {
+        let changes = reload("", "track_applications = true");
+        assert_eq!(changes.needs_restart, vec!["track_applications"]);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::AppMessage;
+use crate::peek::PeekKey;
+use tokio::sync::mpsc::UnboundedSender;
+use zbus::Connection;
+use zbus::interface;
+
+pub const DBUS_NAME: &str = "io.github.ciantic.ProjectTimings";
+pub const DBUS_PATH: &str = "/io/github/ciantic/ProjectTimings";
+
+/// D-Bus interface for controlling the running app, e.g. from scripts
+///
+/// ```sh
+/// busctl --user call io.github.ciantic.ProjectTimings \
+///     /io/github/ciantic/ProjectTimings \
+///     io.github.ciantic.ProjectTimings SetQuietMode b true
+/// busctl --user call io.github.ciantic.ProjectTimings \
+///     /io/github/ciantic/ProjectTimings \
+///     io.github.ciantic.ProjectTimings InhibitIdleTracking u 60
+/// ```
+///
+/// A global shortcut can open the overlay for the keyboard, Tab moves
+/// between the fields, Enter commits and Escape closes:
+///
+/// ```text
+/// bindsym Mod4+t exec busctl --user call ... FocusOverlay
+/// ```
+///
+/// With `peek_mode` the overlay is shown while a key is held, e.g. on Sway:
+///
+/// ```text
+/// bindsym --no-repeat Mod4+p exec busctl --user call ... Peek b true
+/// bindsym --release Mod4+p exec busctl --user call ... Peek b false
+/// ```
+struct TimingsService {
+    sender: UnboundedSender<AppMessage>,
+}
+
+#[interface(name = "io.github.ciantic.ProjectTimings")]
+impl TimingsService {
+    /// Suppresses overlays and notifications while tracking continues
+    fn set_quiet_mode(&self, quiet: bool) {
+        let _ = self.sender.send(AppMessage::SetQuietMode(quiet));
+    }
+
+    /// Keeps the timing running when the user idles for the given minutes,
+    /// e.g. from a remote session script. Zero minutes ends the inhibit.
+    fn inhibit_idle_tracking(&self, minutes: u32) {
+        let _ = self.sender.send(AppMessage::InhibitIdleTracking(minutes));
+    }
+
+    /// Shows the overlay with the keyboard focus on the client field
+    fn focus_overlay(&self) {
+        let _ = self.sender.send(AppMessage::FocusOverlay);
+    }
+
+    /// Peek key pressed or released, shows the overlay while held
+    fn peek(&self, held: bool) {
+        let _ = self.sender.send(AppMessage::Peek(if held {
+            PeekKey::Down
+        } else {
+            PeekKey::Up
+        }));
+    }
+}
+
+/// Serves the app D-Bus interface, the connection must be kept alive
+pub async fn serve_dbus(sender: UnboundedSender<AppMessage>) -> Result<Connection, zbus::Error> {
+    zbus::connection::Builder::session()?
+        .name(DBUS_NAME)?
+        .serve_at(DBUS_PATH, TimingsService { sender })?
+        .build()
+        .await
+}
@@ -0,0 +1,150 @@
+use futures::Stream;
+use futures::StreamExt;
+use virtual_desktops::DesktopId;
+use virtual_desktops::Error;
+use virtual_desktops::KDEVirtualDesktopController;
+use virtual_desktops::ManualDesktopController;
+use virtual_desktops::VirtualDesktopController;
+use virtual_desktops::VirtualDesktopMessage;
+
+/// Desktop controller selected on startup
+///
+/// With `--no-desktop-tracking` the timing follows a single manual desktop
+/// whose name is edited in the overlay, instead of the KDE virtual desktops.
+#[derive(Debug, Clone)]
+pub enum DesktopController {
+    Kde(KDEVirtualDesktopController),
+    Manual(ManualDesktopController),
+}
+
+impl DesktopController {
+    pub async fn new(desktop_tracking: bool) -> Result<Self, Error> {
+        if desktop_tracking {
+            Ok(DesktopController::Kde(
+                KDEVirtualDesktopController::new().await?,
+            ))
+        } else {
+            Ok(DesktopController::Manual(ManualDesktopController::new()))
+        }
+    }
+
+    pub fn is_manual(&self) -> bool {
+        matches!(self, DesktopController::Manual(_))
+    }
+}
+
+impl VirtualDesktopController for DesktopController {
+    async fn listen(&mut self) -> Result<impl Stream<Item = VirtualDesktopMessage>, Error> {
+        Ok(match self {
+            DesktopController::Kde(controller) => controller.listen().await?.boxed(),
+            DesktopController::Manual(controller) => controller.listen().await?.boxed(),
+        })
+    }
+
+    async fn update_desktop_name(
+        &mut self,
+        desktop_id: DesktopId,
+        desktop_name: &str,
+    ) -> Result<(), Error> {
+        match self {
+            DesktopController::Kde(controller) => {
+                controller
+                    .update_desktop_name(desktop_id, desktop_name)
+                    .await
+            }
+            DesktopController::Manual(controller) => {
+                controller
+                    .update_desktop_name(desktop_id, desktop_name)
+                    .await
+            }
+        }
+    }
+
+    async fn get_desktop_name(&self, desktop_id: &DesktopId) -> Result<String, Error> {
+        match self {
+            DesktopController::Kde(controller) => controller.get_desktop_name(desktop_id).await,
+            DesktopController::Manual(controller) => controller.get_desktop_name(desktop_id).await,
+        }
+    }
+
+    async fn get_current_desktop(&self) -> Result<DesktopId, Error> {
+        match self {
+            DesktopController::Kde(controller) => controller.get_current_desktop().await,
+            DesktopController::Manual(controller) => controller.get_current_desktop().await,
+        }
+    }
+
+    async fn get_desktops(&self) -> Result<Vec<(DesktopId, String)>, Error> {
+        match self {
+            DesktopController::Kde(controller) => controller.get_desktops().await,
+            DesktopController::Manual(controller) => controller.get_desktops().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::desktop_name::validate_desktop_name;
+    use chrono::TimeZone;
+    use chrono::Utc;
+    use sqlx::SqlitePool;
+    use timings::TimingsMutations;
+    use timings::TimingsQueries;
+    use timings::TimingsRecorder;
+    use timings::TimingsRecording;
+
+    #[tokio::test]
+    async fn manual_start_and_stop_without_virtual_desktops() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        pool.acquire()
+            .await
+            .unwrap()
+            .create_timings_database()
+            .await
+            .unwrap();
+        let mut recorder = TimingsRecorder::new(pool.clone(), chrono::Duration::seconds(3));
+
+        let mut controller = DesktopController::new(false).await.unwrap();
+        assert!(controller.is_manual());
+        let mut stream = controller.clone().listen().await.unwrap();
+
+        // Overlay fields are written to the manual desktop name
+        let desktop = controller.get_current_desktop().await.unwrap();
+        controller
+            .update_desktop_name(desktop.clone(), "Acme: Website")
+            .await
+            .unwrap();
+        let Some(VirtualDesktopMessage::DesktopNameChanged(id, name)) = stream.next().await else {
+            panic!("Expected desktop name change");
+        };
+        assert_eq!(id, desktop);
+
+        let start = Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap();
+        let (client, project) = validate_desktop_name(&name).unwrap();
+        recorder.start_timing(client, project, start);
+        assert!(recorder.is_running());
+
+        recorder.stop_timing(start + chrono::Duration::minutes(30));
+        assert!(!recorder.is_running());
+        recorder
+            .write_timings(start + chrono::Duration::minutes(31))
+            .await
+            .unwrap();
+
+        let timings = pool
+            .acquire()
+            .await
+            .unwrap()
+            .get_timings(None)
+            .await
+            .unwrap();
+        assert_eq!(timings.len(), 1);
+        assert_eq!(timings[0].client, "Acme");
+        assert_eq!(timings[0].project, "Website");
+        assert_eq!(
+            timings[0].end - timings[0].start,
+            chrono::Duration::minutes(30)
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use timings::UNASSIGNED_PROJECT;
+use timings::normalize_name;
+
+/// Why a desktop name can't be tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DesktopNameError {
+    MissingColon,
+    EmptyClient,
+    EmptyProject,
+}
+
+impl fmt::Display for DesktopNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DesktopNameError::MissingColon => {
+                write!(f, "missing colon, expected \"client: project\"")
+            }
+            DesktopNameError::EmptyClient => write!(f, "empty client"),
+            DesktopNameError::EmptyProject => write!(f, "empty project"),
+        }
+    }
+}
+
+impl std::error::Error for DesktopNameError {}
+
+/// Validates a "client: project" desktop name, returns the normalized client
+/// and project.
+pub fn validate_desktop_name(desktop_name: &str) -> Result<(String, String), DesktopNameError> {
+    let (client, project) = desktop_name
+        .split_once(':')
+        .ok_or(DesktopNameError::MissingColon)?;
+    let client = normalize_name(client);
+    let project = normalize_name(project);
+    if client.is_empty() {
+        return Err(DesktopNameError::EmptyClient);
+    }
+    if project.is_empty() {
+        return Err(DesktopNameError::EmptyProject);
+    }
+    Ok((client, project))
+}
+
+/// Client and project to record for a desktop name
+///
+/// With `track_unassigned`, names without a colon are recorded with the
+/// desktop name as the client and `UNASSIGNED_PROJECT` as the project.
+pub fn timing_for_desktop_name(
+    desktop_name: &str,
+    track_unassigned: bool,
+) -> Result<(String, String), DesktopNameError> {
+    match validate_desktop_name(desktop_name) {
+        Err(DesktopNameError::MissingColon) if track_unassigned => {
+            let client = normalize_name(desktop_name);
+            if client.is_empty() {
+                return Err(DesktopNameError::EmptyClient);
+            }
+            Ok((client, UNASSIGNED_PROJECT.to_string()))
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid() {
+        assert_eq!(
+            validate_desktop_name("Acme:  Website  redesign "),
+            Ok(("Acme".to_string(), "Website redesign".to_string()))
+        );
+        // Only the first colon separates client and project
+        assert_eq!(
+            validate_desktop_name("Acme: API: v2"),
+            Ok(("Acme".to_string(), "API: v2".to_string()))
+        );
+    }
+
+    #[test]
+    fn missing_colon() {
+        assert_eq!(
+            validate_desktop_name("Acme Website"),
+            Err(DesktopNameError::MissingColon)
+        );
+    }
+
+    #[test]
+    fn empty_components() {
+        assert_eq!(
+            validate_desktop_name(" : Website"),
+            Err(DesktopNameError::EmptyClient)
+        );
+        assert_eq!(
+            validate_desktop_name("Acme:"),
+            Err(DesktopNameError::EmptyProject)
+        );
+        assert_eq!(
+            validate_desktop_name(":"),
+            Err(DesktopNameError::EmptyClient)
+        );
+    }
+
+    #[test]
+    fn unassigned_not_tracked_by_default() {
+        assert_eq!(
+            timing_for_desktop_name("Browsing", false),
+            Err(DesktopNameError::MissingColon)
+        );
+        assert_eq!(
+            timing_for_desktop_name("Acme: Website", false),
+            Ok(("Acme".to_string(), "Website".to_string()))
+        );
+    }
+
+    #[test]
+    fn unassigned_tracked() {
+        assert_eq!(
+            timing_for_desktop_name(" Desktop  2 ", true),
+            Ok(("Desktop 2".to_string(), UNASSIGNED_PROJECT.to_string()))
+        );
+        assert_eq!(
+            timing_for_desktop_name("Acme: Website", true),
+            Ok(("Acme".to_string(), "Website".to_string()))
+        );
+        assert_eq!(
+            timing_for_desktop_name("  ", true),
+            Err(DesktopNameError::EmptyClient)
+        );
+        assert_eq!(
+            timing_for_desktop_name("Acme:", true),
+            Err(DesktopNameError::EmptyProject)
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use crate::desktop_controller::DesktopController;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use virtual_desktops::DesktopId;
+use virtual_desktops::Error;
+use virtual_desktops::VirtualDesktopController;
+use virtual_desktops::VirtualDesktopMessage;
+
+#[derive(Debug, Default)]
+struct DesktopNames {
+    // Incremented on each rename not made by the overlay
+    generation: u64,
+    // Last name seen in a name change or written by the overlay
+    known: Option<String>,
+    // Written by the overlay, its name change is not an external rename
+    written: Option<String>,
+}
+
+/// Keeps the debounced desktop renames of the overlay from overwriting the
+/// renames made meanwhile elsewhere, e.g. in the KDE settings
+///
+/// Shared between the overlay and the virtual desktop message handler, which
+/// passes every message to `handle_message`.
+///
+/// Renames are skipped while read-only, when another instance with another
+/// database writes the desktop names, see `DESKTOP_WRITER_ROLE`.
+#[derive(Debug, Clone, Default)]
+pub struct RenameGuard {
+    desktops: Arc<Mutex<HashMap<DesktopId, DesktopNames>>>,
+    read_only: Arc<AtomicBool>,
+}
+
+/// Role of the instance writing the desktop names, one per session
+pub const DESKTOP_WRITER_ROLE: &str = "timings-app.desktop-writer";
+
+/// Edit of a desktop name in the overlay, from its first change until the
+/// overlay loses the keyboard
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenameEdit {
+    desktop_id: DesktopId,
+    // Generation of the desktop name when the edit began
+    generation: u64,
+}
+
+impl RenameEdit {
+    pub fn desktop_id(&self) -> &DesktopId {
+        &self.desktop_id
+    }
+}
+
+impl RenameGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::Relaxed);
+    }
+
+    /// True while another instance writes the desktop names
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    /// Begins an edit of the desktop name, renames made elsewhere from now on
+    /// are not overwritten by it
+    pub fn begin_edit(&self, desktop_id: &DesktopId) -> RenameEdit {
+        let desktops = self.desktops.lock().unwrap();
+        RenameEdit {
+            desktop_id: desktop_id.clone(),
+            generation: desktops.get(desktop_id).map_or(0, |names| names.generation),
+        }
+    }
+
+    /// Records the name changes of the virtual desktop messages
+    pub fn handle_message(&self, message: &VirtualDesktopMessage) {
+        if let VirtualDesktopMessage::DesktopNameChanged(desktop_id, name) = message {
+            self.name_changed(desktop_id, name);
+        }
+    }
+
+    fn name_changed(&self, desktop_id: &DesktopId, name: &str) {
+        let mut desktops = self.desktops.lock().unwrap();
+        let names = desktops.entry(desktop_id.clone()).or_default();
+        if names.written.as_deref() == Some(name) {
+            names.written = None;
+        } else {
+            names.generation += 1;
+        }
+        names.known = Some(name.to_string());
+    }
+
+    /// Renames the desktop unless it was renamed elsewhere after the edit
+    /// began, returns true if renamed.
+    ///
+    /// The current name is read again, as the name change of an external
+    /// rename may not have been handled yet.
+    pub async fn rename_if_unchanged(
+        &self,
+        controller: &mut DesktopController,
+        edit: &RenameEdit,
+        name: &str,
+    ) -> Result<bool, Error> {
+        let desktop_id = edit.desktop_id.clone();
+        if self.is_read_only() {
+            log::info!(
+                "Another instance writes the desktop names, not renaming to '{}'",
+                name
+            );
+            return Ok(false);
+        }
+        let current = controller.get_desktop_name(&desktop_id).await?;
+        {
+            let mut desktops = self.desktops.lock().unwrap();
+            let names = desktops.entry(desktop_id.clone()).or_default();
+            let renamed_since = names.generation != edit.generation
+                || names.known.as_ref().is_some_and(|known| *known != current);
+            if renamed_since {
+                log::info!(
+                    "Desktop was renamed to '{}' while editing, not renaming to '{}'",
+                    current,
+                    name
+                );
+                return Ok(false);
+            }
+            if current == name {
+                return Ok(true);
+            }
+            names.written = Some(name.to_string());
+            names.known = Some(name.to_string());
+        }
+        controller.update_desktop_name(desktop_id, name).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Stream;
+    use futures::StreamExt;
+
+    async fn manual_desktop() -> (
+        DesktopController,
+        DesktopId,
+        impl Stream<Item = VirtualDesktopMessage>,
+    ) {
+        let mut controller = DesktopController::new(false).await.unwrap();
+        let desktop = controller.get_current_desktop().await.unwrap();
+        let messages = controller.listen().await.unwrap();
+        (controller, desktop, messages)
+    }
+
+    /// Handles the next message like the app's virtual desktop handler
+    async fn handle_next_message(
+        guard: &RenameGuard,
+        messages: &mut (impl Stream<Item = VirtualDesktopMessage> + Unpin),
+    ) {
+        guard.handle_message(&messages.next().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn skips_rename_after_external_rename() {
+        let (mut controller, desktop, messages) = manual_desktop().await;
+        let mut messages = std::pin::pin!(messages);
+        let guard = RenameGuard::new();
+
+        // Edit begins, its first change is written
+        let edit = guard.begin_edit(&desktop);
+        assert!(
+            guard
+                .rename_if_unchanged(&mut controller, &edit, "Acme: W")
+                .await
+                .unwrap()
+        );
+        handle_next_message(&guard, &mut messages).await;
+
+        // Renamed in the settings while editing
+        controller
+            .clone()
+            .update_desktop_name(desktop.clone(), "Other: Docs")
+            .await
+            .unwrap();
+        handle_next_message(&guard, &mut messages).await;
+
+        // Next change of the same edit
+        assert!(
+            !guard
+                .rename_if_unchanged(&mut controller, &edit, "Acme: Web")
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            controller.get_desktop_name(&desktop).await.unwrap(),
+            "Other: Docs"
+        );
+
+        // Next edit begins from the external name
+        let edit = guard.begin_edit(&desktop);
+        assert!(
+            guard
+                .rename_if_unchanged(&mut controller, &edit, "Other: Docs v2")
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn own_renames_do_not_block_the_edit() {
+        let (mut controller, desktop, messages) = manual_desktop().await;
+        let mut messages = std::pin::pin!(messages);
+        let guard = RenameGuard::new();
+        let edit = guard.begin_edit(&desktop);
+
+        assert!(
+            guard
+                .rename_if_unchanged(&mut controller, &edit, "Acme: Web")
+                .await
+                .unwrap()
+        );
+        // Name change of the overlay's own rename arrives during the edit
+        handle_next_message(&guard, &mut messages).await;
+
+        assert!(
+            guard
+                .rename_if_unchanged(&mut controller, &edit, "Acme: Website")
+                .await
+                .unwrap()
+        );
+        handle_next_message(&guard, &mut messages).await;
+        assert_eq!(
+            controller.get_desktop_name(&desktop).await.unwrap(),
+            "Acme: Website"
+        );
+        assert_eq!(guard.begin_edit(&desktop), edit);
+    }
+
+    #[tokio::test]
+    async fn skips_rename_while_read_only() {
+        let (mut controller, desktop, _messages) = manual_desktop().await;
+        let guard = RenameGuard::new();
+        let overlay_guard = guard.clone();
+        let edit = guard.begin_edit(&desktop);
+        assert!(!guard.is_read_only());
+
+        // Another instance holds the desktop writer role
+        guard.set_read_only(true);
+        assert!(
+            !overlay_guard
+                .rename_if_unchanged(&mut controller, &edit, "Acme: Web")
+                .await
+                .unwrap()
+        );
+        assert_ne!(
+            controller.get_desktop_name(&desktop).await.unwrap(),
+            "Acme: Web"
+        );
+
+        // Taken over when the other instance exits
+        guard.set_read_only(false);
+        assert!(
+            overlay_guard
+                .rename_if_unchanged(&mut controller, &edit, "Acme: Web")
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_rename_when_name_change_is_not_handled_yet() {
+        let (mut controller, desktop, _messages) = manual_desktop().await;
+        let guard = RenameGuard::new();
+        let edit = guard.begin_edit(&desktop);
+        guard
+            .rename_if_unchanged(&mut controller, &edit, "Acme: Web")
+            .await
+            .unwrap();
+
+        // Renamed elsewhere, the name change is still in the message queue
+        controller
+            .clone()
+            .update_desktop_name(desktop.clone(), "Other: Docs")
+            .await
+            .unwrap();
+
+        assert!(
+            !guard
+                .rename_if_unchanged(&mut controller, &edit, "Acme: Website")
+                .await
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,128 @@
+/// Choice on the exit confirmation shown when writing timings fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitChoice {
+    Retry,
+    QuitAnyway,
+    Cancel,
+    /// No choice was made in time, retry and quit regardless of the result
+    TimedOut,
+}
+
+/// What the app should do next while exiting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExitDecision {
+    /// Write the timings
+    Flush,
+    Quit,
+    /// Ask the user what to do, writing failed with the error
+    Confirm(String),
+    /// Exit was cancelled, keep running
+    Stay,
+}
+
+/// Decides how exiting proceeds, so that unwritten timings are not lost
+/// unknowingly
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExitFlow {
+    error: Option<String>,
+    quit_on_failure: bool,
+}
+
+impl ExitFlow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Error shown on the confirmation, `None` if not confirming
+    pub fn confirmation(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Decision after writing the timings
+    pub fn flushed(&mut self, result: Result<(), String>) -> ExitDecision {
+        match result {
+            Ok(()) => ExitDecision::Quit,
+            Err(e) if self.quit_on_failure => {
+                log::error!("Quitting with unwritten timings: {}", e);
+                ExitDecision::Quit
+            }
+            Err(e) => {
+                self.error = Some(e.clone());
+                ExitDecision::Confirm(e)
+            }
+        }
+    }
+
+    /// Decision after the user chose on the confirmation
+    pub fn choose(&mut self, choice: ExitChoice) -> ExitDecision {
+        self.error = None;
+        match choice {
+            ExitChoice::Retry => ExitDecision::Flush,
+            ExitChoice::QuitAnyway => ExitDecision::Quit,
+            ExitChoice::Cancel => ExitDecision::Stay,
+            ExitChoice::TimedOut => {
+                self.quit_on_failure = true;
+                ExitDecision::Flush
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quits_when_flush_succeeds() {
+        let mut flow = ExitFlow::new();
+        assert_eq!(flow.flushed(Ok(())), ExitDecision::Quit);
+        assert_eq!(flow.confirmation(), None);
+    }
+
+    #[test]
+    fn confirms_when_flush_fails() {
+        let mut flow = ExitFlow::new();
+        assert_eq!(
+            flow.flushed(Err("disk full".to_string())),
+            ExitDecision::Confirm("disk full".to_string())
+        );
+        assert_eq!(flow.confirmation(), Some("disk full"));
+    }
+
+    #[test]
+    fn retry_confirms_again_on_failure() {
+        let mut flow = ExitFlow::new();
+        flow.flushed(Err("disk full".to_string()));
+        assert_eq!(flow.choose(ExitChoice::Retry), ExitDecision::Flush);
+        assert_eq!(flow.confirmation(), None);
+        assert_eq!(
+            flow.flushed(Err("still full".to_string())),
+            ExitDecision::Confirm("still full".to_string())
+        );
+        assert_eq!(flow.choose(ExitChoice::Retry), ExitDecision::Flush);
+        assert_eq!(flow.flushed(Ok(())), ExitDecision::Quit);
+    }
+
+    #[test]
+    fn quit_anyway_and_cancel() {
+        let mut flow = ExitFlow::new();
+        flow.flushed(Err("disk full".to_string()));
+        assert_eq!(flow.choose(ExitChoice::QuitAnyway), ExitDecision::Quit);
+
+        let mut flow = ExitFlow::new();
+        flow.flushed(Err("disk full".to_string()));
+        assert_eq!(flow.choose(ExitChoice::Cancel), ExitDecision::Stay);
+        assert_eq!(flow.confirmation(), None);
+    }
+
+    #[test]
+    fn timeout_retries_then_quits() {
+        let mut flow = ExitFlow::new();
+        flow.flushed(Err("disk full".to_string()));
+        assert_eq!(flow.choose(ExitChoice::TimedOut), ExitDecision::Flush);
+        assert_eq!(
+            flow.flushed(Err("disk full".to_string())),
+            ExitDecision::Quit
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use timings::Timing;
+
+/// Gaps up to this long are not offered for filling, see
+/// `timings::is_tracking_gap`
+pub const MIN_FILL_GAP_MINUTES: i64 = 15;
+
+/// Manual timing attributing the untracked gap to the project
+pub fn fill_gap_timing(gap: (DateTime<Utc>, DateTime<Utc>), client: &str, project: &str) -> Timing {
+    Timing {
+        client: client.trim().to_string(),
+        project: project.trim().to_string(),
+        start: gap.0,
+        end: gap.1,
+    }
+}
+
+/// Label of the overlay button, e.g. "Fill 12:00–13:30 with Website"
+pub fn fill_gap_label<Tz: TimeZone>(
+    gap: (DateTime<Utc>, DateTime<Utc>),
+    timezone: &Tz,
+    project: &str,
+) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    format!(
+        "Fill {}–{} with {}",
+        gap.0.with_timezone(timezone).format("%H:%M"),
+        gap.1.with_timezone(timezone).format("%H:%M"),
+        project.trim()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 4, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn timing_spans_the_gap() {
+        let timing = fill_gap_timing((at(12, 0), at(13, 30)), " Acme ", "Website ");
+        assert_eq!(
+            timing,
+            Timing {
+                client: "Acme".to_string(),
+                project: "Website".to_string(),
+                start: at(12, 0),
+                end: at(13, 30),
+            }
+        );
+    }
+
+    #[test]
+    fn label_in_local_time() {
+        let helsinki = FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(
+            fill_gap_label((at(12, 0), at(13, 30)), &helsinki, "Website"),
+            "Fill 14:00–15:30 with Website"
+        );
+    }
+}
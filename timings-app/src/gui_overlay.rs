@@ -1,7 +1,27 @@
 use crate::AppMessage;
 use crate::TimingsApp;
-use crate::parse_desktop_name;
+use crate::WRITE_TIMINGS_INTERVAL;
+use crate::config::OverlayOn;
+use crate::desktop_controller::DesktopController;
+use crate::desktop_name::validate_desktop_name;
+use crate::desktop_rename::RenameEdit;
+use crate::desktop_rename::RenameGuard;
+use crate::exit_flow::ExitChoice;
+use crate::gap_fill::MIN_FILL_GAP_MINUTES;
+use crate::gap_fill::fill_gap_label;
+use crate::gap_fill::fill_gap_timing;
+use crate::overlay_focus::OverlayField;
+use crate::overlay_focus::OverlayKey;
+use crate::overlay_focus::OverlayKeyAction;
+use crate::overlay_focus::editable_fields;
+use crate::overlay_focus::overlay_key_action;
+use crate::utils::flush_debounced;
+use crate::utils::reconcile;
 use crate::utils::run_debounced_spawn;
+use crate::utils::week_column_labels;
+use crate::utils::week_range;
+use crate::write_status::write_status;
+use chrono::DateTime;
 use chrono::Local;
 use chrono::NaiveDate;
 use chrono::Utc;
@@ -9,6 +29,7 @@ use egui::CentralPanel;
 use egui::Color32;
 use egui::Context;
 use egui::Pos2;
+use smithay_client_toolkit::reexports::client::protocol::wl_output::WlOutput;
 use smithay_client_toolkit::seat::pointer::PointerEventKind;
 use smithay_client_toolkit::shell::WaylandSurface;
 use smithay_client_toolkit::shell::wlr_layer::Anchor;
@@ -16,10 +37,13 @@ use smithay_client_toolkit::shell::wlr_layer::KeyboardInteractivity;
 use smithay_client_toolkit::shell::wlr_layer::Layer;
 use smithay_client_toolkit::shell::wlr_layer::LayerSurface;
 use std::collections::HashMap;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
 use timings::TimingsRecording;
+use timings::UNASSIGNED_PROJECT;
+use timings::progress_fraction;
 use tokio::sync::mpsc::UnboundedSender;
 use virtual_desktops::DesktopId;
-use virtual_desktops::KDEVirtualDesktopController;
 use virtual_desktops::VirtualDesktopController;
 use virtual_desktops::VirtualDesktopMessage;
 use wayapp::Application;
@@ -29,6 +53,7 @@ use wayapp::WaylandEvent;
 #[derive(Debug, PartialEq, Clone)]
 pub enum GuiOverlayEvent {
     UpdateTotalsTimer,
+    WeekOffsetChanged,
     UpdateSummaryCache {
         day: NaiveDate,
         client: String,
@@ -40,15 +65,128 @@ pub enum GuiOverlayEvent {
         project: String,
         summary: String,
     },
+    /// Marks the project billable or not
+    SetBillable {
+        client: String,
+        project: String,
+        billable: bool,
+    },
+    /// Attributes the untracked gap to the project
+    FillGap {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        client: String,
+        project: String,
+    },
+}
+
+/// Debounce id of writing the edited client and project to the desktop name
+const UPDATE_DESKTOP: &str = "update_desktop";
+/// Debounce id of writing the edited summary to the database
+const UPDATE_SUMMARY_DATABASE: &str = "update_summary_database";
+
+/// Commits the edits still waiting for their debounce
+async fn flush_pending_edits() {
+    for id in [UPDATE_DESKTOP, UPDATE_SUMMARY_DATABASE] {
+        if let Some(handle) = flush_debounced(id) {
+            handle.await.ok();
+        }
+    }
+}
+
+/// Egui id of the overlay text field, for moving the focus
+fn field_id(field: OverlayField) -> egui::Id {
+    egui::Id::new(("overlay_field", field))
+}
+
+/// Takes the first of the keyboard-only keys pressed this frame
+fn take_overlay_key(ctx: &Context) -> Option<OverlayKey> {
+    use egui::Key;
+    use egui::Modifiers;
+    // Shifted and alt keys first, a key without modifiers matches them too
+    let keys = [
+        (Modifiers::SHIFT, Key::Tab, OverlayKey::ShiftTab),
+        (Modifiers::NONE, Key::Tab, OverlayKey::Tab),
+        (Modifiers::ALT, Key::ArrowLeft, OverlayKey::AltLeft),
+        (Modifiers::ALT, Key::ArrowRight, OverlayKey::AltRight),
+        (Modifiers::NONE, Key::Enter, OverlayKey::Enter),
+        (Modifiers::NONE, Key::Escape, OverlayKey::Escape),
+    ];
+    ctx.input_mut(|i| {
+        keys.into_iter()
+            .find(|(modifiers, key, _)| i.consume_key(*modifiers, *key))
+            .map(|(_, _, overlay_key)| overlay_key)
+    })
+}
+
+/// Everything the overlay displays, frames are requested only when this
+/// changes or on explicit input
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OverlayDisplay {
+    client: String,
+    project: String,
+    summary: Option<String>,
+    is_running: bool,
+    is_unassigned: bool,
+    totals: Option<[String; 4]>,
+    overtime: Option<String>,
+    daily_remaining: Option<String>,
+    gap: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    billable: Option<bool>,
+    exit_confirmation: Option<String>,
+}
+
+/// Decides whether the overlay must be redrawn
+fn should_redraw(before: &OverlayDisplay, after: &OverlayDisplay, input: bool) -> bool {
+    input || before != after
+}
+
+/// Overlay layer surface on a single output
+struct OverlaySurface {
+    output: Option<WlOutput>,
+    state: EguiSurfaceState<LayerSurface>,
+    has_keyboard_focus: bool,
+}
+
+impl OverlaySurface {
+    fn new(app: &Application, output: Option<WlOutput>, (width, height): (u32, u32)) -> Self {
+        let layer_surface = app.layer_shell.create_layer_surface(
+            &app.qh,
+            app.compositor_state.create_surface(&app.qh),
+            Layer::Top,
+            Some("ProjectTimings"),
+            output.as_ref(),
+        );
+        layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+        #[cfg(debug_assertions)]
+        layer_surface.set_anchor(Anchor::BOTTOM | Anchor::RIGHT);
+        #[cfg(not(debug_assertions))]
+        layer_surface.set_anchor(Anchor::BOTTOM | Anchor::LEFT);
+
+        layer_surface.set_margin(0, 20, 20, 20);
+        layer_surface.set_size(width, height);
+        layer_surface.commit();
+        Self {
+            output,
+            state: EguiSurfaceState::new(&app, layer_surface, width, height),
+            has_keyboard_focus: false,
+        }
+    }
 }
 
 pub struct GuiOverlay {
-    surface_state: Option<EguiSurfaceState<LayerSurface>>,
+    surfaces: Vec<OverlaySurface>,
+    // Outputs when the surfaces were last reconciled
+    outputs: Vec<WlOutput>,
 
+    // True if any of the surfaces has keyboard focus
     has_keyboard_focus: bool,
 
     current_desktop: DesktopId,
-    desktop_controller: KDEVirtualDesktopController,
+    desktop_controller: DesktopController,
+    rename_guard: RenameGuard,
+    // Desktop name edit in progress, ends when the keyboard is lost
+    rename_edit: Option<RenameEdit>,
 
     gui_debug_mode: bool,
     gui_fps: f32,
@@ -56,8 +194,26 @@ pub struct GuiOverlay {
     gui_project: String,
     gui_summary: Option<String>,
     gui_totals: HashMap<(String, String), timings::Totals>,
+    // Weeks the week columns are shifted back, resets when hidden
+    gui_week_offset: u32,
+    // Totals of the past weeks by client, project and week offset
+    gui_week_totals: HashMap<(String, String, u32), chrono::Duration>,
+    // Latest untracked gap of the work hours, looked up once per showing
+    gui_gap: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    gui_gap_checked: bool,
+    // Billable flags of the projects, read when the totals are updated
+    gui_billable: HashMap<(String, String), bool>,
+    // Desktop name typed in the first run onboarding
+    gui_onboarding_name: String,
+    // Field to focus on the next frame, e.g. when opened for the keyboard
+    gui_focus_request: Option<OverlayField>,
+    // Pending edits are committed after the frame
+    gui_commit_requested: bool,
+    // Keyboard is given up after the frame, on Escape or the last Enter
+    gui_release_keyboard: bool,
 
     app_message_sender: UnboundedSender<AppMessage>,
+    refresh_interval: std::time::Duration,
     update_totals_thread: tokio::task::JoinHandle<()>,
 }
 
@@ -66,33 +222,12 @@ impl GuiOverlay {
         app: &Application,
         parent: &mut TimingsApp,
         app_message_sender: UnboundedSender<AppMessage>,
-        desktop_controller: KDEVirtualDesktopController,
+        desktop_controller: DesktopController,
     ) -> Self {
-        let surface_state = {
-            let first_monitor = app
-                .output_state
-                .outputs()
-                .collect::<Vec<_>>()
-                .get(0)
-                .cloned();
-            let layer_surface = app.layer_shell.create_layer_surface(
-                &app.qh,
-                app.compositor_state.create_surface(&app.qh),
-                Layer::Top,
-                Some("ProjectTimings"),
-                first_monitor.as_ref(),
-            );
-            layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
-            #[cfg(debug_assertions)]
-            layer_surface.set_anchor(Anchor::BOTTOM | Anchor::RIGHT);
-            #[cfg(not(debug_assertions))]
-            layer_surface.set_anchor(Anchor::BOTTOM | Anchor::LEFT);
-
-            layer_surface.set_margin(0, 20, 20, 20);
-            layer_surface.set_size(350, 200);
-            layer_surface.commit();
-            Some(EguiSurfaceState::new(&app, layer_surface, 350, 200))
-        };
+        let surfaces = target_outputs(app, parent)
+            .into_iter()
+            .map(|output| OverlaySurface::new(app, output, parent.config.overlay_size()))
+            .collect();
         let current_desktop = desktop_controller
             .get_current_desktop_blocking()
             .expect("Desktop get failed");
@@ -101,21 +236,39 @@ impl GuiOverlay {
             .get_desktop_name_blocking(&current_desktop)
             .expect("Desktop name get failed");
 
-        let (gui_client, gui_project) = parse_desktop_name(&current_desktop_name);
+        let (gui_client, gui_project) =
+            validate_desktop_name(&current_desktop_name).unwrap_or_default();
+        let refresh_interval = parent.config.overlay_refresh_interval();
 
         let mut result = Self {
-            surface_state,
+            surfaces,
+            outputs: app.output_state.outputs().collect(),
             has_keyboard_focus: false,
             gui_debug_mode: false,
             gui_fps: 0.0,
-            gui_client: gui_client.unwrap_or_default(),
-            gui_project: gui_project.unwrap_or_default(),
+            gui_client,
+            gui_project,
             gui_summary: None,
             gui_totals: HashMap::new(),
+            gui_week_offset: 0,
+            gui_week_totals: HashMap::new(),
+            gui_gap: None,
+            gui_gap_checked: false,
+            gui_billable: HashMap::new(),
+            gui_onboarding_name: String::new(),
+            gui_focus_request: None,
+            gui_commit_requested: false,
+            gui_release_keyboard: false,
             current_desktop,
             desktop_controller,
+            rename_guard: parent.rename_guard.clone(),
+            rename_edit: None,
             app_message_sender: app_message_sender.clone(),
-            update_totals_thread: spawn_update_totals_thread(app_message_sender.clone()),
+            refresh_interval,
+            update_totals_thread: spawn_update_totals_thread(
+                app_message_sender.clone(),
+                refresh_interval,
+            ),
         };
         result.update_gui_summary_from_cache(parent);
         result
@@ -125,6 +278,113 @@ impl GuiOverlay {
         self.has_keyboard_focus
     }
 
+    /// Takes the keyboard exclusively on the surface of the output, or the
+    /// first surface, and focuses the first editable field. Focus stays
+    /// until Escape or committing the last field, which restarts the timing
+    /// like any keyboard leave.
+    pub fn grab_keyboard(&mut self, output: Option<&WlOutput>) {
+        let index = self
+            .surfaces
+            .iter()
+            .position(|s| output.is_some() && s.output.as_ref() == output)
+            .unwrap_or(0);
+        for (i, surface) in self.surfaces.iter_mut().enumerate() {
+            surface.state.set_keyboard_interactivity(if i == index {
+                KeyboardInteractivity::Exclusive
+            } else {
+                KeyboardInteractivity::None
+            });
+        }
+        self.gui_focus_request = self.editable_fields().first().copied();
+        self.request_frame();
+    }
+
+    fn editable_fields(&self) -> Vec<OverlayField> {
+        editable_fields(self.rename_guard.is_read_only(), self.gui_summary.is_some())
+    }
+
+    /// Moves the focus, shifts the weeks or dismisses on the keyboard-only
+    /// keys
+    fn handle_overlay_keys(&mut self, ctx: &Context) {
+        let Some(key) = take_overlay_key(ctx) else {
+            return;
+        };
+        let focused = [
+            OverlayField::Client,
+            OverlayField::Project,
+            OverlayField::Summary,
+        ]
+        .into_iter()
+        .find(|field| ctx.memory(|m| m.has_focus(field_id(*field))));
+        match overlay_key_action(focused, key, &self.editable_fields()) {
+            Some(OverlayKeyAction::Focus(field)) => self.gui_focus_request = Some(field),
+            Some(OverlayKeyAction::CommitAndFocus(field)) => {
+                self.gui_commit_requested = true;
+                self.gui_focus_request = Some(field);
+            }
+            Some(OverlayKeyAction::CommitAndDismiss) => {
+                self.gui_commit_requested = true;
+                self.gui_release_keyboard = true;
+            }
+            Some(OverlayKeyAction::Dismiss) => self.gui_release_keyboard = true,
+            Some(OverlayKeyAction::ShiftWeeks(delta)) => self.shift_week_offset(delta),
+            None => {}
+        }
+    }
+
+    /// Moves the overlay to the output the compositor focuses now, unless
+    /// it's being edited
+    pub fn follow_active_output(&mut self, app: &Application, parent: &TimingsApp) {
+        if parent.config.overlay_on != OverlayOn::Active || self.has_keyboard_focus {
+            return;
+        }
+        log::trace!("Moving overlay to the active output");
+        self.surfaces = target_outputs(app, parent)
+            .into_iter()
+            .map(|output| OverlaySurface::new(app, output, parent.config.overlay_size()))
+            .collect();
+    }
+
+    /// Creates and destroys surfaces as outputs come and go
+    async fn reconcile_surfaces(&mut self, parent: &mut TimingsApp, app: &Application) {
+        let outputs = app.output_state.outputs().collect::<Vec<_>>();
+        if outputs != self.outputs {
+            self.outputs = outputs;
+            // Surface left to the compositor is not tied to an output, place
+            // it again
+            if parent.config.overlay_on == OverlayOn::Active {
+                self.surfaces.clear();
+            }
+        }
+
+        let existing = self
+            .surfaces
+            .iter()
+            .map(|s| s.output.clone())
+            .collect::<Vec<_>>();
+        let wanted = target_outputs(app, parent);
+        let (to_add, to_remove) = reconcile(&existing, &wanted);
+        if to_add.is_empty() && to_remove.is_empty() {
+            return;
+        }
+
+        self.surfaces.retain(|s| !to_remove.contains(&s.output));
+        for output in to_add {
+            log::trace!("Creating overlay surface for output {:?}", output);
+            self.surfaces.push(OverlaySurface::new(
+                app,
+                output,
+                parent.config.overlay_size(),
+            ));
+        }
+
+        // Focused surface was destroyed, keyboard leave will not arrive
+        if self.has_keyboard_focus && !self.surfaces.iter().any(|s| s.has_keyboard_focus) {
+            self.has_keyboard_focus = false;
+            parent.start_timing().await.ok();
+        }
+    }
+
     pub async fn update_totals(&mut self, parent: &mut TimingsApp) {
         let client = self.gui_client.trim().to_string();
         let project = self.gui_project.trim().to_string();
@@ -139,22 +399,161 @@ impl GuiOverlay {
             self.gui_totals
                 .insert((client.clone(), project.clone()), totals);
         }
+        self.update_week_totals(parent).await;
+        self.update_billable(parent, client, project).await;
+        if !self.gui_gap_checked {
+            self.gui_gap_checked = true;
+            self.update_gap(parent).await;
+        }
+    }
+
+    /// Reads the billable flag of the project once, it changes only from the
+    /// overlay
+    async fn update_billable(&mut self, parent: &mut TimingsApp, client: String, project: String) {
+        let key = (client, project);
+        if self.gui_billable.contains_key(&key) {
+            return;
+        }
+        let Ok(mut conn) = parent.pool.acquire().await else {
+            return;
+        };
+        match conn.is_project_billable(&key.0, &key.1).await {
+            Ok(billable) => {
+                self.gui_billable.insert(key, billable);
+            }
+            Err(e) => log::error!("Failed to read the billable flag: {}", e),
+        }
+    }
+
+    /// Looks up the latest untracked gap of the work hours before the
+    /// running timing
+    async fn update_gap(&mut self, parent: &mut TimingsApp) {
+        self.gui_gap = None;
+        let Some(work_hours) = parent.config.work_hours else {
+            return;
+        };
+        let Some(until) = parent
+            .timings_recorder
+            .current_timing()
+            .map(|current| current.start)
+        else {
+            return;
+        };
+        // Finished timings not yet written would show up as gaps
+        if let Err(e) = parent.write_timings().await {
+            log::error!("Failed to write timings before finding gaps: {}", e);
+            return;
+        }
+        let Ok(mut conn) = parent.pool.acquire().await else {
+            return;
+        };
+        match conn
+            .find_latest_tracking_gap(
+                until,
+                Local,
+                work_hours.start,
+                work_hours.end,
+                chrono::Duration::minutes(MIN_FILL_GAP_MINUTES),
+            )
+            .await
+        {
+            Ok(gap) => self.gui_gap = gap,
+            Err(e) => log::error!("Failed to find tracking gaps: {}", e),
+        }
+    }
+
+    /// Fetches the totals of the shifted week columns, past weeks are cached
+    async fn update_week_totals(&mut self, parent: &mut TimingsApp) {
+        if self.gui_week_offset == 0 {
+            return;
+        }
+        let client = self.gui_client.trim().to_string();
+        let project = self.gui_project.trim().to_string();
+        let today = Local::now().date_naive();
+        for offset in [self.gui_week_offset, self.gui_week_offset + 1] {
+            let key = (client.clone(), project.clone(), offset);
+            if self.gui_week_totals.contains_key(&key) {
+                continue;
+            }
+            let (from, to) = week_range(today, offset);
+            let Ok(mut conn) = parent.pool.acquire().await else {
+                return;
+            };
+            let Ok(totals) = conn
+                .get_timings_daily_totals(
+                    Local,
+                    from,
+                    to,
+                    Some(timings::normalize_name(&client)),
+                    Some(timings::normalize_name(&project)),
+                )
+                .await
+            else {
+                return;
+            };
+            let hours: f64 = totals.iter().map(|total| total.hours).sum();
+            self.gui_week_totals.insert(
+                key,
+                chrono::Duration::milliseconds((hours * 3_600_000.0).round() as i64),
+            );
+        }
+    }
+
+    /// Totals of the two week columns, the older week first
+    fn week_columns(&self, totals: Option<&timings::Totals>) -> Option<[chrono::Duration; 2]> {
+        let offset = self.gui_week_offset;
+        if offset == 0 {
+            return totals.map(|t| [t.last_week, t.this_week]);
+        }
+        let client = self.gui_client.trim().to_string();
+        let project = self.gui_project.trim().to_string();
+        let older = self
+            .gui_week_totals
+            .get(&(client.clone(), project.clone(), offset + 1))?;
+        let newer = self.gui_week_totals.get(&(client, project, offset))?;
+        Some([*older, *newer])
+    }
+
+    /// Shifts the week columns by `delta` weeks back
+    fn shift_week_offset(&mut self, delta: i32) {
+        self.gui_week_offset = self.gui_week_offset.saturating_add_signed(delta);
+        self.app_message_sender
+            .send(AppMessage::GuiOverlayEvent(
+                GuiOverlayEvent::WeekOffsetChanged,
+            ))
+            .ok();
     }
 
     fn on_gui_client_or_project_changed(&mut self, parent: &mut TimingsApp) {
         let client = self.gui_client.trim().to_string();
         let project = self.gui_project.trim().to_string();
-        let current_desktop = self.current_desktop.clone();
-        let mut controller = self.desktop_controller.clone();
         self.update_gui_summary_from_cache(parent);
+        self.rename_current_desktop(format!("{}: {}", client, project));
+    }
+
+    /// Writes the desktop name after the debounce, unless renamed elsewhere
+    /// since the edit began
+    fn rename_current_desktop(&mut self, name: String) {
+        if self
+            .rename_edit
+            .as_ref()
+            .is_none_or(|edit| *edit.desktop_id() != self.current_desktop)
+        {
+            self.rename_edit = Some(self.rename_guard.begin_edit(&self.current_desktop));
+        }
+        let edit = self.rename_edit.clone();
+        let mut controller = self.desktop_controller.clone();
+        let rename_guard = self.rename_guard.clone();
 
         run_debounced_spawn(
-            "update_desktop",
+            UPDATE_DESKTOP,
             std::time::Duration::from_millis(300),
             async move {
-                let _ = controller
-                    .update_desktop_name(current_desktop, &format!("{}: {}", client, project))
-                    .await;
+                if let Some(edit) = edit {
+                    let _ = rename_guard
+                        .rename_if_unchanged(&mut controller, &edit, &name)
+                        .await;
+                }
             },
         );
     }
@@ -191,7 +590,7 @@ impl GuiOverlay {
             let summary = summary.trim().to_string();
             let tx = self.app_message_sender.clone();
             run_debounced_spawn(
-                "update_summary_database",
+                UPDATE_SUMMARY_DATABASE,
                 std::time::Duration::from_millis(300),
                 async move {
                     tx.send(AppMessage::GuiOverlayEvent(
@@ -219,6 +618,21 @@ impl GuiOverlay {
                 self.gui_project.trim().to_string(),
             ))
             .cloned();
+        let overtime = self.weekly_overtime(parent);
+        let target_progress = parent
+            .config
+            .weekly_target()
+            .zip(totals.as_ref())
+            .map(|(target, t)| (progress_fraction(t, target), t.is_over_budget(target)));
+        let daily_remaining = self.daily_remaining(parent);
+        let week_columns = self.week_columns(totals.as_ref());
+        let week_labels = week_column_labels(Local::now().date_naive(), self.gui_week_offset);
+        let locale = parent.config.locale.clone();
+        let exit_confirmation = parent.exit_confirmation().map(str::to_string);
+        let gap = self.gui_gap;
+        let billable = self.billable(parent);
+        // Desktops without a project are shown dimmed
+        let text_color = is_unassigned(parent).then_some(Color32::GRAY);
         // User is holding alt key:
         let debug_mode = self.gui_debug_mode || ctx.input(|i| i.modifiers.alt);
 
@@ -227,6 +641,19 @@ impl GuiOverlay {
             self.gui_debug_mode = !self.gui_debug_mode;
         }
 
+        // Force a database write with ALT+W
+        if ctx.input(|i| i.modifiers.alt && i.key_pressed(egui::Key::W)) {
+            let _ = self.app_message_sender.send(AppMessage::WriteTimings);
+        }
+        let now = Utc::now();
+        let (write_text, write_stale) = write_status(
+            parent.timings_recorder.last_write_time(),
+            parent.timings_recorder.pending_count(now),
+            now,
+            WRITE_TIMINGS_INTERVAL,
+        );
+        let idle_inhibited_until = parent.idle_inhibit.until(now);
+
         CentralPanel::default()
             .frame(
                 egui::Frame::default()
@@ -250,18 +677,61 @@ impl GuiOverlay {
                         Pos2::new(screen_rect.right() - 5.0, screen_rect.top() + 5.0),
                         egui::Align2::RIGHT_TOP,
                         format!(
-                            "ALT+D {:7.2} / {:>4}",
+                            "ALT+D {:7.2} / {:>4} @ {}s",
                             self.gui_fps,
-                            ctx.cumulative_pass_nr()
+                            ctx.cumulative_pass_nr(),
+                            self.refresh_interval.as_secs()
                         ),
                         egui::FontId::new(10.0, egui::FontFamily::Monospace),
                         egui::Color32::GRAY,
                     );
+                    painter.text(
+                        Pos2::new(screen_rect.right() - 5.0, screen_rect.top() + 17.0),
+                        egui::Align2::RIGHT_TOP,
+                        format!("ALT+W {}", write_text),
+                        egui::FontId::new(10.0, egui::FontFamily::Monospace),
+                        if write_stale {
+                            egui::Color32::RED
+                        } else {
+                            egui::Color32::GRAY
+                        },
+                    );
+                    if let Some(until) = idle_inhibited_until {
+                        painter.text(
+                            Pos2::new(screen_rect.right() - 5.0, screen_rect.top() + 29.0),
+                            egui::Align2::RIGHT_TOP,
+                            format!(
+                                "Idle inhibited until {}",
+                                until.with_timezone(&Local).format("%H:%M")
+                            ),
+                            egui::FontId::new(10.0, egui::FontFamily::Monospace),
+                            egui::Color32::GRAY,
+                        );
+                    }
+                }
+                if let Some(error) = exit_confirmation {
+                    self.exit_confirmation_ui(ui, &error);
+                    return;
+                }
+                if parent.is_onboarding() {
+                    self.onboarding_ui(ui);
+                    return;
                 }
+
+                self.handle_overlay_keys(ctx);
+                if let Some(field) = self.gui_focus_request.take() {
+                    ctx.memory_mut(|m| m.request_focus(field_id(field)));
+                }
+
+                // Renaming is left to the instance writing the desktop names
+                let read_only = self.rename_guard.is_read_only();
                 ui.vertical(|ui| {
                     // Client text field
                     let client_input = ui.add(
                         egui::TextEdit::singleline(&mut self.gui_client)
+                            .id(field_id(OverlayField::Client))
+                            .interactive(!read_only)
+                            .text_color_opt(text_color)
                             .desired_width(f32::INFINITY)
                             .horizontal_align(egui::Align::Center)
                             .background_color(Color32::from_white_alpha(0))
@@ -273,12 +743,29 @@ impl GuiOverlay {
                     // Project text field
                     let project_input = ui.add(
                         egui::TextEdit::singleline(&mut self.gui_project)
+                            .id(field_id(OverlayField::Project))
+                            .interactive(!read_only)
+                            .text_color_opt(text_color)
+                            .hint_text(if text_color.is_some() {
+                                UNASSIGNED_PROJECT
+                            } else {
+                                ""
+                            })
                             .desired_width(f32::INFINITY)
                             .horizontal_align(egui::Align::Center)
                             .background_color(Color32::from_white_alpha(0))
                             .font(egui::FontId::new(20.0, egui::FontFamily::Proportional)),
                     );
 
+                    if read_only {
+                        ui.vertical_centered(|ui| {
+                            ui.colored_label(
+                                egui::Color32::GRAY,
+                                "Another timings instance writes the desktop names",
+                            );
+                        });
+                    }
+
                     ui.add_space(5.0);
 
                     // Summary text field
@@ -289,6 +776,7 @@ impl GuiOverlay {
                             Some(v) => v,
                             None => &mut empty_string,
                         })
+                        .id(field_id(OverlayField::Summary))
                         .desired_width(f32::INFINITY)
                         .horizontal_align(egui::Align::Center)
                         .background_color(Color32::from_white_alpha(0))
@@ -335,6 +823,22 @@ impl GuiOverlay {
                             )
                             .size(20.0),
                         );
+                        if let Some(mut billable) = billable {
+                            let toggle = ui
+                                .toggle_value(&mut billable, "$")
+                                .on_hover_text("Billable project");
+                            if toggle.clicked() {
+                                self.app_message_sender
+                                    .send(AppMessage::GuiOverlayEvent(
+                                        GuiOverlayEvent::SetBillable {
+                                            client: self.gui_client.trim().to_string(),
+                                            project: self.gui_project.trim().to_string(),
+                                            billable,
+                                        },
+                                    ))
+                                    .ok();
+                            }
+                        }
                     });
                 });
 
@@ -345,39 +849,243 @@ impl GuiOverlay {
                         ui.label(
                             &totals
                                 .clone()
-                                .map(|t| duration_to_hours(&t.eight_weeks))
+                                .map(|t| locale.format_duration_hours(&t.eight_weeks))
                                 .unwrap_or_else(|| "N/A".to_string()),
                         );
                     });
 
-                    // Last week column
+                    // Last week column, or the older of the shifted weeks
                     cols[1].vertical_centered(|ui| {
-                        ui.label("Last week");
+                        let previous =
+                            egui::Button::new(format!("‹ {}", week_labels[0])).frame(false);
+                        if ui.add(previous).clicked() {
+                            self.shift_week_offset(1);
+                        }
                         ui.label(
-                            &totals
-                                .clone()
-                                .map(|t| duration_to_hours(&t.last_week))
+                            week_columns
+                                .map(|w| locale.format_duration_hours(&w[0]))
                                 .unwrap_or_else(|| "N/A".to_string()),
                         );
                     });
 
-                    // This week column
+                    // This week column, or the newer of the shifted weeks
                     cols[2].vertical_centered(|ui| {
-                        ui.label("This week");
+                        if self.gui_week_offset == 0 {
+                            ui.label(&week_labels[1]);
+                        } else {
+                            let next =
+                                egui::Button::new(format!("{} ›", week_labels[1])).frame(false);
+                            if ui.add(next).clicked() {
+                                self.shift_week_offset(-1);
+                            }
+                        }
                         ui.label(
-                            &totals
-                                .clone()
-                                .map(|t| duration_to_hours(&t.this_week))
+                            week_columns
+                                .map(|w| locale.format_duration_hours(&w[1]))
                                 .unwrap_or_else(|| "N/A".to_string()),
                         );
                     });
                 });
+
+                if let Some((fraction, over_target)) = target_progress {
+                    let bar = egui::ProgressBar::new(fraction).desired_height(6.0);
+                    ui.add(if over_target {
+                        bar.fill(egui::Color32::RED)
+                    } else {
+                        bar
+                    });
+                }
+
+                if let Some(overtime) = overtime {
+                    ui.vertical_centered(|ui| {
+                        ui.colored_label(
+                            egui::Color32::RED,
+                            format!(
+                                "Over weekly target by {} h",
+                                locale.format_duration_hours(&overtime)
+                            ),
+                        );
+                    });
+                }
+
+                if let Some(gap) = gap.filter(|_| is_running) {
+                    ui.vertical_centered(|ui| {
+                        let project = self.gui_project.trim().to_string();
+                        if ui.button(fill_gap_label(gap, &Local, &project)).clicked() {
+                            self.app_message_sender
+                                .send(AppMessage::GuiOverlayEvent(GuiOverlayEvent::FillGap {
+                                    start: gap.0,
+                                    end: gap.1,
+                                    client: self.gui_client.trim().to_string(),
+                                    project,
+                                }))
+                                .ok();
+                            self.gui_gap = None;
+                        }
+                    });
+                }
+
+                if let Some(remaining) = daily_remaining {
+                    ui.vertical_centered(|ui| {
+                        if remaining > chrono::Duration::zero() {
+                            ui.label(format!(
+                                "{} h left of the daily target",
+                                locale.format_duration_hours(&remaining)
+                            ));
+                        } else {
+                            ui.colored_label(egui::Color32::DARK_GREEN, "Daily target met");
+                        }
+                    });
+                }
+            });
+    }
+
+    /// Asks what to do when writing timings failed on exit
+    fn exit_confirmation_ui(&mut self, ui: &mut egui::Ui, error: &str) {
+        ui.vertical_centered(|ui| {
+            ui.label(egui::RichText::new("Timings are not written").size(20.0));
+            ui.add_space(5.0);
+            ui.colored_label(egui::Color32::RED, error);
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                let choices = [
+                    ("Retry", ExitChoice::Retry),
+                    ("Quit anyway", ExitChoice::QuitAnyway),
+                    ("Cancel", ExitChoice::Cancel),
+                ];
+                for (label, choice) in choices {
+                    if ui.button(label).clicked() {
+                        self.app_message_sender
+                            .send(AppMessage::ExitChoice(choice))
+                            .ok();
+                    }
+                }
             });
+        });
+    }
+
+    /// Explains the desktop naming on the first run
+    fn onboarding_ui(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.label(egui::RichText::new("Welcome to Timings").size(20.0));
+            ui.add_space(5.0);
+            ui.label(
+                "Time is tracked per virtual desktop. Name the desktop as \"client: project\" and \
+                 the project is timed while you are on it.",
+            );
+            ui.add_space(5.0);
+            let name_input = ui.add(
+                egui::TextEdit::singleline(&mut self.gui_onboarding_name)
+                    .hint_text("Acme: Website")
+                    .desired_width(f32::INFINITY)
+                    .horizontal_align(egui::Align::Center)
+                    .font(egui::FontId::new(16.0, egui::FontFamily::Proportional)),
+            );
+            match validate_desktop_name(&self.gui_onboarding_name) {
+                Ok((client, project)) => {
+                    ui.label(format!("Client {}, project {}", client, project));
+                }
+                Err(_) => {
+                    ui.colored_label(
+                        Color32::GRAY,
+                        "Separate the client and project with a colon",
+                    );
+                }
+            }
+            if name_input.changed() {
+                let (client, project) =
+                    validate_desktop_name(&self.gui_onboarding_name).unwrap_or_default();
+                self.gui_client = client;
+                self.gui_project = project;
+                self.rename_current_desktop(self.gui_onboarding_name.trim().to_string());
+            }
+            ui.add_space(5.0);
+            ui.label(
+                "The tray icon is green while timing runs, click it to show the overlay again.",
+            );
+            ui.add_space(5.0);
+            if ui.button("Got it").clicked() {
+                self.app_message_sender
+                    .send(AppMessage::OnboardingDone)
+                    .ok();
+            }
+        });
     }
 
-    fn request_frame(&mut self) {
-        if let Some(ref mut surface_state) = self.surface_state {
-            surface_state.request_frame();
+    fn displayed(&self, parent: &TimingsApp) -> OverlayDisplay {
+        let totals = self.gui_totals.get(&(
+            self.gui_client.trim().to_string(),
+            self.gui_project.trim().to_string(),
+        ));
+        let locale = &parent.config.locale;
+        let overtime = self
+            .weekly_overtime(parent)
+            .map(|t| locale.format_duration_hours(&t));
+        let week_columns = self
+            .week_columns(totals)
+            .map(|w| w.map(|d| locale.format_duration_hours(&d)));
+        OverlayDisplay {
+            client: self.gui_client.clone(),
+            project: self.gui_project.clone(),
+            summary: self.gui_summary.clone(),
+            is_running: parent.timings_recorder.is_running(),
+            is_unassigned: is_unassigned(parent),
+            totals: totals.map(|t| {
+                let [older, newer] =
+                    week_columns.unwrap_or_else(|| ["N/A".to_string(), "N/A".to_string()]);
+                [
+                    duration_to_hh_mm_ss(&t.today),
+                    locale.format_duration_hours(&t.eight_weeks),
+                    older,
+                    newer,
+                ]
+            }),
+            overtime,
+            daily_remaining: self
+                .daily_remaining(parent)
+                .map(|t| locale.format_duration_hours(&t)),
+            gap: self.gui_gap,
+            billable: self.billable(parent),
+            exit_confirmation: parent.exit_confirmation().map(str::to_string),
+        }
+    }
+
+    /// Billable flag of the shown project, `None` until read or if the
+    /// desktop has no project
+    fn billable(&self, parent: &TimingsApp) -> Option<bool> {
+        if is_unassigned(parent) || self.gui_project.trim().is_empty() {
+            return None;
+        }
+        self.gui_billable
+            .get(&(
+                self.gui_client.trim().to_string(),
+                self.gui_project.trim().to_string(),
+            ))
+            .copied()
+    }
+
+    /// Overtime beyond the configured weekly target, `None` if not over
+    fn weekly_overtime(&self, parent: &TimingsApp) -> Option<chrono::Duration> {
+        let target = parent.config.weekly_target()?;
+        let totals = self.gui_totals.get(&(
+            self.gui_client.trim().to_string(),
+            self.gui_project.trim().to_string(),
+        ))?;
+        Some(totals.weekly_overtime(target)).filter(|overtime| *overtime > chrono::Duration::zero())
+    }
+
+    /// Time left of the project's daily target, `None` if it has no target
+    fn daily_remaining(&self, parent: &TimingsApp) -> Option<chrono::Duration> {
+        let client = self.gui_client.trim().to_string();
+        let project = self.gui_project.trim().to_string();
+        let target = parent.config.project_target(&client, &project)?;
+        let totals = self.gui_totals.get(&(client, project))?;
+        Some(totals.remaining_for_target(&target, Local::now().date_naive()))
+    }
+
+    pub fn request_frame(&mut self) {
+        for surface in self.surfaces.iter_mut() {
+            surface.state.request_frame();
         }
     }
 
@@ -387,36 +1095,89 @@ impl GuiOverlay {
         app: &mut Application,
         events: &[WaylandEvent],
     ) {
-        if let Some(mut surface_state) = self.surface_state.take() {
-            self.gui_fps = surface_state.get_fps();
-            surface_state.handle_events(app, events, &mut |ctx| self.overlay_ui(ctx, parent));
+        self.reconcile_surfaces(parent, app).await;
+
+        let mut surfaces = std::mem::take(&mut self.surfaces);
+        let mut changed = false;
+        let displayed_before = self.displayed(parent);
+        if let Some(first) = surfaces.first_mut() {
+            self.gui_fps = first.state.get_fps();
+        }
+        for index in 0..surfaces.len() {
+            surfaces[index]
+                .state
+                .handle_events(app, events, &mut |ctx| self.overlay_ui(ctx, parent));
+
             for event in events {
+                let surface = &mut surfaces[index];
                 if let Some(wl_surface) = event.get_wl_surface() {
-                    if surface_state.get_content().wl_surface() != wl_surface {
+                    if surface.state.get_content().wl_surface() != wl_surface {
                         continue;
                     }
                 }
 
                 match event {
                     WaylandEvent::KeyboardEnter(_, _, _) => {
-                        self.has_keyboard_focus = true;
-                        parent.stop_timing();
-                        self.request_frame();
+                        surface.has_keyboard_focus = true;
+                        if !self.has_keyboard_focus {
+                            self.has_keyboard_focus = true;
+                            parent.stop_timing();
+                        }
+                        changed = true;
                     }
                     WaylandEvent::KeyboardLeave(_) => {
-                        self.has_keyboard_focus = false;
-                        parent.start_timing().await.unwrap();
-                        surface_state.set_keyboard_interactivity(KeyboardInteractivity::None);
-                        self.request_frame();
-                        parent.hide_gui_after_delay();
+                        surface.has_keyboard_focus = false;
+                        surface
+                            .state
+                            .set_keyboard_interactivity(KeyboardInteractivity::None);
+                        if !surfaces.iter().any(|s| s.has_keyboard_focus) {
+                            self.has_keyboard_focus = false;
+                            self.rename_edit = None;
+                            // Overlay may be hidden before the debounce elapses
+                            if parent.config.commit_on_focus_loss() {
+                                flush_pending_edits().await;
+                            }
+                            parent.start_timing().await.unwrap();
+                            parent.hide_gui_after_delay();
+                        }
+                        changed = true;
+                    }
+                    WaylandEvent::PointerEvent((_, _, PointerEventKind::Enter { .. })) => {
+                        parent.last_pointer_output = surface.output.clone();
                     }
                     WaylandEvent::PointerEvent((_, _, PointerEventKind::Press { .. })) => {
-                        surface_state.set_keyboard_interactivity(KeyboardInteractivity::OnDemand);
+                        // Keyboard input goes only to the pressed surface
+                        for (i, other) in surfaces.iter_mut().enumerate() {
+                            other.state.set_keyboard_interactivity(if i == index {
+                                KeyboardInteractivity::OnDemand
+                            } else {
+                                KeyboardInteractivity::None
+                            });
+                        }
                     }
                     _ => {}
                 }
             }
-            self.surface_state = Some(surface_state);
+        }
+        self.surfaces = surfaces;
+
+        if std::mem::take(&mut self.gui_commit_requested) {
+            flush_pending_edits().await;
+        }
+        // Keyboard leave follows, and restarts the timing
+        if std::mem::take(&mut self.gui_release_keyboard) {
+            for surface in self.surfaces.iter_mut() {
+                surface
+                    .state
+                    .set_keyboard_interactivity(KeyboardInteractivity::None);
+            }
+        }
+
+        // Other surfaces render the same state, keep them in sync
+        let texts_changed = self.surfaces.len() > 1
+            && should_redraw(&displayed_before, &self.displayed(parent), false);
+        if changed || texts_changed {
+            self.request_frame();
         }
     }
 
@@ -426,44 +1187,98 @@ impl GuiOverlay {
         _app: &mut Application,
         event: &AppMessage,
     ) {
+        let displayed_before = self.displayed(parent);
         match event {
-            AppMessage::GuiOverlayEvent(gui_event) => {
-                match gui_event {
-                    GuiOverlayEvent::UpdateTotalsTimer => {
-                        self.update_totals(parent).await;
+            AppMessage::GuiOverlayEvent(gui_event) => match gui_event {
+                GuiOverlayEvent::UpdateTotalsTimer => {
+                    self.update_totals(parent).await;
+                }
+                GuiOverlayEvent::WeekOffsetChanged => {
+                    self.update_week_totals(parent).await;
+                }
+                GuiOverlayEvent::UpdateSummaryCache {
+                    day,
+                    client,
+                    project,
+                } => {
+                    let summary = parent
+                        .timings_recorder
+                        .update_summary_cache(
+                            day.clone(),
+                            &client.clone(),
+                            &project.clone(),
+                            Utc::now(),
+                        )
+                        .await
+                        .ok();
+                    self.gui_summary = summary;
+                }
+                GuiOverlayEvent::UpdateSummary {
+                    day,
+                    client,
+                    project,
+                    summary,
+                } => {
+                    parent
+                        .timings_recorder
+                        .queue_summary(*day, client, project, summary);
+                    if let Err(e) = parent.timings_recorder.write_timings(Utc::now()).await {
+                        log::error!("Failed to write timings and summary: {}", e);
                     }
-                    GuiOverlayEvent::UpdateSummaryCache {
-                        day,
-                        client,
-                        project,
-                    } => {
-                        let summary = parent
-                            .timings_recorder
-                            .update_summary_cache(
-                                day.clone(),
-                                &client.clone(),
-                                &project.clone(),
-                                Utc::now(),
-                            )
-                            .await
-                            .ok();
-                        self.gui_summary = summary;
+                }
+                GuiOverlayEvent::SetBillable {
+                    client,
+                    project,
+                    billable,
+                } => {
+                    let result = match parent.pool.acquire().await {
+                        Ok(mut conn) => conn.set_project_billable(client, project, *billable).await,
+                        Err(e) => Err(e.into()),
+                    };
+                    match result {
+                        Ok(()) => {
+                            self.gui_billable
+                                .insert((client.clone(), project.clone()), *billable);
+                        }
+                        Err(e) => log::error!("Failed to set the billable flag: {}", e),
                     }
-                    GuiOverlayEvent::UpdateSummary {
-                        day,
-                        client,
-                        project,
-                        summary,
-                    } => {
-                        parent
-                            .timings_recorder
-                            .update_summary(*day, client, project, summary)
+                }
+                GuiOverlayEvent::FillGap {
+                    start,
+                    end,
+                    client,
+                    project,
+                } => {
+                    let timing = fill_gap_timing((*start, *end), client, project);
+                    let source = parent.timings_recorder.source().map(str::to_string);
+                    let result = match parent.pool.acquire().await {
+                        Ok(mut conn) => {
+                            conn.insert_manual_timing(
+                                &timing,
+                                source.as_deref(),
+                                chrono::Utc::now(),
+                            )
                             .await
-                            .ok();
+                        }
+                        Err(e) => Err(e.into()),
+                    };
+                    match result {
+                        Ok(()) => {
+                            log::info!(
+                                "Filled the gap {} - {} with {}: {}",
+                                start,
+                                end,
+                                client,
+                                project
+                            );
+                            // Cached totals do not include the inserted timing
+                            parent.timings_recorder.invalidate_caches();
+                            self.update_totals(parent).await;
+                        }
+                        Err(e) => log::error!("Failed to fill the gap: {}", e),
                     }
                 }
-                self.request_frame();
-            }
+            },
             AppMessage::VirtualDesktop(vdm) => match vdm {
                 VirtualDesktopMessage::DesktopChange(desktop_id) => {
                     self.current_desktop = desktop_id.clone();
@@ -472,27 +1287,41 @@ impl GuiOverlay {
                         .get_desktop_name(desktop_id)
                         .await
                         .unwrap_or_default();
-                    let (gui_client, gui_project) = parse_desktop_name(&desktop_name);
-                    self.gui_client = gui_client.unwrap_or_default();
-                    self.gui_project = gui_project.unwrap_or_default();
+                    let (gui_client, gui_project) =
+                        validate_desktop_name(&desktop_name).unwrap_or_default();
+                    self.gui_client = gui_client;
+                    self.gui_project = gui_project;
                     self.update_gui_summary_from_cache(parent);
-                    self.request_frame();
                 }
                 VirtualDesktopMessage::DesktopNameChanged(desktop_id, desktop_name) => {
                     if *desktop_id == self.current_desktop {
-                        let (gui_client, gui_project) = parse_desktop_name(&desktop_name);
-                        self.gui_client = gui_client.unwrap_or_default();
-                        self.gui_project = gui_project.unwrap_or_default();
+                        let (gui_client, gui_project) =
+                            validate_desktop_name(desktop_name).unwrap_or_default();
+                        self.gui_client = gui_client;
+                        self.gui_project = gui_project;
                         self.update_gui_summary_from_cache(parent);
-                        self.request_frame();
                     }
                 }
             },
-            AppMessage::RunningChanged(_) => {
-                self.request_frame();
-            }
             _ => {}
         }
+
+        if should_redraw(&displayed_before, &self.displayed(parent), false) {
+            self.request_frame();
+        }
+    }
+}
+
+/// Outputs the overlay should be shown on, `None` lets the compositor decide
+fn target_outputs(app: &Application, parent: &TimingsApp) -> Vec<Option<WlOutput>> {
+    let outputs = app.output_state.outputs().collect::<Vec<_>>();
+    let first = outputs.first().cloned();
+    match parent.config.overlay_on {
+        OverlayOn::Primary => vec![first],
+        OverlayOn::All if outputs.is_empty() => vec![None],
+        OverlayOn::All => outputs.into_iter().map(Some).collect(),
+        // Compositor places it on the output the user last interacted with
+        OverlayOn::Active => vec![None],
     }
 }
 
@@ -502,6 +1331,14 @@ impl Drop for GuiOverlay {
     }
 }
 
+/// True if the running timing is for a desktop without a project
+fn is_unassigned(parent: &TimingsApp) -> bool {
+    parent
+        .timings_recorder
+        .current_timing()
+        .is_some_and(|timing| timings::is_unassigned(&timing.project))
+}
+
 fn duration_to_hh_mm_ss(duration: &chrono::Duration) -> String {
     let total_seconds = duration.num_seconds();
     let hours = total_seconds / 3600;
@@ -510,13 +1347,10 @@ fn duration_to_hh_mm_ss(duration: &chrono::Duration) -> String {
     format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
 }
 
-fn duration_to_hours(duration: &chrono::Duration) -> String {
-    format!("{:.2}", duration.num_seconds() as f64 / 3600.0)
-}
-
-/// Spawns a thread that sends KeepAlive message every 30 seconds
+/// Spawns a thread that sends UpdateTotalsTimer message every interval
 fn spawn_update_totals_thread(
     app_message_sender: UnboundedSender<AppMessage>,
+    interval: std::time::Duration,
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         loop {
@@ -529,7 +1363,90 @@ fn spawn_update_totals_thread(
                 // Main thread has exited, stop the loop
                 break;
             }
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            tokio::time::sleep(interval).await;
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(today: &str) -> OverlayDisplay {
+        OverlayDisplay {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            summary: None,
+            is_running: true,
+            is_unassigned: false,
+            totals: Some([
+                today.to_string(),
+                "10.00".to_string(),
+                "5.00".to_string(),
+                "1.00".to_string(),
+            ]),
+            overtime: None,
+            daily_remaining: None,
+            gap: None,
+            billable: None,
+            exit_confirmation: None,
+        }
+    }
+
+    #[test]
+    fn no_redraw_when_nothing_changes() {
+        assert!(!should_redraw(
+            &display("01:00:00"),
+            &display("01:00:00"),
+            false
+        ));
+    }
+
+    #[test]
+    fn redraw_when_displayed_strings_change() {
+        assert!(should_redraw(
+            &display("01:00:00"),
+            &display("01:00:01"),
+            false
+        ));
+
+        let mut stopped = display("01:00:00");
+        stopped.is_running = false;
+        assert!(should_redraw(&display("01:00:00"), &stopped, false));
+    }
+
+    #[tokio::test]
+    async fn pending_edits_are_committed_on_flush() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let summary = GuiOverlayEvent::UpdateSummary {
+            day: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            summary: "Layout".to_string(),
+        };
+        let summary_ = summary.clone();
+        run_debounced_spawn(
+            UPDATE_SUMMARY_DATABASE,
+            std::time::Duration::from_secs(60),
+            async move {
+                tx.send(AppMessage::GuiOverlayEvent(summary_)).ok();
+            },
+        );
+        assert!(rx.try_recv().is_err(), "Waiting for the debounce");
+
+        flush_pending_edits().await;
+        assert_eq!(
+            rx.try_recv().ok(),
+            Some(AppMessage::GuiOverlayEvent(summary))
+        );
+    }
+
+    #[test]
+    fn redraw_on_input() {
+        assert!(should_redraw(
+            &display("01:00:00"),
+            &display("01:00:00"),
+            true
+        ));
+    }
+}
@@ -1,9 +1,17 @@
 use crate::AppMessage;
 use crate::TimingsApp;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use egui::CentralPanel;
+use egui::Color32;
+use egui::Context;
 use smithay_client_toolkit::shell::WaylandSurface;
 use smithay_client_toolkit::shell::xdg::window::Window;
 use smithay_client_toolkit::shell::xdg::window::WindowDecorations;
 use sqlx::SqlitePool;
+use timings::AppUsageForDay;
+use timings::TimingsQueries;
 use wayapp::Application;
 use wayapp::EguiSurfaceState;
 use wayapp::WaylandEvent;
@@ -15,6 +23,13 @@ enum GuiStatsEvents {
 pub struct GuiStats {
     surface_state: Option<EguiSurfaceState<Window>>,
     pool: SqlitePool,
+    hourly_distribution: [f64; 24],
+    // Day of the application breakdown
+    app_usage_day: NaiveDate,
+    app_usage: Vec<AppUsageForDay>,
+    grand_total: Duration,
+    // Close button clicked, the window is closed by `TimingsApp`
+    close_requested: bool,
 }
 
 impl GuiStats {
@@ -24,16 +39,73 @@ impl GuiStats {
             WindowDecorations::ServerDefault,
             &app.qh,
         );
-        window.set_title("Example Window");
+        window.set_title("Timings statistics");
         window.set_app_id("io.github.ciantic.wayapp.ExampleWindow");
         window.commit();
         let surface_state = Some(EguiSurfaceState::new(app, window, 600, 400));
         Self {
             surface_state,
             pool,
+            hourly_distribution: [0.0; 24],
+            app_usage_day: Local::now().date_naive(),
+            app_usage: Vec::new(),
+            grand_total: Duration::zero(),
+            close_requested: false,
         }
     }
 
+    /// Fetches the statistics shown in the window, e.g. when it's opened
+    pub async fn refresh(&mut self) {
+        if let Err(e) = self.update_hourly_distribution().await {
+            log::error!("Failed to update hourly distribution: {}", e);
+        }
+        if let Err(e) = self.update_grand_total().await {
+            log::error!("Failed to update grand total: {}", e);
+        }
+        if let Err(e) = self.update_app_usage().await {
+            log::error!("Failed to update application usage: {}", e);
+        }
+    }
+
+    /// True once the close button was clicked
+    pub fn close_requested(&self) -> bool {
+        self.close_requested
+    }
+
+    /// Updates the hours of the day chart from the past 8 weeks
+    pub async fn update_hourly_distribution(&mut self) -> Result<(), timings::Error> {
+        let mut conn = self.pool.acquire().await?;
+        let to = Local::now().date_naive();
+        let from = to - chrono::Duration::weeks(8);
+        self.hourly_distribution = conn.get_hourly_distribution(Local, from, to).await?;
+        if let Some(surface_state) = &mut self.surface_state {
+            surface_state.request_frame();
+        }
+        Ok(())
+    }
+
+    /// Updates the application breakdown of the selected day
+    pub async fn update_app_usage(&mut self) -> Result<(), timings::Error> {
+        let mut conn = self.pool.acquire().await?;
+        self.app_usage = conn
+            .get_app_usage_for_day(Local, self.app_usage_day)
+            .await?;
+        if let Some(surface_state) = &mut self.surface_state {
+            surface_state.request_frame();
+        }
+        Ok(())
+    }
+
+    /// Updates the lifetime total of all timings
+    pub async fn update_grand_total(&mut self) -> Result<(), timings::Error> {
+        let mut conn = self.pool.acquire().await?;
+        self.grand_total = conn.get_grand_total().await?;
+        if let Some(surface_state) = &mut self.surface_state {
+            surface_state.request_frame();
+        }
+        Ok(())
+    }
+
     pub async fn handle_app_events(
         &mut self,
         parent: &mut TimingsApp,
@@ -54,8 +126,111 @@ impl GuiStats {
         app: &mut Application,
         events: &[WaylandEvent],
     ) -> () {
+        let hourly_distribution = self.hourly_distribution;
+        let app_usage = &self.app_usage;
+        let grand_total = self.grand_total;
+        let mut day = self.app_usage_day;
+        let mut close = false;
         if let Some(surface_state) = &mut self.surface_state {
-            surface_state.handle_events(app, events, &mut |ctx| ());
+            surface_state.handle_events(app, events, &mut |ctx| {
+                stats_ui(
+                    ctx,
+                    &hourly_distribution,
+                    &mut day,
+                    app_usage,
+                    grand_total,
+                    &mut close,
+                )
+            });
+        }
+        self.close_requested |= close;
+        if day != self.app_usage_day {
+            self.app_usage_day = day;
+            if let Err(e) = self.update_app_usage().await {
+                log::error!("Failed to update application usage: {}", e);
+            }
+        }
+    }
+}
+
+fn stats_ui(
+    ctx: &Context,
+    hourly_distribution: &[f64; 24],
+    app_usage_day: &mut NaiveDate,
+    app_usage: &[AppUsageForDay],
+    grand_total: Duration,
+    close: &mut bool,
+) {
+    CentralPanel::default().show(ctx, |ui| {
+        if ui.button("Close").clicked() {
+            *close = true;
+        }
+        ui.label(format!("Lifetime tracked: {}h", grand_total.num_hours()));
+        ui.heading("Hours of the day");
+        hourly_distribution_chart(ui, hourly_distribution);
+        ui.heading("Applications");
+        app_usage_breakdown(ui, app_usage_day, app_usage);
+    });
+}
+
+/// Hours per application of each project on the selected day
+fn app_usage_breakdown(ui: &mut egui::Ui, day: &mut NaiveDate, app_usage: &[AppUsageForDay]) {
+    ui.horizontal(|ui| {
+        if ui.button("<").clicked() {
+            *day = day.pred_opt().unwrap_or(*day);
         }
+        ui.label(day.format("%Y-%m-%d").to_string());
+        if ui.button(">").clicked() {
+            *day = day.succ_opt().unwrap_or(*day);
+        }
+    });
+    if app_usage.is_empty() {
+        ui.colored_label(Color32::GRAY, "No applications recorded");
+        return;
+    }
+    egui::Grid::new("app_usage").striped(true).show(ui, |ui| {
+        for usage in app_usage {
+            ui.label(format!("{}: {}", usage.client, usage.project));
+            ui.label(&usage.app);
+            ui.label(format!("{:.2} h", usage.hours));
+            ui.end_row();
+        }
+    });
+}
+
+/// Bar chart of hours worked per hour of the day
+fn hourly_distribution_chart(ui: &mut egui::Ui, hourly_distribution: &[f64; 24]) {
+    let label_height = 15.0;
+    let (response, painter) = ui.allocate_painter(
+        egui::Vec2::new(ui.available_width(), 200.0),
+        egui::Sense::hover(),
+    );
+    let rect = response.rect;
+    let max = hourly_distribution.iter().cloned().fold(0.0, f64::max);
+    let bar_width = rect.width() / 24.0;
+    let bottom = rect.bottom() - label_height;
+
+    for (hour, hours) in hourly_distribution.iter().enumerate() {
+        let height = if max > 0.0 {
+            (hours / max) as f32 * (rect.height() - label_height)
+        } else {
+            0.0
+        };
+        let x = rect.left() + hour as f32 * bar_width;
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(x + 1.0, bottom - height),
+                egui::pos2(x + bar_width - 1.0, bottom),
+            ),
+            0.0,
+            Color32::LIGHT_BLUE,
+        );
+        painter.text(
+            egui::pos2(x + bar_width / 2.0, rect.bottom()),
+            egui::Align2::CENTER_BOTTOM,
+            hour.to_string(),
+            egui::FontId::new(10.0, egui::FontFamily::Monospace),
+            Color32::GRAY,
+        );
     }
 }
@@ -0,0 +1,91 @@
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
+/// Longest inhibit, so a forgotten inhibit does not disable idle tracking for
+/// days
+pub const MAX_INHIBIT_MINUTES: u32 = 8 * 60;
+
+/// Temporarily disables stopping the timing when the user idles, e.g. while
+/// working in a remote session the idle monitor does not see
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdleInhibit {
+    until: Option<DateTime<Utc>>,
+}
+
+impl IdleInhibit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inhibits idle tracking for the given minutes from now, capped to
+    /// `MAX_INHIBIT_MINUTES`. Zero minutes ends the inhibit.
+    pub fn inhibit(&mut self, minutes: u32, now: DateTime<Utc>) {
+        self.until =
+            (minutes > 0).then(|| now + Duration::minutes(minutes.min(MAX_INHIBIT_MINUTES) as i64));
+    }
+
+    /// End of the inhibit, `None` if not inhibited
+    pub fn until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.until.filter(|until| now < *until)
+    }
+
+    pub fn is_inhibited(&self, now: DateTime<Utc>) -> bool {
+        self.until(now).is_some()
+    }
+
+    /// Returns true once after the inhibit has expired
+    pub fn take_expired(&mut self, now: DateTime<Utc>) -> bool {
+        match self.until {
+            Some(until) if now >= until => {
+                self.until = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap() + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn expires_after_minutes() {
+        let mut inhibit = IdleInhibit::new();
+        assert!(!inhibit.is_inhibited(at(0)));
+
+        inhibit.inhibit(30, at(0));
+        assert!(inhibit.is_inhibited(at(29)));
+        assert_eq!(inhibit.until(at(10)), Some(at(30)));
+        assert!(!inhibit.take_expired(at(29)));
+
+        assert!(!inhibit.is_inhibited(at(30)));
+        assert!(inhibit.take_expired(at(30)));
+        assert!(!inhibit.take_expired(at(31)), "Expires only once");
+    }
+
+    #[test]
+    fn renewing_extends_and_zero_ends() {
+        let mut inhibit = IdleInhibit::new();
+        inhibit.inhibit(30, at(0));
+        inhibit.inhibit(30, at(20));
+        assert!(inhibit.is_inhibited(at(45)));
+
+        inhibit.inhibit(0, at(46));
+        assert!(!inhibit.is_inhibited(at(46)));
+        assert!(!inhibit.take_expired(at(100)), "Ended, not expired");
+    }
+
+    #[test]
+    fn capped_to_max() {
+        let mut inhibit = IdleInhibit::new();
+        inhibit.inhibit(u32::MAX, at(0));
+        assert_eq!(inhibit.until(at(0)), Some(at(MAX_INHIBIT_MINUTES as i64)));
+    }
+}
@@ -0,0 +1,106 @@
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
+/// What to do when the user idles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleStopAction {
+    /// Stop the timing at the idle start
+    Stop(DateTime<Utc>),
+    /// Call `IdleStop::check` after the delay
+    CheckAfter(Duration),
+}
+
+/// Stops the timing only when the idle lasts `min_idle`, so that short idle
+/// periods, e.g. reading a printed document, do not fragment the timings
+///
+/// The idle time is deducted from the timing when it's stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdleStop {
+    min_idle: Duration,
+    // Start of the ongoing idle, `None` when active
+    idle_since: Option<DateTime<Utc>>,
+}
+
+impl IdleStop {
+    pub fn new(min_idle: Duration) -> Self {
+        Self {
+            min_idle,
+            idle_since: None,
+        }
+    }
+
+    pub fn idled(&mut self, at: DateTime<Utc>) -> IdleStopAction {
+        if self.min_idle <= Duration::zero() {
+            self.idle_since = None;
+            return IdleStopAction::Stop(at);
+        }
+        self.idle_since = Some(at);
+        IdleStopAction::CheckAfter(self.min_idle)
+    }
+
+    /// Returns the idle start to stop the timing at, once the idle has lasted
+    /// `min_idle`
+    pub fn check(&mut self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let since = self.idle_since?;
+        if now - since < self.min_idle {
+            return None;
+        }
+        self.idle_since = None;
+        Some(since)
+    }
+
+    /// Cancels the pending stop
+    pub fn resumed(&mut self) {
+        self.idle_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 4, 12, 0, 0).unwrap() + Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn stops_immediately_without_min_idle() {
+        let mut idle_stop = IdleStop::new(Duration::zero());
+        assert_eq!(idle_stop.idled(at(0)), IdleStopAction::Stop(at(0)));
+        assert_eq!(idle_stop.check(at(10)), None);
+    }
+
+    #[test]
+    fn stops_at_idle_start_after_min_idle() {
+        let mut idle_stop = IdleStop::new(Duration::minutes(10));
+        assert_eq!(
+            idle_stop.idled(at(0)),
+            IdleStopAction::CheckAfter(Duration::minutes(10))
+        );
+        assert_eq!(idle_stop.check(at(9)), None);
+        assert_eq!(idle_stop.check(at(10)), Some(at(0)));
+        // Stopped once
+        assert_eq!(idle_stop.check(at(11)), None);
+    }
+
+    #[test]
+    fn resumed_cancels_stop() {
+        let mut idle_stop = IdleStop::new(Duration::minutes(10));
+        idle_stop.idled(at(0));
+        idle_stop.resumed();
+        assert_eq!(idle_stop.check(at(10)), None);
+    }
+
+    #[test]
+    fn stale_check_of_earlier_idle() {
+        let mut idle_stop = IdleStop::new(Duration::minutes(10));
+        idle_stop.idled(at(0));
+        idle_stop.resumed();
+        idle_stop.idled(at(5));
+        // Check scheduled by the first idle
+        assert_eq!(idle_stop.check(at(10)), None);
+        assert_eq!(idle_stop.check(at(15)), Some(at(5)));
+    }
+}
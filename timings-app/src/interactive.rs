@@ -0,0 +1,24 @@
+/// Whether the stdin command reader runs
+///
+/// It's enabled with `--interactive`, or when stdin is a terminal. Under a
+/// desktop session stdin is not a terminal, and the command menu would only
+/// be noise in the session log.
+pub fn stdin_reader_enabled(interactive: bool, stdin_is_terminal: bool) -> bool {
+    interactive || stdin_is_terminal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enabled_on_terminal() {
+        assert!(stdin_reader_enabled(false, true));
+    }
+
+    #[test]
+    fn disabled_without_terminal_unless_interactive() {
+        assert!(!stdin_reader_enabled(false, false));
+        assert!(stdin_reader_enabled(true, false));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::utils::is_memory_database;
+use std::path::Path;
+use std::path::PathBuf;
+use timings::SavedTiming;
+
+/// Journal file kept next to the database, carries state across restarts
+///
+/// Returns `None` for in-memory databases.
+pub fn journal_path(database_path: &str) -> Option<PathBuf> {
+    sidecar_path(database_path, "journal.json")
+}
+
+/// Sidecar file next to the database with the unwritten recorder state, used
+/// to recover after a crash
+///
+/// Returns `None` for in-memory databases.
+pub fn state_path(database_path: &str) -> Option<PathBuf> {
+    sidecar_path(database_path, "state.json")
+}
+
+fn sidecar_path(database_path: &str, extension: &str) -> Option<PathBuf> {
+    if is_memory_database(database_path) {
+        return None;
+    }
+    Some(PathBuf::from(format!("{}.{}", database_path, extension)))
+}
+
+/// Persists the running timing, removes the journal if nothing is running
+pub fn save_running_timing(
+    path: &Path,
+    saved: Option<&SavedTiming>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match saved {
+        Some(saved) => std::fs::write(path, serde_json::to_string(saved)?)?,
+        None => {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads and removes the persisted running timing
+pub fn take_running_timing(path: &Path) -> Result<Option<SavedTiming>, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(path)?;
+    std::fs::remove_file(path)?;
+    Ok(Some(serde_json::from_str(&contents)?))
+}
+
+/// Reads and removes the recorder state sidecar
+pub fn take_recorder_state(path: &Path) -> Result<Option<String>, std::io::Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(path)?;
+    std::fs::remove_file(path)?;
+    Ok(Some(contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use chrono::Utc;
+
+    #[test]
+    fn journal_path_for_memory_database() {
+        assert_eq!(journal_path("sqlite::memory:"), None);
+        assert_eq!(
+            journal_path("/home/user/timings.db"),
+            Some(PathBuf::from("/home/user/timings.db.journal.json"))
+        );
+        assert_eq!(state_path("sqlite::memory:"), None);
+        assert_eq!(
+            state_path("/home/user/timings.db"),
+            Some(PathBuf::from("/home/user/timings.db.state.json"))
+        );
+    }
+
+    #[test]
+    fn save_and_take_running_timing() {
+        let path =
+            std::env::temp_dir().join(format!("timings-journal-test-{}.json", std::process::id()));
+        let saved = SavedTiming {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            start: Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap(),
+            last_keep_alive: Utc.with_ymd_and_hms(2024, 3, 4, 10, 0, 0).unwrap(),
+        };
+
+        save_running_timing(&path, Some(&saved)).unwrap();
+        assert_eq!(take_running_timing(&path).unwrap(), Some(saved));
+        assert_eq!(
+            take_running_timing(&path).unwrap(),
+            None,
+            "Journal is removed"
+        );
+    }
+}
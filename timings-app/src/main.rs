@@ -1,36 +1,118 @@
 use chrono::Duration;
 use chrono::Local;
 use clap::Parser;
+use clap::Subcommand;
 use futures::StreamExt;
-use idle_monitor::run_idle_monitor;
+use idle_monitor::IdleEvent;
+use idle_monitor::IdleMonitorHandle;
+use idle_monitor::run_idle_monitor_on_seat;
 use log::trace;
-use single_instance::only_single_instance;
+use single_instance::DEFAULT_PREFIX;
+use single_instance::Handlers;
+use single_instance::claim_role;
+use single_instance::only_single_instance_with_handlers;
+use single_instance::send_to_primary;
+use smithay_client_toolkit::reexports::client::protocol::wl_output::WlOutput;
 use sqlx::SqlitePool;
 use sqlx::sqlite::SqliteConnectOptions;
+use std::io::IsTerminal;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::thread;
 use timings::TimingsMockdata;
-use timings::TimingsMutations;
 use timings::TimingsRecorder;
 use timings::TimingsRecording;
 use tokio::sync::mpsc::UnboundedSender;
 use trayicon::Icon;
 use trayicon::MenuBuilder;
 use trayicon::TrayIconBuilder;
+use virtual_desktops::ActiveApplicationSource;
 use virtual_desktops::DesktopId;
-use virtual_desktops::KDEVirtualDesktopController;
+use virtual_desktops::KDEActiveApplicationSource;
 use virtual_desktops::VirtualDesktopController;
 use virtual_desktops::VirtualDesktopMessage;
 use wayapp::Application;
 use wayapp::DispatchToken;
+mod autostart;
+mod config;
+mod config_reload;
+mod dbus_service;
+mod desktop_controller;
+mod desktop_name;
+mod desktop_rename;
+mod exit_flow;
+mod gap_fill;
 mod gui_overlay;
 mod gui_stats;
+mod idle_inhibit;
+mod idle_stop;
+mod interactive;
+mod journal;
+mod migration;
+mod notifications;
+mod onboarding;
+mod overlay_focus;
+mod peek;
+mod quiet;
+mod screensaver;
+mod timezone;
+mod tooltip;
 mod utils;
+mod weekly_report;
+mod write_status;
+use crate::config::Config;
+use crate::config::DEFAULT_CONFIG;
+use crate::config::HttpServerConfig;
+use crate::config::expand_tilde;
+use crate::config_reload::ConfigSource;
+use crate::config_reload::config_changes;
+use crate::dbus_service::serve_dbus;
+use crate::desktop_controller::DesktopController;
+use crate::desktop_name::timing_for_desktop_name;
+use crate::desktop_rename::DESKTOP_WRITER_ROLE;
+use crate::desktop_rename::RenameGuard;
+use crate::exit_flow::ExitChoice;
+use crate::exit_flow::ExitDecision;
+use crate::exit_flow::ExitFlow;
 use crate::gui_overlay::GuiOverlay;
 use crate::gui_overlay::GuiOverlayEvent;
+use crate::gui_stats::GuiStats;
+use crate::idle_inhibit::IdleInhibit;
+use crate::idle_stop::IdleStop;
+use crate::idle_stop::IdleStopAction;
+use crate::interactive::stdin_reader_enabled;
+use crate::journal::journal_path;
+use crate::journal::save_running_timing;
+use crate::journal::state_path;
+use crate::journal::take_recorder_state;
+use crate::journal::take_running_timing;
+use crate::migration::Preparation;
+use crate::migration::prepare_database;
+use crate::notifications::notify;
+use crate::onboarding::first_run_marker_path;
+use crate::onboarding::mark_first_run_done;
+use crate::onboarding::needs_onboarding;
+use crate::peek::Peek;
+use crate::peek::PeekAction;
+use crate::peek::PeekKey;
+use crate::screensaver::ScreenIdle;
+use crate::screensaver::forward_screensaver_states;
+use crate::screensaver::screensaver_states;
+use crate::timezone::forward_timezone_changes;
+use crate::timezone::timezones;
+use crate::tooltip::presence_text;
+use crate::tooltip::screen_locked_text;
+use crate::tooltip::tooltip_text;
+use crate::utils::Shutdown;
+use crate::utils::StopSignal;
+use crate::utils::is_memory_database;
 use crate::utils::run_debounced_spawn;
+use crate::weekly_report::due_report_paths;
+use crate::weekly_report::week_range;
 
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+const EXIT_CONFIRMATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+const WRITE_TIMINGS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3 * 60);
 const DEFAULT_DATABASE: &str = "~/.config/timings/timings.db";
 const ICON_GREEN: &[u8] = include_bytes!("../resources/green.ico");
 const ICON_RED: &[u8] = include_bytes!("../resources/red.ico");
@@ -56,28 +138,138 @@ struct Cli {
 
     /// Idle timeout in seconds (how long before user is considered idle)
     ///
-    /// Set to 0 to disable idle monitoring.
-    #[arg(short = 't', long, default_value_t = 180)]
-    idle_timeout: u64,
+    /// Set to 0 to disable idle monitoring. Overrides `idle_timeout_seconds`
+    /// in the configuration, which defaults to 180.
+    #[arg(short = 't', long)]
+    idle_timeout: Option<u64>,
+
+    /// Path to the TOML configuration file
+    #[arg(short, long, default_value = DEFAULT_CONFIG)]
+    config: String,
+
+    /// Track the client and project typed in the overlay instead of virtual
+    /// desktop names, for compositors without KDE virtual desktops
+    #[arg(long)]
+    no_desktop_tracking: bool,
+
+    /// Include timings of desktops without a project in reports, see
+    /// `track_unassigned` in the configuration
+    #[arg(long)]
+    include_unassigned: bool,
+
+    /// Read commands from stdin even if it's not a terminal, by default the
+    /// command reader runs only in a terminal
+    #[arg(long)]
+    interactive: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Start timings-app on login with the current database and config
+    InstallAutostart,
+    /// Remove the login autostart entry
+    UninstallAutostart,
+    /// Ask the running instance to show the stats
+    ShowStats,
+    /// Ask the running instance to write timings to the database
+    Flush,
+    /// Ask the running instance to write timings and exit
+    Quit,
+    /// Check the database and report implausible days, e.g. from clock jumps
+    Doctor {
+        /// Days with more hours than this are reported
+        #[arg(long, default_value_t = 16.0)]
+        max_hours: f64,
+        /// How many days back to check
+        #[arg(long, default_value_t = 365)]
+        days: u32,
+    },
+    /// Check the database for broken rows and overlapping timings
+    Repair {
+        /// Delete the broken rows, overlapping timings are only reported
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+// Actions the running instance performs for `timings-app <command>`
+const ACTION_SHOW_STATS: &str = "show-stats";
+const ACTION_FLUSH: &str = "flush";
+const ACTION_QUIT: &str = "quit";
+
+/// Handlers of the actions, forwarding them to the app message channel
+fn primary_handlers(sender: &UnboundedSender<AppMessage>) -> Handlers {
+    let action = |message: fn() -> AppMessage, reply: &'static str| {
+        let sender = sender.clone();
+        move |_: &str| match sender.send(message()) {
+            Ok(()) => reply.to_string(),
+            Err(_) => "Exiting".to_string(),
+        }
+    };
+    Handlers::new()
+        .on(
+            ACTION_SHOW_STATS,
+            action(|| AppMessage::ShowStats, "Showing stats"),
+        )
+        .on(
+            ACTION_FLUSH,
+            action(|| AppMessage::WriteTimings, "Writing timings"),
+        )
+        .on(ACTION_QUIT, action(|| AppMessage::Exit, "Exiting"))
 }
 
 #[derive(Debug, PartialEq, Clone)]
 enum AppMessage {
     WaylandDispatch(DispatchToken),
     Exit,
+    ExitChoice(ExitChoice),
     WriteTimings,
     KeepAlive,
     ShowStats,
+    /// Open or close the statistics window
+    ToggleStatsWindow,
     ShowDailyTotals,
+    ShowWeeklyTotals,
     ShowDailySummaries,
     TrayIconClicked,
+    /// Show the overlay with the keyboard focus, e.g. from a global shortcut
+    FocusOverlay,
     VirtualDesktop(VirtualDesktopMessage),
     VirtualDesktopThreadExited,
+    ActiveApplication(String),
+    TimezoneChanged,
+    /// Re-read the configuration file, from the tray or on SIGHUP
+    ReloadConfig,
+    /// Screensaver or lock screen became active or inactive
+    ScreenSaverActive(bool),
+    /// First run onboarding dismissed from the overlay
+    OnboardingDone,
+    /// This instance got or lost the role of writing desktop names
+    DesktopWriter(bool),
     HideLayerOverlay,
-    UserIdled,
+    /// User idled at the time, as seen by the idle monitor
+    UserIdled(chrono::DateTime<chrono::Utc>),
+    /// Idle monitor failed, idle time is tracked as work
+    IdleMonitorFailed(String),
+    /// Minimum idle to stop may have elapsed, see `IdleStop`
+    IdleStopCheck,
     RunningChanged(bool),
-    UserResumed,
+    /// User resumed at the time, as seen by the idle monitor
+    UserResumed(chrono::DateTime<chrono::Utc>),
     AnotherInstanceTriedToStart,
+    CheckWeeklyReport,
+    /// Timings may be due for compacting, see `Config::compact_gap_seconds`
+    CheckCompaction,
+    ToggleQuietMode,
+    SetQuietMode(bool),
+    InhibitIdleTracking(u32),
+    /// Peek key pressed or released, see `Config::peek_mode`
+    Peek(PeekKey),
+    StartTiming,
+    StopTiming,
     RequestRender,
     GuiOverlayEvent(GuiOverlayEvent),
 }
@@ -99,41 +291,157 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     .init();
 
     let cli = Cli::parse();
+    let config_source = ConfigSource {
+        path: cli.config.clone(),
+        include_unassigned: cli.include_unassigned,
+        idle_timeout_seconds: cli.idle_timeout,
+    };
+    let config = config_source.load()?;
     let database_path = handle_database_path(&cli.database).await?;
+
+    if let Some(command) = &cli.command {
+        return run_command(command, &cli, &database_path).await;
+    }
     let (appmsg_sender, mut appmsgs) = tokio::sync::mpsc::unbounded_channel::<AppMessage>();
 
+    let mut shutdown = Shutdown::new();
+
     // Ensure only a single instance is running for this database path
     let sender_for_single_instance = appmsg_sender.clone();
-    only_single_instance(&database_path, move || {
-        let _ = sender_for_single_instance.send(AppMessage::AnotherInstanceTriedToStart);
-    })?;
+    let mut stop = shutdown.signal();
+    let single_instance = only_single_instance_with_handlers(
+        DEFAULT_PREFIX,
+        &database_path,
+        move |args| {
+            log::debug!("Another instance tried to start with {:?}", args);
+            let _ = sender_for_single_instance.send(AppMessage::AnotherInstanceTriedToStart);
+        },
+        primary_handlers(&appmsg_sender),
+        async move { stop.stopped().await },
+    )?;
+    shutdown.add_thread("single instance", single_instance);
+
+    // Instances with other databases share the desktops, only one renames them
+    let sender_for_desktop_writer = appmsg_sender.clone();
+    let mut stop = shutdown.signal();
+    match claim_role(
+        DEFAULT_PREFIX,
+        DESKTOP_WRITER_ROLE,
+        move |holds| {
+            let _ = sender_for_desktop_writer.send(AppMessage::DesktopWriter(holds));
+        },
+        async move { stop.stopped().await },
+    ) {
+        Ok(handle) => shutdown.add_thread("desktop writer", handle),
+        Err(e) => log::warn!("Could not claim the desktop writer role: {:?}", e),
+    }
 
-    let desktop_controller = KDEVirtualDesktopController::new().await?;
+    let desktop_controller = DesktopController::new(!cli.no_desktop_tracking).await?;
 
     // Stats GUI
     // Start the timings app
     let mut timings_app = TimingsApp::new(
         cli.minimum_timing as i64,
         &database_path,
+        config,
+        config_source,
+        first_run_marker_path(&cli.config),
         appmsg_sender.clone(),
         &desktop_controller,
     )
     .await?;
 
-    // Initialize timing for the current desktop
-    timings_app.start_timing().await?;
+    // Flush timings left unwritten by a crash
+    timings_app.recover_state().await;
+
+    // Initialize timing for the current desktop, resuming the timing running
+    // on last shutdown if possible
+    timings_app.restore_timing().await?;
 
     let appmsg_sender_ = appmsg_sender.clone();
     let mut app = Application::new(move |t| {
         let _ = appmsg_sender_.send(AppMessage::WaylandDispatch(t));
     });
-    spawn_idle_monitor_thread(appmsg_sender.clone(), cli.idle_timeout);
-    spawn_stdin_reader(appmsg_sender.clone());
-    spawn_write_timings_thread(appmsg_sender.clone());
-    spawn_keepalive_thread(appmsg_sender.clone());
-    spawn_virtual_desktop_listener(desktop_controller.clone(), appmsg_sender.clone());
+    if let Some((thread, handle)) = spawn_idle_monitor_thread(
+        appmsg_sender.clone(),
+        timings_app.config.idle_timeout(),
+        timings_app.config.idle_seat.clone(),
+        shutdown.signal(),
+    ) {
+        shutdown.add_thread("idle monitor", thread);
+        timings_app.idle_monitor = Some(handle);
+    }
+    // Blocked in reading stdin and holds no resources, so it's not joined
+    if stdin_reader_enabled(cli.interactive, std::io::stdin().is_terminal()) {
+        spawn_stdin_reader(appmsg_sender.clone());
+    }
+    shutdown.add_task(
+        "write timings",
+        spawn_write_timings_thread(appmsg_sender.clone(), shutdown.signal()),
+    );
+    shutdown.add_task(
+        "keep alive",
+        spawn_keepalive_thread(appmsg_sender.clone(), shutdown.signal()),
+    );
+    shutdown.add_task(
+        "virtual desktop listener",
+        spawn_virtual_desktop_listener(
+            desktop_controller.clone(),
+            appmsg_sender.clone(),
+            shutdown.signal(),
+        ),
+    );
+    #[cfg(unix)]
+    shutdown.add_task(
+        "reload signal listener",
+        spawn_reload_signal_listener(appmsg_sender.clone(), shutdown.signal()),
+    );
+    shutdown.add_task(
+        "timezone listener",
+        spawn_timezone_listener(appmsg_sender.clone(), shutdown.signal()),
+    );
+    shutdown.add_task(
+        "screensaver listener",
+        spawn_screensaver_listener(appmsg_sender.clone(), shutdown.signal()),
+    );
+    if timings_app.config.track_applications {
+        if desktop_controller.is_manual() {
+            log::warn!("Application tracking requires KDE, not tracking applications");
+        } else {
+            shutdown.add_task(
+                "active application listener",
+                spawn_active_application_listener(appmsg_sender.clone(), shutdown.signal()),
+            );
+        }
+    }
+    if let Some(http_config) = timings_app.config.http_server.clone() {
+        shutdown.add_task(
+            "http server",
+            spawn_http_server(
+                http_config,
+                database_path.clone(),
+                timings_app.pool.clone(),
+                shutdown.signal(),
+            ),
+        );
+    }
+    if timings_app.config.weekly_report.is_some() {
+        shutdown.add_task(
+            "weekly report",
+            spawn_weekly_report_thread(appmsg_sender.clone(), shutdown.signal()),
+        );
+    }
+    if timings_app.config.compact_gap_seconds.is_some() {
+        shutdown.add_task(
+            "compaction",
+            spawn_compaction_thread(appmsg_sender.clone(), shutdown.signal()),
+        );
+    }
     app.run_dispatcher();
-    loop {
+    if timings_app.is_onboarding() {
+        timings_app.show_gui(&mut app);
+    }
+    let result = loop {
         if let Some(event) = appmsgs.recv().await {
             match timings_app.handle_app_events(&mut app, &event).await {
                 Ok(true) => break Ok(()),
@@ -141,7 +449,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 Ok(false) => {}
             }
         }
+    };
+
+    // Stop spawned tasks and threads so that D-Bus and Wayland connections are
+    // released before exit
+    let unfinished = shutdown.shutdown(SHUTDOWN_TIMEOUT).await;
+    if !unfinished.is_empty() {
+        log::warn!("Exiting without stopping: {}", unfinished.join(", "));
     }
+    drop(timings_app);
+    result
 }
 
 struct TimingsApp {
@@ -149,13 +466,68 @@ struct TimingsApp {
     timings_recorder: timings::TimingsRecorder,
     pool: SqlitePool,
     sender: UnboundedSender<AppMessage>,
-    desktop_controller: KDEVirtualDesktopController,
+    desktop_controller: DesktopController,
+    config: Config,
+
+    // Configuration file and command line options, for reloading
+    config_source: ConfigSource,
+
+    // Running idle monitor, `None` if idle monitoring is disabled
+    idle_monitor: Option<IdleMonitorHandle>,
+
+    // Timing stopped from the tray, not resumed after idle
+    manually_stopped: bool,
+
+    // Idle monitor reported the user idle at, `None` while active
+    user_idle: Option<chrono::DateTime<chrono::Utc>>,
+
+    // Idle monitor failed, shown in the tray tooltip
+    idle_detection_unavailable: bool,
+
+    // Shared with the overlay, keeps its renames from overwriting others
+    rename_guard: RenameGuard,
+
+    // Idle tracking inhibited over D-Bus, e.g. from a remote session
+    idle_inhibit: IdleInhibit,
+
+    // Delays stopping the timing on short idles
+    idle_stop: IdleStop,
+
+    // Screen locked time per day, from the screensaver states
+    screen_idle: ScreenIdle<Local>,
+
+    // Day the timings were last compacted, see `Config::compact_gap_seconds`
+    compacted_on: Option<chrono::NaiveDate>,
+
+    // Journal carrying the running timing across restarts
+    journal_path: Option<PathBuf>,
+
+    // Unwritten recorder state, saved on keep alive for crash recovery
+    state_path: Option<PathBuf>,
+
+    // Set while exiting, confirms exit if writing timings fails
+    exit_flow: Option<ExitFlow>,
+
+    // Marker written when the first run onboarding is dismissed, set while
+    // the onboarding is shown
+    onboarding: Option<PathBuf>,
 
     // Current desktop, updated on desktop change
     current_desktop: DesktopId,
 
     // Gui state
     gui_overlay: Option<GuiOverlay>,
+    gui_stats: Option<GuiStats>,
+
+    // Output where the pointer last entered the overlay
+    last_pointer_output: Option<WlOutput>,
+
+    // Quiet mode toggled manually from tray or D-Bus
+    quiet_mode: bool,
+
+    // Overlay shown while the peek key is held
+    peek: Peek,
+    _dbus_connection: Option<zbus::Connection>,
 
     // Tray icon
     tray_icon: trayicon::TrayIcon<AppMessage>,
@@ -167,17 +539,33 @@ impl TimingsApp {
     pub async fn new(
         minimum_timing: i64,
         database: &str,
+        config: Config,
+        config_source: ConfigSource,
+        first_run_marker: PathBuf,
         sender: UnboundedSender<AppMessage>,
-        desktop_controller: &KDEVirtualDesktopController,
+        desktop_controller: &DesktopController,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let options = SqliteConnectOptions::from_str(database)?.create_if_missing(true);
+        let idle_stop = IdleStop::new(config.min_idle_to_stop());
 
         let pool = SqlitePool::connect_with(options).await?;
         let mut conn = pool.acquire().await?;
-        conn.create_timings_database().await?;
+        let preparation = prepare_database(&mut conn, database).await?;
+        match &preparation {
+            Preparation::Migrated { from, to, backup } => log::info!(
+                "Migrated database from version {} to {}, backup {:?}",
+                from,
+                to,
+                backup
+            ),
+            preparation => log::trace!("Database: {:?}", preparation),
+        }
+        let onboarding =
+            needs_onboarding(&preparation, &first_run_marker).then_some(first_run_marker);
 
         let mut timings_recorder =
             TimingsRecorder::new(pool.clone(), Duration::seconds(minimum_timing));
+        timings_recorder.set_source(config.source.clone());
 
         let sender_ = sender.clone();
         timings_recorder.set_running_changed_callback(move |running| {
@@ -186,7 +574,7 @@ impl TimingsApp {
 
         // Insert mockdata in debug mode with :memory:
         #[cfg(debug_assertions)]
-        if database == "sqlite::memory:" {
+        if is_memory_database(database) {
             conn.insert_mockdata(chrono::Utc::now()).await?;
         }
 
@@ -199,6 +587,12 @@ impl TimingsApp {
         let green_icon = Icon::from_buffer(ICON_GREEN, None, None)?;
         let red_icon = Icon::from_buffer(ICON_RED, None, None)?;
         let tray_icon_sender = sender.clone();
+        let mut menu = MenuBuilder::new();
+        if desktop_controller.is_manual() {
+            menu = menu
+                .item("Start timing", AppMessage::StartTiming)
+                .item("Stop timing", AppMessage::StopTiming);
+        }
         let tray_icon = TrayIconBuilder::new()
             .sender(move |m: &AppMessage| {
                 let _ = tray_icon_sender.send(m.clone());
@@ -207,19 +601,49 @@ impl TimingsApp {
             .icon(green_icon.clone())
             .tooltip(format!("Timings").as_str())
             .menu(
-                MenuBuilder::new()
-                    .item("Show stats", AppMessage::ShowStats)
+                menu.item("Show stats", AppMessage::ShowStats)
+                    .item("Statistics window", AppMessage::ToggleStatsWindow)
+                    .checkable("Quiet mode", false, AppMessage::ToggleQuietMode)
+                    .item("Reload config", AppMessage::ReloadConfig)
                     .item("Exit", AppMessage::Exit),
             )
             .build()?;
 
+        let dbus_connection = match serve_dbus(sender.clone()).await {
+            Ok(connection) => Some(connection),
+            Err(e) => {
+                log::warn!("Failed to serve D-Bus interface: {}", e);
+                None
+            }
+        };
+
         Ok(Self {
             timings_recorder,
             pool,
             sender,
             desktop_controller: desktop_controller.clone(),
+            config,
+            config_source,
+            idle_monitor: None,
+            manually_stopped: false,
+            user_idle: None,
+            idle_detection_unavailable: false,
+            rename_guard: RenameGuard::new(),
+            idle_inhibit: IdleInhibit::new(),
+            idle_stop,
+            screen_idle: ScreenIdle::new(Local),
+            compacted_on: None,
+            journal_path: journal_path(database),
+            state_path: state_path(database),
+            exit_flow: None,
+            onboarding,
             current_desktop,
             gui_overlay: None,
+            gui_stats: None,
+            last_pointer_output: None,
+            quiet_mode: false,
+            peek: Peek::new(),
+            _dbus_connection: dbus_connection,
             tray_icon,
             green_icon,
             red_icon,
@@ -228,11 +652,14 @@ impl TimingsApp {
 
     /// Starts timing from a desktop name.
     /// The desktop name is expected to be in the format "client: project".
-    /// If no colon is present, the entire name is used as the client.
-    /// Only starts timing if both client and project can be parsed.
-    fn start_timing_from_desktop_name(&mut self, desktop_name: &str) -> bool {
-        let (client, project) = parse_desktop_name(desktop_name);
-
+    /// Stops timing if the name is not valid, see `timing_for_desktop_name`.
+    /// Names without a project are recorded as unassigned if
+    /// `track_unassigned` is configured.
+    fn start_timing_from_desktop_name(
+        &mut self,
+        desktop_name: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> bool {
         if self
             .gui_overlay
             .as_ref()
@@ -246,40 +673,188 @@ impl TimingsApp {
             return false;
         }
 
-        if let (Some(client), Some(project)) = (client, project) {
-            trace!(
-                "Starting timing: desktop name '{}' parsed to client '{}' and project '{}'",
-                desktop_name, client, project
-            );
-            self.timings_recorder
-                .start_timing(client.clone(), project.clone(), chrono::Utc::now());
-            self.sender.send(AppMessage::RequestRender).ok();
+        match timing_for_desktop_name(desktop_name, self.config.track_unassigned) {
+            Ok((client, project)) => {
+                trace!(
+                    "Starting timing: desktop name '{}' parsed to client '{}' and project '{}'",
+                    desktop_name, client, project
+                );
+                self.timings_recorder.start_timing(client, project, now);
+                self.sender.send(AppMessage::RequestRender).ok();
 
-            true
-        } else {
-            log::warn!(
-                "Stopping timing: desktop name '{}' has no valid project",
-                desktop_name
-            );
-            self.stop_timing();
-            false
+                true
+            }
+            Err(e) => {
+                log::warn!(
+                    "Stopping timing: desktop name '{}' is not valid: {}",
+                    desktop_name,
+                    e
+                );
+                self.stop_timing_at(now);
+                false
+            }
         }
     }
 
     pub async fn start_timing(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.start_timing_at(chrono::Utc::now()).await
+    }
+
+    /// Starts timing for the current desktop from the given time, e.g. when
+    /// the user resumed
+    pub async fn start_timing_at(
+        &mut self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let current_desktop_name = self
+            .desktop_controller
+            .get_desktop_name(&self.current_desktop)
+            .await
+            .unwrap_or_else(|_| "Unknown".to_string());
+        self.start_timing_from_desktop_name(&current_desktop_name, now);
+        Ok(())
+    }
+
+    /// Restores the timing running on last shutdown, or starts timing for the
+    /// current desktop if there is none.
+    pub async fn restore_timing(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let saved = match &self.journal_path {
+            Some(path) => take_running_timing(path).unwrap_or_else(|e| {
+                log::warn!("Failed to read journal {:?}: {}", path, e);
+                None
+            }),
+            None => None,
+        };
+        let Some(saved) = saved else {
+            return self.start_timing().await;
+        };
+
+        // Without virtual desktops the saved timing is the current project
+        if let DesktopController::Manual(controller) = &mut self.desktop_controller {
+            let name = format!("{}: {}", saved.client, saved.project);
+            controller
+                .update_desktop_name(self.current_desktop.clone(), &name)
+                .await?;
+        }
+
         let current_desktop_name = self
             .desktop_controller
             .get_desktop_name(&self.current_desktop)
             .await
             .unwrap_or_else(|_| "Unknown".to_string());
-        self.start_timing_from_desktop_name(&current_desktop_name);
+        let (client, project) =
+            timing_for_desktop_name(&current_desktop_name, self.config.track_unassigned)
+                .unwrap_or_default();
+        let outcome =
+            self.timings_recorder
+                .restore_timing(saved, &client, &project, chrono::Utc::now());
+        log::info!("Restored saved timing: {:?}", outcome);
         Ok(())
     }
 
+    /// Persists the running timing to the journal for the next start.
+    pub fn save_running_timing(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(path) = &self.journal_path {
+            let saved = self.timings_recorder.saved_timing(chrono::Utc::now());
+            save_running_timing(path, saved.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Writes timings left unwritten by a crash, saved with `save_state`.
+    pub async fn recover_state(&mut self) {
+        let Some(path) = &self.state_path else {
+            return;
+        };
+        let state = match take_recorder_state(path) {
+            Ok(Some(state)) => state,
+            Ok(None) => return,
+            Err(e) => {
+                log::error!("Failed to read recorder state {:?}: {}", path, e);
+                return;
+            }
+        };
+        log::warn!("Recovering timings left unwritten by a crash");
+        if let Err(e) = self.timings_recorder.restore_state(&state) {
+            log::error!("Failed to restore recorder state: {}", e);
+            return;
+        }
+        if let Err(e) = self.write_timings().await {
+            log::error!("Failed to write recovered timings: {}", e);
+        }
+    }
+
+    /// Saves the unwritten recorder state for crash recovery.
+    pub fn save_state(&self) {
+        if let Some(path) = &self.state_path {
+            if let Err(e) = std::fs::write(path, self.timings_recorder.serialize_state()) {
+                log::error!("Failed to save recorder state {:?}: {}", path, e);
+            }
+        }
+    }
+
+    /// Removes the saved recorder state after timings are written on exit.
+    fn remove_state(&self) {
+        if let Some(path) = &self.state_path {
+            if path.exists() {
+                if let Err(e) = std::fs::remove_file(path) {
+                    log::error!("Failed to remove recorder state {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+
     /// Stops the current timing.
     pub fn stop_timing(&mut self) {
-        log::info!("Stopping timing");
-        self.timings_recorder.stop_timing(chrono::Utc::now());
+        self.stop_timing_at(chrono::Utc::now());
+    }
+
+    /// Stops the current timing at the given time, e.g. when the user idled
+    pub fn stop_timing_at(&mut self, now: chrono::DateTime<chrono::Utc>) {
+        log::info!("Stopping timing at {}", now);
+        self.timings_recorder.stop_timing(now);
+    }
+
+    /// Pauses the current timing at the given time, e.g. when the user idled,
+    /// `resume_timing_at` continues the same project
+    pub fn pause_timing_at(&mut self, now: chrono::DateTime<chrono::Utc>) {
+        log::info!("Pausing timing at {}", now);
+        self.timings_recorder.pause_timing(now);
+    }
+
+    /// Pauses the timing for the idle started at the given time, at once or
+    /// once the idle has lasted `min_idle_to_stop`
+    fn idle_timing(&mut self, idled_at: chrono::DateTime<chrono::Utc>) {
+        match self.idle_stop.idled(idled_at) {
+            IdleStopAction::Stop(at) => self.pause_timing_at(at),
+            IdleStopAction::CheckAfter(min_idle) => {
+                // Idle may have started before, e.g. when an inhibit expired
+                let delay = (idled_at + min_idle - chrono::Utc::now()).max(Duration::zero());
+                let tx = self.sender.clone();
+                run_debounced_spawn(
+                    "idle_stop_check",
+                    delay.to_std().unwrap_or_default(),
+                    async move {
+                        let _ = tx.send(AppMessage::IdleStopCheck);
+                    },
+                );
+            }
+        }
+    }
+
+    /// Resumes the paused timing from the given time even if the desktop was
+    /// renamed meanwhile, or starts timing for the current desktop if none is
+    /// paused
+    pub async fn resume_timing_at(
+        &mut self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if self.timings_recorder.resume_timing(now) {
+            log::info!("Resumed timing at {}", now);
+            self.sender.send(AppMessage::RequestRender).ok();
+            return Ok(());
+        }
+        self.start_timing_at(now).await
     }
 
     /// Keeps the current timing alive.
@@ -288,6 +863,41 @@ impl TimingsApp {
         self.timings_recorder.keep_alive_timing(chrono::Utc::now());
     }
 
+    /// Shows the running timing and its hours today in the tray tooltip, with
+    /// the presence and screen lock time of the day.
+    async fn update_tooltip(&mut self) {
+        let mut tooltip = match self.timings_recorder.current_timing().cloned() {
+            Some(current) => {
+                let today = self
+                    .timings_recorder
+                    .get_totals(&current.client, &current.project, chrono::Utc::now())
+                    .await
+                    .map(|totals| totals.today)
+                    .ok();
+                tooltip_text(
+                    &current.client,
+                    &current.project,
+                    today,
+                    self.config.tooltip_rounding_minutes(),
+                )
+            }
+            None => "Timings".to_string(),
+        };
+        let now = chrono::Utc::now();
+        if let Some(presence) = presence_text(self.timings_recorder.presence_today(now)) {
+            tooltip.push('\n');
+            tooltip.push_str(&presence);
+        }
+        if let Some(locked) = screen_locked_text(self.screen_idle_today(now)) {
+            tooltip.push('\n');
+            tooltip.push_str(&locked);
+        }
+        if self.idle_detection_unavailable {
+            tooltip.push_str("\nIdle detection unavailable");
+        }
+        self.tray_icon.set_tooltip(&tooltip).ok();
+    }
+
     /// Writes accumulated timings to the database.
     pub async fn write_timings(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         log::info!("Writing timings to database");
@@ -323,10 +933,53 @@ impl TimingsApp {
         println!("{}", "-".repeat(64));
 
         // Print each row
+        let locale = &self.config.locale;
         for total in totals {
             println!(
-                "{:<12} {:<20} {:<20} {:>10.2}",
-                total.day, total.client, total.project, total.hours
+                "{:<12} {:<20} {:<20} {:>10}",
+                locale.format_date(total.day),
+                total.client,
+                total.project,
+                locale.format_hours(total.hours)
+            );
+        }
+        println!();
+
+        Ok(())
+    }
+
+    /// Shows weekly totals from the past 6 months, for invoicing.
+    pub async fn show_weekly_totals(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        use timings::TimingsQueries;
+
+        let mut conn = self.pool.acquire().await?;
+        let end_date = chrono::Local::now().naive_local().date();
+        let start_date = end_date - chrono::Duration::days(180);
+
+        let mut totals = conn
+            .get_timings_weekly_totals(Local, start_date, end_date, None, None)
+            .await?;
+        totals.reverse();
+
+        if totals.is_empty() {
+            println!("No timings found for the past 6 months.");
+            return Ok(());
+        }
+
+        println!(
+            "\n{:<12} {:<20} {:<20} {:>10}",
+            "Week", "Client", "Project", "Hours"
+        );
+        println!("{}", "-".repeat(64));
+
+        let locale = &self.config.locale;
+        for total in totals {
+            println!(
+                "{:<12} {:<20} {:<20} {:>10}",
+                total.year_week,
+                total.client,
+                total.project,
+                locale.format_hours(total.hours)
             );
         }
         println!();
@@ -341,9 +994,7 @@ impl TimingsApp {
         let end_date = chrono::Local::now().naive_local().date();
         let start_date = end_date - chrono::Duration::days(28);
 
-        let mut summaries = conn
-            .get_timings_daily_totals_and_summaries(Local, start_date, end_date, None, None)
-            .await?;
+        let mut summaries = conn.get_full_report(start_date, end_date, Local).await?;
         summaries.reverse();
 
         if summaries.is_empty() {
@@ -359,10 +1010,15 @@ impl TimingsApp {
         println!("{}", "-".repeat(100));
 
         // Print each row
+        let locale = &self.config.locale;
         for summary in summaries {
             println!(
-                "{:<12} {:<20} {:<20} {:>10.2} {}",
-                summary.day, summary.client, summary.project, summary.hours, summary.summary
+                "{:<12} {:<20} {:<20} {:>10} {}",
+                locale.format_date(summary.day),
+                summary.client,
+                summary.project,
+                locale.format_hours(summary.hours),
+                summary.summary
             );
         }
         println!();
@@ -370,7 +1026,254 @@ impl TimingsApp {
         Ok(())
     }
 
+    /// Writes the weekly reports which are due according to the schedule,
+    /// including the ones missed while the app was not running.
+    pub async fn write_weekly_reports(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(report_config) = self.config.weekly_report.clone() else {
+            return Ok(());
+        };
+        let due = due_report_paths(&report_config, Local::now().naive_local());
+        if due.is_empty() {
+            return Ok(());
+        }
+
+        // Ensure the running timing is included in the report
+        self.timings_recorder
+            .write_timings(chrono::Utc::now())
+            .await?;
+        tokio::fs::create_dir_all(report_config.dir()).await?;
+
+        for (week, path) in due {
+            let Some((from, to)) = week_range(week) else {
+                continue;
+            };
+            let mut conn = self.pool.acquire().await?;
+            let title = format!("Timings {}", self.config.locale.format_week(week));
+            let report = timings::markdown_report(
+                &mut *conn,
+                Local,
+                &title,
+                from,
+                to,
+                &self.config.locale,
+                self.config.include_unassigned,
+            )
+            .await?;
+            tokio::fs::write(&path, report).await?;
+            log::info!("Wrote weekly report {:?}", path);
+
+            self.notify("Weekly report written", &path.to_string_lossy())
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Compacts the timings started before today once a day, see
+    /// `Config::compact_gap_seconds`. The running timing is left alone, it's
+    /// written again by its start, the timings recorded before it are written
+    /// first so that they are compacted too. Only the days before today are
+    /// compacted, the gaps of today are shown in the overlay.
+    pub async fn compact_timings(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        use timings::TimingsMutations;
+
+        let Some(gap_seconds) = self.config.compact_gap_seconds else {
+            return Ok(());
+        };
+        let today = Local::now().date_naive();
+        if self.compacted_on == Some(today) {
+            return Ok(());
+        }
+        let Some(midnight) = today
+            .and_time(chrono::NaiveTime::MIN)
+            .and_local_timezone(Local)
+            .earliest()
+        else {
+            return Ok(());
+        };
+        let mut to = midnight.with_timezone(&chrono::Utc);
+        if let Some(current) = self.timings_recorder.current_timing() {
+            to = to.min(current.start);
+        }
+        let filters = timings::GetTimingsFilters {
+            to: Some(to - Duration::milliseconds(1)),
+            ..Default::default()
+        };
+
+        self.write_timings().await?;
+        let mut conn = self.pool.acquire().await?;
+        let removed = conn
+            .compact_timings(Local, Duration::seconds(gap_seconds as i64), filters)
+            .await?;
+        self.compacted_on = Some(today);
+        log::info!("Compacted timings, removed {}", removed);
+        Ok(())
+    }
+
+    /// Time the screen has been locked today, including an ongoing lock
+    pub fn screen_idle_today(&self, now: chrono::DateTime<chrono::Utc>) -> chrono::Duration {
+        let today = now.with_timezone(&Local).date_naive();
+        self.screen_idle.idle_on(today, now)
+    }
+
+    /// Returns true if overlays and notifications should be suppressed
+    pub fn is_quiet(&self) -> bool {
+        quiet::is_quiet(self.quiet_mode, &self.config.quiet, Local::now().time())
+    }
+
+    pub fn set_quiet_mode(&mut self, quiet: bool) {
+        log::info!("Quiet mode: {}", quiet);
+        self.quiet_mode = quiet;
+        self.tray_icon
+            .set_menu_item_checkable(AppMessage::ToggleQuietMode, quiet)
+            .ok();
+    }
+
+    /// Shows a desktop notification unless in quiet mode
+    pub async fn notify(&self, summary: &str, body: &str) {
+        if self.is_quiet() {
+            log::info!("Quiet mode, suppressed notification: {}", summary);
+            return;
+        }
+        if let Err(e) = notify(summary, body).await {
+            log::error!("Failed to show notification: {}", e);
+        }
+    }
+
+    /// Re-reads the configuration and applies the changes which can be
+    /// applied without restarting
+    pub async fn reload_config(&mut self, app: &mut Application) {
+        let config = match self.config_source.load() {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("Failed to reload config: {}", e);
+                self.notify("Config not reloaded", &e.to_string()).await;
+                return;
+            }
+        };
+        let changes = config_changes(&self.config, &config);
+        log::info!("Reloaded config: {:?}", changes);
+        self.config = config;
+
+        for setting in &changes.needs_restart {
+            log::warn!("Changed {} takes effect after restart", setting);
+        }
+        if changes
+            .idle_timeout
+            .is_some_and(|timeout| apply_idle_timeout(&mut self.idle_monitor, timeout))
+        {
+            // Nothing resumes the user once the monitor is gone
+            self.user_idle = None;
+            self.idle_stop.resumed();
+            self.timings_recorder
+                .set_user_idle(false, chrono::Utc::now());
+        }
+        self.timings_recorder.set_source(self.config.source.clone());
+
+        // Overlay reads its settings when shown, show it again unless it's
+        // being edited
+        let editing = self
+            .gui_overlay
+            .as_ref()
+            .is_some_and(|overlay| overlay.has_keyboard_focus());
+        if (changes.overlay_size.is_some() || changes.overlay)
+            && !editing
+            && self.gui_overlay.take().is_some()
+        {
+            self.show_gui(app);
+        }
+        self.update_tooltip().await;
+    }
+
+    /// True while the first run onboarding is shown in the overlay
+    pub fn is_onboarding(&self) -> bool {
+        self.onboarding.is_some()
+    }
+
+    /// Dismisses the onboarding for good and hides the overlay
+    fn finish_onboarding(&mut self) {
+        if let Some(marker) = self.onboarding.take() {
+            if let Err(e) = mark_first_run_done(&marker) {
+                log::warn!("Failed to write first run marker {:?}: {}", marker, e);
+            }
+        }
+        self.gui_overlay.take();
+    }
+
+    /// Error shown on the exit confirmation, if writing timings failed on exit
+    pub fn exit_confirmation(&self) -> Option<&str> {
+        self.exit_flow.as_ref().and_then(ExitFlow::confirmation)
+    }
+
+    /// Saves the journal and writes timings before exit
+    async fn flush_before_exit(&mut self) -> Result<(), String> {
+        if let Err(e) = self.save_running_timing() {
+            log::error!("Failed to save running timing: {}", e);
+        }
+        self.write_timings().await.map_err(|e| e.to_string())?;
+        self.remove_state();
+        Ok(())
+    }
+
+    /// Carries out exit decisions, returns true if the app should quit
+    async fn run_exit_decision(
+        &mut self,
+        app: &mut Application,
+        mut decision: ExitDecision,
+    ) -> bool {
+        loop {
+            decision = match decision {
+                ExitDecision::Flush => {
+                    let result = self.flush_before_exit().await;
+                    self.exit_flow
+                        .get_or_insert_with(ExitFlow::new)
+                        .flushed(result)
+                }
+                ExitDecision::Quit => return true,
+                ExitDecision::Confirm(e) => {
+                    log::error!("Failed to write timings on exit: {}", e);
+                    self.show_gui(app);
+                    self.request_gui_frame();
+                    let tx = self.sender.clone();
+                    run_debounced_spawn(
+                        "exit_confirmation_timeout",
+                        EXIT_CONFIRMATION_TIMEOUT,
+                        async move {
+                            let _ = tx.send(AppMessage::ExitChoice(ExitChoice::TimedOut));
+                        },
+                    );
+                    return false;
+                }
+                ExitDecision::Stay => {
+                    log::info!("Exit cancelled");
+                    self.exit_flow = None;
+                    self.request_gui_frame();
+                    self.hide_gui_after_delay();
+                    return false;
+                }
+            };
+        }
+    }
+
     // GUI methods
+    pub fn request_gui_frame(&mut self) {
+        if let Some(overlay) = self.gui_overlay.as_mut() {
+            overlay.request_frame();
+        }
+    }
+
+    /// Opens the statistics window with fresh statistics, or closes it if
+    /// open
+    pub async fn toggle_gui_stats(&mut self, app: &mut Application) {
+        if self.gui_stats.take().is_some() {
+            log::trace!("Closing statistics window");
+            return;
+        }
+        let mut stats = GuiStats::new(app, self.pool.clone());
+        stats.refresh().await;
+        self.gui_stats = Some(stats);
+    }
+
     pub fn show_gui(&mut self, app: &mut Application) {
         if self.gui_overlay.is_none() {
             log::trace!("Showing overlay GUI");
@@ -381,11 +1284,32 @@ impl TimingsApp {
                 self.desktop_controller.clone(),
             );
             self.gui_overlay = Some(overlay);
+        } else if let Some(mut overlay) = self.gui_overlay.take() {
+            overlay.follow_active_output(app, self);
+            self.gui_overlay = Some(overlay);
         }
         self.hide_gui_after_delay();
     }
 
+    /// Shows the overlay with the keyboard on the client field, on the output
+    /// of the pointer
+    pub fn show_gui_for_keyboard(&mut self, app: &mut Application) {
+        self.show_gui(app);
+        let output = self.last_pointer_output.clone();
+        if let Some(overlay) = self.gui_overlay.as_mut() {
+            overlay.grab_keyboard(output.as_ref());
+        }
+    }
+
     pub fn hide_gui(&mut self) {
+        if self.exit_confirmation().is_some() {
+            log::trace!("Not hiding overlay, exit confirmation is shown");
+            return;
+        }
+        if self.is_onboarding() {
+            log::trace!("Not hiding overlay, onboarding is shown");
+            return;
+        }
         if let Some(ref overlay) = self.gui_overlay {
             if overlay.has_keyboard_focus() {
                 log::trace!("Not hiding overlay, has keyboard focus");
@@ -397,15 +1321,17 @@ impl TimingsApp {
         self.gui_overlay.take();
     }
 
+    /// Schedules hiding the overlay after the configured delay, does nothing
+    /// if auto-hide is disabled
     pub fn hide_gui_after_delay(&mut self) {
+        let Some(delay) = self.config.overlay_hide_delay() else {
+            log::trace!("Not scheduling overlay hide, auto-hide is disabled");
+            return;
+        };
         let tx = self.sender.clone();
-        run_debounced_spawn(
-            "hide_gui_after_delay",
-            std::time::Duration::from_secs(3),
-            async move {
-                let _ = tx.send(AppMessage::HideLayerOverlay);
-            },
-        );
+        run_debounced_spawn("hide_gui_after_delay", delay, async move {
+            let _ = tx.send(AppMessage::HideLayerOverlay);
+        });
     }
 
     pub async fn handle_app_events(
@@ -427,18 +1353,44 @@ impl TimingsApp {
                     overlay.handle_wayland_events(self, app, &events).await;
                     self.gui_overlay = Some(overlay);
                 }
+                if let Some(mut stats) = self.gui_stats.take() {
+                    stats.handle_wayland_events(self, app, &events).await;
+                    if !stats.close_requested() {
+                        self.gui_stats = Some(stats);
+                    }
+                }
             }
             AppMessage::Exit => {
-                return Ok(true);
+                if self.exit_flow.is_none() {
+                    self.exit_flow = Some(ExitFlow::new());
+                    return Ok(self.run_exit_decision(app, ExitDecision::Flush).await);
+                }
+            }
+            AppMessage::ExitChoice(choice) => {
+                if let Some(flow) = self.exit_flow.as_mut() {
+                    let decision = flow.choose(*choice);
+                    return Ok(self.run_exit_decision(app, decision).await);
+                }
             }
             AppMessage::WriteTimings => {
                 if let Err(e) = self.write_timings().await {
                     log::error!("Failed to write timings: {}", e);
                 }
             }
+            AppMessage::GuiOverlayEvent(GuiOverlayEvent::UpdateTotalsTimer) => {
+                self.update_tooltip().await;
+            }
             AppMessage::KeepAlive => {
                 log::trace!("Keep alive timing");
+                if self.idle_inhibit.take_expired(chrono::Utc::now())
+                    && let Some(idled_at) = self.user_idle
+                {
+                    log::info!("Idle tracking inhibit expired while idle");
+                    self.idle_timing(idled_at);
+                }
                 self.keep_alive();
+                self.save_state();
+                self.update_tooltip().await;
             }
             AppMessage::ShowStats => {
                 // Execute bash script to show stats in a separate thread
@@ -456,11 +1408,19 @@ impl TimingsApp {
                     },
                 );
             }
+            AppMessage::ToggleStatsWindow => {
+                self.toggle_gui_stats(app).await;
+            }
             AppMessage::ShowDailyTotals => {
                 if let Err(e) = self.show_daily_totals().await {
                     log::error!("Failed to show daily totals: {}", e);
                 }
             }
+            AppMessage::ShowWeeklyTotals => {
+                if let Err(e) = self.show_weekly_totals().await {
+                    log::error!("Failed to show weekly totals: {}", e);
+                }
+            }
             AppMessage::ShowDailySummaries => {
                 if let Err(e) = self.show_daily_summaries().await {
                     log::error!("Failed to show daily summaries: {}", e);
@@ -469,10 +1429,15 @@ impl TimingsApp {
             AppMessage::TrayIconClicked => {
                 self.show_gui(app);
             }
+            AppMessage::FocusOverlay => {
+                self.show_gui_for_keyboard(app);
+            }
             AppMessage::VirtualDesktop(vd_msg) => match vd_msg {
                 VirtualDesktopMessage::DesktopNameChanged(id, name) => {
+                    self.rename_guard.handle_message(vd_msg);
                     if *id == self.current_desktop {
-                        self.start_timing_from_desktop_name(name);
+                        self.manually_stopped = false;
+                        self.start_timing_from_desktop_name(name, chrono::Utc::now());
                     }
                 }
                 VirtualDesktopMessage::DesktopChange(id) => {
@@ -482,17 +1447,51 @@ impl TimingsApp {
                         .await
                         .unwrap_or_else(|_| "Unknown".to_string());
                     self.current_desktop = id.clone();
-                    self.start_timing_from_desktop_name(&name);
-                    self.show_gui(app);
+                    self.start_timing_from_desktop_name(&name, chrono::Utc::now());
+                    if !self.is_quiet() {
+                        self.show_gui(app);
+                    }
                 }
             },
-            AppMessage::UserIdled => {
-                log::trace!("User activity changed to idling");
-                self.stop_timing();
+            AppMessage::UserIdled(at) => {
+                log::trace!("User activity changed to idling at {}", at);
+                self.user_idle = Some(*at);
+                self.timings_recorder.set_user_idle(true, *at);
+                if let Some(until) = self.idle_inhibit.until(chrono::Utc::now()) {
+                    log::info!("Idle tracking inhibited until {}, not stopping", until);
+                } else {
+                    self.idle_timing(*at);
+                }
             }
-            AppMessage::UserResumed => {
-                log::trace!("User activity changed to resumed");
-                self.start_timing().await?;
+            AppMessage::IdleStopCheck => {
+                let now = chrono::Utc::now();
+                if let Some(idle_since) = self.idle_stop.check(now) {
+                    if self.idle_inhibit.is_inhibited(now) {
+                        log::info!("Idle tracking inhibited, not stopping");
+                    } else {
+                        log::info!("Stopping timing, idle since {}", idle_since);
+                        self.timings_recorder
+                            .stop_timing_deducting_idle(idle_since, now);
+                    }
+                }
+            }
+            AppMessage::IdleMonitorFailed(e) => {
+                log::error!("Idle monitor failed, idle time is not detected: {}", e);
+                self.idle_detection_unavailable = true;
+                self.user_idle = None;
+                self.idle_stop.resumed();
+                self.timings_recorder
+                    .set_user_idle(false, chrono::Utc::now());
+                self.update_tooltip().await;
+            }
+            AppMessage::UserResumed(at) => {
+                log::trace!("User activity changed to resumed at {}", at);
+                self.user_idle = None;
+                self.idle_stop.resumed();
+                self.timings_recorder.set_user_idle(false, *at);
+                if !self.manually_stopped {
+                    self.resume_timing_at(*at).await?;
+                }
             }
             AppMessage::VirtualDesktopThreadExited => {
                 log::warn!(
@@ -502,12 +1501,95 @@ impl TimingsApp {
                 );
                 return Err("Virtual desktop listener thread has exited".into());
             }
+            AppMessage::ActiveApplication(application) => {
+                self.timings_recorder
+                    .set_active_app(application, chrono::Utc::now());
+            }
+            AppMessage::ReloadConfig => {
+                self.reload_config(app).await;
+            }
+            AppMessage::TimezoneChanged => {
+                self.timings_recorder.invalidate_caches();
+                self.request_gui_frame();
+                self.update_tooltip().await;
+            }
+            AppMessage::ScreenSaverActive(active) => {
+                let now = chrono::Utc::now();
+                self.screen_idle.set_active(*active, now);
+                log::info!(
+                    "Screensaver {}, screen idle today {} minutes",
+                    if *active { "active" } else { "inactive" },
+                    self.screen_idle_today(now).num_minutes()
+                );
+                self.update_tooltip().await;
+            }
+            AppMessage::DesktopWriter(holds) => {
+                if *holds {
+                    log::info!("Writing desktop names");
+                } else {
+                    log::warn!("Another instance writes the desktop names, overlay edits are off");
+                }
+                self.rename_guard.set_read_only(!holds);
+                self.request_gui_frame();
+            }
+            AppMessage::OnboardingDone => {
+                self.finish_onboarding();
+            }
             AppMessage::AnotherInstanceTriedToStart => {
                 log::info!("Another instance tried to start");
             }
+            AppMessage::StartTiming => {
+                self.manually_stopped = false;
+                self.start_timing().await?;
+            }
+            AppMessage::StopTiming => {
+                self.manually_stopped = true;
+                self.stop_timing();
+            }
+            AppMessage::ToggleQuietMode => {
+                self.set_quiet_mode(!self.quiet_mode);
+            }
+            AppMessage::SetQuietMode(quiet) => {
+                self.set_quiet_mode(*quiet);
+            }
+            AppMessage::InhibitIdleTracking(minutes) => {
+                let now = chrono::Utc::now();
+                self.idle_inhibit.inhibit(*minutes, now);
+                match self.idle_inhibit.until(now) {
+                    Some(until) => log::info!("Idle tracking inhibited until {}", until),
+                    None => {
+                        log::info!("Idle tracking inhibit ended");
+                        if let Some(idled_at) = self.user_idle {
+                            self.idle_timing(idled_at);
+                        }
+                    }
+                }
+            }
+            AppMessage::CheckWeeklyReport => {
+                if let Err(e) = self.write_weekly_reports().await {
+                    log::error!("Failed to write weekly report: {}", e);
+                }
+            }
+            AppMessage::CheckCompaction => {
+                if let Err(e) = self.compact_timings().await {
+                    log::error!("Failed to compact timings: {}", e);
+                }
+            }
             AppMessage::HideLayerOverlay => {
-                self.hide_gui();
+                if self.peek.is_held() {
+                    log::trace!("Not hiding overlay, peek key is held");
+                } else {
+                    self.hide_gui();
+                }
             }
+            AppMessage::Peek(_) if !self.config.peek_mode => {
+                log::warn!("Peek ignored, peek_mode is not enabled in the config");
+            }
+            AppMessage::Peek(key) => match self.peek.key(*key, self.gui_overlay.is_some()) {
+                PeekAction::Show => self.show_gui(app),
+                PeekAction::Hide => self.hide_gui(),
+                PeekAction::Nothing => {}
+            },
             AppMessage::RequestRender => {
                 // timings_app.request_gui_frame();
             }
@@ -519,6 +1601,7 @@ impl TimingsApp {
                     &self.red_icon
                 };
                 self.tray_icon.set_icon(icon).ok();
+                self.update_tooltip().await;
             }
             _ => {}
         }
@@ -527,26 +1610,161 @@ impl TimingsApp {
     }
 }
 
+/// Runs a subcommand instead of the app
+async fn run_command(
+    command: &Command,
+    cli: &Cli,
+    database_path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::InstallAutostart => {
+            let exec = std::env::current_exe()?;
+            let mut args = vec![
+                "--database".to_string(),
+                database_path.to_string(),
+                "--config".to_string(),
+                expand_tilde(&cli.config).to_string_lossy().to_string(),
+            ];
+            if cli.no_desktop_tracking {
+                args.push("--no-desktop-tracking".to_string());
+            }
+            if cli.include_unassigned {
+                args.push("--include-unassigned".to_string());
+            }
+            let path = autostart::install_autostart(&exec, &args)?;
+            println!("Installed autostart entry {}", path.display());
+        }
+        Command::UninstallAutostart => {
+            if autostart::uninstall_autostart()? {
+                println!("Removed autostart entry");
+            } else {
+                println!("No autostart entry installed");
+            }
+        }
+        Command::ShowStats => {
+            println!(
+                "{}",
+                send_to_primary(DEFAULT_PREFIX, database_path, ACTION_SHOW_STATS, "")?
+            )
+        }
+        Command::Flush => println!(
+            "{}",
+            send_to_primary(DEFAULT_PREFIX, database_path, ACTION_FLUSH, "")?
+        ),
+        Command::Quit => println!(
+            "{}",
+            send_to_primary(DEFAULT_PREFIX, database_path, ACTION_QUIT, "")?
+        ),
+        Command::Doctor { max_hours, days } => {
+            doctor(database_path, *max_hours, *days).await?;
+        }
+        Command::Repair { fix } => repair(database_path, *fix).await?,
+    }
+    Ok(())
+}
+
+/// Prints the problems found in the database
+async fn doctor(
+    database_path: &str,
+    max_hours: f64,
+    days: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use timings::TimingsQueries;
+
+    let options = SqliteConnectOptions::from_str(database_path)?.read_only(true);
+    let pool = SqlitePool::connect_with(options).await?;
+    let mut conn = pool.acquire().await?;
+    conn.check_timings_database().await?;
+    println!("Database integrity ok");
+
+    let to = Local::now().date_naive();
+    let from = to - Duration::days(days as i64);
+    let print_timing = |timing: &timings::Timing| {
+        println!(
+            "  {} - {} {}: {}",
+            timing.start.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            timing.end.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            timing.client,
+            timing.project
+        );
+    };
+
+    let implausible = conn
+        .get_implausible_days(Local, from, to, max_hours)
+        .await?;
+    for day in &implausible {
+        println!("{} has {:.1} hours:", day.day, day.hours);
+        day.timings.iter().for_each(print_timing);
+    }
+
+    let spanning = conn.get_timings_spanning_midnights(Local, from, to).await?;
+    if !spanning.is_empty() {
+        println!("Timings spanning more than one midnight:");
+        spanning.iter().for_each(print_timing);
+    }
+
+    if implausible.is_empty() && spanning.is_empty() {
+        println!("No implausible days in the past {} days", days);
+    }
+    Ok(())
+}
+
+/// Prints the broken rows and overlapping timings, and with `fix` deletes the
+/// broken rows
+async fn repair(database_path: &str, fix: bool) -> Result<(), Box<dyn std::error::Error>> {
+    use timings::TimingsMutations;
+    use timings::TimingsQueries;
+
+    let options = SqliteConnectOptions::from_str(database_path)?.read_only(!fix);
+    let pool = SqlitePool::connect_with(options).await?;
+    let mut conn = pool.acquire().await?;
+
+    let mut problems = conn.find_database_problems().await?;
+    if problems.is_empty() {
+        println!("No problems found");
+        return Ok(());
+    }
+    if !fix {
+        for problem in &problems {
+            let note = if problem.is_fixable() {
+                ""
+            } else {
+                " (manual)"
+            };
+            println!("{}{}", problem, note);
+        }
+        println!("Run with --fix to delete the broken rows");
+        return Ok(());
+    }
+
+    // Deleting a row may break the rows referring to it, they are found on
+    // the next round
+    while problems.iter().any(|problem| problem.is_fixable()) {
+        for problem in &problems {
+            if conn.fix_database_problem(problem).await? {
+                println!("Fixed: {}", problem);
+            }
+        }
+        problems = conn.find_database_problems().await?;
+    }
+    for problem in &problems {
+        println!("Not fixed: {}", problem);
+    }
+    Ok(())
+}
+
 /// Expands ~ to the home directory and ensures parent directories exist (only
 /// for DEFAULT_DATABASE)
 ///
 /// Canonicalizes the path to absolute path.
 async fn handle_database_path(path: &str) -> Result<String, Box<dyn std::error::Error>> {
-    if path.starts_with(":") || path == "sqlite::memory:" {
+    if is_memory_database(path) {
         // Special SQLite in-memory or URI path, return as is
         return Ok(path.to_string());
     }
 
     // Expand ~ to home directory
-    let expanded = if path.starts_with("~") {
-        if let Some(home) = std::env::var_os("HOME") {
-            PathBuf::from(home).join(path.strip_prefix("~/").unwrap_or(&path[1..]))
-        } else {
-            PathBuf::from(path)
-        }
-    } else {
-        PathBuf::from(path)
-    };
+    let expanded = expand_tilde(path);
 
     // Create parent directories only if they don't exist and path matches
     // DEFAULT_DATABASE
@@ -569,13 +1787,22 @@ async fn handle_database_path(path: &str) -> Result<String, Box<dyn std::error::
 /// Spawns a task that listens to virtual desktop messages and forwards them to
 /// the app message channel
 fn spawn_virtual_desktop_listener(
-    desktop_controller: KDEVirtualDesktopController,
+    desktop_controller: DesktopController,
     app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>,
-) {
+    mut stop: StopSignal,
+) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         let mut vd_controller_listener = desktop_controller;
-        if let Ok(mut vd_stream) = vd_controller_listener.listen().await {
-            while let Some(vd_msg) = vd_stream.next().await {
+        if let Ok(vd_stream) = vd_controller_listener.listen().await {
+            let mut vd_stream = std::pin::pin!(vd_stream);
+            loop {
+                let vd_msg = tokio::select! {
+                    _ = stop.stopped() => return,
+                    vd_msg = vd_stream.next() => vd_msg,
+                };
+                let Some(vd_msg) = vd_msg else {
+                    break;
+                };
                 if app_message_sender
                     .send(AppMessage::VirtualDesktop(vd_msg))
                     .is_err()
@@ -587,7 +1814,120 @@ fn spawn_virtual_desktop_listener(
         }
 
         let _ = app_message_sender.send(AppMessage::VirtualDesktopThreadExited);
-    });
+    })
+}
+
+/// Spawns a task that loads a KWin script reporting the active application
+/// and forwards the applications to the app message channel.
+///
+/// The script is unloaded when stopped, so it does not outlive the app.
+fn spawn_active_application_listener(
+    app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>,
+    mut stop: StopSignal,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut source = match KDEActiveApplicationSource::new().await {
+            Ok(source) => source,
+            Err(e) => {
+                log::error!("Failed to connect active application source: {}", e);
+                return;
+            }
+        };
+        match source.listen().await {
+            Ok(stream) => {
+                let mut stream = std::pin::pin!(stream);
+                loop {
+                    let application = tokio::select! {
+                        _ = stop.stopped() => break,
+                        application = stream.next() => application,
+                    };
+                    let Some(application) = application else {
+                        break;
+                    };
+                    if app_message_sender
+                        .send(AppMessage::ActiveApplication(application))
+                        .is_err()
+                    {
+                        // Main thread has exited, stop the loop
+                        break;
+                    }
+                }
+            }
+            Err(e) => log::error!("Failed to listen to active application: {}", e),
+        }
+        if let Err(e) = source.unload().await {
+            log::warn!("Failed to unload active application script: {}", e);
+        }
+    })
+}
+
+/// Spawns a task that invalidates cached totals when the system timezone
+/// changes, e.g. when travelling
+fn spawn_timezone_listener(
+    app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>,
+    mut stop: StopSignal,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let timezones = match timezones().await {
+            Ok(timezones) => timezones,
+            Err(e) => {
+                log::warn!("Failed to listen to timezone changes: {}", e);
+                return;
+            }
+        };
+        tokio::select! {
+            _ = stop.stopped() => {}
+            _ = forward_timezone_changes(timezones, app_message_sender) => {}
+        }
+    })
+}
+
+/// Spawns a task that reloads the configuration on SIGHUP
+#[cfg(unix)]
+fn spawn_reload_signal_listener(
+    app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>,
+    mut stop: StopSignal,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        use tokio::signal::unix::SignalKind;
+        let mut hangups = match tokio::signal::unix::signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                log::warn!("Failed to listen to SIGHUP: {}", e);
+                return;
+            }
+        };
+        loop {
+            tokio::select! {
+                _ = stop.stopped() => break,
+                _ = hangups.recv() => {
+                    log::info!("SIGHUP received, reloading config");
+                    let _ = app_message_sender.send(AppMessage::ReloadConfig);
+                }
+            }
+        }
+    })
+}
+
+/// Spawns a task that feeds the screensaver states to the screen idle time,
+/// the screensaver is not available on every desktop
+fn spawn_screensaver_listener(
+    app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>,
+    mut stop: StopSignal,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let states = match screensaver_states().await {
+            Ok(states) => states,
+            Err(e) => {
+                log::warn!("Failed to listen to screensaver: {}", e);
+                return;
+            }
+        };
+        tokio::select! {
+            _ = stop.stopped() => {}
+            _ = forward_screensaver_states(states, app_message_sender) => {}
+        }
+    })
 }
 
 /// Spawns a thread to read lines from stdin
@@ -598,6 +1938,7 @@ fn spawn_stdin_reader(app_message_sender: tokio::sync::mpsc::UnboundedSender<App
         println!("1: Write timings to database");
         println!("2: Show daily totals from past 6 months");
         println!("3: Show daily summaries from past 4 weeks");
+        println!("4: Show weekly totals from past 6 months");
         println!("Type command and press Enter: ");
     }
     // let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
@@ -618,6 +1959,9 @@ fn spawn_stdin_reader(app_message_sender: tokio::sync::mpsc::UnboundedSender<App
                 "3" => {
                     let _ = app_message_sender.send(AppMessage::ShowDailySummaries);
                 }
+                "4" => {
+                    let _ = app_message_sender.send(AppMessage::ShowWeeklyTotals);
+                }
                 _ => {
                     print_info();
                 }
@@ -627,78 +1971,254 @@ fn spawn_stdin_reader(app_message_sender: tokio::sync::mpsc::UnboundedSender<App
 }
 
 /// Spawns a thread that sends WriteTimings message every 3 minutes
-fn spawn_write_timings_thread(app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>) {
+fn spawn_write_timings_thread(
+    app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>,
+    mut stop: StopSignal,
+) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(3 * 60)).await;
+            tokio::select! {
+                _ = stop.stopped() => break,
+                _ = tokio::time::sleep(WRITE_TIMINGS_INTERVAL) => {}
+            }
             if app_message_sender.send(AppMessage::WriteTimings).is_err() {
                 // Main thread has exited, stop the loop
                 break;
             }
         }
-    });
+    })
+}
+
+/// Spawns a thread that checks every minute whether a weekly report is due.
+///
+/// The first check happens immediately to catch up with missed schedules.
+fn spawn_weekly_report_thread(
+    app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>,
+    mut stop: StopSignal,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if app_message_sender
+                .send(AppMessage::CheckWeeklyReport)
+                .is_err()
+            {
+                // Main thread has exited, stop the loop
+                break;
+            }
+            tokio::select! {
+                _ = stop.stopped() => break,
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(60)) => {}
+            }
+        }
+    })
+}
+
+/// Spawns a thread that checks hourly whether to compact the timings, they
+/// are compacted at most once per day
+fn spawn_compaction_thread(
+    app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>,
+    mut stop: StopSignal,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if app_message_sender
+                .send(AppMessage::CheckCompaction)
+                .is_err()
+            {
+                // Main thread has exited, stop the loop
+                break;
+            }
+            tokio::select! {
+                _ = stop.stopped() => break,
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(3600)) => {}
+            }
+        }
+    })
+}
+
+/// Spawns the read-only HTTP API
+///
+/// Database files are opened separately in read-only mode, in-memory database
+/// is shared with the app.
+fn spawn_http_server(
+    config: HttpServerConfig,
+    database_path: String,
+    pool: SqlitePool,
+    mut stop: StopSignal,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let pool = if is_memory_database(&database_path) {
+            pool
+        } else {
+            match timings::read_only_pool(&database_path).await {
+                Ok(pool) => pool,
+                Err(e) => {
+                    log::error!("Failed to open database for HTTP API: {}", e);
+                    return;
+                }
+            }
+        };
+        let listener = match tokio::net::TcpListener::bind(config.address).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::error!("Failed to bind HTTP API to {}: {}", config.address, e);
+                return;
+            }
+        };
+        if let Err(e) =
+            timings::serve_http(
+                listener,
+                pool,
+                config.token,
+                async move { stop.stopped().await },
+            )
+            .await
+        {
+            log::error!("HTTP API error: {}", e);
+        }
+    })
 }
 
 /// Spawns a keep alive thread for timings recorder
-fn spawn_keepalive_thread(app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>) {
+fn spawn_keepalive_thread(
+    app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>,
+    mut stop: StopSignal,
+) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+            tokio::select! {
+                _ = stop.stopped() => break,
+                _ = tokio::time::sleep(tokio::time::Duration::from_secs(30)) => {}
+            }
             if app_message_sender.send(AppMessage::KeepAlive).is_err() {
                 // Main thread has exited, stop the loop
                 break;
             }
         }
-    });
+    })
 }
 
-/// Spawns a thread that runs the idle monitor
+/// Spawns a thread that runs the idle monitor, failures are sent as
+/// `AppMessage::IdleMonitorFailed`
 fn spawn_idle_monitor_thread(
     app_message_sender: tokio::sync::mpsc::UnboundedSender<AppMessage>,
-    idle_timeout: u64,
-) {
-    if idle_timeout == 0 {
+    idle_timeout: std::time::Duration,
+    seat_name: Option<String>,
+    stop: StopSignal,
+) -> Option<(
+    thread::JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+    IdleMonitorHandle,
+)> {
+    if idle_timeout.is_zero() {
         log::info!("Idle timeout is 0, not starting idle monitor");
-        return;
+        return None;
     }
 
-    thread::spawn(move || {
-        let monitor_thread = run_idle_monitor(
-            move |i| match i {
-                idle_monitor::IdleNotification::Idle => {
-                    let _ = app_message_sender.send(AppMessage::UserIdled);
-                }
-                idle_monitor::IdleNotification::Resumed => {
-                    let _ = app_message_sender.send(AppMessage::UserResumed);
-                }
-            },
-            std::time::Duration::from_secs(idle_timeout),
-        );
-
-        match monitor_thread.join() {
-            Ok(Ok(())) => {
-                log::info!("Idle monitor completed successfully");
-            }
-            Ok(Err(e)) => {
-                log::error!("Idle monitor error: {}", e);
-            }
-            Err(_) => {
-                log::error!("Idle monitor thread panic");
+    // Idle state is tracked from the events, see `TimingsApp::user_idle`
+    let (thread, handle) = run_idle_monitor_on_seat(
+        move |event| {
+            if let Some(message) = idle_event_message(event) {
+                let _ = app_message_sender.send(message);
             }
+        },
+        idle_timeout,
+        seat_name,
+        stop.flag(),
+    );
+    Some((thread, handle))
+}
+
+/// Applies a reloaded idle timeout to the running idle monitor, a zero
+/// timeout stops the monitor. Returns true if the monitor was stopped.
+fn apply_idle_timeout(
+    idle_monitor: &mut Option<IdleMonitorHandle>,
+    timeout: std::time::Duration,
+) -> bool {
+    match idle_monitor {
+        Some(handle) if timeout.is_zero() => {
+            log::info!("Idle timeout is 0, stopping idle monitor");
+            handle.stop();
+            *idle_monitor = None;
+            true
         }
-    });
+        Some(handle) => {
+            handle.set_timeout(timeout);
+            false
+        }
+        None if timeout.is_zero() => false,
+        None => {
+            log::warn!("Idle monitor is not running, restart to enable idle timeout");
+            false
+        }
+    }
 }
 
-/// Parses a desktop name into client and project.
-/// Format: "client: project" or just "client"
-fn parse_desktop_name(desktop_name: &str) -> (Option<String>, Option<String>) {
-    let parts: Vec<&str> = desktop_name.splitn(2, ':').collect();
-    if parts.len() == 2 {
-        (
-            Some(parts[0].trim().to_string()),
-            Some(parts[1].trim().to_string()),
-        )
-    } else {
-        (Some(desktop_name.trim().to_string()), None)
+/// Message of an idle monitor event, the event time is kept so that timings
+/// stop and resume when the event happened rather than when it's handled
+fn idle_event_message(event: IdleEvent) -> Option<AppMessage> {
+    match event {
+        IdleEvent::Idle(at) => Some(AppMessage::UserIdled(at.into())),
+        IdleEvent::Resumed { at, idle_duration } => {
+            log::trace!("Resumed after {:?} idle", idle_duration);
+            Some(AppMessage::UserResumed(at.into()))
+        }
+        IdleEvent::Error(e) => Some(AppMessage::IdleMonitorFailed(e)),
+        IdleEvent::Stopped => {
+            log::info!("Idle monitor stopped");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn reloaded_idle_timeout_is_applied() {
+        let handle = IdleMonitorHandle::default();
+        let mut idle_monitor = Some(handle.clone());
+        assert!(!apply_idle_timeout(
+            &mut idle_monitor,
+            std::time::Duration::from_secs(60)
+        ));
+        assert!(idle_monitor.is_some());
+        assert!(!handle.is_stopped());
+    }
+
+    #[test]
+    fn zero_idle_timeout_stops_the_monitor() {
+        let handle = IdleMonitorHandle::default();
+        let mut idle_monitor = Some(handle.clone());
+        assert!(apply_idle_timeout(
+            &mut idle_monitor,
+            std::time::Duration::ZERO
+        ));
+        assert!(idle_monitor.is_none());
+        assert!(handle.is_stopped());
+
+        // Stopped already
+        assert!(!apply_idle_timeout(
+            &mut idle_monitor,
+            std::time::Duration::ZERO
+        ));
+    }
+
+    #[test]
+    fn idle_event_message_carries_event_time() {
+        let at = chrono::Utc.with_ymd_and_hms(2024, 3, 4, 9, 30, 0).unwrap();
+        assert_eq!(
+            idle_event_message(IdleEvent::Idle(at.into())),
+            Some(AppMessage::UserIdled(at))
+        );
+        assert_eq!(
+            idle_event_message(IdleEvent::Resumed {
+                at: at.into(),
+                idle_duration: std::time::Duration::from_secs(60),
+            }),
+            Some(AppMessage::UserResumed(at))
+        );
+        assert_eq!(idle_event_message(IdleEvent::Stopped), None);
     }
 }
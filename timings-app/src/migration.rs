@@ -0,0 +1,189 @@
+use crate::utils::is_memory_database;
+use chrono::Local;
+use sqlx::SqliteConnection;
+use std::path::Path;
+use std::path::PathBuf;
+use timings::SCHEMA_VERSION;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+
+/// What `prepare_database` did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Preparation {
+    UpToDate,
+    Created,
+    Migrated {
+        from: i64,
+        to: i64,
+        /// `None` for in-memory databases
+        backup: Option<PathBuf>,
+    },
+}
+
+/// Brings the database to the schema of this build.
+///
+/// An existing database with an older schema is first backed up to the
+/// `pre-migration/` folder next to it, then migrated and verified. A database
+/// newer than this build supports is refused and left untouched.
+pub async fn prepare_database(
+    conn: &mut SqliteConnection,
+    database_path: &str,
+) -> Result<Preparation, Box<dyn std::error::Error>> {
+    let Some(from) = conn.get_schema_version().await? else {
+        conn.create_timings_database().await?;
+        return Ok(Preparation::Created);
+    };
+    if from > SCHEMA_VERSION {
+        return Err(format!(
+            "Database {} has schema version {}, this version of timings-app supports up to {}. \
+             Upgrade timings-app to use this database.",
+            database_path, from, SCHEMA_VERSION
+        )
+        .into());
+    }
+    if from == SCHEMA_VERSION {
+        return Ok(Preparation::UpToDate);
+    }
+
+    log::warn!(
+        "Migrating database {} from schema version {} to {}",
+        database_path,
+        from,
+        SCHEMA_VERSION
+    );
+    let backup = match backup_path(database_path, from) {
+        Some(path) => {
+            std::fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+            conn.backup_timings_database(&path).await?;
+            log::info!("Backed up database to {:?}", path);
+            Some(path)
+        }
+        None => None,
+    };
+
+    conn.migrate_timings_database().await?;
+
+    let to = conn.get_schema_version().await?.unwrap_or(0);
+    if to != SCHEMA_VERSION {
+        return Err(format!(
+            "Migration ended at schema version {} instead of {}, backup is at {:?}",
+            to, SCHEMA_VERSION, backup
+        )
+        .into());
+    }
+    conn.check_timings_database().await?;
+
+    Ok(Preparation::Migrated { from, to, backup })
+}
+
+/// Backup file in `pre-migration/` next to the database, `None` for in-memory
+/// databases
+fn backup_path(database_path: &str, from: i64) -> Option<PathBuf> {
+    if is_memory_database(database_path) {
+        return None;
+    }
+    let path = Path::new(database_path);
+    let file_name = path.file_name()?.to_string_lossy();
+    Some(path.with_file_name("pre-migration").join(format!(
+        "{}.v{}.{}",
+        file_name,
+        from,
+        Local::now().format("%Y%m%d%H%M%S")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::Connection;
+    use sqlx::Executor;
+    use sqlx::sqlite::SqliteConnectOptions;
+
+    /// Tables of a database created before schema versioning
+    const VERSION_0_SCHEMA: &str =
+        include_str!("../../timings/tests/fixtures/version_0_schema.sql");
+
+    async fn connect_file(dir: &Path) -> (SqliteConnection, String) {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join("timings.db").to_string_lossy().to_string();
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        (
+            SqliteConnection::connect_with(&options).await.unwrap(),
+            path,
+        )
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "timings-migration-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn backs_up_and_migrates_old_database() {
+        let dir = temp_dir("old");
+        let (mut conn, path) = connect_file(&dir).await;
+        conn.execute(VERSION_0_SCHEMA).await.unwrap();
+
+        let preparation = prepare_database(&mut conn, &path).await.unwrap();
+        let Preparation::Migrated {
+            from: 0,
+            to: SCHEMA_VERSION,
+            backup: Some(backup),
+        } = preparation
+        else {
+            panic!("Expected migration, got {:?}", preparation);
+        };
+        assert!(backup.starts_with(dir.join("pre-migration")));
+
+        // Backup has the old schema and data
+        let options = SqliteConnectOptions::new().filename(&backup);
+        let mut backup_conn = SqliteConnection::connect_with(&options).await.unwrap();
+        assert_eq!(backup_conn.get_schema_version().await.unwrap(), Some(0));
+        assert_eq!(backup_conn.get_timings(None).await.unwrap().len(), 1);
+
+        assert_eq!(conn.get_timings(None).await.unwrap().len(), 1);
+        assert_eq!(
+            prepare_database(&mut conn, &path).await.unwrap(),
+            Preparation::UpToDate
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn creates_new_database_without_backup() {
+        let dir = temp_dir("new");
+        let (mut conn, path) = connect_file(&dir).await;
+        assert_eq!(
+            prepare_database(&mut conn, &path).await.unwrap(),
+            Preparation::Created
+        );
+        assert!(!dir.join("pre-migration").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_newer_database() {
+        let mut conn = SqliteConnection::connect("sqlite::memory:").await.unwrap();
+        conn.create_timings_database().await.unwrap();
+        conn.execute(format!("PRAGMA user_version = {}", SCHEMA_VERSION + 1).as_str())
+            .await
+            .unwrap();
+
+        let error = prepare_database(&mut conn, "sqlite::memory:")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Upgrade timings-app"));
+        assert_eq!(
+            conn.get_schema_version().await.unwrap(),
+            Some(SCHEMA_VERSION + 1),
+            "Database is left untouched"
+        );
+    }
+}
@@ -0,0 +1,20 @@
+use std::collections::HashMap;
+use zbus::Connection;
+use zbus::zvariant::Value;
+
+/// Shows a desktop notification using `org.freedesktop.Notifications`
+pub async fn notify(summary: &str, body: &str) -> Result<(), zbus::Error> {
+    let connection = Connection::session().await?;
+    let actions: Vec<&str> = Vec::new();
+    let hints: HashMap<&str, Value> = HashMap::new();
+    connection
+        .call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "Notify",
+            &("Timings", 0u32, "", summary, body, actions, hints, -1i32),
+        )
+        .await?;
+    Ok(())
+}
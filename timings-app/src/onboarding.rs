@@ -0,0 +1,79 @@
+use crate::config::expand_tilde;
+use crate::migration::Preparation;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// File written next to the config file when the onboarding is dismissed
+const FIRST_RUN_MARKER: &str = "first_run_done";
+
+/// Marker of the dismissed onboarding, in the directory of the config file
+pub fn first_run_marker_path(config_path: &str) -> PathBuf {
+    expand_tilde(config_path)
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join(FIRST_RUN_MARKER)
+}
+
+/// Onboarding is shown when the database was created on this start, unless
+/// it was dismissed before, e.g. with another database
+pub fn needs_onboarding(preparation: &Preparation, marker: &Path) -> bool {
+    *preparation == Preparation::Created && !marker.exists()
+}
+
+/// Writes the marker so the onboarding is not shown again
+pub fn mark_first_run_done(marker: &Path) -> std::io::Result<()> {
+    if let Some(dir) = marker.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(marker, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "timings-onboarding-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn marker_is_next_to_config() {
+        assert_eq!(
+            first_run_marker_path("/home/user/.config/timings/config.toml"),
+            PathBuf::from("/home/user/.config/timings/first_run_done")
+        );
+    }
+
+    #[test]
+    fn shown_only_for_created_database_without_marker() {
+        let dir = temp_dir("detect");
+        let marker = dir.join(FIRST_RUN_MARKER);
+
+        assert!(needs_onboarding(&Preparation::Created, &marker));
+        assert!(!needs_onboarding(&Preparation::UpToDate, &marker));
+        assert!(!needs_onboarding(
+            &Preparation::Migrated {
+                from: 0,
+                to: 1,
+                backup: None
+            },
+            &marker
+        ));
+
+        mark_first_run_done(&marker).unwrap();
+        assert!(marker.exists());
+        assert!(!needs_onboarding(&Preparation::Created, &marker));
+
+        // Dismissing again keeps the marker
+        mark_first_run_done(&marker).unwrap();
+        assert!(marker.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
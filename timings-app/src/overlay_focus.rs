@@ -0,0 +1,171 @@
+/// Text fields of the overlay, in the focus order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverlayField {
+    Client,
+    Project,
+    Summary,
+}
+
+/// Keys of the keyboard-only overlay use, taken before the text fields see
+/// them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayKey {
+    Tab,
+    ShiftTab,
+    Enter,
+    Escape,
+    /// Alt+Left, older weeks
+    AltLeft,
+    /// Alt+Right, newer weeks
+    AltRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayKeyAction {
+    Focus(OverlayField),
+    /// Commits the pending edits, then focuses the field
+    CommitAndFocus(OverlayField),
+    /// Commits the pending edits and gives up the keyboard
+    CommitAndDismiss,
+    /// Gives up the keyboard, the timing continues on keyboard leave
+    Dismiss,
+    /// Shifts the week columns back, negative shifts forward
+    ShiftWeeks(i32),
+}
+
+/// Fields which take input, the client and project are not editable while
+/// another instance writes the desktop names, the summary needs a project
+pub fn editable_fields(names_read_only: bool, has_summary: bool) -> Vec<OverlayField> {
+    let mut fields = Vec::new();
+    if !names_read_only {
+        fields.extend([OverlayField::Client, OverlayField::Project]);
+    }
+    if has_summary {
+        fields.push(OverlayField::Summary);
+    }
+    fields
+}
+
+/// What the key does with the focus in the field, `None` if nothing.
+///
+/// Tab and Shift+Tab cycle the editable fields, Enter commits and advances
+/// to the next field, committing on the last one dismisses the overlay.
+pub fn overlay_key_action(
+    focused: Option<OverlayField>,
+    key: OverlayKey,
+    editable: &[OverlayField],
+) -> Option<OverlayKeyAction> {
+    let position = focused.and_then(|field| editable.iter().position(|f| *f == field));
+    let len = editable.len();
+    match key {
+        OverlayKey::Escape => Some(OverlayKeyAction::Dismiss),
+        OverlayKey::AltLeft => Some(OverlayKeyAction::ShiftWeeks(1)),
+        OverlayKey::AltRight => Some(OverlayKeyAction::ShiftWeeks(-1)),
+        _ if len == 0 => None,
+        OverlayKey::Tab => {
+            let next = position.map_or(0, |p| (p + 1) % len);
+            Some(OverlayKeyAction::Focus(editable[next]))
+        }
+        OverlayKey::ShiftTab => {
+            let previous = position.map_or(len - 1, |p| (p + len - 1) % len);
+            Some(OverlayKeyAction::Focus(editable[previous]))
+        }
+        OverlayKey::Enter => match position {
+            None => Some(OverlayKeyAction::Focus(editable[0])),
+            Some(p) if p + 1 < len => Some(OverlayKeyAction::CommitAndFocus(editable[p + 1])),
+            Some(_) => Some(OverlayKeyAction::CommitAndDismiss),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use OverlayField::*;
+
+    #[test]
+    fn tab_cycles_editable_fields() {
+        let all = editable_fields(false, true);
+        assert_eq!(all, vec![Client, Project, Summary]);
+        let focus = |focused, key| overlay_key_action(focused, key, &all);
+
+        assert_eq!(
+            focus(None, OverlayKey::Tab),
+            Some(OverlayKeyAction::Focus(Client))
+        );
+        assert_eq!(
+            focus(Some(Client), OverlayKey::Tab),
+            Some(OverlayKeyAction::Focus(Project))
+        );
+        assert_eq!(
+            focus(Some(Summary), OverlayKey::Tab),
+            Some(OverlayKeyAction::Focus(Client))
+        );
+        assert_eq!(
+            focus(None, OverlayKey::ShiftTab),
+            Some(OverlayKeyAction::Focus(Summary))
+        );
+        assert_eq!(
+            focus(Some(Client), OverlayKey::ShiftTab),
+            Some(OverlayKeyAction::Focus(Summary))
+        );
+    }
+
+    #[test]
+    fn enter_commits_and_advances() {
+        let all = editable_fields(false, true);
+        let focus = |focused, key| overlay_key_action(focused, key, &all);
+
+        assert_eq!(
+            focus(None, OverlayKey::Enter),
+            Some(OverlayKeyAction::Focus(Client))
+        );
+        assert_eq!(
+            focus(Some(Client), OverlayKey::Enter),
+            Some(OverlayKeyAction::CommitAndFocus(Project))
+        );
+        assert_eq!(
+            focus(Some(Summary), OverlayKey::Enter),
+            Some(OverlayKeyAction::CommitAndDismiss)
+        );
+
+        // Without a summary the project is the last field
+        let names = editable_fields(false, false);
+        assert_eq!(
+            overlay_key_action(Some(Project), OverlayKey::Enter, &names),
+            Some(OverlayKeyAction::CommitAndDismiss)
+        );
+    }
+
+    #[test]
+    fn read_only_names_are_skipped() {
+        let summary_only = editable_fields(true, true);
+        assert_eq!(summary_only, vec![Summary]);
+        assert_eq!(
+            overlay_key_action(Some(Summary), OverlayKey::Tab, &summary_only),
+            Some(OverlayKeyAction::Focus(Summary))
+        );
+        // Focus left on a field which became read-only
+        assert_eq!(
+            overlay_key_action(Some(Client), OverlayKey::Tab, &summary_only),
+            Some(OverlayKeyAction::Focus(Summary))
+        );
+
+        let none = editable_fields(true, false);
+        assert_eq!(overlay_key_action(None, OverlayKey::Tab, &none), None);
+        assert_eq!(overlay_key_action(None, OverlayKey::Enter, &none), None);
+        // Escape and the week keys work without fields
+        assert_eq!(
+            overlay_key_action(None, OverlayKey::Escape, &none),
+            Some(OverlayKeyAction::Dismiss)
+        );
+        assert_eq!(
+            overlay_key_action(None, OverlayKey::AltLeft, &none),
+            Some(OverlayKeyAction::ShiftWeeks(1))
+        );
+        assert_eq!(
+            overlay_key_action(None, OverlayKey::AltRight, &none),
+            Some(OverlayKeyAction::ShiftWeeks(-1))
+        );
+    }
+}
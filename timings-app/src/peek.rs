@@ -0,0 +1,104 @@
+/// Key event of the peek gesture, e.g. from compositor bindings on press and
+/// release of Super
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeekKey {
+    Down,
+    Up,
+}
+
+/// What to do with the overlay after a peek key event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeekAction {
+    Show,
+    Hide,
+    Nothing,
+}
+
+/// Shows the overlay while the peek key is held
+///
+/// Overlay shown otherwise, e.g. from the tray, is not hidden on release.
+/// Key repeats are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Peek {
+    held: bool,
+    shown_by_peek: bool,
+}
+
+impl Peek {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.held
+    }
+
+    pub fn key(&mut self, key: PeekKey, overlay_visible: bool) -> PeekAction {
+        match key {
+            PeekKey::Down if self.held => PeekAction::Nothing,
+            PeekKey::Down => {
+                self.held = true;
+                self.shown_by_peek = !overlay_visible;
+                if self.shown_by_peek {
+                    PeekAction::Show
+                } else {
+                    PeekAction::Nothing
+                }
+            }
+            PeekKey::Up => {
+                let shown_by_peek = self.held && self.shown_by_peek;
+                self.held = false;
+                self.shown_by_peek = false;
+                if shown_by_peek && overlay_visible {
+                    PeekAction::Hide
+                } else {
+                    PeekAction::Nothing
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shown_while_held() {
+        let mut peek = Peek::new();
+        assert_eq!(peek.key(PeekKey::Down, false), PeekAction::Show);
+        assert!(peek.is_held());
+        assert_eq!(peek.key(PeekKey::Up, true), PeekAction::Hide);
+        assert!(!peek.is_held());
+    }
+
+    #[test]
+    fn key_repeat_is_ignored() {
+        let mut peek = Peek::new();
+        assert_eq!(peek.key(PeekKey::Down, false), PeekAction::Show);
+        assert_eq!(peek.key(PeekKey::Down, true), PeekAction::Nothing);
+        assert_eq!(peek.key(PeekKey::Up, true), PeekAction::Hide);
+    }
+
+    #[test]
+    fn already_visible_overlay_stays() {
+        let mut peek = Peek::new();
+        assert_eq!(peek.key(PeekKey::Down, true), PeekAction::Nothing);
+        assert_eq!(peek.key(PeekKey::Up, true), PeekAction::Nothing);
+    }
+
+    #[test]
+    fn release_without_press() {
+        let mut peek = Peek::new();
+        assert_eq!(peek.key(PeekKey::Up, true), PeekAction::Nothing);
+        assert_eq!(peek.key(PeekKey::Up, false), PeekAction::Nothing);
+    }
+
+    #[test]
+    fn overlay_hidden_meanwhile() {
+        let mut peek = Peek::new();
+        assert_eq!(peek.key(PeekKey::Down, false), PeekAction::Show);
+        assert_eq!(peek.key(PeekKey::Up, false), PeekAction::Nothing);
+        assert_eq!(peek.key(PeekKey::Down, false), PeekAction::Show);
+    }
+}
@@ -0,0 +1,103 @@
+use chrono::NaiveTime;
+use serde::Deserialize;
+use serde::Deserializer;
+use std::str::FromStr;
+
+/// Daily time window when quiet mode is on, e.g. "09:00-10:00"
+///
+/// Window may span midnight, e.g. "22:00-06:00".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for QuietWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid quiet window '{}', expected HH:MM-HH:MM", s))?;
+        let parse = |t: &str| {
+            NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .map_err(|e| format!("Invalid time '{}' in quiet window: {}", t, e))
+        };
+        Ok(QuietWindow {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for QuietWindow {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Decides whether overlays and notifications are suppressed
+///
+/// Quiet mode is on when toggled manually, or when the time is within one of
+/// the scheduled windows.
+pub fn is_quiet(manual: bool, schedule: &[QuietWindow], now: NaiveTime) -> bool {
+    manual || schedule.iter().any(|window| window.contains(now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn manual_toggle_without_schedule() {
+        assert!(is_quiet(true, &[], time(12, 0)));
+        assert!(!is_quiet(false, &[], time(12, 0)));
+    }
+
+    #[test]
+    fn scheduled_window() {
+        let schedule = ["09:00-10:00".parse().unwrap()];
+        assert!(!is_quiet(false, &schedule, time(8, 59)));
+        assert!(is_quiet(false, &schedule, time(9, 0)));
+        assert!(is_quiet(false, &schedule, time(9, 59)));
+        assert!(!is_quiet(false, &schedule, time(10, 0)));
+    }
+
+    #[test]
+    fn manual_toggle_outside_scheduled_window() {
+        let schedule = ["09:00-10:00".parse().unwrap()];
+        assert!(is_quiet(true, &schedule, time(15, 0)));
+    }
+
+    #[test]
+    fn scheduled_window_over_midnight() {
+        let schedule = ["22:00-06:00".parse().unwrap()];
+        assert!(is_quiet(false, &schedule, time(23, 0)));
+        assert!(is_quiet(false, &schedule, time(5, 0)));
+        assert!(!is_quiet(false, &schedule, time(12, 0)));
+    }
+
+    #[test]
+    fn invalid_window() {
+        assert!("09:00".parse::<QuietWindow>().is_err());
+        assert!("9am-10am".parse::<QuietWindow>().is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::AppMessage;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use futures::Stream;
+use futures::StreamExt;
+use std::collections::HashMap;
+use timings::split_by_local_days;
+use tokio::sync::mpsc::UnboundedSender;
+use zbus::proxy;
+
+/// D-Bus proxy for `org.freedesktop.ScreenSaver`, implemented by KDE
+#[proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/ScreenSaver"
+)]
+pub trait ScreenSaver {
+    /// Whether the screensaver or lock screen is active
+    fn get_active(&self) -> zbus::Result<bool>;
+
+    #[zbus(signal)]
+    fn active_changed(&self, active: bool) -> zbus::Result<()>;
+}
+
+/// Streams whether the screensaver is active, starting with the current state
+pub async fn screensaver_states() -> Result<impl Stream<Item = bool>, zbus::Error> {
+    let connection = zbus::Connection::session().await?;
+    let proxy = ScreenSaverProxy::new(&connection).await?;
+    let changes = proxy.receive_active_changed().await?;
+    let current = proxy.get_active().await?;
+    Ok(futures::stream::once(async move { current })
+        .chain(changes.filter_map(|change| async move { change.args().ok().map(|a| a.active) })))
+}
+
+/// Sends `AppMessage::ScreenSaverActive` on every screensaver state, until
+/// the stream or the app ends
+pub async fn forward_screensaver_states(
+    states: impl Stream<Item = bool>,
+    sender: UnboundedSender<AppMessage>,
+) {
+    let mut states = std::pin::pin!(states);
+    while let Some(active) = states.next().await {
+        if sender.send(AppMessage::ScreenSaverActive(active)).is_err() {
+            // Main thread has exited
+            break;
+        }
+    }
+}
+
+/// Time the screen was locked or blanked per local day, fed by the
+/// screensaver states
+///
+/// This is separate from the Wayland idle monitor, which stops the timing.
+/// Idle time over midnight is split between the days.
+pub struct ScreenIdle<Tz: TimeZone> {
+    timezone: Tz,
+    idle_since: Option<DateTime<Utc>>,
+    idle: HashMap<NaiveDate, Duration>,
+}
+
+impl<Tz: TimeZone> ScreenIdle<Tz> {
+    pub fn new(timezone: Tz) -> Self {
+        ScreenIdle {
+            timezone,
+            idle_since: None,
+            idle: HashMap::new(),
+        }
+    }
+
+    /// Screensaver became active or inactive at `now`, repeated states are
+    /// ignored
+    pub fn set_active(&mut self, active: bool, now: DateTime<Utc>) {
+        match (active, self.idle_since) {
+            (true, None) => self.idle_since = Some(now),
+            (false, Some(since)) => {
+                self.idle_since = None;
+                for (day, idle) in split_by_local_days(&self.timezone, &since, &now) {
+                    *self.idle.entry(day).or_insert(Duration::zero()) += idle;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Screen idle time of the day, including the ongoing idle until `now`
+    pub fn idle_on(&self, day: NaiveDate, now: DateTime<Utc>) -> Duration {
+        let ongoing = self
+            .idle_since
+            .map(|since| split_by_local_days(&self.timezone, &since, &now))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(idle_day, _)| *idle_day == day)
+            .map(|(_, idle)| idle)
+            .sum::<Duration>();
+        self.idle.get(&day).copied().unwrap_or(Duration::zero()) + ongoing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::FixedOffset;
+
+    #[test]
+    fn accumulates_idle_between_transitions() {
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let at = |d, h, m| Utc.with_ymd_and_hms(2024, 3, d, h, m, 0).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let mut idle = ScreenIdle::new(tz);
+
+        idle.set_active(false, at(4, 8, 0));
+        idle.set_active(true, at(4, 9, 0));
+        // Repeated state does not restart the idle
+        idle.set_active(true, at(4, 9, 10));
+        idle.set_active(false, at(4, 9, 30));
+        idle.set_active(false, at(4, 10, 0));
+        assert_eq!(idle.idle_on(day(4), at(4, 12, 0)), Duration::minutes(30));
+
+        // Ongoing idle counts until now
+        idle.set_active(true, at(4, 12, 0));
+        assert_eq!(idle.idle_on(day(4), at(4, 12, 15)), Duration::minutes(45));
+
+        // Split at the local midnight, 22:00 UTC
+        idle.set_active(false, at(4, 23, 0));
+        assert_eq!(
+            idle.idle_on(day(4), at(5, 8, 0)),
+            Duration::minutes(30) + Duration::hours(10)
+        );
+        assert_eq!(idle.idle_on(day(5), at(5, 8, 0)), Duration::hours(1));
+    }
+
+    #[tokio::test]
+    async fn forwards_states() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        forward_screensaver_states(futures::stream::iter([false, true]), tx).await;
+
+        assert_eq!(rx.recv().await, Some(AppMessage::ScreenSaverActive(false)));
+        assert_eq!(rx.recv().await, Some(AppMessage::ScreenSaverActive(true)));
+        assert_eq!(rx.recv().await, None);
+    }
+}
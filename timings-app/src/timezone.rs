@@ -0,0 +1,69 @@
+use crate::AppMessage;
+use futures::Stream;
+use futures::StreamExt;
+use tokio::sync::mpsc::UnboundedSender;
+use zbus::proxy;
+
+/// D-Bus proxy for `org.freedesktop.timedate1`, the systemd time and date
+/// settings
+#[proxy(
+    interface = "org.freedesktop.timedate1",
+    default_service = "org.freedesktop.timedate1",
+    default_path = "/org/freedesktop/timedate1"
+)]
+pub trait Timedate {
+    /// Timezone property, e.g. "Europe/Helsinki"
+    #[zbus(property)]
+    fn timezone(&self) -> zbus::Result<String>;
+}
+
+/// Streams the system timezone, starting with the current one
+pub async fn timezones() -> Result<impl Stream<Item = String>, zbus::Error> {
+    let connection = zbus::Connection::system().await?;
+    let proxy = TimedateProxy::new(&connection).await?;
+    let current = proxy.timezone().await?;
+    let changes = proxy.receive_timezone_changed().await;
+    Ok(futures::stream::once(async { current })
+        .chain(changes.filter_map(|change| async move { change.get().await.ok() })))
+}
+
+/// Sends `AppMessage::TimezoneChanged` when the timezone differs from the
+/// previous one, until the stream or the app ends.
+///
+/// The first timezone is the current one and is not a change. Cached totals
+/// are bucketed by local date, so they must be recalculated on a change.
+pub async fn forward_timezone_changes(
+    timezones: impl Stream<Item = String>,
+    sender: UnboundedSender<AppMessage>,
+) {
+    let mut timezones = std::pin::pin!(timezones);
+    let mut current: Option<String> = None;
+    while let Some(timezone) = timezones.next().await {
+        let previous = current.replace(timezone.clone());
+        if previous.is_none_or(|previous| previous == timezone) {
+            continue;
+        }
+        log::info!("Timezone changed to {}", timezone);
+        if sender.send(AppMessage::TimezoneChanged).is_err() {
+            // Main thread has exited
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sends_only_changes() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let signals = futures::stream::iter(
+            ["Europe/Helsinki", "Europe/Helsinki", "America/New_York"].map(str::to_string),
+        );
+        forward_timezone_changes(signals, tx).await;
+
+        assert_eq!(rx.recv().await, Some(AppMessage::TimezoneChanged));
+        assert_eq!(rx.recv().await, None);
+    }
+}
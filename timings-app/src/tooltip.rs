@@ -0,0 +1,94 @@
+use chrono::Duration;
+
+/// Tray tooltip text, e.g. "Acme: Website — 2:15"
+///
+/// Today's total is rounded to the nearest `rounding_minutes`, it's left out
+/// if the totals are not available.
+pub fn tooltip_text(
+    client: &str,
+    project: &str,
+    today: Option<Duration>,
+    rounding_minutes: u32,
+) -> String {
+    match today {
+        Some(today) => format!(
+            "{}: {} — {}",
+            client,
+            project,
+            format_h_mm(round_to_minutes(today, rounding_minutes))
+        ),
+        None => format!("{}: {}", client, project),
+    }
+}
+
+/// Tray tooltip line of the time the screen was locked today, e.g. "Screen
+/// locked today 1:05", left out if it was locked less than a minute
+pub fn screen_locked_text(locked: Duration) -> Option<String> {
+    (locked >= Duration::minutes(1)).then(|| format!("Screen locked today {}", format_h_mm(locked)))
+}
+
+/// Tray tooltip line of the time the user was present today, e.g. "Present
+/// today 8:30", left out before the first minute
+pub fn presence_text(presence: Duration) -> Option<String> {
+    (presence >= Duration::minutes(1)).then(|| format!("Present today {}", format_h_mm(presence)))
+}
+
+fn round_to_minutes(duration: Duration, minutes: u32) -> Duration {
+    let step = minutes.max(1) as i64 * 60;
+    let seconds = duration.num_seconds().max(0);
+    Duration::seconds((seconds + step / 2) / step * step)
+}
+
+fn format_h_mm(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tooltip_with_today_total() {
+        let today = Duration::hours(2) + Duration::minutes(14) + Duration::seconds(40);
+        assert_eq!(
+            tooltip_text("Acme", "Website", Some(today), 1),
+            "Acme: Website — 2:15"
+        );
+        assert_eq!(
+            tooltip_text("Acme", "Website", Some(today), 15),
+            "Acme: Website — 2:15"
+        );
+        assert_eq!(
+            tooltip_text("Acme", "Website", Some(Duration::minutes(7)), 15),
+            "Acme: Website — 0:00"
+        );
+        assert_eq!(
+            tooltip_text("Acme", "Website", Some(Duration::minutes(8)), 15),
+            "Acme: Website — 0:15"
+        );
+    }
+
+    #[test]
+    fn tooltip_without_totals() {
+        assert_eq!(tooltip_text("Acme", "Website", None, 1), "Acme: Website");
+    }
+
+    #[test]
+    fn screen_locked_line() {
+        assert_eq!(
+            screen_locked_text(Duration::minutes(65)),
+            Some("Screen locked today 1:05".to_string())
+        );
+        assert_eq!(screen_locked_text(Duration::seconds(59)), None);
+    }
+
+    #[test]
+    fn presence_line() {
+        assert_eq!(
+            presence_text(Duration::hours(8) + Duration::minutes(30)),
+            Some("Present today 8:30".to_string())
+        );
+        assert_eq!(presence_text(Duration::zero()), None);
+    }
+}
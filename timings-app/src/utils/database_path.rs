@@ -0,0 +1,20 @@
+/// True for SQLite in-memory databases and special paths starting with `:`,
+/// which have no file to keep sidecar files or backups next to
+pub fn is_memory_database(database_path: &str) -> bool {
+    database_path.starts_with(":") || database_path == "sqlite::memory:"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_databases() {
+        assert!(is_memory_database("sqlite::memory:"));
+        assert!(is_memory_database(":memory:"));
+        assert!(!is_memory_database("timings.db"));
+        assert!(!is_memory_database(
+            "/home/user/.local/share/timings/timings.db"
+        ));
+    }
+}
@@ -1,5 +1,13 @@
+mod database_path;
+mod reconcile;
 mod run_debounced;
 mod run_sync;
+mod shutdown;
+mod week;
+pub use database_path::*;
+pub use reconcile::*;
 pub use run_debounced::*;
 #[allow(unused_imports)]
 pub use run_sync::*;
+pub use shutdown::*;
+pub use week::*;
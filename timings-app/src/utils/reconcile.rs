@@ -0,0 +1,42 @@
+/// Compares the existing items to the wanted items, returns the items to add
+/// and the items to remove.
+pub fn reconcile<T: PartialEq + Clone>(existing: &[T], wanted: &[T]) -> (Vec<T>, Vec<T>) {
+    let to_add = wanted
+        .iter()
+        .filter(|w| !existing.contains(w))
+        .cloned()
+        .collect();
+    let to_remove = existing
+        .iter()
+        .filter(|e| !wanted.contains(e))
+        .cloned()
+        .collect();
+    (to_add, to_remove)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconcile_outputs_coming_and_going() {
+        // Output 2 unplugged, output 3 plugged in
+        let (to_add, to_remove) = reconcile(&[1, 2], &[1, 3]);
+        assert_eq!(to_add, vec![3]);
+        assert_eq!(to_remove, vec![2]);
+    }
+
+    #[test]
+    fn reconcile_unchanged() {
+        let (to_add, to_remove) = reconcile(&[1, 2], &[2, 1]);
+        assert!(to_add.is_empty());
+        assert!(to_remove.is_empty());
+    }
+
+    #[test]
+    fn reconcile_from_empty() {
+        let (to_add, to_remove) = reconcile(&[], &[Some(1), None]);
+        assert_eq!(to_add, vec![Some(1), None]);
+        assert!(to_remove.is_empty());
+    }
+}
@@ -1,9 +1,18 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::OnceLock;
 use std::time::Duration;
 
-static DEBOUNCERS: OnceLock<Mutex<HashMap<String, tokio::task::JoinHandle<()>>>> = OnceLock::new();
+type Callback = Box<dyn FnOnce() -> tokio::task::JoinHandle<()> + Send>;
+
+/// Scheduled callback, taken either by the timer or by `flush_debounced`
+struct Pending {
+    handle: tokio::task::JoinHandle<()>,
+    callback: Arc<Mutex<Option<Callback>>>,
+}
+
+static DEBOUNCERS: OnceLock<Mutex<HashMap<String, Pending>>> = OnceLock::new();
 
 /// Schedules a callback to run after `delay`. If another call with the same
 /// `id` is made before the delay elapses, the previous scheduled callback is
@@ -18,16 +27,21 @@ where
     let map_mutex = DEBOUNCERS.get_or_init(|| Mutex::new(HashMap::new()));
     let mut map = map_mutex.lock().unwrap();
 
-    if let Some(handle) = map.remove(&id) {
-        handle.abort();
+    if let Some(pending) = map.remove(&id) {
+        pending.handle.abort();
     }
 
+    let callback: Arc<Mutex<Option<Callback>>> = Arc::new(Mutex::new(Some(Box::new(callback))));
+    let callback_ = callback.clone();
     let handle = tokio::spawn(async move {
         tokio::time::sleep(delay).await;
-        callback();
+        let callback = callback_.lock().unwrap().take();
+        if let Some(callback) = callback {
+            callback();
+        }
     });
 
-    map.insert(id, handle);
+    map.insert(id, Pending { handle, callback });
 }
 
 /// Schedules a future to run after `delay`. If another call with the same
@@ -42,6 +56,16 @@ where
     run_debounced_task(id, delay, move || tokio::spawn(fut));
 }
 
+/// Runs the callback scheduled with `id` now instead of after the delay.
+///
+/// Returns the handle of the spawned task, or `None` if nothing was pending.
+pub fn flush_debounced(id: &str) -> Option<tokio::task::JoinHandle<()>> {
+    let pending = DEBOUNCERS.get()?.lock().unwrap().remove(id)?;
+    pending.handle.abort();
+    let callback = pending.callback.lock().unwrap().take()?;
+    Some(callback())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,4 +118,22 @@ mod tests {
         sleep(Duration::from_millis(300)).await;
         assert_eq!(counter.load(Ordering::SeqCst), 1);
     }
+
+    #[tokio::test]
+    async fn flush_debounced_runs_pending_once() {
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let c1 = counter.clone();
+        run_debounced_spawn("debounce-flush", Duration::from_millis(100), async move {
+            c1.fetch_add(1, Ordering::SeqCst);
+        });
+
+        flush_debounced("debounce-flush").unwrap().await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+        // Timer does not run it again, and nothing is left to flush
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+        assert!(flush_debounced("debounce-flush").is_none());
+    }
 }
@@ -0,0 +1,187 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Stop signal handed to spawned tasks and threads
+#[derive(Clone)]
+pub struct StopSignal {
+    flag: Arc<AtomicBool>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl StopSignal {
+    pub fn is_stopped(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    /// Flag for threads which poll it, e.g. the idle monitor
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.flag.clone()
+    }
+
+    /// Resolves when stop is signaled
+    pub async fn stopped(&mut self) {
+        let _ = self.receiver.wait_for(|stopped| *stopped).await;
+    }
+}
+
+/// Thread handle with its result type erased
+trait ThreadHandle: Send {
+    fn is_finished(&self) -> bool;
+    fn join(self: Box<Self>);
+}
+
+impl<T: Send + 'static> ThreadHandle for std::thread::JoinHandle<T> {
+    fn is_finished(&self) -> bool {
+        std::thread::JoinHandle::is_finished(self)
+    }
+
+    fn join(self: Box<Self>) {
+        let _ = std::thread::JoinHandle::join(*self);
+    }
+}
+
+/// Collects spawned tasks and threads, and stops them on exit
+pub struct Shutdown {
+    flag: Arc<AtomicBool>,
+    sender: watch::Sender<bool>,
+    tasks: Vec<(&'static str, tokio::task::JoinHandle<()>)>,
+    threads: Vec<(&'static str, Box<dyn ThreadHandle>)>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            flag: Arc::new(AtomicBool::new(false)),
+            sender,
+            tasks: Vec::new(),
+            threads: Vec::new(),
+        }
+    }
+
+    pub fn signal(&self) -> StopSignal {
+        StopSignal {
+            flag: self.flag.clone(),
+            receiver: self.sender.subscribe(),
+        }
+    }
+
+    pub fn add_task(&mut self, name: &'static str, handle: tokio::task::JoinHandle<()>) {
+        self.tasks.push((name, handle));
+    }
+
+    /// Adds a thread, its result is ignored
+    pub fn add_thread<T: Send + 'static>(
+        &mut self,
+        name: &'static str,
+        handle: std::thread::JoinHandle<T>,
+    ) {
+        self.threads.push((name, Box::new(handle)));
+    }
+
+    /// Signals stop and waits for all tasks and threads to finish.
+    ///
+    /// Tasks still running after the timeout are aborted, threads are left
+    /// detached. Returns the names of those which did not finish in time.
+    pub async fn shutdown(self, timeout: Duration) -> Vec<&'static str> {
+        self.flag.store(true, Ordering::SeqCst);
+        self.sender.send_replace(true);
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut unfinished = Vec::new();
+
+        for (name, mut handle) in self.tasks {
+            if tokio::time::timeout_at(deadline, &mut handle)
+                .await
+                .is_err()
+            {
+                log::warn!("Task '{}' did not stop in time, aborting", name);
+                handle.abort();
+                unfinished.push(name);
+            }
+        }
+
+        for (name, handle) in self.threads {
+            while !handle.is_finished() && tokio::time::Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            if handle.is_finished() {
+                handle.join();
+            } else {
+                log::warn!("Thread '{}' did not stop in time", name);
+                unfinished.push(name);
+            }
+        }
+
+        unfinished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stops_tasks_and_threads() {
+        let mut shutdown = Shutdown::new();
+
+        let mut signal = shutdown.signal();
+        shutdown.add_task(
+            "task",
+            tokio::spawn(async move {
+                signal.stopped().await;
+            }),
+        );
+
+        let signal = shutdown.signal();
+        shutdown.add_thread(
+            "thread",
+            std::thread::spawn(move || {
+                while !signal.is_stopped() {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+            }),
+        );
+
+        let unfinished = shutdown.shutdown(Duration::from_secs(1)).await;
+        assert!(unfinished.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_tasks_and_threads_ignoring_stop() {
+        let mut shutdown = Shutdown::new();
+        shutdown.add_task(
+            "stuck task",
+            tokio::spawn(async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            }),
+        );
+        let release = Arc::new(AtomicBool::new(false));
+        let release_ = release.clone();
+        shutdown.add_thread(
+            "stuck thread",
+            std::thread::spawn(move || {
+                while !release_.load(Ordering::SeqCst) {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+            }),
+        );
+
+        let unfinished = shutdown.shutdown(Duration::from_millis(50)).await;
+        assert_eq!(unfinished, vec!["stuck task", "stuck thread"]);
+        release.store(true, Ordering::SeqCst);
+    }
+
+    #[tokio::test]
+    async fn stopped_resolves_after_shutdown() {
+        let shutdown = Shutdown::new();
+        let mut signal = shutdown.signal();
+        shutdown.shutdown(Duration::from_millis(10)).await;
+        assert!(signal.is_stopped());
+        // Must not hang
+        signal.stopped().await;
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::Datelike;
+use chrono::Duration;
+use chrono::NaiveDate;
+
+/// Monday and Sunday of the week `offset` weeks before the week of `today`
+pub fn week_range(today: NaiveDate, offset: u32) -> (NaiveDate, NaiveDate) {
+    let monday = today
+        - Duration::days(today.weekday().num_days_from_monday() as i64)
+        - Duration::weeks(offset as i64);
+    (monday, monday + Duration::days(6))
+}
+
+/// ISO week number of the day, e.g. "W06"
+pub fn week_label(day: NaiveDate) -> String {
+    format!("W{:02}", day.iso_week().week())
+}
+
+/// Labels of the two week columns of the overlay, the older week first
+///
+/// Without an offset the columns are the last week and this week, shifted
+/// columns are labeled by their week numbers.
+pub fn week_column_labels(today: NaiveDate, offset: u32) -> [String; 2] {
+    if offset == 0 {
+        return ["Last week".to_string(), "This week".to_string()];
+    }
+    [
+        week_label(week_range(today, offset + 1).0),
+        week_label(week_range(today, offset).0),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn week_range_starts_on_monday() {
+        // Wednesday
+        let today = date(2024, 2, 14);
+        assert_eq!(week_range(today, 0), (date(2024, 2, 12), date(2024, 2, 18)));
+        assert_eq!(week_range(today, 2), (date(2024, 1, 29), date(2024, 2, 4)));
+    }
+
+    #[test]
+    fn week_range_on_sunday() {
+        let today = date(2024, 2, 18);
+        assert_eq!(week_range(today, 0), (date(2024, 2, 12), date(2024, 2, 18)));
+    }
+
+    #[test]
+    fn week_range_over_year_boundary() {
+        let today = date(2024, 1, 3);
+        assert_eq!(
+            week_range(today, 1),
+            (date(2023, 12, 25), date(2023, 12, 31))
+        );
+        assert_eq!(week_label(date(2023, 12, 25)), "W52");
+    }
+
+    #[test]
+    fn column_labels() {
+        let today = date(2024, 2, 14);
+        assert_eq!(week_column_labels(today, 0), ["Last week", "This week"]);
+        assert_eq!(week_column_labels(today, 1), ["W05", "W06"]);
+        assert_eq!(week_column_labels(today, 2), ["W04", "W05"]);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::config::WeeklyReportConfig;
+use chrono::Datelike;
+use chrono::Duration;
+use chrono::IsoWeek;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::NaiveTime;
+use chrono::Weekday;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Returns the ISO weeks whose weekly report is due, oldest first.
+///
+/// A report is due when its scheduled moment (`weekday` at `time` of that
+/// week) has passed and the report does not exist yet. The previous week is
+/// included to catch up with a schedule missed while the computer was off.
+pub fn due_weekly_reports(
+    weekday: Weekday,
+    time: NaiveTime,
+    now: NaiveDateTime,
+    report_exists: impl Fn(IsoWeek) -> bool,
+) -> Vec<IsoWeek> {
+    let this_week = now.date().iso_week();
+    let previous_week = (now.date() - Duration::weeks(1)).iso_week();
+
+    [previous_week, this_week]
+        .into_iter()
+        .filter(|week| {
+            scheduled_at(*week, weekday, time)
+                .map(|scheduled| scheduled <= now)
+                .unwrap_or(false)
+        })
+        .filter(|week| !report_exists(*week))
+        .collect()
+}
+
+/// Moment when the report of the given week is scheduled
+fn scheduled_at(week: IsoWeek, weekday: Weekday, time: NaiveTime) -> Option<NaiveDateTime> {
+    NaiveDate::from_isoywd_opt(week.year(), week.week(), weekday).map(|day| day.and_time(time))
+}
+
+/// First and last day (Monday to Sunday) of the ISO week
+pub fn week_range(week: IsoWeek) -> Option<(NaiveDate, NaiveDate)> {
+    let monday = NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Mon)?;
+    Some((monday, monday + Duration::days(6)))
+}
+
+pub fn week_label(week: IsoWeek) -> String {
+    format!("{}-W{:02}", week.year(), week.week())
+}
+
+pub fn report_path(dir: &Path, week: IsoWeek) -> PathBuf {
+    dir.join(format!("timings-{}.md", week_label(week)))
+}
+
+/// Weekly reports that should be written now
+pub fn due_report_paths(
+    config: &WeeklyReportConfig,
+    now: NaiveDateTime,
+) -> Vec<(IsoWeek, PathBuf)> {
+    let dir = config.dir();
+    due_weekly_reports(config.weekday, config.time, now, |week| {
+        report_path(&dir, week).exists()
+    })
+    .into_iter()
+    .map(|week| (week, report_path(&dir, week)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    fn friday_16() -> (Weekday, NaiveTime) {
+        (Weekday::Fri, NaiveTime::from_hms_opt(16, 0, 0).unwrap())
+    }
+
+    fn week(y: i32, w: u32) -> IsoWeek {
+        NaiveDate::from_isoywd_opt(y, w, Weekday::Mon)
+            .unwrap()
+            .iso_week()
+    }
+
+    #[test]
+    fn not_due_before_schedule() {
+        let (weekday, time) = friday_16();
+        // Friday 2024-02-16 15:59, week 7; last week's report exists
+        let due = due_weekly_reports(weekday, time, at(2024, 2, 16, 15, 59), |w| {
+            w == week(2024, 6)
+        });
+        assert!(due.is_empty());
+    }
+
+    #[test]
+    fn due_at_schedule() {
+        let (weekday, time) = friday_16();
+        let due = due_weekly_reports(weekday, time, at(2024, 2, 16, 16, 0), |w| {
+            w == week(2024, 6)
+        });
+        assert_eq!(due, vec![week(2024, 7)]);
+    }
+
+    #[test]
+    fn not_due_when_report_exists() {
+        let (weekday, time) = friday_16();
+        let due = due_weekly_reports(weekday, time, at(2024, 2, 17, 10, 0), |_| true);
+        assert!(due.is_empty());
+    }
+
+    #[test]
+    fn missed_schedule_catches_up_on_next_startup() {
+        let (weekday, time) = friday_16();
+        // Laptop was off on Friday, started on Monday of the next week
+        let due = due_weekly_reports(weekday, time, at(2024, 2, 19, 8, 0), |_| false);
+        assert_eq!(due, vec![week(2024, 7)]);
+    }
+
+    #[test]
+    fn catch_up_and_current_week_both_due() {
+        let (weekday, time) = friday_16();
+        let due = due_weekly_reports(weekday, time, at(2024, 2, 23, 17, 0), |_| false);
+        assert_eq!(due, vec![week(2024, 7), week(2024, 8)]);
+    }
+
+    #[test]
+    fn week_range_and_label() {
+        let w = week(2024, 7);
+        assert_eq!(
+            week_range(w),
+            Some((
+                NaiveDate::from_ymd_opt(2024, 2, 12).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 2, 18).unwrap()
+            ))
+        );
+        assert_eq!(week_label(w), "2024-W07");
+    }
+}
@@ -0,0 +1,64 @@
+use chrono::DateTime;
+use chrono::Utc;
+
+/// Describes the database write state for the overlay debug view
+///
+/// Returns the text, e.g. "written 1m23s ago / 2 pending", and whether the
+/// last write is stale, i.e. older than two write intervals.
+pub fn write_status(
+    last_write: Option<DateTime<Utc>>,
+    pending: usize,
+    now: DateTime<Utc>,
+    interval: std::time::Duration,
+) -> (String, bool) {
+    match last_write {
+        Some(last_write) => {
+            let age = (now - last_write).num_seconds().max(0);
+            let stale = age as u64 > 2 * interval.as_secs();
+            (
+                format!(
+                    "written {}m{:02}s ago / {} pending",
+                    age / 60,
+                    age % 60,
+                    pending
+                ),
+                stale,
+            )
+        }
+        None => (format!("not written yet / {} pending", pending), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const INTERVAL: std::time::Duration = std::time::Duration::from_secs(3 * 60);
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap() + chrono::Duration::seconds(seconds)
+    }
+
+    #[test]
+    fn not_written_yet() {
+        assert_eq!(
+            write_status(None, 1, at(0), INTERVAL),
+            ("not written yet / 1 pending".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn recent_write() {
+        assert_eq!(
+            write_status(Some(at(0)), 2, at(83), INTERVAL),
+            ("written 1m23s ago / 2 pending".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn stale_after_two_intervals() {
+        assert!(!write_status(Some(at(0)), 0, at(6 * 60), INTERVAL).1);
+        assert!(write_status(Some(at(0)), 0, at(6 * 60 + 1), INTERVAL).1);
+    }
+}
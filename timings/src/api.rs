@@ -1,10 +1,17 @@
 use crate::Error;
+use crate::MergeStats;
+use crate::TimingAppUsage;
 use chrono::DateTime;
+use chrono::Duration;
 use chrono::NaiveDate;
+use chrono::NaiveTime;
 use chrono::TimeZone;
 use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow, Serialize, Deserialize)]
 pub struct Timing {
     pub start: DateTime<Utc>,
     pub end: DateTime<Utc>,
@@ -12,12 +19,59 @@ pub struct Timing {
     pub client: String,
 }
 
+/// True if the interval without timings is long enough to be a gap of
+/// `TimingsQueries::find_tracking_gaps`, i.e. longer than `min_gap`
+pub fn is_tracking_gap(start: DateTime<Utc>, end: DateTime<Utc>, min_gap: Duration) -> bool {
+    end > start && end - start > min_gap
+}
+
+/// Timing with its row id, e.g. to edit it with
+/// `TimingsMutations::update_timing_by_id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimingWithId {
+    pub id: i64,
+    pub timing: Timing,
+}
+
+/// Timings of the same project overlapping each other, see
+/// `TimingsQueries::find_overlapping_timings`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimingOverlap {
+    pub first: TimingWithId,
+    /// Timing starting within the first one
+    pub second: TimingWithId,
+}
+
+/// How `TimingsMutations::resolve_overlaps` repairs overlapping timings,
+/// a timing within the other one is deleted by every strategy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapStrategy {
+    /// Ends the first timing where the second one starts
+    Clip,
+    /// Extends the first timing to the end of the second one, and deletes
+    /// the second one moving its application usage to the first
+    Merge,
+    /// Deletes the shorter timing, the second one of equally long timings
+    DeleteShorter,
+}
+
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct GetTimingsFilters {
     pub from: Option<DateTime<Utc>>,
     pub to: Option<DateTime<Utc>>,
     pub client: Option<String>,
     pub project: Option<String>,
+    /// Any of the clients, together with `client` both must match. An empty
+    /// list is like `None` and matches every client.
+    pub clients: Option<Vec<String>>,
+    /// Any of the projects, like `clients`
+    pub projects: Option<Vec<String>>,
+    /// Session tag the timings were recorded with, e.g. the hostname
+    pub source: Option<String>,
+    /// Maximum number of timings, e.g. the size of a page
+    pub limit: Option<u32>,
+    /// Number of the newest timings to skip, e.g. the pages before
+    pub offset: Option<u32>,
 }
 
 pub struct DailyTotalSummary {
@@ -27,6 +81,38 @@ pub struct DailyTotalSummary {
     pub project: String,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeeklyTotalSummary {
+    /// ISO week year and week number, e.g. "2024-W10" or "2025-W01" for
+    /// 2024-12-30
+    pub year_week: String,
+    pub hours: f64,
+    pub client: String,
+    pub project: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthlyTotalSummary {
+    pub year: i32,
+    /// 1 to 12
+    pub month: u32,
+    pub hours: f64,
+    pub client: String,
+    pub project: String,
+}
+
+/// Summary as stored, covering the UTC range of the local day it was written
+/// for
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StoredSummary {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub text: String,
+    pub client: String,
+    pub project: String,
+    pub archived: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct SummaryForDay {
     pub day: NaiveDate,
@@ -45,17 +131,250 @@ pub struct SummaryAndTotalForDay {
     pub hours: f64,
 }
 
+/// Hours and summaries of a project in an ISO week, see
+/// `TimingsQueries::get_weekly_timesheet`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeeklyTimesheetRow {
+    /// ISO week year and week number like in `WeeklyTotalSummary`
+    pub year_week: String,
+    pub client: String,
+    pub project: String,
+    pub hours: f64,
+    /// Distinct summaries of the days in the week, oldest first, separated by
+    /// "; "
+    pub summaries: String,
+}
+
+/// Hours, summary and timings of a project on a day, see
+/// `TimingsQueries::get_full_report`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportRow {
+    pub day: NaiveDate,
+    pub client: String,
+    pub project: String,
+    pub hours: f64,
+    /// Number of timings on the day, a timing over midnight counts on both
+    pub timings: u32,
+    pub summary: String,
+    pub archived: bool,
+    /// Billable flag of the project, see
+    /// `TimingsMutations::set_project_billable`
+    pub billable: bool,
+}
+
+/// Hours of the billable and the non-billable projects, see
+/// `TimingsQueries::get_billable_totals`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BillableTotals {
+    pub billable: f64,
+    pub non_billable: f64,
+}
+
+/// Hours in a foreground application, see `TimingsRecorder::set_active_app`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppUsageForDay {
+    pub day: NaiveDate,
+    pub client: String,
+    pub project: String,
+    pub app: String,
+    pub hours: f64,
+}
+
+/// Day with more hours than is plausible, e.g. from a clock jump, see
+/// `TimingsQueries::get_implausible_days`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImplausibleDay {
+    pub day: NaiveDate,
+    pub hours: f64,
+    /// Timings started on the day, ordered by start
+    pub timings: Vec<Timing>,
+}
+
+/// Timing overlapping a day, see `TimingsQueries::get_timings_for_day`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimingOfDay {
+    /// Whole timing, may start on the previous day or end on the next
+    pub timing: Timing,
+    /// Part of the timing within the day
+    pub duration: Duration,
+}
+
+/// Daily summary moved to the midnights of another timezone, see
+/// `TimingsMutations::shift_day_attribution`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShiftedSummary {
+    pub day: NaiveDate,
+    pub client: String,
+    pub project: String,
+    pub summary: String,
+    pub old_start: DateTime<Utc>,
+    pub new_start: DateTime<Utc>,
+}
+
+/// Kind of a manual timing change, see `TimingAuditEntry`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimingChange {
+    Update,
+    Delete,
+    /// Timing added by hand, e.g. a filled gap
+    Insert,
+}
+
+impl TimingChange {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TimingChange::Update => "update",
+            TimingChange::Delete => "delete",
+            TimingChange::Insert => "insert",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Result<Self, Error> {
+        match value {
+            "update" => Ok(TimingChange::Update),
+            "delete" => Ok(TimingChange::Delete),
+            "insert" => Ok(TimingChange::Insert),
+            _ => Err(Error::InvalidTiming(format!(
+                "Unknown audit change '{}'",
+                value
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for TimingChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Manual change of a timing, written by `TimingsMutations::update_timing`,
+/// `TimingsMutations::delete_timing` and
+/// `TimingsMutations::insert_manual_timing`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimingAuditEntry {
+    pub changed_at: DateTime<Utc>,
+    pub change: TimingChange,
+    /// Timing before the change, `None` if inserted
+    pub old: Option<Timing>,
+    /// Timing after the change, `None` if deleted
+    pub new: Option<Timing>,
+}
+
+/// Problem found by `TimingsQueries::find_database_problems`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DatabaseProblem {
+    /// SQLite integrity check failed, not fixable here
+    Integrity(String),
+    /// Project of a missing client
+    OrphanProject { id: i64, name: String },
+    /// Timing of a missing project
+    OrphanTiming { id: i64 },
+    /// Summary of a missing project
+    OrphanSummary { id: i64 },
+    /// Application usage of a missing timing
+    OrphanAppUsage { id: i64 },
+    /// Timing ending before it starts
+    InvertedTiming {
+        id: i64,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+    /// Timings overlapping each other, only reported as it's unknown which
+    /// one is right, see `TimingsMutations::resolve_overlaps` for the
+    /// timings of a project
+    OverlappingTimings(Timing, Timing),
+}
+
+impl DatabaseProblem {
+    /// Whether `TimingsMutations::fix_database_problem` can fix this safely
+    pub fn is_fixable(&self) -> bool {
+        !matches!(
+            self,
+            DatabaseProblem::Integrity(_) | DatabaseProblem::OverlappingTimings(..)
+        )
+    }
+}
+
+impl std::fmt::Display for DatabaseProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatabaseProblem::Integrity(msg) => write!(f, "Integrity check failed: {}", msg),
+            DatabaseProblem::OrphanProject { id, name } => {
+                write!(f, "Project {} '{}' has no client", id, name)
+            }
+            DatabaseProblem::OrphanTiming { id } => write!(f, "Timing {} has no project", id),
+            DatabaseProblem::OrphanSummary { id } => write!(f, "Summary {} has no project", id),
+            DatabaseProblem::OrphanAppUsage { id } => {
+                write!(f, "Application usage {} has no timing", id)
+            }
+            DatabaseProblem::InvertedTiming { id, start, end } => {
+                write!(
+                    f,
+                    "Timing {} ends at {} before its start {}",
+                    id, end, start
+                )
+            }
+            DatabaseProblem::OverlappingTimings(a, b) => write!(
+                f,
+                "Timings overlap: {}: {} {} - {} and {}: {} {} - {}",
+                a.client, a.project, a.start, a.end, b.client, b.project, b.start, b.end
+            ),
+        }
+    }
+}
+
+/// Schema version of this build, stored in `PRAGMA user_version`
+///
+/// Databases created before schema versioning have version 0.
+pub const SCHEMA_VERSION: i64 = 6;
+
 /// Trait for querying timings database.
 ///
 /// This is implemented for &mut SqliteConnection in
 /// repository/timings_queries.rs
+///
+/// Every query has a deterministic order. Rows of the same day or start are
+/// ordered by client and then project name, so inserting in a different order
+/// gives the same result.
 #[allow(async_fn_in_trait)]
 pub trait TimingsQueries {
+    /// Timings newest first
     async fn get_timings(
         &mut self,
         filters: Option<GetTimingsFilters>,
     ) -> Result<Vec<Timing>, Error>;
 
+    /// Timings with their row ids newest first, the ids stay the same when
+    /// the running timing is extended or the timing is updated
+    async fn get_timings_with_ids(
+        &mut self,
+        filters: Option<GetTimingsFilters>,
+    ) -> Result<Vec<TimingWithId>, Error>;
+
+    /// Number of timings matching the filters, ignoring the limit and the
+    /// offset, e.g. for the number of pages
+    async fn count_timings(&mut self, filters: Option<GetTimingsFilters>) -> Result<u64, Error>;
+
+    /// Schema version of the database, `None` if it has no timings tables yet
+    async fn get_schema_version(&mut self) -> Result<Option<i64>, Error>;
+
+    /// Runs the SQLite integrity check
+    async fn check_timings_database(&mut self) -> Result<(), Error>;
+
+    /// Integrity problems, rows referring to missing rows, inverted and
+    /// overlapping timings
+    async fn find_database_problems(&mut self) -> Result<Vec<DatabaseProblem>, Error>;
+
+    /// Timings of the same project overlapping each other, which inflate the
+    /// totals, ordered by the start of the first timing. A timing
+    /// overlapping many others is in many pairs.
+    async fn find_overlapping_timings(&mut self) -> Result<Vec<TimingOverlap>, Error>;
+
+    /// Hours per day and project in the timezone, newest day first.
+    ///
+    /// Timings over midnight are split between the days they overlap, like
+    /// `DailyTotals::insert_timing` does.
     async fn get_timings_daily_totals(
         &mut self,
         timezone: impl TimeZone,
@@ -65,6 +384,58 @@ pub trait TimingsQueries {
         project: Option<String>,
     ) -> Result<Vec<DailyTotalSummary>, Error>;
 
+    /// Hours per day and project of any of the clients and projects, like
+    /// `get_timings_daily_totals`. The names match exactly, an empty list is
+    /// like `None` and matches every name.
+    async fn get_timings_daily_totals_in(
+        &mut self,
+        timezone: impl TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+        clients: Option<Vec<String>>,
+        projects: Option<Vec<String>>,
+    ) -> Result<Vec<DailyTotalSummary>, Error>;
+
+    /// Hours per week and project in the timezone, newest week first.
+    ///
+    /// ISO weeks start on Monday in the timezone. Timings are split between
+    /// the days like in `get_timings_daily_totals`, so a timing from Sunday to
+    /// Monday counts to both weeks. Weeks cut by the range are returned with
+    /// the hours within the range.
+    async fn get_timings_weekly_totals(
+        &mut self,
+        timezone: impl TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+        client: Option<String>,
+        project: Option<String>,
+    ) -> Result<Vec<WeeklyTotalSummary>, Error>;
+
+    /// Hours per month and project in the timezone, newest month first.
+    ///
+    /// Timings are split between the days like in `get_timings_daily_totals`,
+    /// so a timing over midnight at the end of a month counts to both months.
+    async fn get_timings_monthly_totals(
+        &mut self,
+        timezone: impl TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+        client: Option<String>,
+        project: Option<String>,
+    ) -> Result<Vec<MonthlyTotalSummary>, Error>;
+
+    /// Timings of the project overlapping the day in the timezone, newest
+    /// first, with the part of each within the day like in
+    /// `get_timings_daily_totals`. This is the drill-down of a daily total.
+    async fn get_timings_for_day(
+        &mut self,
+        timezone: impl TimeZone,
+        day: NaiveDate,
+        client: &str,
+        project: &str,
+    ) -> Result<Vec<TimingOfDay>, Error>;
+
+    /// Summaries per day and project in the timezone, newest day first
     async fn get_timings_daily_summaries(
         &mut self,
         timezone: impl TimeZone,
@@ -74,6 +445,166 @@ pub trait TimingsQueries {
         project: Option<String>,
     ) -> Result<Vec<SummaryForDay>, Error>;
 
+    /// Summaries of any of the clients and projects, like
+    /// `get_timings_daily_summaries`. An empty list is like `None`.
+    async fn get_timings_daily_summaries_in(
+        &mut self,
+        timezone: impl TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+        clients: Option<Vec<String>>,
+        projects: Option<Vec<String>>,
+    ) -> Result<Vec<SummaryForDay>, Error>;
+
+    /// Total hours worked per hour of the day (0-23) in the given timezone.
+    ///
+    /// Each timing is apportioned across the hours it spans.
+    async fn get_hourly_distribution(
+        &mut self,
+        timezone: impl TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<[f64; 24], Error>;
+
+    /// Intervals within the work hours of the day with no recorded timing,
+    /// newest first. Only gaps longer than `min_gap` are returned, see
+    /// `is_tracking_gap`.
+    ///
+    /// Helps to reconstruct forgotten time.
+    async fn find_tracking_gaps(
+        &mut self,
+        day: NaiveDate,
+        timezone: impl TimeZone,
+        work_start: NaiveTime,
+        work_end: NaiveTime,
+        min_gap: Duration,
+    ) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>, Error>;
+
+    /// Latest gap of the work hours of the day of `until` which starts
+    /// before `until`, cut at `until`, e.g. the start of the running timing.
+    ///
+    /// Helps to attribute forgotten time to the running project.
+    async fn find_latest_tracking_gap(
+        &mut self,
+        until: DateTime<Utc>,
+        timezone: impl TimeZone,
+        work_start: NaiveTime,
+        work_end: NaiveTime,
+        min_gap: Duration,
+    ) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>, Error> {
+        let day = until.with_timezone(&timezone).date_naive();
+        let gaps = self
+            .find_tracking_gaps(day, timezone, work_start, work_end, min_gap)
+            .await?;
+        Ok(gaps
+            .into_iter()
+            .map(|(start, end)| (start, end.min(until)))
+            .find(|(start, end)| is_tracking_gap(*start, *end, min_gap)))
+    }
+
+    /// Top projects as `(client, project, hours)` of the timings started in
+    /// the range, ordered by descending hours.
+    async fn get_projects_by_total(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        timezone: impl TimeZone,
+        limit: u32,
+    ) -> Result<Vec<(String, String, f64)>, Error>;
+
+    /// Days in the range whose timings sum to more than `max_hours`, with
+    /// the timings started on each of them, oldest first.
+    async fn get_implausible_days(
+        &mut self,
+        timezone: impl TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+        max_hours: f64,
+    ) -> Result<Vec<ImplausibleDay>, Error>;
+
+    /// Timings started in the range which span more than one midnight in the
+    /// timezone, a sign of the clock jumping while tracking, oldest first.
+    async fn get_timings_spanning_midnights(
+        &mut self,
+        timezone: impl TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<Timing>, Error>;
+
+    /// Distinct non-empty summary texts of the project, most recently used
+    /// first, e.g. to suggest past notes.
+    async fn get_recent_distinct_summaries(
+        &mut self,
+        client: &str,
+        project: &str,
+        limit: u32,
+    ) -> Result<Vec<String>, Error>;
+
+    /// Rows of the projects with timings on the days, with the summaries of
+    /// the days, oldest day first like in the reports. Timings over midnight
+    /// are split like in `get_timings_daily_totals`.
+    ///
+    /// This is the source of the exported reports.
+    async fn get_full_report(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        timezone: impl TimeZone,
+    ) -> Result<Vec<ReportRow>, Error>;
+
+    /// Manual timing changes, newest first
+    async fn get_timing_audit(&mut self, limit: u32) -> Result<Vec<TimingAuditEntry>, Error>;
+
+    /// Total time of all timings in the database
+    async fn get_grand_total(&mut self) -> Result<Duration, Error>;
+
+    /// Number of clients and projects, projects of the same name under
+    /// different clients are counted separately
+    async fn get_counts(&mut self) -> Result<(u64, u64), Error>;
+
+    /// Whether the project is billable, projects are billable until set
+    /// otherwise and a project not in the database is billable
+    async fn is_project_billable(&mut self, client: &str, project: &str) -> Result<bool, Error>;
+
+    /// Hours of the billable and the non-billable projects on the days in
+    /// the timezone, timings are clipped to the days like in
+    /// `get_timings_daily_totals`
+    async fn get_billable_totals(
+        &mut self,
+        timezone: impl TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<BillableTotals, Error>;
+
+    /// Distinct sources the timings were recorded with, sorted
+    async fn get_timing_sources(&mut self) -> Result<Vec<String>, Error>;
+
+    /// Sources of the timings matching the filters by the timing row id,
+    /// timings without a source are left out. The limit and the offset are
+    /// ignored.
+    async fn get_timing_sources_by_id(
+        &mut self,
+        filters: Option<GetTimingsFilters>,
+    ) -> Result<HashMap<i64, String>, Error>;
+
+    /// All summaries as stored, oldest first
+    async fn get_stored_summaries(&mut self) -> Result<Vec<StoredSummary>, Error>;
+
+    /// Application usage of the timings matching the filters, oldest timing
+    /// first, e.g. for exporting. The limit and the offset are ignored.
+    async fn get_timing_app_usage(
+        &mut self,
+        filters: Option<GetTimingsFilters>,
+    ) -> Result<Vec<TimingAppUsage>, Error>;
+
+    /// Hours per foreground application of the timings started on the day,
+    /// ordered by client, project and most used application.
+    async fn get_app_usage_for_day(
+        &mut self,
+        timezone: impl TimeZone,
+        day: NaiveDate,
+    ) -> Result<Vec<AppUsageForDay>, Error>;
+
     async fn get_timings_daily_totals_and_summaries(
         &mut self,
         timezone: impl TimeZone,
@@ -116,6 +647,56 @@ pub trait TimingsQueries {
 
         Ok(result)
     }
+
+    /// Hours and summaries per week and project in the timezone for a
+    /// timesheet, newest week first like `get_timings_weekly_totals`.
+    ///
+    /// Summaries of a project without hours in the week are left out.
+    async fn get_weekly_timesheet(
+        &mut self,
+        timezone: impl TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<WeeklyTimesheetRow>, Error> {
+        let totals = self
+            .get_timings_weekly_totals(timezone.clone(), from, to, None, None)
+            .await?;
+
+        let mut summaries_map: HashMap<(String, String, String), Vec<String>> = HashMap::new();
+        let summaries = self
+            .get_timings_daily_summaries(timezone, from, to, None, None)
+            .await?;
+        for s in summaries.into_iter().rev() {
+            let week = s.day.format("%G-W%V").to_string();
+            let texts = summaries_map
+                .entry((week, s.client, s.project))
+                .or_default();
+            if !s.summary.trim().is_empty() && !texts.contains(&s.summary) {
+                texts.push(s.summary);
+            }
+        }
+
+        Ok(totals
+            .into_iter()
+            .map(|total| {
+                let summaries = summaries_map
+                    .remove(&(
+                        total.year_week.clone(),
+                        total.client.clone(),
+                        total.project.clone(),
+                    ))
+                    .unwrap_or_default()
+                    .join("; ");
+                WeeklyTimesheetRow {
+                    year_week: total.year_week,
+                    client: total.client,
+                    project: total.project,
+                    hours: total.hours,
+                    summaries,
+                }
+            })
+            .collect())
+    }
 }
 
 /// Trait for mutating timings database.
@@ -124,18 +705,244 @@ pub trait TimingsQueries {
 /// repository/timings_mutations.rs
 #[allow(async_fn_in_trait)]
 pub trait TimingsMutations {
+    /// Creates the database, or migrates it to `SCHEMA_VERSION`
     async fn create_timings_database(&mut self) -> Result<(), Error>;
 
+    /// Applies the pending migrations, each in its own transaction.
+    ///
+    /// Fails with `Error::UnsupportedSchema` if the database is newer than
+    /// `SCHEMA_VERSION`.
+    async fn migrate_timings_database(&mut self) -> Result<(), Error>;
+
+    /// Writes a consistent copy of the database to a file with the SQLite
+    /// online backup API, an existing file is overwritten
+    async fn backup_timings_database(&mut self, path: &std::path::Path) -> Result<(), Error>;
+
+    /// Merges the timings and summaries of another database, given as a
+    /// path or an sqlite URL, in one transaction. Clients and projects are
+    /// matched by name, rows already here as is are skipped, see
+    /// `import_json`. The application usage of the merged timings is copied.
+    ///
+    /// The other database is only read. It must be of `SCHEMA_VERSION`, e.g.
+    /// opened once by this version, otherwise fails with
+    /// `Error::UnsupportedSchema`.
+    async fn merge_database(&mut self, other: &str) -> Result<MergeStats, Error>;
+
     async fn insert_timings(
         &mut self,
         timings: impl IntoIterator<Item = &Timing>,
     ) -> Result<(), Error>;
 
+    /// Inserts the timings tagged with the source, e.g. the hostname or the
+    /// session, so the timings of a device can be filtered.
+    ///
+    /// Updating an existing timing without a source keeps its source.
+    async fn insert_timings_with_source(
+        &mut self,
+        timings: impl IntoIterator<Item = &Timing>,
+        source: Option<&str>,
+    ) -> Result<(), Error>;
+
     async fn insert_timings_daily_summaries(
         &mut self,
         timezone: impl TimeZone,
         summaries: impl IntoIterator<Item = &SummaryForDay>,
     ) -> Result<(), Error>;
+
+    /// Inserts the daily summaries and the timings tagged with the source in
+    /// one transaction, neither is written if either fails
+    async fn insert_timings_and_summaries(
+        &mut self,
+        timings: impl IntoIterator<Item = &Timing>,
+        source: Option<&str>,
+        timezone: impl TimeZone,
+        summaries: impl IntoIterator<Item = &SummaryForDay>,
+    ) -> Result<(), Error>;
+
+    /// Inserts summaries as stored, e.g. from `get_stored_summaries` of
+    /// another database. Existing summaries of the same range are updated,
+    /// summaries with an empty text are deleted like in
+    /// `insert_timings_daily_summaries`.
+    async fn insert_stored_summaries(
+        &mut self,
+        summaries: impl IntoIterator<Item = &StoredSummary>,
+    ) -> Result<(), Error>;
+
+    /// Adds the seconds to the application usage of the timings.
+    ///
+    /// Usage of timings which are not in the database is ignored.
+    async fn insert_timing_app_usage(
+        &mut self,
+        usage: impl IntoIterator<Item = &TimingAppUsage>,
+    ) -> Result<(), Error>;
+
+    /// Sets the seconds of the application usage of the timings, replacing
+    /// the seconds of the same application, e.g. when importing a timing
+    /// again.
+    ///
+    /// Usage of timings which are not in the database is ignored.
+    async fn replace_timing_app_usage(
+        &mut self,
+        usage: impl IntoIterator<Item = &TimingAppUsage>,
+    ) -> Result<(), Error>;
+
+    /// Fixes the problem by deleting the broken row, returns false if the
+    /// problem is not fixable, see `DatabaseProblem::is_fixable`
+    async fn fix_database_problem(&mut self, problem: &DatabaseProblem) -> Result<bool, Error>;
+
+    /// Marks the project billable or not, the project is created if it has
+    /// no timings yet, e.g. the running timing is not written
+    async fn set_project_billable(
+        &mut self,
+        client: &str,
+        project: &str,
+        billable: bool,
+    ) -> Result<(), Error>;
+
+    /// Repairs the timings of `find_overlapping_timings` with the strategy in
+    /// one transaction, returns the number of repaired overlaps. The changes
+    /// are recorded in the audit log.
+    async fn resolve_overlaps(
+        &mut self,
+        strategy: OverlapStrategy,
+        now: DateTime<Utc>,
+    ) -> Result<u64, Error>;
+
+    /// Merges consecutive timings of a project and source, separated by at
+    /// most `max_gap`, into the first one in one transaction, e.g. the
+    /// fragments left by restarts and keep-alive writes. Returns the number
+    /// of removed timings.
+    ///
+    /// The recorded time is kept exactly: the merged timing lasts the sum of
+    /// the timings, so it ends earlier than the last one by the gaps. Only
+    /// timings matching the filters like in `delete_timings` are merged, a
+    /// timing of another project in between keeps them apart and overlapping
+    /// timings are left to `resolve_overlaps`. Timings are merged only within
+    /// a day in the timezone, so that no time moves to another day.
+    /// Application usage is moved to the merged timing, the merges are not
+    /// recorded in the audit log.
+    ///
+    /// As the merged timing ends earlier, `find_tracking_gaps` reports the
+    /// time after it as a gap although it was recorded, so compact only the
+    /// days whose gaps are no longer looked at.
+    ///
+    /// Fails with `Error::InvalidTiming` if the filters set a limit or an
+    /// offset, a page of timings would split the runs.
+    async fn compact_timings(
+        &mut self,
+        timezone: impl TimeZone,
+        max_gap: Duration,
+        filters: GetTimingsFilters,
+    ) -> Result<u64, Error>;
+
+    /// Replaces the timing identified by the client, project and start of
+    /// `old`, and records the change in the audit log in the same
+    /// transaction.
+    ///
+    /// Fails with `Error::TimingConflict` if another timing of the new
+    /// project starts at the new start.
+    async fn update_timing(
+        &mut self,
+        old: &Timing,
+        new: &Timing,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error>;
+
+    /// Replaces the timing of the row id, see `get_timings_with_ids`, and
+    /// records the change in the audit log like `update_timing`.
+    ///
+    /// Fails with `Error::TimingConflict` if another timing of the new
+    /// project starts at the new start.
+    async fn update_timing_by_id(
+        &mut self,
+        id: i64,
+        new: &Timing,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error>;
+
+    /// Inserts a timing added by hand, e.g. to fill a gap, tagged with the
+    /// source like the recorded ones, and records it in the audit log in the
+    /// same transaction. A timing of the project with the same start is
+    /// updated, and recorded as `TimingChange::Update`.
+    async fn insert_manual_timing(
+        &mut self,
+        timing: &Timing,
+        source: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error>;
+
+    /// Deletes the timing identified by its client, project and start, and
+    /// records the change in the audit log in the same transaction.
+    async fn delete_timing(&mut self, timing: &Timing, now: DateTime<Utc>) -> Result<(), Error>;
+
+    /// Deletes the timing of the project starting at `start`, returns false
+    /// if there is no such timing. The deletion is recorded in the audit log
+    /// like in `delete_timing`, the client and project are kept.
+    async fn delete_timing_at(
+        &mut self,
+        client: &str,
+        project: &str,
+        start: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<bool, Error>;
+
+    /// Deletes the timings matching the filters like in `get_timings`, with
+    /// the limit and the offset the same page of timings, returns the number
+    /// of deleted timings. Each deletion is recorded in the audit log, all in
+    /// one transaction.
+    ///
+    /// With `remove_unused` the projects and clients left without timings
+    /// are deleted too, except projects which still have summaries.
+    async fn delete_timings(
+        &mut self,
+        filters: GetTimingsFilters,
+        remove_unused: bool,
+        now: DateTime<Utc>,
+    ) -> Result<u64, Error>;
+
+    /// Moves the daily summaries of the days from the midnights of `old_tz`
+    /// to the midnights of `new_tz`, e.g. after moving to another timezone.
+    ///
+    /// Timings are stored as UTC instants and are not changed, daily totals
+    /// and summaries are then attributed to days by the timezone given to the
+    /// queries.
+    ///
+    /// A summary colliding with one already at the new midnights is joined to
+    /// it. Returns the affected summaries, with `dry_run` nothing is changed.
+    async fn shift_day_attribution(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        old_tz: impl TimeZone,
+        new_tz: impl TimeZone,
+        dry_run: bool,
+    ) -> Result<Vec<ShiftedSummary>, Error>;
+
+    /// Renames clients and projects in one transaction, e.g. to clean up
+    /// imported data.
+    ///
+    /// Renaming to an existing name merges into it, timings starting at the
+    /// same time are combined and summaries of the same day are joined.
+    /// Project renames are applied after the client renames, so they refer
+    /// to the renamed clients. Names which are not in the database are
+    /// ignored, and clients left without projects are deleted.
+    ///
+    /// Renaming to a name which is renamed as well, e.g. `A` to `B` and `B`
+    /// to `C`, is rejected with `Error::InvalidRename` as the result would
+    /// depend on the order of the map.
+    async fn apply_rename_map(
+        &mut self,
+        client_map: HashMap<String, String>,
+        project_map: HashMap<(String, String), (String, String)>,
+    ) -> Result<(), Error>;
+
+    /// Renames the client, merging it into an existing client of the new
+    /// name like `apply_rename_map`
+    async fn rename_client(&mut self, old: &str, new: &str) -> Result<(), Error>;
+
+    /// Renames the project of the client, merging it into an existing
+    /// project of the new name like `apply_rename_map`
+    async fn rename_project(&mut self, client: &str, old: &str, new: &str) -> Result<(), Error>;
 }
 
 /// Trait for inserting mockdata into timings database.
@@ -155,9 +962,19 @@ pub trait TimingsRecording {
     /// Starts a new timing for the given client and project at the given time.
     fn start_timing(&mut self, client: String, project: String, now: DateTime<Utc>) -> bool;
 
-    /// Stops the current timing at the given time.
+    /// Stops the current timing at the given time, a paused timing is not
+    /// resumed anymore.
     fn stop_timing(&mut self, now: DateTime<Utc>) -> ();
 
+    /// Stops the current timing at the given time, and keeps its client and
+    /// project for `resume_timing`, e.g. when the user idles.
+    fn pause_timing(&mut self, now: DateTime<Utc>) -> ();
+
+    /// Starts a new timing with the client and project of the paused timing,
+    /// returns false if nothing is paused. Starting or stopping a timing
+    /// forgets the paused one.
+    fn resume_timing(&mut self, now: DateTime<Utc>) -> bool;
+
     /// Keeps the current timing alive by updating its end time to now.
     ///
     /// Must be called at least once a minute, if there is gap lasting longer
@@ -0,0 +1,122 @@
+use crate::CurrentTiming;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+use std::collections::HashMap;
+
+/// Time spent in a foreground application during a timing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimingAppUsage {
+    pub client: String,
+    pub project: String,
+    /// Start of the timing
+    pub start: DateTime<Utc>,
+    pub app: String,
+    pub seconds: i64,
+}
+
+/// Aggregates the foreground application time of the running timing
+///
+/// Fed by `TimingsRecorder` on timing changes and by the app on application
+/// activations. Nothing is counted until an application is activated, so it
+/// is inert unless per-application tracking is enabled.
+#[derive(Debug, Default)]
+pub struct AppUsageAggregator {
+    timing: Option<CurrentTiming>,
+    app: Option<String>,
+    since: Option<DateTime<Utc>>,
+    usage: HashMap<(CurrentTiming, String), Duration>,
+}
+
+impl AppUsageAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Active application changed at `now`, empty name if no window is active
+    pub fn app_activated(&mut self, app: &str, now: DateTime<Utc>) {
+        self.count_until(now);
+        self.app = (!app.is_empty()).then(|| app.to_string());
+        self.since = Some(now);
+    }
+
+    /// Running timing changed at `now`, `None` if the timing stopped
+    pub fn timing_changed(&mut self, timing: Option<&CurrentTiming>, now: DateTime<Utc>) {
+        self.timing_split(now, timing, now);
+    }
+
+    /// Running timing ended at `end` and continues as `timing` from `now`,
+    /// the time in between is not counted, e.g. after a missed keep-alive
+    pub fn timing_split(
+        &mut self,
+        end: DateTime<Utc>,
+        timing: Option<&CurrentTiming>,
+        now: DateTime<Utc>,
+    ) {
+        self.count_until(end);
+        self.timing = timing.cloned();
+        self.since = Some(now);
+    }
+
+    /// Takes the whole seconds counted up to `now` for writing.
+    ///
+    /// Usage of the running timing is kept back if `include_running` is false,
+    /// e.g. when the running timing is too short to be written yet.
+    pub fn take_usage(&mut self, now: DateTime<Utc>, include_running: bool) -> Vec<TimingAppUsage> {
+        self.count_until(now);
+        self.since = Some(now);
+
+        let mut taken = Vec::new();
+        for ((timing, app), duration) in std::mem::take(&mut self.usage) {
+            let running = self.timing.as_ref() == Some(&timing);
+            if running && !include_running {
+                self.usage.insert((timing, app), duration);
+                continue;
+            }
+            let seconds = duration.num_seconds();
+            if running {
+                // Fractions are carried over to the next write
+                let remainder = duration - Duration::seconds(seconds);
+                self.usage.insert((timing.clone(), app.clone()), remainder);
+            }
+            if seconds > 0 {
+                taken.push(TimingAppUsage {
+                    client: timing.client,
+                    project: timing.project,
+                    start: timing.start,
+                    app,
+                    seconds,
+                });
+            }
+        }
+        taken.sort_by(|a, b| (a.start, &a.app).cmp(&(b.start, &b.app)));
+        taken
+    }
+
+    /// Puts back usage taken with `take_usage` that could not be written, so
+    /// the next write includes it
+    pub fn restore_usage(&mut self, usage: Vec<TimingAppUsage>) {
+        for usage in usage {
+            let timing = CurrentTiming {
+                start: usage.start,
+                project: usage.project,
+                client: usage.client,
+            };
+            *self
+                .usage
+                .entry((timing, usage.app))
+                .or_insert_with(Duration::zero) += Duration::seconds(usage.seconds);
+        }
+    }
+
+    fn count_until(&mut self, until: DateTime<Utc>) {
+        if let (Some(timing), Some(app), Some(since)) = (&self.timing, &self.app, self.since)
+            && until > since
+        {
+            *self
+                .usage
+                .entry((timing.clone(), app.clone()))
+                .or_insert_with(Duration::zero) += until - since;
+        }
+    }
+}
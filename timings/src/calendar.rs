@@ -0,0 +1,190 @@
+use crate::Error;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+use chrono::Utc;
+
+/// Event of an iCalendar file, e.g. a meeting
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    pub summary: String,
+    pub start: DateTime<Utc>,
+    /// Exclusive end
+    pub end: DateTime<Utc>,
+}
+
+/// Events of an iCalendar (.ics) file
+///
+/// Only single events are supported, recurrence rules are ignored. Times
+/// with a `TZID` and floating times are read in the timezone given to
+/// `parse_ics`, UTC times ending with `Z` as is.
+#[derive(Debug, Clone, Default)]
+pub struct Calendar {
+    pub events: Vec<CalendarEvent>,
+}
+
+impl Calendar {
+    pub fn parse_ics(text: &str, timezone: impl TimeZone) -> Result<Calendar, Error> {
+        let mut events = Vec::new();
+        let mut event: Option<Vec<(String, String, String)>> = None;
+        for line in unfold_lines(text) {
+            let Some((name_and_params, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (name, params) = name_and_params
+                .split_once(';')
+                .unwrap_or((name_and_params, ""));
+            let name = name.to_ascii_uppercase();
+            match (name.as_str(), value, event.as_mut()) {
+                ("BEGIN", "VEVENT", _) => event = Some(Vec::new()),
+                ("END", "VEVENT", Some(properties)) => {
+                    events.push(parse_event(properties, &timezone)?);
+                    event = None;
+                }
+                (_, _, Some(properties)) => {
+                    properties.push((name, params.to_string(), value.to_string()))
+                }
+                _ => {}
+            }
+        }
+        events.sort_by(|a, b| a.start.cmp(&b.start).then(a.end.cmp(&b.end)));
+        Ok(Calendar { events })
+    }
+
+    /// Event going on at `now`, the latest started one if events overlap
+    pub fn current_event(&self, now: DateTime<Utc>) -> Option<&CalendarEvent> {
+        self.events
+            .iter()
+            .rev()
+            .find(|event| event.start <= now && now < event.end)
+    }
+}
+
+/// Lines of the file, with the continuation lines starting with a space or a
+/// tab joined to the previous line
+fn unfold_lines(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn parse_event(
+    properties: &[(String, String, String)],
+    timezone: &impl TimeZone,
+) -> Result<CalendarEvent, Error> {
+    let property = |name: &str| {
+        properties
+            .iter()
+            .find(|(n, _, _)| n == name)
+            .map(|(_, params, value)| (params.as_str(), value.as_str()))
+    };
+    let summary = property("SUMMARY")
+        .map(|(_, value)| unescape_text(value))
+        .unwrap_or_default();
+    let (start_params, start_value) = property("DTSTART")
+        .ok_or_else(|| Error::CalendarError(format!("Event '{}' has no DTSTART", summary)))?;
+    let (start, all_day) = parse_date_time(start_params, start_value, timezone)?;
+    let end = match (property("DTEND"), property("DURATION")) {
+        (Some((params, value)), _) => parse_date_time(params, value, timezone)?.0,
+        (None, Some((_, value))) => start + parse_duration(value)?,
+        // Without an end a date lasts the day, a time is an instant
+        (None, None) if all_day => start + Duration::days(1),
+        (None, None) => start,
+    };
+    Ok(CalendarEvent {
+        summary,
+        start,
+        end,
+    })
+}
+
+/// Date time of a `DTSTART` or `DTEND`, and whether it's a date
+fn parse_date_time(
+    params: &str,
+    value: &str,
+    timezone: &impl TimeZone,
+) -> Result<(DateTime<Utc>, bool), Error> {
+    let invalid = || Error::CalendarError(format!("Invalid date time '{}'", value));
+    let is_date = params
+        .split(';')
+        .any(|param| param.eq_ignore_ascii_case("VALUE=DATE"))
+        || value.len() == 8;
+    if is_date {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+        return Ok((local_to_utc(timezone, date.and_time(NaiveTime::MIN))?, true));
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+        return Ok((time.and_utc(), false));
+    }
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+    Ok((local_to_utc(timezone, time)?, false))
+}
+
+fn local_to_utc(timezone: &impl TimeZone, time: NaiveDateTime) -> Result<DateTime<Utc>, Error> {
+    timezone
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| Error::CalendarError(format!("Time {} does not exist", time)))
+}
+
+/// Duration like `PT1H30M` or `P1D`, weeks and days are 7 and 1 days
+fn parse_duration(value: &str) -> Result<Duration, Error> {
+    let invalid = || Error::CalendarError(format!("Invalid duration '{}'", value));
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let value = value.strip_prefix('P').ok_or_else(invalid)?;
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        if c == 'T' {
+            continue;
+        }
+        let n = number.parse::<i64>().map_err(|_| invalid())?;
+        number.clear();
+        duration += match c {
+            'W' => Duration::weeks(n),
+            'D' => Duration::days(n),
+            'H' => Duration::hours(n),
+            'M' => Duration::minutes(n),
+            'S' => Duration::seconds(n),
+            _ => return Err(invalid()),
+        };
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(if negative { -duration } else { duration })
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => text.push('\n'),
+            Some(escaped) => text.push(escaped),
+            None => {}
+        }
+    }
+    text
+}
@@ -1,9 +1,24 @@
+use crate::Timing;
 use std::fmt;
 
 #[derive(Debug)]
 pub enum Error {
     ChronoError(String),
     SqlxError(sqlx::Error),
+    InvalidTiming(String),
+    InvalidState(String),
+    UnsupportedSchema(i64),
+    IntegrityError(String),
+    CalendarError(String),
+    /// Timing of the project starting at the same time already exists
+    TimingConflict(Timing),
+    IoError(std::io::Error),
+    InvalidImport(String),
+    /// Exported document is newer than `PORTABLE_VERSION`
+    UnsupportedExportVersion(u32),
+    JsonError(serde_json::Error),
+    /// Rename map renames a name to another name which is renamed as well
+    InvalidRename(String),
 }
 
 impl fmt::Display for Error {
@@ -11,6 +26,31 @@ impl fmt::Display for Error {
         match self {
             Error::ChronoError(msg) => write!(f, "Chrono error: {}", msg),
             Error::SqlxError(err) => write!(f, "SQLx error: {}", err),
+            Error::InvalidTiming(msg) => write!(f, "Invalid timing: {}", msg),
+            Error::InvalidState(msg) => write!(f, "Invalid recorder state: {}", msg),
+            Error::UnsupportedSchema(version) => write!(
+                f,
+                "Database schema version {} is not the supported version {}",
+                version,
+                crate::SCHEMA_VERSION
+            ),
+            Error::IntegrityError(msg) => write!(f, "Database integrity check failed: {}", msg),
+            Error::CalendarError(msg) => write!(f, "Invalid calendar: {}", msg),
+            Error::TimingConflict(timing) => write!(
+                f,
+                "Timing of '{}: {}' starting at {:?} already exists",
+                timing.client, timing.project, timing.start
+            ),
+            Error::IoError(err) => write!(f, "I/O error: {}", err),
+            Error::InvalidImport(msg) => write!(f, "Invalid import: {}", msg),
+            Error::UnsupportedExportVersion(version) => write!(
+                f,
+                "Exported document version {} is newer than supported version {}",
+                version,
+                crate::PORTABLE_VERSION
+            ),
+            Error::JsonError(err) => write!(f, "JSON error: {}", err),
+            Error::InvalidRename(msg) => write!(f, "Invalid rename: {}", msg),
         }
     }
 }
@@ -22,3 +62,15 @@ impl From<sqlx::Error> for Error {
         Error::SqlxError(err)
     }
 }
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::IoError(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::JsonError(err)
+    }
+}
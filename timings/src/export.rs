@@ -0,0 +1,124 @@
+//! CSV export
+//!
+//! Machine readable, so dates are ISO and hours use dot decimals regardless
+//! of the locale. Records end with CRLF as in RFC 4180.
+
+use crate::Error;
+use crate::GetTimingsFilters;
+use crate::SummaryAndTotalForDay;
+use crate::TimingsQueries;
+use chrono::NaiveDate;
+use chrono::SecondsFormat;
+use chrono::TimeZone;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Writes the filtered timings as CSV, oldest first. The summary of the day
+/// is on the first timing of the project starting on that day.
+///
+/// The header is `start,end,client,project,hours,summary`, the times are
+/// ISO-8601 in the timezone and hours have two decimals.
+pub async fn export_timings_csv<Tz: TimeZone, W: Write>(
+    conn: &mut impl TimingsQueries,
+    timezone: Tz,
+    filters: Option<GetTimingsFilters>,
+    mut writer: W,
+) -> Result<(), Error>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let mut timings = conn.get_timings(filters).await?;
+    timings.sort_by_key(|timing| timing.start);
+
+    let day_of = |timing: &crate::Timing| timing.start.with_timezone(&timezone).date_naive();
+    let mut summaries: HashMap<_, _> = match (timings.first(), timings.last()) {
+        (Some(first), Some(last)) => conn
+            .get_timings_daily_summaries(timezone.clone(), day_of(first), day_of(last), None, None)
+            .await?
+            .into_iter()
+            .map(|s| ((s.day, s.client, s.project), s.summary))
+            .collect(),
+        _ => HashMap::new(),
+    };
+
+    write!(writer, "start,end,client,project,hours,summary\r\n")?;
+    for timing in &timings {
+        let summary = summaries
+            .remove(&(
+                day_of(timing),
+                timing.client.clone(),
+                timing.project.clone(),
+            ))
+            .unwrap_or_default();
+        write!(
+            writer,
+            "{},{},{},{},{:.2},{}\r\n",
+            timing
+                .start
+                .with_timezone(&timezone)
+                .to_rfc3339_opts(SecondsFormat::Secs, false),
+            timing
+                .end
+                .with_timezone(&timezone)
+                .to_rfc3339_opts(SecondsFormat::Secs, false),
+            escape_csv_field(&timing.client),
+            escape_csv_field(&timing.project),
+            (timing.end - timing.start).num_seconds() as f64 / 3600.0,
+            escape_csv_field(&summary)
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the daily totals and summaries of the date range as CSV, oldest day
+/// first.
+///
+/// The header is `date,client,project,hours,summary`, hours have two
+/// decimals.
+pub async fn export_daily_totals_csv<W: Write>(
+    conn: &mut impl TimingsQueries,
+    timezone: impl TimeZone,
+    from: NaiveDate,
+    to: NaiveDate,
+    writer: W,
+) -> Result<(), Error> {
+    let rows = conn
+        .get_timings_daily_totals_and_summaries(timezone, from, to, None, None)
+        .await?;
+    write_daily_totals_csv(&rows, writer)
+}
+
+/// Writes the rows as CSV, see `export_daily_totals_csv`
+pub fn write_daily_totals_csv<W: Write>(
+    rows: &[SummaryAndTotalForDay],
+    mut writer: W,
+) -> Result<(), Error> {
+    let mut rows: Vec<_> = rows.iter().collect();
+    rows.sort_by_key(|row| row.day);
+
+    write!(writer, "date,client,project,hours,summary\r\n")?;
+    for row in rows {
+        write!(
+            writer,
+            "{},{},{},{:.2},{}\r\n",
+            row.day.format("%Y-%m-%d"),
+            escape_csv_field(&row.client),
+            escape_csv_field(&row.project),
+            row.hours,
+            escape_csv_field(&row.summary)
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Quotes the field if it contains a comma, a quote or a line break, quotes
+/// are doubled (RFC 4180)
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,94 @@
+//! Locale aware formatting of hours, dates and week labels
+//!
+//! Used for everything shown to humans: reports, overlay and tray. Machine
+//! readable exports such as CSV always use dot decimals and ISO dates
+//! regardless of the locale, so that they can be imported elsewhere.
+
+use chrono::Datelike;
+use chrono::Duration;
+use chrono::IsoWeek;
+use chrono::NaiveDate;
+use chrono::Weekday;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::de::Error as _;
+use std::fmt::Write;
+
+/// Formatting locale, set explicitly in the configuration
+///
+/// ```toml
+/// [locale]
+/// decimal_separator = ","
+/// date_format = "%d.%m.%Y"
+/// week_format = "Week %V/%G"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Locale {
+    pub decimal_separator: char,
+    /// chrono format string for dates
+    #[serde(deserialize_with = "deserialize_date_format")]
+    pub date_format: String,
+    /// chrono format string applied to the Monday of the week, e.g. `%G-W%V`
+    #[serde(deserialize_with = "deserialize_date_format")]
+    pub week_format: String,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale {
+            decimal_separator: '.',
+            date_format: "%Y-%m-%d".to_string(),
+            week_format: "%G-W%V".to_string(),
+        }
+    }
+}
+
+impl Locale {
+    /// Formats hours with two decimals, e.g. "3.25" or "3,25"
+    pub fn format_hours(&self, hours: f64) -> String {
+        let formatted = format!("{:.2}", hours);
+        if self.decimal_separator == '.' {
+            formatted
+        } else {
+            formatted.replace('.', &self.decimal_separator.to_string())
+        }
+    }
+
+    pub fn format_duration_hours(&self, duration: &Duration) -> String {
+        self.format_hours(duration.num_seconds() as f64 / 3600.0)
+    }
+
+    /// Formats the date, in ISO format if the date format is invalid
+    pub fn format_date(&self, date: NaiveDate) -> String {
+        try_format_date(date, &self.date_format).unwrap_or_else(|| date.to_string())
+    }
+
+    /// Formats the week, e.g. "2024-W03" if the week format is invalid
+    pub fn format_week(&self, week: IsoWeek) -> String {
+        NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Mon)
+            .and_then(|monday| try_format_date(monday, &self.week_format))
+            .unwrap_or_else(|| format!("{}-W{:02}", week.year(), week.week()))
+    }
+}
+
+/// Formats the date, `None` if the format is invalid or needs a time, e.g.
+/// "%H", formatting with `to_string` would panic
+fn try_format_date(date: NaiveDate, format: &str) -> Option<String> {
+    let mut formatted = String::new();
+    write!(formatted, "{}", date.format(format)).ok()?;
+    Some(formatted)
+}
+
+/// Rejects a format which can't format dates, e.g. in the configuration
+fn deserialize_date_format<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let format = String::deserialize(deserializer)?;
+    match try_format_date(NaiveDate::default(), &format) {
+        Some(_) => Ok(format),
+        None => Err(D::Error::custom(format!(
+            "invalid date format '{}'",
+            format
+        ))),
+    }
+}
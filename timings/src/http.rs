@@ -0,0 +1,254 @@
+//! Read-only HTTP API
+//!
+//! Serves totals, daily totals and summaries as JSON, e.g. to glance at the
+//! hours from a phone on the LAN. Only timings written to the database are
+//! included, the running timing is written periodically by the app.
+//!
+//! Endpoints:
+//!
+//! - `/totals?client=Acme&project=Website&days=30`
+//! - `/daily?from=2024-03-04&to=2024-03-10&client=Acme`
+//! - `/summaries?from=2024-03-04&to=2024-03-10&project=Website`
+//!
+//! Dates are ISO dates in the local timezone, `from` and `to` default to
+//! today. The `client` and `project` filters match the names exactly after
+//! normalizing them. The optional `days` of `/totals` adds a rolling total of
+//! the last days including today. If a token is given, requests must have the
+//! header `Authorization: Bearer <token>`.
+
+use crate::DailyTotals;
+use crate::Error;
+use crate::ReportRow;
+use crate::TimingsQueries;
+use crate::normalize_name;
+use axum::Json;
+use axum::Router;
+use axum::extract::Query;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::http::header::AUTHORIZATION;
+use axum::middleware::Next;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use chrono::Duration;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use sqlx::sqlite::SqliteConnectOptions;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+#[derive(Clone)]
+struct HttpState {
+    pool: SqlitePool,
+    token: Option<Arc<str>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TotalsQuery {
+    pub client: String,
+    pub project: String,
+    pub days: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RangeQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub client: Option<String>,
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TotalsResponse {
+    pub client: String,
+    pub project: String,
+    pub today_hours: f64,
+    pub this_week_hours: f64,
+    pub last_week_hours: f64,
+    pub eight_weeks_hours: f64,
+    /// Total of the last `days` of the query, if given
+    pub rolling_hours: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyResponse {
+    pub day: NaiveDate,
+    pub client: String,
+    pub project: String,
+    pub hours: f64,
+}
+
+/// Row of `/summaries`, see `TimingsQueries::get_full_report`
+pub type SummaryResponse = ReportRow;
+
+struct HttpError(Error);
+
+impl From<Error> for HttpError {
+    fn from(err: Error) -> Self {
+        HttpError(err)
+    }
+}
+
+impl IntoResponse for HttpError {
+    fn into_response(self) -> Response {
+        // The details stay in the log, they may reveal paths or the schema
+        log::error!("HTTP request failed: {}", self.0);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
+    }
+}
+
+/// Opens the database file in read-only mode for serving
+pub async fn read_only_pool(database: &str) -> Result<SqlitePool, Error> {
+    let options = SqliteConnectOptions::from_str(database)?.read_only(true);
+    Ok(SqlitePool::connect_with(options).await?)
+}
+
+/// Router with the read-only endpoints
+pub fn http_router(pool: SqlitePool, token: Option<String>) -> Router {
+    let state = HttpState {
+        pool,
+        token: token.map(Arc::from),
+    };
+    Router::new()
+        .route("/totals", get(totals))
+        .route("/daily", get(daily))
+        .route("/summaries", get(summaries))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            require_token,
+        ))
+        .with_state(state)
+}
+
+/// Serves the read-only endpoints until `shutdown` resolves
+pub async fn serve_http(
+    listener: TcpListener,
+    pool: SqlitePool,
+    token: Option<String>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> Result<(), std::io::Error> {
+    log::info!("Serving HTTP API on {:?}", listener.local_addr());
+    axum::serve(listener, http_router(pool, token))
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+async fn require_token(State(state): State<HttpState>, request: Request, next: Next) -> Response {
+    if let Some(token) = &state.token {
+        let authorized = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|value| constant_time_eq(value.as_bytes(), token.as_bytes()));
+        if !authorized {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Compares without returning at the first differing byte, so that the time
+/// does not reveal how much of the token was guessed right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn hours(duration: Duration) -> f64 {
+    duration.num_milliseconds() as f64 / 3_600_000.0
+}
+
+fn date_range(query: &RangeQuery) -> (NaiveDate, NaiveDate) {
+    let today = Local::now().date_naive();
+    let to = query.to.unwrap_or(today);
+    (query.from.unwrap_or(to), to)
+}
+
+async fn totals(
+    State(state): State<HttpState>,
+    Query(query): Query<TotalsQuery>,
+) -> Result<Json<TotalsResponse>, HttpError> {
+    let now = Utc::now();
+    let mut conn = state.pool.acquire().await.map_err(Error::from)?;
+    let loaded_days = query.days.unwrap_or(0).max(8 * 7);
+    let daily_totals = DailyTotals::from_database(
+        &mut conn,
+        &query.client,
+        &query.project,
+        now - Duration::days(loaded_days as i64),
+        now,
+    )
+    .await?;
+    let totals = daily_totals.to_totals(now);
+    let rolling = query
+        .days
+        .map(|days| daily_totals.get_rolling_totals(days, now));
+
+    Ok(Json(TotalsResponse {
+        client: query.client,
+        project: query.project,
+        today_hours: hours(totals.today),
+        this_week_hours: hours(totals.this_week),
+        last_week_hours: hours(totals.last_week),
+        eight_weeks_hours: hours(totals.eight_weeks),
+        rolling_hours: rolling.map(hours),
+    }))
+}
+
+async fn daily(
+    State(state): State<HttpState>,
+    Query(query): Query<RangeQuery>,
+) -> Result<Json<Vec<DailyResponse>>, HttpError> {
+    let (from, to) = date_range(&query);
+    let mut conn = state.pool.acquire().await.map_err(Error::from)?;
+    let exactly = |name: Option<String>| name.map(|name| vec![normalize_name(&name)]);
+    let totals = conn
+        .get_timings_daily_totals_in(
+            Local,
+            from,
+            to,
+            exactly(query.client),
+            exactly(query.project),
+        )
+        .await?;
+
+    Ok(Json(
+        totals
+            .into_iter()
+            .map(|total| DailyResponse {
+                day: total.day,
+                client: total.client,
+                project: total.project,
+                hours: total.hours,
+            })
+            .collect(),
+    ))
+}
+
+async fn summaries(
+    State(state): State<HttpState>,
+    Query(query): Query<RangeQuery>,
+) -> Result<Json<Vec<SummaryResponse>>, HttpError> {
+    let (from, to) = date_range(&query);
+    let mut conn = state.pool.acquire().await.map_err(Error::from)?;
+    let mut rows = conn.get_full_report(from, to, Local).await?;
+    rows.retain(|row| {
+        query
+            .client
+            .as_ref()
+            .is_none_or(|client| normalize_name(client) == row.client)
+            && query
+                .project
+                .as_ref()
+                .is_none_or(|project| normalize_name(project) == row.project)
+    });
+
+    Ok(Json(rows))
+}
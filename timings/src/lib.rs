@@ -1,9 +1,31 @@
 mod api;
+mod app_usage;
+mod calendar;
 mod error;
+mod export;
+mod format;
+#[cfg(feature = "http")]
+mod http;
+mod names;
+#[cfg(feature = "pdf")]
+mod pdf;
+mod portable;
+mod report;
 mod repository;
 mod timings_recorder;
 mod totals_cache;
 pub use api::*;
+pub use app_usage::*;
+pub use calendar::*;
 pub use error::*;
+pub use export::*;
+pub use format::*;
+#[cfg(feature = "http")]
+pub use http::*;
+pub use names::*;
+#[cfg(feature = "pdf")]
+pub use pdf::*;
+pub use portable::*;
+pub use report::*;
 pub use timings_recorder::*;
 pub use totals_cache::*;
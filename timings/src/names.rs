@@ -0,0 +1,19 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Project of timings recorded for desktops without a project
+pub const UNASSIGNED_PROJECT: &str = "(unassigned)";
+
+/// Normalizes a client or project name
+///
+/// Applies Unicode NFC normalization, collapses internal whitespace (including
+/// non-breaking spaces) to a single space and trims the ends, so that visually
+/// identical names map to the same client or project.
+pub fn normalize_name(name: &str) -> String {
+    let composed = name.nfc().collect::<String>();
+    composed.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// True if the project is the placeholder for desktops without a project
+pub fn is_unassigned(project: &str) -> bool {
+    project == UNASSIGNED_PROJECT
+}
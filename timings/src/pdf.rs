@@ -0,0 +1,202 @@
+//! PDF invoice rendering
+//!
+//! Renders the hours of a client into an invoice with the standard Helvetica
+//! font, so no fonts are embedded. Text is encoded as
+//! WinAnsi, characters outside Latin-1 are replaced with `?`.
+
+use crate::Error;
+use crate::Locale;
+use crate::ReportRow;
+use crate::TimingsQueries;
+use crate::is_unassigned;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use pdf_writer::Content;
+use pdf_writer::Finish;
+use pdf_writer::Name;
+use pdf_writer::Pdf;
+use pdf_writer::Rect;
+use pdf_writer::Ref;
+use pdf_writer::Str;
+use std::collections::BTreeMap;
+
+/// A4 in points
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 72.0;
+const LINE_HEIGHT: f32 = 16.0;
+const LINES_PER_PAGE: usize = 45;
+
+const PROJECT_X: f32 = MARGIN;
+const HOURS_X: f32 = 340.0;
+const COST_X: f32 = 430.0;
+
+/// Line of the invoice, cells are at x positions
+struct Line {
+    size: f32,
+    cells: Vec<(f32, String)>,
+}
+
+impl Line {
+    fn text(size: f32, text: impl Into<String>) -> Self {
+        Line {
+            size,
+            cells: vec![(MARGIN, text.into())],
+        }
+    }
+
+    fn empty() -> Self {
+        Line {
+            size: 11.0,
+            cells: Vec::new(),
+        }
+    }
+}
+
+/// Renders a PDF invoice of the client's hours in the date range.
+///
+/// Rows are fetched with `get_full_report`, unassigned
+/// timings are left out.
+pub async fn pdf_invoice(
+    conn: &mut impl TimingsQueries,
+    timezone: impl TimeZone,
+    client: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    locale: &Locale,
+    hourly_rate: Option<f64>,
+) -> Result<Vec<u8>, Error> {
+    let mut rows = conn.get_full_report(from, to, timezone).await?;
+    rows.retain(|row| row.client == client && !is_unassigned(&row.project));
+    Ok(render_pdf_invoice(
+        client,
+        from,
+        to,
+        &rows,
+        locale,
+        hourly_rate,
+    ))
+}
+
+/// Renders rows as a PDF invoice with a line item per project and a total,
+/// with costs if `hourly_rate` is given.
+pub fn render_pdf_invoice(
+    client: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    rows: &[ReportRow],
+    locale: &Locale,
+    hourly_rate: Option<f64>,
+) -> Vec<u8> {
+    let mut lines = vec![
+        Line::text(20.0, "Invoice"),
+        Line::empty(),
+        Line::text(12.0, client),
+        Line::text(
+            11.0,
+            format!(
+                "Period: {} - {}",
+                locale.format_date(from),
+                locale.format_date(to)
+            ),
+        ),
+        Line::empty(),
+    ];
+
+    let mut header = vec![
+        (PROJECT_X, "Project".to_string()),
+        (HOURS_X, "Hours".to_string()),
+    ];
+    if hourly_rate.is_some() {
+        header.push((COST_X, "Cost".to_string()));
+    }
+    lines.push(Line {
+        size: 11.0,
+        cells: header,
+    });
+
+    let mut project_totals: BTreeMap<&str, f64> = BTreeMap::new();
+    for row in rows {
+        *project_totals.entry(row.project.as_str()).or_default() += row.hours;
+    }
+    for (project, hours) in &project_totals {
+        let mut cells = vec![
+            (PROJECT_X, project.to_string()),
+            (HOURS_X, locale.format_hours(*hours)),
+        ];
+        if let Some(rate) = hourly_rate {
+            cells.push((COST_X, locale.format_hours(hours * rate)));
+        }
+        lines.push(Line { size: 11.0, cells });
+    }
+
+    let total: f64 = project_totals.values().sum();
+    let mut cells = vec![
+        (PROJECT_X, "Total".to_string()),
+        (HOURS_X, locale.format_hours(total)),
+    ];
+    if let Some(rate) = hourly_rate {
+        cells.push((COST_X, locale.format_hours(total * rate)));
+    }
+    lines.push(Line::empty());
+    lines.push(Line { size: 12.0, cells });
+
+    render_pages(&lines)
+}
+
+fn render_pages(lines: &[Line]) -> Vec<u8> {
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let font_id = Ref::new(3);
+    let font_name = Name(b"F1");
+
+    let pages: Vec<&[Line]> = lines.chunks(LINES_PER_PAGE).collect();
+    // Page and its content stream for each page
+    let page_ids: Vec<(Ref, Ref)> = (0..pages.len() as i32)
+        .map(|i| (Ref::new(4 + 2 * i), Ref::new(5 + 2 * i)))
+        .collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id)
+        .kids(page_ids.iter().map(|(page_id, _)| *page_id))
+        .count(pages.len() as i32);
+    pdf.type1_font(font_id)
+        .base_font(Name(b"Helvetica"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    for (page_lines, (page_id, content_id)) in pages.iter().zip(&page_ids) {
+        let mut page = pdf.page(*page_id);
+        page.media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT));
+        page.parent(page_tree_id);
+        page.contents(*content_id);
+        page.resources().fonts().pair(font_name, font_id);
+        page.finish();
+
+        let mut content = Content::new();
+        let mut y = PAGE_HEIGHT - MARGIN;
+        for line in page_lines.iter() {
+            for (x, text) in &line.cells {
+                content.begin_text();
+                content.set_font(font_name, line.size);
+                content.next_line(*x, y);
+                content.show(Str(&win_ansi(text)));
+                content.end_text();
+            }
+            y -= LINE_HEIGHT.max(line.size * 1.4);
+        }
+        pdf.stream(*content_id, &content.finish());
+    }
+
+    pdf.finish()
+}
+
+/// Latin-1 subset of WinAnsi, other characters are replaced with `?`
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x20..=0x7E | 0xA0..=0xFF => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
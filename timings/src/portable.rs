@@ -0,0 +1,273 @@
+//! Portable JSON export and import of the whole database
+//!
+//! For backups and moving the timings to another machine. The document is
+//! versioned, `{ "version": 2, "timings": [...], "summaries": [...] }`.
+
+use crate::Error;
+use crate::GetTimingsFilters;
+use crate::StoredSummary;
+use crate::Timing;
+use crate::TimingAppUsage;
+use crate::TimingsMutations;
+use crate::TimingsQueries;
+use crate::normalize_name;
+use serde::Deserialize;
+use serde::Serialize;
+use sqlx::Connection;
+use sqlx::SqliteConnection;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// Version of the exported document, importing a newer version fails.
+///
+/// Version 2 added the sources and the application usage of the timings, and
+/// the projects which are not billable. Documents of version 1 are imported
+/// as timings without them, of billable projects.
+pub const PORTABLE_VERSION: u32 = 2;
+
+/// Exported timing with the source it was recorded with and its application
+/// usage
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PortableTiming {
+    #[serde(flatten)]
+    pub timing: Timing,
+    /// Session tag the timing was recorded with, e.g. the hostname
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Seconds per foreground application, see `TimingAppUsage`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub app_usage: BTreeMap<String, i64>,
+}
+
+/// Project of an exported timing or summary
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PortableProject {
+    pub client: String,
+    pub project: String,
+}
+
+/// Exported timings and summaries, oldest first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortableDocument {
+    /// `PORTABLE_VERSION` of the exporting crate
+    pub version: u32,
+    pub timings: Vec<PortableTiming>,
+    /// Summaries with their archived flags
+    pub summaries: Vec<StoredSummary>,
+    /// Projects of the timings and summaries which are not billable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub non_billable_projects: Vec<PortableProject>,
+}
+
+/// Rows of `import_all_json`, rows already in the database as is are
+/// skipped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    pub inserted_timings: usize,
+    pub skipped_timings: usize,
+    pub inserted_summaries: usize,
+    pub skipped_summaries: usize,
+}
+
+/// Rows of `TimingsMutations::merge_database`, counted like on import
+pub type MergeStats = ImportStats;
+
+/// Whether the name is in the list, like the `clients` and `projects` filters
+/// no list and an empty list match any name, the listed names are normalized
+fn matches_any(names: &Option<Vec<String>>, name: &str) -> bool {
+    names
+        .as_ref()
+        .is_none_or(|names| names.is_empty() || names.iter().any(|n| normalize_name(n) == name))
+}
+
+/// Exports the filtered timings, and the summaries of the same clients,
+/// projects and range. The source filter applies to the timings only.
+pub async fn export_json(
+    conn: &mut impl TimingsQueries,
+    filters: Option<GetTimingsFilters>,
+) -> Result<PortableDocument, Error> {
+    let filters = GetTimingsFilters {
+        limit: None,
+        offset: None,
+        ..filters.unwrap_or_default()
+    };
+    let mut sources = conn.get_timing_sources_by_id(Some(filters.clone())).await?;
+    let mut app_usage: HashMap<_, BTreeMap<String, i64>> = HashMap::new();
+    for usage in conn.get_timing_app_usage(Some(filters.clone())).await? {
+        app_usage
+            .entry((usage.client, usage.project, usage.start))
+            .or_default()
+            .insert(usage.app, usage.seconds);
+    }
+    let mut timings: Vec<PortableTiming> = conn
+        .get_timings_with_ids(Some(filters.clone()))
+        .await?
+        .into_iter()
+        .map(|row| {
+            let key = (
+                row.timing.client.clone(),
+                row.timing.project.clone(),
+                row.timing.start,
+            );
+            PortableTiming {
+                source: sources.remove(&row.id),
+                app_usage: app_usage.remove(&key).unwrap_or_default(),
+                timing: row.timing,
+            }
+        })
+        .collect();
+    timings.reverse();
+    let summaries = conn
+        .get_stored_summaries()
+        .await?
+        .into_iter()
+        .filter(|s| {
+            filters
+                .client
+                .as_ref()
+                .is_none_or(|client| s.client == normalize_name(client))
+        })
+        .filter(|s| {
+            filters
+                .project
+                .as_ref()
+                .is_none_or(|project| s.project == normalize_name(project))
+        })
+        .filter(|s| matches_any(&filters.clients, &s.client))
+        .filter(|s| matches_any(&filters.projects, &s.project))
+        .filter(|s| filters.from.is_none_or(|from| s.end > from))
+        .filter(|s| filters.to.is_none_or(|to| s.start <= to))
+        .collect::<Vec<_>>();
+
+    let projects: BTreeSet<PortableProject> = timings
+        .iter()
+        .map(|timing| (&timing.timing.client, &timing.timing.project))
+        .chain(summaries.iter().map(|s| (&s.client, &s.project)))
+        .map(|(client, project)| PortableProject {
+            client: client.clone(),
+            project: project.clone(),
+        })
+        .collect();
+    let mut non_billable_projects = Vec::new();
+    for project in projects {
+        if !conn
+            .is_project_billable(&project.client, &project.project)
+            .await?
+        {
+            non_billable_projects.push(project);
+        }
+    }
+
+    Ok(PortableDocument {
+        version: PORTABLE_VERSION,
+        timings,
+        summaries,
+        non_billable_projects,
+    })
+}
+
+/// Exports all timings and summaries as a JSON document
+pub async fn export_all_json(conn: &mut impl TimingsQueries) -> Result<String, Error> {
+    let document = export_json(conn, None).await?;
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+/// Imports a document of `export_all_json`, see `import_json`
+pub async fn import_all_json(
+    conn: &mut SqliteConnection,
+    json: &str,
+) -> Result<ImportStats, Error> {
+    #[derive(Deserialize)]
+    struct Version {
+        version: u32,
+    }
+    let Version { version } =
+        serde_json::from_str(json).map_err(|e| Error::InvalidImport(e.to_string()))?;
+    if version > PORTABLE_VERSION {
+        return Err(Error::UnsupportedExportVersion(version));
+    }
+    let document: PortableDocument =
+        serde_json::from_str(json).map_err(|e| Error::InvalidImport(e.to_string()))?;
+    import_json(conn, &document).await
+}
+
+/// Imports the document in one transaction.
+///
+/// Importing is idempotent, timings are identified by their project and
+/// start, and summaries by their project and range like on insert. A
+/// differing end or text updates the existing row, and the application usage
+/// of an updated timing replaces the existing usage. The projects listed as
+/// not billable are marked so.
+pub async fn import_json(
+    conn: &mut SqliteConnection,
+    document: &PortableDocument,
+) -> Result<ImportStats, Error> {
+    if document.version > PORTABLE_VERSION {
+        return Err(Error::UnsupportedExportVersion(document.version));
+    }
+
+    let mut tx = conn.begin().await?;
+    let timing_key = |timing: &Timing| {
+        (
+            normalize_name(&timing.client),
+            normalize_name(&timing.project),
+            timing.start,
+        )
+    };
+    let existing_ends: HashMap<_, _> = tx
+        .get_timings(None)
+        .await?
+        .into_iter()
+        .map(|timing| (timing_key(&timing), timing.end))
+        .collect();
+    let existing_summaries: HashSet<StoredSummary> =
+        tx.get_stored_summaries().await?.into_iter().collect();
+
+    let (skipped_timings, new_timings): (Vec<_>, Vec<_>) =
+        document.timings.iter().partition(|timing| {
+            existing_ends.get(&timing_key(&timing.timing)) == Some(&timing.timing.end)
+        });
+    let (skipped_summaries, new_summaries): (Vec<_>, Vec<_>) = document
+        .summaries
+        .iter()
+        .partition(|summary| existing_summaries.contains(*summary));
+
+    for timing in &new_timings {
+        tx.insert_timings_with_source([&timing.timing], timing.source.as_deref())
+            .await?;
+    }
+    // Only the usage of the new and updated timings, replacing so that
+    // importing again does not add it twice
+    let usage: Vec<TimingAppUsage> = new_timings
+        .iter()
+        .flat_map(|timing| {
+            timing
+                .app_usage
+                .iter()
+                .map(|(app, seconds)| TimingAppUsage {
+                    client: timing.timing.client.clone(),
+                    project: timing.timing.project.clone(),
+                    start: timing.timing.start,
+                    app: app.clone(),
+                    seconds: *seconds,
+                })
+        })
+        .collect();
+    tx.replace_timing_app_usage(&usage).await?;
+    tx.insert_stored_summaries(new_summaries.iter().copied())
+        .await?;
+    for project in &document.non_billable_projects {
+        tx.set_project_billable(&project.client, &project.project, false)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(ImportStats {
+        inserted_timings: new_timings.len(),
+        skipped_timings: skipped_timings.len(),
+        inserted_summaries: new_summaries.len(),
+        skipped_summaries: skipped_summaries.len(),
+    })
+}
@@ -0,0 +1,100 @@
+//! Report rendering
+//!
+//! Renders daily totals and summaries into human readable reports.
+
+use crate::Error;
+use crate::Locale;
+use crate::ReportRow;
+use crate::TimingsQueries;
+use crate::is_unassigned;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Renders a Markdown report for the given date range.
+///
+/// Rows are fetched with `get_full_report` and rendered
+/// oldest day first. Unassigned timings are left out unless
+/// `include_unassigned` is set.
+pub async fn markdown_report(
+    conn: &mut impl TimingsQueries,
+    timezone: impl TimeZone,
+    title: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    locale: &Locale,
+    include_unassigned: bool,
+) -> Result<String, Error> {
+    let mut rows = conn.get_full_report(from, to, timezone).await?;
+    rows.retain(|row| include_unassigned || !is_unassigned(&row.project));
+    Ok(render_markdown_report(title, &rows, locale))
+}
+
+/// Renders rows as a Markdown report with a daily table, per project totals
+/// and a grand total.
+pub fn render_markdown_report(title: &str, rows: &[ReportRow], locale: &Locale) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}", title);
+    let _ = writeln!(out);
+
+    if rows.is_empty() {
+        let _ = writeln!(out, "No timings recorded.");
+        return out;
+    }
+
+    let _ = writeln!(out, "| Date | Client | Project | Hours | Summary |");
+    let _ = writeln!(out, "|------|--------|---------|------:|---------|");
+    for row in rows {
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            locale.format_date(row.day),
+            escape_markdown_cell(&row.client),
+            escape_markdown_cell(&row.project),
+            locale.format_hours(row.hours),
+            escape_markdown_cell(&row.summary)
+        );
+    }
+
+    let mut project_totals: BTreeMap<(&str, &str), f64> = BTreeMap::new();
+    for row in rows {
+        *project_totals
+            .entry((row.client.as_str(), row.project.as_str()))
+            .or_default() += row.hours;
+    }
+
+    let _ = writeln!(out);
+    let _ = writeln!(out, "## Totals");
+    let _ = writeln!(out);
+    for ((client, project), hours) in &project_totals {
+        let _ = writeln!(
+            out,
+            "- {}: {} — {} h",
+            client,
+            project,
+            locale.format_hours(*hours)
+        );
+    }
+
+    let total: f64 = rows.iter().map(|r| r.hours).sum();
+    let _ = writeln!(out);
+    let _ = writeln!(out, "**Total: {} h**", locale.format_hours(total));
+
+    // Split only if some of the projects are not billable
+    if rows.iter().any(|r| !r.billable) {
+        let billable: f64 = rows.iter().filter(|r| r.billable).map(|r| r.hours).sum();
+        let _ = writeln!(out);
+        let _ = writeln!(
+            out,
+            "Billable: {} h, non-billable: {} h",
+            locale.format_hours(billable),
+            locale.format_hours(total - billable)
+        );
+    }
+    out
+}
+
+fn escape_markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
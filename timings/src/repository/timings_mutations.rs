@@ -3,33 +3,59 @@
 //! Not to be used directly, use the traits in `timings.rs` instead.
 
 use super::utils::datetime_to_ms;
+use super::utils::local_day_to_utc_bounds;
+use super::utils::ms_to_datetime;
+use super::utils::ms_to_local_day;
+use super::utils::push_names_in;
+use crate::DatabaseProblem;
+use crate::GetTimingsFilters;
+use crate::MergeStats;
+use crate::OverlapStrategy;
+use crate::SCHEMA_VERSION;
+use crate::ShiftedSummary;
+use crate::StoredSummary;
 use crate::SummaryForDay;
 use crate::Timing;
+use crate::TimingAppUsage;
+use crate::TimingChange;
+use crate::TimingOverlap;
 use crate::TimingsMutations;
+use crate::TimingsQueries;
 use crate::error::Error;
+use crate::normalize_name;
 use chrono::DateTime;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::TimeZone;
 use chrono::Utc;
+use libsqlite3_sys::SQLITE_DONE;
+use libsqlite3_sys::SQLITE_OK;
+use libsqlite3_sys::sqlite3;
+use libsqlite3_sys::sqlite3_backup_finish;
+use libsqlite3_sys::sqlite3_backup_init;
+use libsqlite3_sys::sqlite3_backup_step;
+use libsqlite3_sys::sqlite3_errmsg;
 use sqlx::Acquire;
 use sqlx::Executor;
+use sqlx::QueryBuilder;
+use sqlx::Sqlite;
 use sqlx::SqliteConnection;
-
-#[derive(Debug, Clone)]
-struct Summary {
-    pub start: DateTime<Utc>,
-    pub end: DateTime<Utc>,
-    pub text: String,
-    pub client: String,
-    pub project: String,
-    pub archived: bool,
-}
+use sqlx::sqlite::SqliteConnectOptions;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::path::Path;
+use std::ptr::NonNull;
+use std::str::FromStr;
 
 async fn get_or_create_client_id(
     conn: &mut SqliteConnection,
     client_name: &str,
 ) -> Result<i64, sqlx::Error> {
+    let client_name = normalize_name(client_name);
+
     // Try to get existing client
     let existing: Option<(i64,)> = sqlx::query_as("SELECT id FROM client WHERE name = ?")
-        .bind(client_name)
+        .bind(&client_name)
         .fetch_optional(&mut *conn)
         .await?;
 
@@ -39,7 +65,7 @@ async fn get_or_create_client_id(
 
     // Create new client
     let result = sqlx::query("INSERT INTO client (name) VALUES (?)")
-        .bind(client_name)
+        .bind(&client_name)
         .execute(&mut *conn)
         .await?;
 
@@ -51,10 +77,12 @@ async fn get_or_create_project_id(
     project_name: &str,
     client_id: i64,
 ) -> Result<i64, sqlx::Error> {
+    let project_name = normalize_name(project_name);
+
     // Try to get existing project
     let existing: Option<(i64,)> =
         sqlx::query_as("SELECT id FROM project WHERE name = ? AND clientId = ?")
-            .bind(project_name)
+            .bind(&project_name)
             .bind(client_id)
             .fetch_optional(&mut *conn)
             .await?;
@@ -65,7 +93,7 @@ async fn get_or_create_project_id(
 
     // Create new project
     let result = sqlx::query("INSERT INTO project (name, clientId) VALUES (?, ?)")
-        .bind(project_name)
+        .bind(&project_name)
         .bind(client_id)
         .execute(&mut *conn)
         .await?;
@@ -73,9 +101,549 @@ async fn get_or_create_project_id(
     Ok(result.last_insert_rowid())
 }
 
+/// Id and end of the timing identified by its client, project and start,
+/// `None` if there is no such timing
+async fn lookup_timing(
+    conn: &mut SqliteConnection,
+    client: &str,
+    project: &str,
+    start: DateTime<Utc>,
+) -> Result<Option<(i64, DateTime<Utc>)>, Error> {
+    let Some(project_id) = find_project_id(conn, client, project).await? else {
+        return Ok(None);
+    };
+    let existing: Option<(i64, i64)> =
+        sqlx::query_as("SELECT id, [end] FROM timing WHERE projectId = ? AND start = ?")
+            .bind(project_id)
+            .bind(datetime_to_ms(&start))
+            .fetch_optional(&mut *conn)
+            .await?;
+    existing
+        .map(|(id, end)| Ok((id, ms_to_datetime(end)?)))
+        .transpose()
+}
+
+/// Id and end of the timing identified by its client, project and start
+async fn find_timing(
+    conn: &mut SqliteConnection,
+    timing: &Timing,
+) -> Result<(i64, DateTime<Utc>), Error> {
+    lookup_timing(conn, &timing.client, &timing.project, timing.start)
+        .await?
+        .ok_or_else(|| {
+            Error::InvalidTiming(format!(
+                "no timing of '{}: {}' starting at {:?}",
+                timing.client, timing.project, timing.start
+            ))
+        })
+}
+
+/// Replaces the timing row with `new` and records the change from `old` in
+/// the audit log, the start must stay unique within the project
+async fn replace_timing(
+    conn: &mut SqliteConnection,
+    timing_id: i64,
+    old: &Timing,
+    new: &Timing,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    let new = Timing {
+        client: normalize_name(&new.client),
+        project: normalize_name(&new.project),
+        ..new.clone()
+    };
+    let client_id = get_or_create_client_id(conn, &new.client).await?;
+    let project_id = get_or_create_project_id(conn, &new.project, client_id).await?;
+    let conflict: Option<(i64,)> =
+        sqlx::query_as("SELECT id FROM timing WHERE projectId = ? AND start = ? AND id != ?")
+            .bind(project_id)
+            .bind(datetime_to_ms(&new.start))
+            .bind(timing_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+    if conflict.is_some() {
+        return Err(Error::TimingConflict(new));
+    }
+    sqlx::query("UPDATE timing SET start = ?, [end] = ?, projectId = ? WHERE id = ?")
+        .bind(datetime_to_ms(&new.start))
+        .bind(datetime_to_ms(&new.end))
+        .bind(project_id)
+        .bind(timing_id)
+        .execute(&mut *conn)
+        .await?;
+    insert_timing_audit(conn, TimingChange::Update, Some(old), Some(&new), now).await?;
+    Ok(())
+}
+
+/// Deletes the timing row and its application usage, and records the change
+/// in the audit log
+async fn delete_timing_row(
+    conn: &mut SqliteConnection,
+    timing_id: i64,
+    old: &Timing,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    sqlx::query("DELETE FROM timing_app_usage WHERE timingId = ?")
+        .bind(timing_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM timing WHERE id = ?")
+        .bind(timing_id)
+        .execute(&mut *conn)
+        .await?;
+    insert_timing_audit(conn, TimingChange::Delete, Some(old), None, now).await?;
+    Ok(())
+}
+
+/// Repairs one overlap, see `OverlapStrategy`
+async fn resolve_overlap(
+    conn: &mut SqliteConnection,
+    overlap: &TimingOverlap,
+    strategy: OverlapStrategy,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    let (first, second) = (&overlap.first, &overlap.second);
+    let duration = |t: &Timing| t.end - t.start;
+    let within = second.timing.end <= first.timing.end;
+    match strategy {
+        OverlapStrategy::Clip | OverlapStrategy::Merge if within => {
+            delete_timing_row(conn, second.id, &second.timing, now).await
+        }
+        OverlapStrategy::Clip => {
+            // The first one starts earlier, the start of the second one may
+            // not be moved to an existing start
+            let new = Timing {
+                end: second.timing.start,
+                ..first.timing.clone()
+            };
+            replace_timing(conn, first.id, &first.timing, &new, now).await
+        }
+        OverlapStrategy::Merge => {
+            let new = Timing {
+                end: second.timing.end,
+                ..first.timing.clone()
+            };
+            replace_timing(conn, first.id, &first.timing, &new, now).await?;
+            move_app_usage(conn, second.id, first.id).await?;
+            delete_timing_row(conn, second.id, &second.timing, now).await
+        }
+        OverlapStrategy::DeleteShorter => {
+            let shorter = if duration(&second.timing) <= duration(&first.timing) {
+                second
+            } else {
+                first
+            };
+            delete_timing_row(conn, shorter.id, &shorter.timing, now).await
+        }
+    }
+}
+
+/// Adds the application usage of the timing to another timing, e.g. before
+/// deleting it
+async fn move_app_usage(
+    conn: &mut SqliteConnection,
+    from_timing_id: i64,
+    to_timing_id: i64,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO timing_app_usage (timingId, app, seconds)
+        SELECT ?, app, seconds
+        FROM timing_app_usage
+        WHERE timingId = ?
+        ON CONFLICT (timingId, app)
+        DO UPDATE SET seconds = seconds + excluded.seconds
+        "#,
+    )
+    .bind(to_timing_id)
+    .bind(from_timing_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Consecutive timings merged by `compact_timings`, in milliseconds
+struct CompactRun {
+    id: i64,
+    project_id: i64,
+    source: Option<String>,
+    /// Day of the first timing in the timezone, the run does not go past it
+    day: NaiveDate,
+    start: i64,
+    end: i64,
+    recorded: i64,
+    merged: Vec<i64>,
+}
+
+/// Shortens the first timing of the run to the recorded time of the run,
+/// and deletes the rest moving their application usage to it
+async fn merge_compact_run(conn: &mut SqliteConnection, run: &CompactRun) -> Result<(), Error> {
+    if run.merged.is_empty() {
+        return Ok(());
+    }
+    sqlx::query("UPDATE timing SET [end] = ? WHERE id = ?")
+        .bind(run.start + run.recorded)
+        .bind(run.id)
+        .execute(&mut *conn)
+        .await?;
+    for timing_id in &run.merged {
+        move_app_usage(conn, *timing_id, run.id).await?;
+        sqlx::query("DELETE FROM timing_app_usage WHERE timingId = ?")
+            .bind(timing_id)
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("DELETE FROM timing WHERE id = ?")
+            .bind(timing_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Upserts the usage rows, `seconds` is the new value of an existing row of
+/// the same timing and application
+async fn upsert_timing_app_usage(
+    conn: &mut SqliteConnection,
+    usage: impl IntoIterator<Item = &TimingAppUsage>,
+    seconds: &str,
+) -> Result<(), Error> {
+    for usage in usage {
+        // Timing is identified by its project and start
+        sqlx::query(&format!(
+            r#"
+                INSERT INTO timing_app_usage (timingId, app, seconds)
+                SELECT timing.id, ?, ?
+                FROM timing, project, client
+                WHERE timing.projectId = project.id
+                    AND project.clientId = client.id
+                    AND client.name = ?
+                    AND project.name = ?
+                    AND timing.start = ?
+                ON CONFLICT (timingId, app)
+                DO UPDATE SET seconds = {seconds}
+            "#,
+        ))
+        .bind(&usage.app)
+        .bind(usage.seconds)
+        .bind(normalize_name(&usage.client))
+        .bind(normalize_name(&usage.project))
+        .bind(datetime_to_ms(&usage.start))
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Pushes the client, project and source conditions of the filters, the
+/// names normalized like when inserting
+fn push_name_conditions(builder: &mut QueryBuilder<'_, Sqlite>, filters: &GetTimingsFilters) {
+    if let Some(client) = &filters.client {
+        builder.push(" AND client.name = ");
+        builder.push_bind(normalize_name(client));
+    }
+    if let Some(project) = &filters.project {
+        builder.push(" AND project.name = ");
+        builder.push_bind(normalize_name(project));
+    }
+    if let Some(source) = &filters.source {
+        builder.push(" AND timing.source = ");
+        builder.push_bind(source.clone());
+    }
+    let normalize_names = |names: &Option<Vec<String>>| -> Option<Vec<String>> {
+        names
+            .as_ref()
+            .map(|names| names.iter().map(|name| normalize_name(name)).collect())
+    };
+    push_names_in(builder, "client.name", normalize_names(&filters.clients));
+    push_names_in(builder, "project.name", normalize_names(&filters.projects));
+}
+
+/// Pushes the conditions of the start of the timing being within the
+/// filtered range
+fn push_range_conditions(builder: &mut QueryBuilder<'_, Sqlite>, filters: &GetTimingsFilters) {
+    if let Some(from) = &filters.from {
+        builder.push(" AND timing.start >= ");
+        builder.push_bind(datetime_to_ms(from));
+    }
+    if let Some(to) = &filters.to {
+        builder.push(" AND timing.start <= ");
+        builder.push_bind(datetime_to_ms(to));
+    }
+}
+
+/// Records a manual timing change in the audit log
+async fn insert_timing_audit(
+    conn: &mut SqliteConnection,
+    change: TimingChange,
+    old: Option<&Timing>,
+    new: Option<&Timing>,
+    now: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO timing_audit (changedAt, change, oldClient, oldProject, oldStart, oldEnd,
+            newClient, newProject, newStart, newEnd)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(datetime_to_ms(&now))
+    .bind(change.to_string())
+    .bind(old.map(|t| t.client.clone()))
+    .bind(old.map(|t| t.project.clone()))
+    .bind(old.map(|t| datetime_to_ms(&t.start)))
+    .bind(old.map(|t| datetime_to_ms(&t.end)))
+    .bind(new.map(|t| t.client.clone()))
+    .bind(new.map(|t| t.project.clone()))
+    .bind(new.map(|t| datetime_to_ms(&t.start)))
+    .bind(new.map(|t| datetime_to_ms(&t.end)))
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+async fn find_project_id(
+    conn: &mut SqliteConnection,
+    client_name: &str,
+    project_name: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let existing: Option<(i64,)> = sqlx::query_as(
+        r#"
+        SELECT project.id
+        FROM project, client
+        WHERE project.clientId = client.id
+            AND client.name = ?
+            AND project.name = ?
+        "#,
+    )
+    .bind(normalize_name(client_name))
+    .bind(normalize_name(project_name))
+    .fetch_optional(&mut *conn)
+    .await?;
+    Ok(existing.map(|(id,)| id))
+}
+
+/// Moves the timings, application usage and summaries of a project to
+/// another project and deletes it
+///
+/// Timings starting at the same time are combined keeping the later end, and
+/// summaries of the same day are joined.
+async fn merge_project(
+    conn: &mut SqliteConnection,
+    from_project_id: i64,
+    to_project_id: i64,
+) -> Result<(), sqlx::Error> {
+    // Application usage of timings which collide with a timing of the target
+    sqlx::query(
+        r#"
+        INSERT INTO timing_app_usage (timingId, app, seconds)
+        SELECT target.id, usage.app, usage.seconds
+        FROM timing_app_usage AS usage, timing AS source, timing AS target
+        WHERE usage.timingId = source.id
+            AND source.projectId = ?
+            AND target.projectId = ?
+            AND target.start = source.start
+        ON CONFLICT (timingId, app)
+        DO UPDATE SET seconds = seconds + excluded.seconds
+        "#,
+    )
+    .bind(from_project_id)
+    .bind(to_project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE timing AS target
+        SET [end] = MAX(target.[end], source.[end])
+        FROM timing AS source
+        WHERE source.projectId = ?
+            AND target.projectId = ?
+            AND target.start = source.start
+        "#,
+    )
+    .bind(from_project_id)
+    .bind(to_project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM timing_app_usage
+        WHERE timingId IN (
+            SELECT source.id
+            FROM timing AS source, timing AS target
+            WHERE source.projectId = ?
+                AND target.projectId = ?
+                AND target.start = source.start
+        )
+        "#,
+    )
+    .bind(from_project_id)
+    .bind(to_project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM timing
+        WHERE projectId = ?
+            AND start IN (SELECT start FROM timing WHERE projectId = ?)
+        "#,
+    )
+    .bind(from_project_id)
+    .bind(to_project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("UPDATE timing SET projectId = ? WHERE projectId = ?")
+        .bind(to_project_id)
+        .bind(from_project_id)
+        .execute(&mut *conn)
+        .await?;
+
+    // Summaries of the same day are joined
+    sqlx::query(
+        r#"
+        UPDATE summary AS target
+        SET text = target.text || char(10) || source.text,
+            archived = target.archived AND source.archived
+        FROM summary AS source
+        WHERE source.projectId = ?
+            AND target.projectId = ?
+            AND target.start = source.start
+            AND target.[end] = source.[end]
+        "#,
+    )
+    .bind(from_project_id)
+    .bind(to_project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM summary
+        WHERE projectId = ?
+            AND EXISTS (
+                SELECT 1 FROM summary AS target
+                WHERE target.projectId = ?
+                    AND target.start = summary.start
+                    AND target.[end] = summary.[end]
+            )
+        "#,
+    )
+    .bind(from_project_id)
+    .bind(to_project_id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("UPDATE summary SET projectId = ? WHERE projectId = ?")
+        .bind(to_project_id)
+        .bind(from_project_id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query("DELETE FROM project WHERE id = ?")
+        .bind(from_project_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Moves a project under the client and renames it, merging it to an
+/// existing project of the same name
+async fn move_project(
+    conn: &mut SqliteConnection,
+    project_id: i64,
+    client_id: i64,
+    project_name: &str,
+) -> Result<(), sqlx::Error> {
+    let project_name = normalize_name(project_name);
+    let existing: Option<(i64,)> =
+        sqlx::query_as("SELECT id FROM project WHERE name = ? AND clientId = ?")
+            .bind(&project_name)
+            .bind(client_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+    match existing {
+        Some((existing_id,)) if existing_id == project_id => Ok(()),
+        Some((existing_id,)) => merge_project(conn, project_id, existing_id).await,
+        None => {
+            sqlx::query("UPDATE project SET name = ?, clientId = ? WHERE id = ?")
+                .bind(&project_name)
+                .bind(client_id)
+                .bind(project_id)
+                .execute(&mut *conn)
+                .await?;
+            Ok(())
+        }
+    }
+}
+
+/// Copies the main database of `source` over `target` with the SQLite online
+/// backup API, in one step
+fn online_backup(source: NonNull<sqlite3>, target: NonNull<sqlite3>) -> Result<(), Error> {
+    // SAFETY: Both handles are locked by the caller for the duration of the
+    // call, and the backup object is finished before returning.
+    unsafe {
+        let backup = sqlite3_backup_init(
+            target.as_ptr(),
+            c"main".as_ptr(),
+            source.as_ptr(),
+            c"main".as_ptr(),
+        );
+        if backup.is_null() {
+            return Err(backup_error(target));
+        }
+        let step = sqlite3_backup_step(backup, -1);
+        let finish = sqlite3_backup_finish(backup);
+        if step != SQLITE_DONE || finish != SQLITE_OK {
+            return Err(backup_error(target));
+        }
+    }
+    Ok(())
+}
+
+/// Error of the backup, which is recorded on the target connection
+fn backup_error(target: NonNull<sqlite3>) -> Error {
+    // SAFETY: The handle is locked by the caller and the message is copied
+    // before the handle is used again.
+    let message = unsafe { CStr::from_ptr(sqlite3_errmsg(target.as_ptr())) };
+    Error::IoError(std::io::Error::other(format!(
+        "Database backup failed: {}",
+        message.to_string_lossy()
+    )))
+}
+
+/// Finds a rename whose new name is renamed by another entry, applying such
+/// chains would depend on the order of the renames
+fn find_rename_chain<T: PartialEq>(renames: &[(T, T)]) -> Option<&(T, T)> {
+    renames.iter().find(|(from, to)| {
+        from != to
+            && renames
+                .iter()
+                .any(|(other_from, other_to)| other_from == to && other_from != other_to)
+    })
+}
+
+async fn delete_client_without_projects(
+    conn: &mut SqliteConnection,
+    client_id: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DELETE FROM client WHERE id = ? AND NOT EXISTS (SELECT 1 FROM project WHERE clientId = \
+         client.id)",
+    )
+    .bind(client_id)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
 async fn insert_timings_summary(
     conn: &mut SqliteConnection,
-    summary: Summary,
+    summary: StoredSummary,
 ) -> Result<(), Error> {
     // Get or create the client id from the client name
     let client_id = get_or_create_client_id(conn, &summary.client).await?;
@@ -122,20 +690,163 @@ async fn insert_timings_summary(
     Ok(())
 }
 
-static CLIENT_SCHEMA: &str = include_str!("schema.sql");
+const CLIENT_SCHEMA: &str = include_str!("schema.sql");
+
+/// Migrations in order, migration at index `i` upgrades version `i` to `i + 1`
+const MIGRATIONS: &[&str] = &[
+    // 1: Creates the schema, tables of databases created before versioning
+    // already exist
+    CLIENT_SCHEMA,
+    // 2: Foreground application time per timing
+    r#"
+    CREATE TABLE IF NOT EXISTS timing_app_usage (
+        id       INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+        timingId INTEGER NOT NULL,
+        app      TEXT NOT NULL, -- Window class, e.g. "firefox"
+        seconds  INTEGER NOT NULL,
+        CONSTRAINT UQ_TIMING_APP UNIQUE (timingId, app),
+        CONSTRAINT FK_TIMING_APP_USAGE_TIMING_ID FOREIGN KEY (timingId)
+        REFERENCES timing (id) ON DELETE CASCADE
+                               ON UPDATE NO ACTION
+    ) STRICT;
+    "#,
+    // 3: Audit log of manual timing changes, names are stored so that the log
+    // outlives the projects
+    r#"
+    CREATE TABLE IF NOT EXISTS timing_audit (
+        id         INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+        changedAt  INTEGER NOT NULL, -- Unix timestamp in milliseconds
+        change     TEXT NOT NULL, -- "update" or "delete"
+        oldClient  TEXT NOT NULL,
+        oldProject TEXT NOT NULL,
+        oldStart   INTEGER NOT NULL, -- Unix timestamp in milliseconds
+        oldEnd     INTEGER NOT NULL, -- Unix timestamp in milliseconds
+        newClient  TEXT, -- NULL if deleted
+        newProject TEXT,
+        newStart   INTEGER,
+        newEnd     INTEGER
+    ) STRICT;
+
+    CREATE INDEX IF NOT EXISTS IDX_TIMING_AUDIT_CHANGED_AT ON timing_audit (changedAt);
+    "#,
+    // 4: Session tag of the timing, e.g. the hostname, NULL if not recorded
+    r#"
+    ALTER TABLE timing ADD COLUMN source TEXT;
+    "#,
+    // 5: Billable flag of the project, projects are billable by default
+    r#"
+    ALTER TABLE project ADD COLUMN billable INT NOT NULL DEFAULT 1; -- BOOLEAN
+    "#,
+    // 6: Old timing of the audit log is NULL for inserted timings
+    r#"
+    CREATE TABLE timing_audit_6 (
+        id         INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+        changedAt  INTEGER NOT NULL, -- Unix timestamp in milliseconds
+        change     TEXT NOT NULL, -- "update", "delete" or "insert"
+        oldClient  TEXT, -- NULL if inserted
+        oldProject TEXT,
+        oldStart   INTEGER,
+        oldEnd     INTEGER,
+        newClient  TEXT, -- NULL if deleted
+        newProject TEXT,
+        newStart   INTEGER,
+        newEnd     INTEGER
+    ) STRICT;
+
+    INSERT INTO timing_audit_6
+    SELECT id, changedAt, change,
+        CASE WHEN change = 'insert' THEN NULL ELSE oldClient END,
+        CASE WHEN change = 'insert' THEN NULL ELSE oldProject END,
+        CASE WHEN change = 'insert' THEN NULL ELSE oldStart END,
+        CASE WHEN change = 'insert' THEN NULL ELSE oldEnd END,
+        newClient, newProject, newStart, newEnd
+    FROM timing_audit;
+
+    DROP TABLE timing_audit;
+    ALTER TABLE timing_audit_6 RENAME TO timing_audit;
+    CREATE INDEX IF NOT EXISTS IDX_TIMING_AUDIT_CHANGED_AT ON timing_audit (changedAt);
+    "#,
+];
 
 impl TimingsMutations for SqliteConnection {
     async fn create_timings_database(&mut self) -> Result<(), Error> {
-        self.execute(CLIENT_SCHEMA).await?;
+        self.migrate_timings_database().await
+    }
+
+    async fn migrate_timings_database(&mut self) -> Result<(), Error> {
+        let version = self.get_schema_version().await?.unwrap_or(0);
+        if version > SCHEMA_VERSION {
+            return Err(Error::UnsupportedSchema(version));
+        }
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let to = index + 1;
+            log::info!("Migrating timings database to version {}", to);
+            let mut tx = self.begin().await?;
+            (&mut *tx).execute(*migration).await?;
+            (&mut *tx)
+                .execute(format!("PRAGMA user_version = {}", to).as_str())
+                .await?;
+            tx.commit().await?;
+        }
         Ok(())
     }
 
+    async fn backup_timings_database(&mut self, path: &Path) -> Result<(), Error> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let mut target = <SqliteConnection as sqlx::Connection>::connect_with(&options).await?;
+        {
+            let mut source_handle = self.lock_handle().await?;
+            let mut target_handle = target.lock_handle().await?;
+            online_backup(source_handle.as_raw_handle(), target_handle.as_raw_handle())?;
+        }
+        <SqliteConnection as sqlx::Connection>::close(target).await?;
+        Ok(())
+    }
+
+    async fn merge_database(&mut self, other: &str) -> Result<MergeStats, Error> {
+        let options = SqliteConnectOptions::from_str(other)?.read_only(true);
+        // Connection is not imported, its `begin` is ambiguous with `Acquire`
+        let mut other_conn = <SqliteConnection as sqlx::Connection>::connect_with(&options).await?;
+        match other_conn.get_schema_version().await? {
+            None => {
+                return Err(Error::InvalidImport(format!(
+                    "{} is not a timings database",
+                    other
+                )));
+            }
+            Some(version) if version != SCHEMA_VERSION => {
+                return Err(Error::UnsupportedSchema(version));
+            }
+            Some(_) => {}
+        }
+        let document = crate::export_json(&mut other_conn, None).await?;
+        sqlx::Connection::close(other_conn).await?;
+        crate::import_json(self, &document).await
+    }
+
     async fn insert_timings(
         &mut self,
         timings: impl IntoIterator<Item = &Timing>,
+    ) -> Result<(), Error> {
+        self.insert_timings_with_source(timings, None).await
+    }
+
+    async fn insert_timings_with_source(
+        &mut self,
+        timings: impl IntoIterator<Item = &Timing>,
+        source: Option<&str>,
     ) -> Result<(), Error> {
         let mut tx = self.begin().await?;
         for timing in timings {
+            if timing.end <= timing.start {
+                return Err(Error::InvalidTiming(format!(
+                    "end {:?} is not after start {:?}",
+                    timing.end, timing.start
+                )));
+            }
+
             // Get or create the client id from the client name
             let client_id = get_or_create_client_id(&mut tx, &timing.client).await?;
 
@@ -150,15 +861,18 @@ impl TimingsMutations for SqliteConnection {
             // Using UPSERT to update end time if the timing already exists
             sqlx::query(
                 r#"
-                    INSERT INTO timing (start, [end], projectId) 
-                    VALUES (?, ?, ?)
+                    INSERT INTO timing (start, [end], projectId, source) 
+                    VALUES (?, ?, ?, ?)
                     ON CONFLICT (projectId, start) 
-                    DO UPDATE SET [end] = excluded.[end]
+                    DO UPDATE SET 
+                        [end] = excluded.[end],
+                        source = COALESCE(excluded.source, source)
                 "#,
             )
             .bind(start_ms)
             .bind(end_ms)
             .bind(project_id)
+            .bind(source)
             .execute(<&mut SqliteConnection>::from(&mut tx))
             .await?;
         }
@@ -208,7 +922,7 @@ impl TimingsMutations for SqliteConnection {
             // Insert summary using the existing insert_timings_summary
             insert_timings_summary(
                 &mut tx,
-                Summary {
+                StoredSummary {
                     start: start_dt,
                     end: next_day_dt,
                     project: summary.project.clone(),
@@ -223,4 +937,665 @@ impl TimingsMutations for SqliteConnection {
 
         Ok(())
     }
+
+    async fn insert_timings_and_summaries(
+        &mut self,
+        timings: impl IntoIterator<Item = &Timing>,
+        source: Option<&str>,
+        timezone: impl chrono::TimeZone,
+        summaries: impl IntoIterator<Item = &SummaryForDay>,
+    ) -> Result<(), Error> {
+        // Nested transactions of the inserts are savepoints of this one
+        let mut tx = self.begin().await?;
+        tx.insert_timings_daily_summaries(timezone, summaries)
+            .await?;
+        tx.insert_timings_with_source(timings, source).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn insert_stored_summaries(
+        &mut self,
+        summaries: impl IntoIterator<Item = &StoredSummary>,
+    ) -> Result<(), Error> {
+        let mut tx = self.begin().await?;
+        for summary in summaries {
+            insert_timings_summary(&mut tx, summary.clone()).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn insert_timing_app_usage(
+        &mut self,
+        usage: impl IntoIterator<Item = &TimingAppUsage>,
+    ) -> Result<(), Error> {
+        let mut tx = self.begin().await?;
+        upsert_timing_app_usage(&mut tx, usage, "seconds + excluded.seconds").await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn replace_timing_app_usage(
+        &mut self,
+        usage: impl IntoIterator<Item = &TimingAppUsage>,
+    ) -> Result<(), Error> {
+        let mut tx = self.begin().await?;
+        upsert_timing_app_usage(&mut tx, usage, "excluded.seconds").await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn shift_day_attribution(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        old_tz: impl TimeZone,
+        new_tz: impl TimeZone,
+        dry_run: bool,
+    ) -> Result<Vec<ShiftedSummary>, Error> {
+        let mut tx = self.begin().await?;
+        let mut shifted = Vec::new();
+
+        for day in from.iter_days().take_while(|day| *day <= to) {
+            let (old_start, old_end) = local_day_to_utc_bounds(&old_tz, day)?;
+            let (new_start, new_end) = local_day_to_utc_bounds(&new_tz, day)?;
+            if (old_start, old_end) == (new_start, new_end) {
+                continue;
+            }
+            let (old_start_ms, old_end_ms) = (datetime_to_ms(&old_start), datetime_to_ms(&old_end));
+            let (new_start_ms, new_end_ms) = (datetime_to_ms(&new_start), datetime_to_ms(&new_end));
+
+            let rows: Vec<(i64, String, String, String)> = sqlx::query_as(
+                r#"
+                SELECT summary.start, summary.text, client.name, project.name
+                FROM summary, project, client
+                WHERE summary.projectId = project.id
+                    AND project.clientId = client.id
+                    AND summary.start = ?
+                    AND summary.[end] = ?
+                ORDER BY client.name, project.name
+                "#,
+            )
+            .bind(old_start_ms)
+            .bind(old_end_ms)
+            .fetch_all(<&mut SqliteConnection>::from(&mut tx))
+            .await?;
+
+            for (start, summary, client, project) in rows {
+                shifted.push(ShiftedSummary {
+                    day,
+                    client,
+                    project,
+                    summary,
+                    old_start: ms_to_datetime(start)?,
+                    new_start,
+                });
+            }
+
+            if dry_run {
+                continue;
+            }
+
+            // Summaries colliding with one at the new midnights are joined
+            sqlx::query(
+                r#"
+                UPDATE summary AS target
+                SET text = target.text || char(10) || source.text,
+                    archived = target.archived AND source.archived
+                FROM summary AS source
+                WHERE source.start = ?
+                    AND source.[end] = ?
+                    AND target.start = ?
+                    AND target.[end] = ?
+                    AND target.projectId = source.projectId
+                "#,
+            )
+            .bind(old_start_ms)
+            .bind(old_end_ms)
+            .bind(new_start_ms)
+            .bind(new_end_ms)
+            .execute(<&mut SqliteConnection>::from(&mut tx))
+            .await?;
+
+            sqlx::query(
+                r#"
+                DELETE FROM summary
+                WHERE start = ?
+                    AND [end] = ?
+                    AND projectId IN (
+                        SELECT projectId FROM summary WHERE start = ? AND [end] = ?
+                    )
+                "#,
+            )
+            .bind(old_start_ms)
+            .bind(old_end_ms)
+            .bind(new_start_ms)
+            .bind(new_end_ms)
+            .execute(<&mut SqliteConnection>::from(&mut tx))
+            .await?;
+
+            sqlx::query("UPDATE summary SET start = ?, [end] = ? WHERE start = ? AND [end] = ?")
+                .bind(new_start_ms)
+                .bind(new_end_ms)
+                .bind(old_start_ms)
+                .bind(old_end_ms)
+                .execute(<&mut SqliteConnection>::from(&mut tx))
+                .await?;
+        }
+
+        if dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+
+        Ok(shifted)
+    }
+
+    async fn fix_database_problem(&mut self, problem: &DatabaseProblem) -> Result<bool, Error> {
+        if !problem.is_fixable() {
+            return Ok(false);
+        }
+        let mut tx = self.begin().await?;
+        let conn = <&mut SqliteConnection>::from(&mut tx);
+        match problem {
+            DatabaseProblem::OrphanProject { id, .. } => {
+                // Rows referring to the project are deleted first, foreign
+                // keys are enforced
+                sqlx::query(
+                    r#"
+                    DELETE FROM timing_app_usage
+                    WHERE timingId IN (SELECT id FROM timing WHERE projectId = ?)
+                    "#,
+                )
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+                sqlx::query("DELETE FROM timing WHERE projectId = ?")
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+                sqlx::query("DELETE FROM summary WHERE projectId = ?")
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+                sqlx::query("DELETE FROM project WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            DatabaseProblem::OrphanTiming { id } | DatabaseProblem::InvertedTiming { id, .. } => {
+                // Cascade of the application usage needs foreign keys enabled
+                sqlx::query("DELETE FROM timing_app_usage WHERE timingId = ?")
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+                sqlx::query("DELETE FROM timing WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            DatabaseProblem::OrphanSummary { id } => {
+                sqlx::query("DELETE FROM summary WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            DatabaseProblem::OrphanAppUsage { id } => {
+                sqlx::query("DELETE FROM timing_app_usage WHERE id = ?")
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+            DatabaseProblem::Integrity(_) | DatabaseProblem::OverlappingTimings(..) => {}
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn set_project_billable(
+        &mut self,
+        client: &str,
+        project: &str,
+        billable: bool,
+    ) -> Result<(), Error> {
+        let mut tx = self.begin().await?;
+        let client_id = get_or_create_client_id(&mut tx, client).await?;
+        let project_id = get_or_create_project_id(&mut tx, project, client_id).await?;
+        sqlx::query("UPDATE project SET billable = ? WHERE id = ?")
+            .bind(billable)
+            .bind(project_id)
+            .execute(<&mut SqliteConnection>::from(&mut tx))
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn resolve_overlaps(
+        &mut self,
+        strategy: OverlapStrategy,
+        now: DateTime<Utc>,
+    ) -> Result<u64, Error> {
+        let mut tx = self.begin().await?;
+        let mut resolved = 0;
+        // Repairing an overlap changes the others of the same timings, they
+        // are looked up again after each repair
+        while let Some(overlap) = tx.find_overlapping_timings().await?.into_iter().next() {
+            resolve_overlap(&mut tx, &overlap, strategy, now).await?;
+            resolved += 1;
+        }
+        tx.commit().await?;
+        Ok(resolved)
+    }
+
+    async fn compact_timings(
+        &mut self,
+        timezone: impl TimeZone,
+        max_gap: Duration,
+        filters: GetTimingsFilters,
+    ) -> Result<u64, Error> {
+        if filters.limit.is_some() || filters.offset.is_some() {
+            return Err(Error::InvalidTiming(
+                "limit and offset are not supported when compacting".to_string(),
+            ));
+        }
+        let mut tx = self.begin().await?;
+
+        // Every timing of the range, one not matching the filters still keeps
+        // the timings around it apart
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT timing.id, timing.projectId, timing.source, timing.start, timing.[end],
+                IFNULL((1 = 1"#,
+        );
+        push_name_conditions(&mut builder, &filters);
+        builder.push(
+            r#"), 0)
+            FROM timing, project, client
+            WHERE timing.projectId = project.id AND project.clientId = client.id
+            "#,
+        );
+        push_range_conditions(&mut builder, &filters);
+        builder.push(" ORDER BY timing.start, timing.id");
+        let rows: Vec<(i64, i64, Option<String>, i64, i64, bool)> = builder
+            .build_query_as()
+            .fetch_all(<&mut SqliteConnection>::from(&mut tx))
+            .await?;
+
+        let max_gap = max_gap.num_milliseconds();
+        let mut removed = 0;
+        let mut run: Option<CompactRun> = None;
+        for (id, project_id, source, start, end, matches) in rows {
+            let day = ms_to_local_day(&timezone, start)?;
+            // Timing ending at midnight is within the day
+            let end_day = ms_to_local_day(&timezone, (end - 1).max(start))?;
+            let continues = |current: &&mut CompactRun| {
+                matches
+                    && current.project_id == project_id
+                    && current.source == source
+                    && current.day == day
+                    && current.day == end_day
+                    && (0..=max_gap).contains(&(start - current.end))
+            };
+            if let Some(current) = run.as_mut().filter(continues) {
+                current.recorded += end - start;
+                current.end = end;
+                current.merged.push(id);
+                continue;
+            }
+            if let Some(finished) = run.take() {
+                merge_compact_run(&mut tx, &finished).await?;
+                removed += finished.merged.len() as u64;
+            }
+            run = matches.then(|| CompactRun {
+                id,
+                project_id,
+                source,
+                day,
+                start,
+                end,
+                recorded: end - start,
+                merged: Vec::new(),
+            });
+        }
+        if let Some(finished) = run {
+            merge_compact_run(&mut tx, &finished).await?;
+            removed += finished.merged.len() as u64;
+        }
+
+        tx.commit().await?;
+        Ok(removed)
+    }
+
+    async fn update_timing(
+        &mut self,
+        old: &Timing,
+        new: &Timing,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        if new.end <= new.start {
+            return Err(Error::InvalidTiming(format!(
+                "end {:?} is not after start {:?}",
+                new.end, new.start
+            )));
+        }
+        let mut tx = self.begin().await?;
+        let (timing_id, old_end) = find_timing(&mut tx, old).await?;
+        let old = Timing {
+            client: normalize_name(&old.client),
+            project: normalize_name(&old.project),
+            start: old.start,
+            end: old_end,
+        };
+        replace_timing(&mut tx, timing_id, &old, new, now).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn update_timing_by_id(
+        &mut self,
+        id: i64,
+        new: &Timing,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        if new.end <= new.start {
+            return Err(Error::InvalidTiming(format!(
+                "end {:?} is not after start {:?}",
+                new.end, new.start
+            )));
+        }
+        let mut tx = self.begin().await?;
+        let existing: Option<(i64, i64, String, String)> = sqlx::query_as(
+            r#"
+            SELECT timing.start, timing.[end], client.name, project.name
+            FROM timing, project, client
+            WHERE timing.projectId = project.id AND project.clientId = client.id
+                AND timing.id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(<&mut SqliteConnection>::from(&mut tx))
+        .await?;
+        let Some((start, end, client, project)) = existing else {
+            return Err(Error::InvalidTiming(format!("no timing with id {}", id)));
+        };
+        let old = Timing {
+            client,
+            project,
+            start: ms_to_datetime(start)?,
+            end: ms_to_datetime(end)?,
+        };
+        replace_timing(&mut tx, id, &old, new, now).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn insert_manual_timing(
+        &mut self,
+        timing: &Timing,
+        source: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let timing = Timing {
+            client: normalize_name(&timing.client),
+            project: normalize_name(&timing.project),
+            ..timing.clone()
+        };
+        let mut tx = self.begin().await?;
+        // Inserting onto an existing timing of the project updates its end
+        let old = lookup_timing(&mut tx, &timing.client, &timing.project, timing.start)
+            .await?
+            .map(|(_, end)| Timing {
+                end,
+                ..timing.clone()
+            });
+        <&mut SqliteConnection>::from(&mut tx)
+            .insert_timings_with_source([&timing], source)
+            .await?;
+        let change = match old {
+            Some(_) => TimingChange::Update,
+            None => TimingChange::Insert,
+        };
+        insert_timing_audit(&mut tx, change, old.as_ref(), Some(&timing), now).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_timing(&mut self, timing: &Timing, now: DateTime<Utc>) -> Result<(), Error> {
+        if !self
+            .delete_timing_at(&timing.client, &timing.project, timing.start, now)
+            .await?
+        {
+            return Err(Error::InvalidTiming(format!(
+                "no timing of '{}: {}' starting at {:?}",
+                timing.client, timing.project, timing.start
+            )));
+        }
+        Ok(())
+    }
+
+    async fn delete_timing_at(
+        &mut self,
+        client: &str,
+        project: &str,
+        start: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<bool, Error> {
+        let mut tx = self.begin().await?;
+        let Some((timing_id, end)) = lookup_timing(&mut tx, client, project, start).await? else {
+            return Ok(false);
+        };
+        sqlx::query("DELETE FROM timing_app_usage WHERE timingId = ?")
+            .bind(timing_id)
+            .execute(<&mut SqliteConnection>::from(&mut tx))
+            .await?;
+        sqlx::query("DELETE FROM timing WHERE id = ?")
+            .bind(timing_id)
+            .execute(<&mut SqliteConnection>::from(&mut tx))
+            .await?;
+
+        // Client and project are kept, even without timings
+        let old = Timing {
+            client: normalize_name(client),
+            project: normalize_name(project),
+            start,
+            end,
+        };
+        insert_timing_audit(&mut tx, TimingChange::Delete, Some(&old), None, now).await?;
+        tx.commit().await?;
+
+        Ok(true)
+    }
+
+    async fn delete_timings(
+        &mut self,
+        filters: GetTimingsFilters,
+        remove_unused: bool,
+        now: DateTime<Utc>,
+    ) -> Result<u64, Error> {
+        let mut tx = self.begin().await?;
+
+        // Same conditions as in `get_timings`
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT timing.id, timing.start, timing.[end], project.id, client.id,
+                client.name, project.name
+            FROM timing, project, client
+            WHERE timing.projectId = project.id AND project.clientId = client.id
+            "#,
+        );
+        push_name_conditions(&mut builder, &filters);
+        push_range_conditions(&mut builder, &filters);
+        // Ordered like in `get_timings` for the page, negative limit is no
+        // limit
+        builder.push(" ORDER BY timing.start DESC, client.name, project.name LIMIT ");
+        builder.push_bind(filters.limit.map_or(-1, i64::from));
+        builder.push(" OFFSET ");
+        builder.push_bind(i64::from(filters.offset.unwrap_or(0)));
+        let rows: Vec<(i64, i64, i64, i64, i64, String, String)> = builder
+            .build_query_as()
+            .fetch_all(<&mut SqliteConnection>::from(&mut tx))
+            .await?;
+
+        let mut project_ids = Vec::new();
+        let mut client_ids = Vec::new();
+        for (timing_id, start, end, project_id, client_id, client, project) in &rows {
+            sqlx::query("DELETE FROM timing_app_usage WHERE timingId = ?")
+                .bind(timing_id)
+                .execute(<&mut SqliteConnection>::from(&mut tx))
+                .await?;
+            sqlx::query("DELETE FROM timing WHERE id = ?")
+                .bind(timing_id)
+                .execute(<&mut SqliteConnection>::from(&mut tx))
+                .await?;
+            let old = Timing {
+                client: client.clone(),
+                project: project.clone(),
+                start: ms_to_datetime(*start)?,
+                end: ms_to_datetime(*end)?,
+            };
+            insert_timing_audit(&mut tx, TimingChange::Delete, Some(&old), None, now).await?;
+            if !project_ids.contains(project_id) {
+                project_ids.push(*project_id);
+            }
+            if !client_ids.contains(client_id) {
+                client_ids.push(*client_id);
+            }
+        }
+
+        if remove_unused {
+            // Projects with summaries are kept, the summaries refer to them
+            for project_id in &project_ids {
+                sqlx::query(
+                    r#"
+                    DELETE FROM project WHERE id = ?1
+                        AND NOT EXISTS (SELECT 1 FROM timing WHERE projectId = ?1)
+                        AND NOT EXISTS (SELECT 1 FROM summary WHERE projectId = ?1)
+                    "#,
+                )
+                .bind(project_id)
+                .execute(<&mut SqliteConnection>::from(&mut tx))
+                .await?;
+            }
+            for client_id in &client_ids {
+                sqlx::query(
+                    r#"
+                    DELETE FROM client WHERE id = ?1
+                        AND NOT EXISTS (SELECT 1 FROM project WHERE clientId = ?1)
+                    "#,
+                )
+                .bind(client_id)
+                .execute(<&mut SqliteConnection>::from(&mut tx))
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(rows.len() as u64)
+    }
+
+    async fn apply_rename_map(
+        &mut self,
+        client_map: HashMap<String, String>,
+        project_map: HashMap<(String, String), (String, String)>,
+    ) -> Result<(), Error> {
+        let client_renames: Vec<(String, String)> = client_map
+            .iter()
+            .map(|(from, to)| (normalize_name(from), normalize_name(to)))
+            .collect();
+        if let Some((from, to)) = find_rename_chain(&client_renames) {
+            return Err(Error::InvalidRename(format!(
+                "client '{}' is renamed to '{}' which is renamed as well",
+                from, to
+            )));
+        }
+        let project_renames: Vec<((String, String), (String, String))> = project_map
+            .iter()
+            .map(|((from_client, from_project), (to_client, to_project))| {
+                (
+                    (normalize_name(from_client), normalize_name(from_project)),
+                    (normalize_name(to_client), normalize_name(to_project)),
+                )
+            })
+            .collect();
+        if let Some(((from_client, from_project), (to_client, to_project))) =
+            find_rename_chain(&project_renames)
+        {
+            return Err(Error::InvalidRename(format!(
+                "project '{}: {}' is renamed to '{}: {}' which is renamed as well",
+                from_client, from_project, to_client, to_project
+            )));
+        }
+
+        let mut tx = self.begin().await?;
+
+        for (from, to) in client_renames {
+            if from == to {
+                continue;
+            }
+            let existing: Option<(i64,)> = sqlx::query_as("SELECT id FROM client WHERE name = ?")
+                .bind(&from)
+                .fetch_optional(<&mut SqliteConnection>::from(&mut tx))
+                .await?;
+            let Some((from_client_id,)) = existing else {
+                continue;
+            };
+
+            let to_client_id = get_or_create_client_id(&mut tx, &to).await?;
+            let projects: Vec<(i64, String)> =
+                sqlx::query_as("SELECT id, name FROM project WHERE clientId = ?")
+                    .bind(from_client_id)
+                    .fetch_all(<&mut SqliteConnection>::from(&mut tx))
+                    .await?;
+            for (project_id, project_name) in projects {
+                move_project(&mut tx, project_id, to_client_id, &project_name).await?;
+            }
+            delete_client_without_projects(&mut tx, from_client_id).await?;
+        }
+
+        for ((from_client, from_project), (to_client, to_project)) in &project_map {
+            let Some(project_id) = find_project_id(&mut tx, from_client, from_project).await?
+            else {
+                continue;
+            };
+            let from_client_id: (i64,) =
+                sqlx::query_as("SELECT clientId FROM project WHERE id = ?")
+                    .bind(project_id)
+                    .fetch_one(<&mut SqliteConnection>::from(&mut tx))
+                    .await?;
+
+            let to_client_id = get_or_create_client_id(&mut tx, to_client).await?;
+            move_project(&mut tx, project_id, to_client_id, to_project).await?;
+            delete_client_without_projects(&mut tx, from_client_id.0).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn rename_client(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        self.apply_rename_map(
+            HashMap::from([(old.to_string(), new.to_string())]),
+            HashMap::new(),
+        )
+        .await
+    }
+
+    async fn rename_project(&mut self, client: &str, old: &str, new: &str) -> Result<(), Error> {
+        self.apply_rename_map(
+            HashMap::new(),
+            HashMap::from([(
+                (client.to_string(), old.to_string()),
+                (client.to_string(), new.to_string()),
+            )]),
+        )
+        .await
+    }
 }
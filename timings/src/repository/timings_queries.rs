@@ -3,19 +3,43 @@
 //! Not to be used directly, use the traits in `timings.rs` instead.
 
 use super::utils::datetime_to_ms;
+use super::utils::local_dates_to_utc_range;
 use super::utils::ms_to_datetime;
+use super::utils::push_names_in;
+use crate::AppUsageForDay;
+use crate::BillableTotals;
 use crate::DailyTotalSummary;
+use crate::DatabaseProblem;
 use crate::GetTimingsFilters;
+use crate::ImplausibleDay;
+use crate::MonthlyTotalSummary;
+use crate::ReportRow;
+use crate::StoredSummary;
 use crate::SummaryForDay;
 use crate::Timing;
+use crate::TimingAppUsage;
+use crate::TimingAuditEntry;
+use crate::TimingChange;
+use crate::TimingOfDay;
+use crate::TimingOverlap;
+use crate::TimingWithId;
 use crate::TimingsQueries;
+use crate::WeeklyTotalSummary;
 use crate::error::Error;
+use crate::is_tracking_gap;
+use crate::normalize_name;
+use chrono::DateTime;
+use chrono::Duration;
 use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+use chrono::Timelike;
 use chrono::Utc;
 use const_format::str_split;
 use sqlx::Sqlite;
 use sqlx::SqliteConnection;
 use sqlx::query_builder::QueryBuilder;
+use std::collections::HashMap;
 
 // #[derive(Debug, Clone)]
 // struct Summary {
@@ -28,15 +52,305 @@ use sqlx::query_builder::QueryBuilder;
 // }
 
 // Trait implementations for &mut SqliteConnection
+/// Start and exclusive end in milliseconds of each day in the timezone
+fn day_ranges_ms(
+    timezone: &impl chrono::TimeZone,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<(NaiveDate, i64, i64)>, Error> {
+    from.iter_days()
+        .take_while(|day| *day <= to)
+        .map(|day| {
+            let (start, end) = local_dates_to_utc_range(timezone, day, day)?;
+            Ok((
+                day,
+                datetime_to_ms(&start),
+                datetime_to_ms(&(end + Duration::seconds(1))),
+            ))
+        })
+        .collect()
+}
+
+/// Starts the query with the `days (day, dayStart, dayEnd)` table
+fn with_days(days: &[(NaiveDate, i64, i64)]) -> QueryBuilder<'static, Sqlite> {
+    let mut builder = QueryBuilder::<Sqlite>::new("WITH days (day, dayStart, dayEnd) AS (");
+    builder.push_values(days, |mut row, (day, start, end)| {
+        row.push_bind(day.to_string())
+            .push_bind(*start)
+            .push_bind(*end);
+    });
+    builder.push(")");
+    builder
+}
+
+#[derive(sqlx::FromRow)]
+struct PeriodTotalRow {
+    period: String,
+    hours: f64,
+    client: String,
+    project: String,
+}
+
+/// Hours per project and period of the local days, newest period first.
+///
+/// The period of a day is formatted with `period_format`, which must sort
+/// chronologically, e.g. `%Y-%m`. Timings are split between the days like in
+/// `get_timings_daily_totals`.
+async fn period_totals(
+    conn: &mut SqliteConnection,
+    timezone: &impl chrono::TimeZone,
+    from: NaiveDate,
+    to: NaiveDate,
+    client: Option<String>,
+    project: Option<String>,
+    period_format: &str,
+) -> Result<Vec<PeriodTotalRow>, Error> {
+    let days = day_ranges_ms(timezone, from, to)?;
+    if days.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Periods are formatted here, SQLite's `strftime` has no ISO week year
+    // in older versions
+    let mut builder = QueryBuilder::<Sqlite>::new("WITH days (day, dayStart, dayEnd, period) AS (");
+    builder.push_values(&days, |mut row, (day, start, end)| {
+        row.push_bind(day.to_string())
+            .push_bind(*start)
+            .push_bind(*end)
+            .push_bind(day.format(period_format).to_string());
+    });
+    builder.push(")");
+    builder.push(
+        r#"
+        SELECT days.period AS period,
+            CAST (SUM(MIN(timing.[end], days.dayEnd) - MAX(timing.start, days.dayStart)) AS REAL)
+                / 3600000 AS hours,
+            client.name AS client,
+            project.name AS project
+        FROM days, timing, project, client
+        WHERE timing.projectId = project.id
+            AND project.clientId = client.id
+            AND timing.start < days.dayEnd
+            AND timing.[end] > days.dayStart
+        "#,
+    );
+
+    if let Some(client_filter) = client {
+        builder.push(" AND client.name LIKE ");
+        builder.push_bind(client_filter);
+    }
+
+    if let Some(project_filter) = project {
+        builder.push(" AND project.name LIKE ");
+        builder.push_bind(project_filter);
+    }
+
+    builder.push(
+        " GROUP BY timing.projectId, days.period ORDER BY days.period DESC, client.name, \
+         project.name",
+    );
+
+    Ok(builder.build_query_as().fetch_all(conn).await?)
+}
+
+/// Hours per day and project, the client and project conditions are pushed
+/// by `push_names`, see `get_timings_daily_totals`
+async fn daily_totals(
+    conn: &mut SqliteConnection,
+    timezone: &impl chrono::TimeZone,
+    from: NaiveDate,
+    to: NaiveDate,
+    push_names: impl FnOnce(&mut QueryBuilder<'static, Sqlite>),
+) -> Result<Vec<DailyTotalSummary>, Error> {
+    let days = day_ranges_ms(timezone, from, to)?;
+    if days.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Timings are clipped to the days they overlap, so a timing over
+    // midnight is split between the days
+    let mut builder = with_days(&days);
+    builder.push(
+        r#"
+        SELECT days.day AS day,
+            CAST (SUM(MIN(timing.[end], days.dayEnd) - MAX(timing.start, days.dayStart)) AS REAL)
+                / 3600000 AS hours,
+            client.name AS client,
+            project.name AS project
+        FROM days, timing, project, client
+        WHERE timing.projectId = project.id
+            AND project.clientId = client.id
+            AND timing.start < days.dayEnd
+            AND timing.[end] > days.dayStart
+        "#,
+    );
+
+    push_names(&mut builder);
+
+    builder.push(
+        " GROUP BY timing.projectId, days.day ORDER BY days.day DESC, client.name, project.name",
+    );
+
+    #[derive(sqlx::FromRow)]
+    struct DailyTotalRow {
+        day: String,
+        hours: f64,
+        client: String,
+        project: String,
+    }
+
+    let rows: Vec<DailyTotalRow> = builder.build_query_as().fetch_all(conn).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| -> Option<DailyTotalSummary> {
+            let day = NaiveDate::parse_from_str(&row.day, "%Y-%m-%d").ok()?;
+            // Parse day string "YYYY-MM-DD" to DateTime<Utc> at midnight UTC
+            Some(DailyTotalSummary {
+                day: day,
+                hours: row.hours,
+                client: row.client,
+                project: row.project,
+            })
+        })
+        .flatten()
+        .collect())
+}
+
+/// Summaries per day and project, the client and project conditions of the
+/// `c` and `p` tables are pushed by `push_names`, see
+/// `get_timings_daily_summaries`
+async fn daily_summaries(
+    conn: &mut SqliteConnection,
+    timezone: &impl chrono::TimeZone,
+    from: NaiveDate,
+    to: NaiveDate,
+    push_names: impl FnOnce(&mut QueryBuilder<'static, Sqlite>),
+) -> Result<Vec<SummaryForDay>, Error> {
+    // Convert NaiveDate to milliseconds timestamps
+    let (from_dt, to_dt) = local_dates_to_utc_range(timezone, from, to)?;
+
+    let from_ms = datetime_to_ms(&from_dt);
+    let to_ms = datetime_to_ms(&to_dt);
+
+    let mut builder = QueryBuilder::<Sqlite>::new(
+        r#"
+        SELECT 
+            s.start, 
+            s.end, 
+            s.text as summary, 
+            c.name as client, 
+            p.name as project, 
+            s.archived 
+        FROM summary as s, client as c, project as p 
+        WHERE p.id = s.projectId AND p.clientId = c.id
+        "#,
+    );
+
+    builder.push(" AND s.start >= ");
+    builder.push_bind(from_ms);
+
+    builder.push(" AND s.start <= ");
+    builder.push_bind(to_ms);
+
+    push_names(&mut builder);
+
+    builder.push(" ORDER BY s.start DESC, c.name, p.name");
+
+    #[derive(sqlx::FromRow)]
+    struct DailySummaryRow {
+        start: i64,
+        // end: i64,
+        summary: String,
+        client: String,
+        project: String,
+        archived: i32,
+    }
+
+    let rows: Vec<DailySummaryRow> = builder.build_query_as().fetch_all(conn).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| -> Option<SummaryForDay> {
+            // Convert UTC timestamp to the provided timezone and extract the date
+            let start_dt = ms_to_datetime(row.start).ok()?;
+            let start_in_tz = start_dt.with_timezone(timezone);
+            let day = start_in_tz.naive_local().date();
+
+            Some(SummaryForDay {
+                day,
+                project: row.project,
+                client: row.client,
+                summary: row.summary,
+                archived: row.archived != 0,
+            })
+        })
+        .flatten()
+        .collect())
+}
+
+/// Pushes the conditions of the filters, the query parts have the conditions
+/// at indexes 1 to 5 like in `get_timings_with_ids`, the lists of names are
+/// pushed after them. Names are normalized like when inserting.
+fn push_timings_filters(
+    builder: &mut QueryBuilder<'_, Sqlite>,
+    query_parts: &[&'static str],
+    filters: GetTimingsFilters,
+) {
+    if let Some(client) = filters.client {
+        builder.push(query_parts[1]);
+        builder.push_bind(normalize_name(&client));
+    }
+
+    if let Some(project) = filters.project {
+        builder.push(query_parts[2]);
+        builder.push_bind(normalize_name(&project));
+    }
+
+    if let Some(from) = filters.from {
+        builder.push(query_parts[3]);
+        builder.push_bind(datetime_to_ms(&from));
+    }
+
+    if let Some(to) = filters.to {
+        builder.push(query_parts[4]);
+        builder.push_bind(datetime_to_ms(&to));
+    }
+
+    if let Some(source) = filters.source {
+        builder.push(query_parts[5]);
+        builder.push_bind(source);
+    }
+
+    let normalize_names = |names: Option<Vec<String>>| -> Option<Vec<String>> {
+        names.map(|names| names.iter().map(|name| normalize_name(name)).collect())
+    };
+    push_names_in(builder, "client.name", normalize_names(filters.clients));
+    push_names_in(builder, "project.name", normalize_names(filters.projects));
+}
+
 impl TimingsQueries for SqliteConnection {
     async fn get_timings(
         &mut self,
         filters: Option<GetTimingsFilters>,
     ) -> Result<Vec<Timing>, Error> {
+        Ok(self
+            .get_timings_with_ids(filters)
+            .await?
+            .into_iter()
+            .map(|row| row.timing)
+            .collect())
+    }
+
+    async fn get_timings_with_ids(
+        &mut self,
+        filters: Option<GetTimingsFilters>,
+    ) -> Result<Vec<TimingWithId>, Error> {
         let filters = filters.unwrap_or_default();
         let query_parts = str_split!(
             r#"
             SELECT
+                timing.id as id,
                 timing.start as start,
                 timing.end as end,
                 project.name as project,
@@ -47,39 +361,27 @@ impl TimingsQueries for SqliteConnection {
             AND project.name = ? -- CONDITIONAL
             AND timing.start >= ? -- CONDITIONAL
             AND timing.start <= ? -- CONDITIONAL
-            ORDER BY timing.start DESC;
+            AND timing.source = ? -- CONDITIONAL
+            ORDER BY timing.start DESC, client.name, project.name
+            LIMIT ? OFFSET ?;
         "#,
             "?"
         );
 
         let mut builder = QueryBuilder::<Sqlite>::new(query_parts[0]);
+        let (limit, offset) = (filters.limit, filters.offset);
+        push_timings_filters(&mut builder, &query_parts, filters);
 
-        if let Some(client) = filters.client {
-            builder.push(query_parts[1]);
-            builder.push_bind(client);
-        }
-
-        if let Some(project) = filters.project.as_deref() {
-            builder.push(query_parts[2]);
-            builder.push_bind(project);
-        }
-
-        if let Some(from) = filters.from {
-            let from_ms = datetime_to_ms(&from);
-            builder.push(query_parts[3]);
-            builder.push_bind(from_ms);
-        }
-
-        if let Some(to) = filters.to {
-            let to_ms = datetime_to_ms(&to);
-            builder.push(query_parts[4]);
-            builder.push_bind(to_ms);
-        }
-
-        builder.push(query_parts[5]);
+        // Negative limit is no limit
+        builder.push(query_parts[6]);
+        builder.push_bind(limit.map_or(-1, i64::from));
+        builder.push(query_parts[7]);
+        builder.push_bind(i64::from(offset.unwrap_or(0)));
+        builder.push(query_parts[8]);
 
         #[derive(sqlx::FromRow)]
         struct TimingRow {
+            id: i64,
             start: i64,
             end: i64,
             project: String,
@@ -90,18 +392,241 @@ impl TimingsQueries for SqliteConnection {
 
         Ok(rows
             .into_iter()
-            .map(|row| -> Option<Timing> {
-                Some(Timing {
-                    start: ms_to_datetime(row.start).ok()?,
-                    end: ms_to_datetime(row.end).ok()?,
-                    project: row.project,
-                    client: row.client,
+            .map(|row| -> Option<TimingWithId> {
+                Some(TimingWithId {
+                    id: row.id,
+                    timing: Timing {
+                        start: ms_to_datetime(row.start).ok()?,
+                        end: ms_to_datetime(row.end).ok()?,
+                        project: row.project,
+                        client: row.client,
+                    },
                 })
             })
             .flatten()
             .collect())
     }
 
+    async fn count_timings(&mut self, filters: Option<GetTimingsFilters>) -> Result<u64, Error> {
+        let query_parts = str_split!(
+            r#"
+            SELECT count(*)
+            FROM timing, project, client
+            WHERE timing.projectId = project.id AND project.clientId = client.id -- ?
+            AND client.name = ? -- CONDITIONAL
+            AND project.name = ? -- CONDITIONAL
+            AND timing.start >= ? -- CONDITIONAL
+            AND timing.start <= ? -- CONDITIONAL
+            AND timing.source = ? -- CONDITIONAL
+            ;
+        "#,
+            "?"
+        );
+
+        let mut builder = QueryBuilder::<Sqlite>::new(query_parts[0]);
+        push_timings_filters(&mut builder, &query_parts, filters.unwrap_or_default());
+        builder.push(query_parts[6]);
+
+        let (count,): (i64,) = builder.build_query_as().fetch_one(self).await?;
+        Ok(count as u64)
+    }
+
+    async fn get_schema_version(&mut self) -> Result<Option<i64>, Error> {
+        let (tables,): (i64,) = sqlx::query_as(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'timing'",
+        )
+        .fetch_one(&mut *self)
+        .await?;
+        if tables == 0 {
+            return Ok(None);
+        }
+        let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+            .fetch_one(&mut *self)
+            .await?;
+        Ok(Some(version))
+    }
+
+    async fn check_timings_database(&mut self) -> Result<(), Error> {
+        let rows: Vec<(String,)> = sqlx::query_as("PRAGMA integrity_check")
+            .fetch_all(&mut *self)
+            .await?;
+        let problems = rows
+            .into_iter()
+            .map(|(row,)| row)
+            .filter(|row| row != "ok")
+            .collect::<Vec<_>>();
+        if !problems.is_empty() {
+            return Err(Error::IntegrityError(problems.join(", ")));
+        }
+        Ok(())
+    }
+
+    async fn find_database_problems(&mut self) -> Result<Vec<DatabaseProblem>, Error> {
+        let mut problems = Vec::new();
+        match self.check_timings_database().await {
+            Err(Error::IntegrityError(msg)) => problems.push(DatabaseProblem::Integrity(msg)),
+            result => result?,
+        }
+
+        let projects: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT id, name FROM project WHERE clientId NOT IN (SELECT id FROM client)",
+        )
+        .fetch_all(&mut *self)
+        .await?;
+        problems.extend(
+            projects
+                .into_iter()
+                .map(|(id, name)| DatabaseProblem::OrphanProject { id, name }),
+        );
+
+        let timings: Vec<(i64,)> =
+            sqlx::query_as("SELECT id FROM timing WHERE projectId NOT IN (SELECT id FROM project)")
+                .fetch_all(&mut *self)
+                .await?;
+        problems.extend(
+            timings
+                .into_iter()
+                .map(|(id,)| DatabaseProblem::OrphanTiming { id }),
+        );
+
+        let summaries: Vec<(i64,)> = sqlx::query_as(
+            "SELECT id FROM summary WHERE projectId NOT IN (SELECT id FROM project)",
+        )
+        .fetch_all(&mut *self)
+        .await?;
+        problems.extend(
+            summaries
+                .into_iter()
+                .map(|(id,)| DatabaseProblem::OrphanSummary { id }),
+        );
+
+        let usages: Vec<(i64,)> = sqlx::query_as(
+            "SELECT id FROM timing_app_usage WHERE timingId NOT IN (SELECT id FROM timing)",
+        )
+        .fetch_all(&mut *self)
+        .await?;
+        problems.extend(
+            usages
+                .into_iter()
+                .map(|(id,)| DatabaseProblem::OrphanAppUsage { id }),
+        );
+
+        let inverted: Vec<(i64, i64, i64)> = sqlx::query_as(
+            "SELECT id, start, [end] FROM timing WHERE [end] < start ORDER BY start",
+        )
+        .fetch_all(&mut *self)
+        .await?;
+        for (id, start, end) in inverted {
+            problems.push(DatabaseProblem::InvertedTiming {
+                id,
+                start: ms_to_datetime(start)?,
+                end: ms_to_datetime(end)?,
+            });
+        }
+
+        // Later timing starting within the earlier one, the range on start
+        // uses the index
+        #[derive(sqlx::FromRow)]
+        struct OverlapRow {
+            a_start: i64,
+            a_end: i64,
+            a_client: String,
+            a_project: String,
+            b_start: i64,
+            b_end: i64,
+            b_client: String,
+            b_project: String,
+        }
+        let overlaps: Vec<OverlapRow> = sqlx::query_as(
+            r#"
+            SELECT a.start AS a_start, a.[end] AS a_end,
+                a_client.name AS a_client, a_project.name AS a_project,
+                b.start AS b_start, b.[end] AS b_end,
+                b_client.name AS b_client, b_project.name AS b_project
+            FROM timing AS a, timing AS b,
+                project AS a_project, client AS a_client,
+                project AS b_project, client AS b_client
+            WHERE b.start >= a.start
+                AND b.start < a.[end]
+                AND (b.start > a.start OR b.id > a.id)
+                AND a_project.id = a.projectId AND a_client.id = a_project.clientId
+                AND b_project.id = b.projectId AND b_client.id = b_project.clientId
+            ORDER BY a.start, b.start, a_client.name, a_project.name
+            "#,
+        )
+        .fetch_all(&mut *self)
+        .await?;
+        for row in overlaps {
+            problems.push(DatabaseProblem::OverlappingTimings(
+                Timing {
+                    client: row.a_client,
+                    project: row.a_project,
+                    start: ms_to_datetime(row.a_start)?,
+                    end: ms_to_datetime(row.a_end)?,
+                },
+                Timing {
+                    client: row.b_client,
+                    project: row.b_project,
+                    start: ms_to_datetime(row.b_start)?,
+                    end: ms_to_datetime(row.b_end)?,
+                },
+            ));
+        }
+
+        Ok(problems)
+    }
+
+    async fn find_overlapping_timings(&mut self) -> Result<Vec<TimingOverlap>, Error> {
+        // Like the overlaps of `find_database_problems` within a project
+        #[derive(sqlx::FromRow)]
+        struct OverlapRow {
+            a_id: i64,
+            a_start: i64,
+            a_end: i64,
+            b_id: i64,
+            b_start: i64,
+            b_end: i64,
+            client: String,
+            project: String,
+        }
+        let rows: Vec<OverlapRow> = sqlx::query_as(
+            r#"
+            SELECT a.id AS a_id, a.start AS a_start, a.[end] AS a_end,
+                b.id AS b_id, b.start AS b_start, b.[end] AS b_end,
+                client.name AS client, project.name AS project
+            FROM timing AS a, timing AS b, project, client
+            WHERE b.projectId = a.projectId
+                AND b.start >= a.start
+                AND b.start < a.[end]
+                AND (b.start > a.start OR b.id > a.id)
+                AND project.id = a.projectId AND client.id = project.clientId
+            ORDER BY a.start, b.start, client.name, project.name
+            "#,
+        )
+        .fetch_all(&mut *self)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let timing = |id, start, end| -> Result<TimingWithId, Error> {
+                    Ok(TimingWithId {
+                        id,
+                        timing: Timing {
+                            client: row.client.clone(),
+                            project: row.project.clone(),
+                            start: ms_to_datetime(start)?,
+                            end: ms_to_datetime(end)?,
+                        },
+                    })
+                };
+                Ok(TimingOverlap {
+                    first: timing(row.a_id, row.a_start, row.a_end)?,
+                    second: timing(row.b_id, row.b_start, row.b_end)?,
+                })
+            })
+            .collect()
+    }
+
     async fn get_timings_daily_totals(
         &mut self,
         timezone: impl chrono::TimeZone,
@@ -110,90 +635,591 @@ impl TimingsQueries for SqliteConnection {
         client: Option<String>,
         project: Option<String>,
     ) -> Result<Vec<DailyTotalSummary>, Error> {
-        // Convert NaiveDate to milliseconds timestamps
-        let from_dt = timezone
-            .from_local_datetime(&from.and_hms_opt(0, 0, 0).ok_or_else(|| {
-                Error::ChronoError("Failed to create time at midnight for from date".to_string())
-            })?)
-            .single()
-            .map(|dt| dt.with_timezone(&Utc))
-            .ok_or_else(|| Error::ChronoError("Failed to convert from date to UTC".to_string()))?;
-
-        let to_dt = timezone
-            .from_local_datetime(&to.and_hms_opt(23, 59, 59).ok_or_else(|| {
-                Error::ChronoError("Failed to create time at end of day for to date".to_string())
-            })?)
-            .single()
-            .map(|dt| dt.with_timezone(&Utc))
-            .ok_or_else(|| Error::ChronoError("Failed to convert to date to UTC".to_string()))?;
-
-        let from_ms = datetime_to_ms(&from_dt);
-        let to_ms = datetime_to_ms(&to_dt);
+        daily_totals(self, &timezone, from, to, |builder| {
+            if let Some(client_filter) = client {
+                builder.push(" AND client.name LIKE ");
+                builder.push_bind(client_filter);
+            }
 
-        let query_parts = str_split!(
+            if let Some(project_filter) = project {
+                builder.push(" AND project.name LIKE ");
+                builder.push_bind(project_filter);
+            }
+        })
+        .await
+    }
+
+    async fn get_timings_daily_totals_in(
+        &mut self,
+        timezone: impl chrono::TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+        clients: Option<Vec<String>>,
+        projects: Option<Vec<String>>,
+    ) -> Result<Vec<DailyTotalSummary>, Error> {
+        daily_totals(self, &timezone, from, to, |builder| {
+            push_names_in(builder, "client.name", clients);
+            push_names_in(builder, "project.name", projects);
+        })
+        .await
+    }
+
+    async fn get_timings_weekly_totals(
+        &mut self,
+        timezone: impl chrono::TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+        client: Option<String>,
+        project: Option<String>,
+    ) -> Result<Vec<WeeklyTotalSummary>, Error> {
+        let rows = period_totals(self, &timezone, from, to, client, project, "%G-W%V").await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| WeeklyTotalSummary {
+                year_week: row.period,
+                hours: row.hours,
+                client: row.client,
+                project: row.project,
+            })
+            .collect())
+    }
+
+    async fn get_timings_monthly_totals(
+        &mut self,
+        timezone: impl chrono::TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+        client: Option<String>,
+        project: Option<String>,
+    ) -> Result<Vec<MonthlyTotalSummary>, Error> {
+        let rows = period_totals(self, &timezone, from, to, client, project, "%Y-%m").await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let (year, month) = row.period.split_once('-')?;
+                Some(MonthlyTotalSummary {
+                    year: year.parse().ok()?,
+                    month: month.parse().ok()?,
+                    hours: row.hours,
+                    client: row.client,
+                    project: row.project,
+                })
+            })
+            .collect())
+    }
+
+    async fn get_timings_for_day(
+        &mut self,
+        timezone: impl chrono::TimeZone,
+        day: NaiveDate,
+        client: &str,
+        project: &str,
+    ) -> Result<Vec<TimingOfDay>, Error> {
+        let days = day_ranges_ms(&timezone, day, day)?;
+
+        #[derive(sqlx::FromRow)]
+        struct TimingOfDayRow {
+            start: i64,
+            end: i64,
+            client: String,
+            project: String,
+            ms: i64,
+        }
+
+        // Clipped like in `get_timings_daily_totals`
+        let mut builder = with_days(&days);
+        builder.push(
             r#"
-                SELECT strftime('%Y-%m-%d', CAST (start AS REAL) / 1000, 'unixepoch', 'localtime') AS day,
-                    CAST (SUM([end] - start) AS REAL) / 3600000 AS hours,
-                    client.name AS client,
-                    project.name AS project
+            SELECT timing.start AS start,
+                timing.[end] AS [end],
+                client.name AS client,
+                project.name AS project,
+                MIN(timing.[end], days.dayEnd) - MAX(timing.start, days.dayStart) AS ms
+            FROM days, timing, project, client
+            WHERE timing.projectId = project.id
+                AND project.clientId = client.id
+                AND timing.start < days.dayEnd
+                AND timing.[end] > days.dayStart
+                AND client.name = "#,
+        );
+        builder.push_bind(normalize_name(client));
+        builder.push(" AND project.name = ");
+        builder.push_bind(normalize_name(project));
+        builder.push(" ORDER BY timing.start DESC, client.name, project.name");
+
+        let rows: Vec<TimingOfDayRow> = builder.build_query_as().fetch_all(self).await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(TimingOfDay {
+                    timing: Timing {
+                        client: row.client,
+                        project: row.project,
+                        start: ms_to_datetime(row.start)?,
+                        end: ms_to_datetime(row.end)?,
+                    },
+                    duration: Duration::milliseconds(row.ms),
+                })
+            })
+            .collect()
+    }
+
+    async fn get_projects_by_total(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        timezone: impl chrono::TimeZone,
+        limit: u32,
+    ) -> Result<Vec<(String, String, f64)>, Error> {
+        let (from_dt, to_dt) = local_dates_to_utc_range(&timezone, from, to)?;
+
+        let rows: Vec<(String, String, f64)> = sqlx::query_as(
+            r#"
+                SELECT client.name AS client,
+                    project.name AS project,
+                    CAST (SUM([end] - start) AS REAL) / 3600000 AS hours
                 FROM timing,
                     project,
                     client
-                WHERE 1=1        
+                WHERE 1=1
                     AND timing.projectId = project.id
                     AND project.clientId = client.id
                     AND timing.start >= ?
                     AND timing.start <= ?
-                    AND client.name LIKE ? -- CONDITIONAL
-                    AND project.name LIKE ? -- CONDITIONAL
-                GROUP BY timing.projectId, day
-                ORDER BY start DESC
-        "#,
-            "?"
-        );
+                GROUP BY timing.projectId
+                ORDER BY hours DESC, client.name, project.name
+                LIMIT ?
+            "#,
+        )
+        .bind(datetime_to_ms(&from_dt))
+        .bind(datetime_to_ms(&to_dt))
+        .bind(limit)
+        .fetch_all(self)
+        .await?;
 
-        let mut builder = QueryBuilder::<Sqlite>::new(query_parts[0]);
-        builder.push_bind(from_ms);
+        Ok(rows)
+    }
 
-        builder.push(query_parts[1]);
-        builder.push_bind(to_ms);
+    async fn get_implausible_days(
+        &mut self,
+        timezone: impl chrono::TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+        max_hours: f64,
+    ) -> Result<Vec<ImplausibleDay>, Error> {
+        let days = day_ranges_ms(&timezone, from, to)?;
+        if days.is_empty() {
+            return Ok(Vec::new());
+        }
 
-        if let Some(client_filter) = client {
-            builder.push(query_parts[2]);
-            builder.push_bind(client_filter);
+        #[derive(sqlx::FromRow)]
+        struct ImplausibleRow {
+            day: String,
+            hours: f64,
+            start: i64,
+            end: i64,
+            client: String,
+            project: String,
         }
 
-        if let Some(project_filter) = project {
-            builder.push(query_parts[3]);
-            builder.push_bind(project_filter);
+        // Day totals are summed over the timings with a window function, so
+        // the timings of the implausible days come in the same query
+        let mut builder = with_days(&days);
+        builder.push(
+            r#"
+            SELECT day, hours, start, [end], client, project
+            FROM (
+                SELECT days.day AS day,
+                    CAST (SUM(timing.[end] - timing.start) OVER (PARTITION BY days.day) AS REAL)
+                        / 3600000 AS hours,
+                    timing.start AS start,
+                    timing.[end] AS [end],
+                    client.name AS client,
+                    project.name AS project
+                FROM days, timing, project, client
+                WHERE timing.start >= days.dayStart
+                    AND timing.start < days.dayEnd
+                    AND timing.projectId = project.id
+                    AND project.clientId = client.id
+            )
+            WHERE hours > "#,
+        );
+        builder.push_bind(max_hours);
+        builder.push(" ORDER BY day, start, client, project");
+
+        let rows: Vec<ImplausibleRow> = builder.build_query_as().fetch_all(self).await?;
+
+        let mut implausible: Vec<ImplausibleDay> = Vec::new();
+        for row in rows {
+            let day = NaiveDate::parse_from_str(&row.day, "%Y-%m-%d")
+                .map_err(|e| Error::ChronoError(format!("Invalid day {}: {}", row.day, e)))?;
+            let timing = Timing {
+                client: row.client,
+                project: row.project,
+                start: ms_to_datetime(row.start)?,
+                end: ms_to_datetime(row.end)?,
+            };
+            match implausible.last_mut() {
+                Some(last) if last.day == day => last.timings.push(timing),
+                _ => implausible.push(ImplausibleDay {
+                    day,
+                    hours: row.hours,
+                    timings: vec![timing],
+                }),
+            }
         }
 
-        builder.push(query_parts[4]);
+        Ok(implausible)
+    }
+
+    async fn get_timings_spanning_midnights(
+        &mut self,
+        timezone: impl chrono::TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<Timing>, Error> {
+        if from > to {
+            return Ok(Vec::new());
+        }
+        let (from_dt, to_dt) = local_dates_to_utc_range(&timezone, from, to)?;
+        // Timing started on the last day spans two midnights if it reaches
+        // the start of the second day after it
+        let last_midnight = to
+            .checked_add_days(chrono::Days::new(2))
+            .ok_or_else(|| Error::ChronoError(format!("Failed to get two days after {}", to)))?;
+        let days = day_ranges_ms(&timezone, from, last_midnight)?;
+
+        let mut builder = with_days(&days);
+        builder.push(
+            r#"
+            SELECT timing.start, timing.[end], client.name, project.name
+            FROM timing, days, project, client
+            WHERE days.dayStart > timing.start
+                AND days.dayStart < timing.[end]
+                AND timing.projectId = project.id
+                AND project.clientId = client.id
+                AND timing.start >= "#,
+        );
+        builder.push_bind(datetime_to_ms(&from_dt));
+        builder.push(" AND timing.start <= ");
+        builder.push_bind(datetime_to_ms(&to_dt));
+        builder.push(
+            r#"
+            GROUP BY timing.id
+            HAVING COUNT(*) > 1
+            ORDER BY timing.start, client.name, project.name
+            "#,
+        );
+
+        let rows: Vec<(i64, i64, String, String)> =
+            builder.build_query_as().fetch_all(self).await?;
+
+        rows.into_iter()
+            .map(|(start, end, client, project)| {
+                Ok(Timing {
+                    client,
+                    project,
+                    start: ms_to_datetime(start)?,
+                    end: ms_to_datetime(end)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_recent_distinct_summaries(
+        &mut self,
+        client: &str,
+        project: &str,
+        limit: u32,
+    ) -> Result<Vec<String>, Error> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT summary.text
+            FROM summary, project, client
+            WHERE summary.projectId = project.id
+                AND project.clientId = client.id
+                AND client.name = ?
+                AND project.name = ?
+                AND summary.text != ''
+            GROUP BY summary.text
+            ORDER BY MAX(summary.start) DESC, summary.text
+            LIMIT ?
+            "#,
+        )
+        .bind(client)
+        .bind(project)
+        .bind(limit)
+        .fetch_all(self)
+        .await?;
+
+        Ok(rows.into_iter().map(|(text,)| text).collect())
+    }
+
+    async fn get_full_report(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        timezone: impl chrono::TimeZone,
+    ) -> Result<Vec<ReportRow>, Error> {
+        let days = day_ranges_ms(&timezone, from, to)?;
+        if days.is_empty() {
+            return Ok(Vec::new());
+        }
 
         #[derive(sqlx::FromRow)]
-        struct DailyTotalRow {
+        struct FullReportRow {
             day: String,
-            hours: f64,
             client: String,
             project: String,
+            hours: f64,
+            timings: i64,
+            summary: String,
+            archived: i32,
+            billable: i32,
         }
 
-        let rows: Vec<DailyTotalRow> = builder.build_query_as().fetch_all(self).await?;
+        // Totals are grouped before joining the summaries, so the hours are
+        // not multiplied by the summaries of the day. Timings are clipped to
+        // the days like in `get_timings_daily_totals`
+        let mut builder = with_days(&days);
+        builder.push(
+            r#"
+            , totals AS (
+                SELECT days.day AS day,
+                    days.dayStart AS dayStart,
+                    days.dayEnd AS dayEnd,
+                    timing.projectId AS projectId,
+                    SUM(MIN(timing.[end], days.dayEnd) - MAX(timing.start, days.dayStart)) AS ms,
+                    COUNT(*) AS timings
+                FROM days, timing
+                WHERE timing.start < days.dayEnd
+                    AND timing.[end] > days.dayStart
+                GROUP BY days.day, timing.projectId
+            )
+            SELECT totals.day AS day,
+                client.name AS client,
+                project.name AS project,
+                CAST (totals.ms AS REAL) / 3600000 AS hours,
+                totals.timings AS timings,
+                COALESCE(MAX(summary.text), '') AS summary,
+                COALESCE(MAX(summary.archived), 0) AS archived,
+                project.billable AS billable
+            FROM totals
+            JOIN project ON project.id = totals.projectId
+            JOIN client ON client.id = project.clientId
+            LEFT JOIN summary ON summary.projectId = totals.projectId
+                AND summary.start >= totals.dayStart
+                AND summary.start < totals.dayEnd
+            GROUP BY totals.day, totals.projectId
+            ORDER BY totals.day, client.name, project.name
+            "#,
+        );
 
-        Ok(rows
-            .into_iter()
-            .map(|row| -> Option<DailyTotalSummary> {
-                let day = NaiveDate::parse_from_str(&row.day, "%Y-%m-%d").ok()?;
-                // Parse day string "YYYY-MM-DD" to DateTime<Utc> at midnight UTC
-                Some(DailyTotalSummary {
-                    day: day,
-                    hours: row.hours,
+        let rows: Vec<FullReportRow> = builder.build_query_as().fetch_all(self).await?;
+        rows.into_iter()
+            .map(|row| {
+                Ok(ReportRow {
+                    day: NaiveDate::parse_from_str(&row.day, "%Y-%m-%d").map_err(|e| {
+                        Error::ChronoError(format!("Invalid day {}: {}", row.day, e))
+                    })?,
                     client: row.client,
                     project: row.project,
+                    hours: row.hours,
+                    timings: row.timings as u32,
+                    summary: row.summary,
+                    archived: row.archived != 0,
+                    billable: row.billable != 0,
                 })
             })
-            .flatten()
-            .collect())
+            .collect()
+    }
+
+    async fn get_timing_audit(&mut self, limit: u32) -> Result<Vec<TimingAuditEntry>, Error> {
+        #[derive(sqlx::FromRow)]
+        struct AuditRow {
+            changed_at: i64,
+            change: String,
+            old_client: Option<String>,
+            old_project: Option<String>,
+            old_start: Option<i64>,
+            old_end: Option<i64>,
+            new_client: Option<String>,
+            new_project: Option<String>,
+            new_start: Option<i64>,
+            new_end: Option<i64>,
+        }
+
+        let rows: Vec<AuditRow> = sqlx::query_as(
+            r#"
+            SELECT changedAt AS changed_at,
+                change,
+                oldClient AS old_client,
+                oldProject AS old_project,
+                oldStart AS old_start,
+                oldEnd AS old_end,
+                newClient AS new_client,
+                newProject AS new_project,
+                newStart AS new_start,
+                newEnd AS new_end
+            FROM timing_audit
+            ORDER BY id DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(self)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let timing = |client, project, start, end| -> Result<Option<Timing>, Error> {
+                    match (client, project, start, end) {
+                        (Some(client), Some(project), Some(start), Some(end)) => Ok(Some(Timing {
+                            client,
+                            project,
+                            start: ms_to_datetime(start)?,
+                            end: ms_to_datetime(end)?,
+                        })),
+                        _ => Ok(None),
+                    }
+                };
+                Ok(TimingAuditEntry {
+                    changed_at: ms_to_datetime(row.changed_at)?,
+                    change: TimingChange::parse(&row.change)?,
+                    old: timing(row.old_client, row.old_project, row.old_start, row.old_end)?,
+                    new: timing(row.new_client, row.new_project, row.new_start, row.new_end)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_grand_total(&mut self) -> Result<Duration, Error> {
+        let (total_ms,): (Option<i64>,) = sqlx::query_as("SELECT SUM([end] - start) FROM timing")
+            .fetch_one(self)
+            .await?;
+        Ok(Duration::milliseconds(total_ms.unwrap_or(0)))
+    }
+
+    async fn get_counts(&mut self) -> Result<(u64, u64), Error> {
+        let (clients, projects): (i64, i64) =
+            sqlx::query_as("SELECT (SELECT COUNT(*) FROM client), (SELECT COUNT(*) FROM project)")
+                .fetch_one(self)
+                .await?;
+        Ok((clients as u64, projects as u64))
+    }
+
+    async fn is_project_billable(&mut self, client: &str, project: &str) -> Result<bool, Error> {
+        let billable: Option<(i32,)> = sqlx::query_as(
+            r#"
+            SELECT project.billable
+            FROM project, client
+            WHERE project.clientId = client.id AND client.name = ? AND project.name = ?
+            "#,
+        )
+        .bind(normalize_name(client))
+        .bind(normalize_name(project))
+        .fetch_optional(self)
+        .await?;
+        Ok(billable.is_none_or(|(billable,)| billable != 0))
+    }
+
+    async fn get_billable_totals(
+        &mut self,
+        timezone: impl chrono::TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<BillableTotals, Error> {
+        let days = day_ranges_ms(&timezone, from, to)?;
+        if days.is_empty() {
+            return Ok(BillableTotals::default());
+        }
+
+        let mut builder = with_days(&days);
+        builder.push(
+            r#"
+            SELECT project.billable,
+                CAST (SUM(MIN(timing.[end], days.dayEnd) - MAX(timing.start, days.dayStart)) AS REAL)
+                    / 3600000
+            FROM days, timing, project
+            WHERE timing.projectId = project.id
+                AND timing.start < days.dayEnd
+                AND timing.[end] > days.dayStart
+            GROUP BY project.billable
+            "#,
+        );
+        let rows: Vec<(i32, f64)> = builder.build_query_as().fetch_all(self).await?;
+
+        let mut totals = BillableTotals::default();
+        for (billable, hours) in rows {
+            if billable != 0 {
+                totals.billable += hours;
+            } else {
+                totals.non_billable += hours;
+            }
+        }
+        Ok(totals)
+    }
+
+    async fn get_timing_sources(&mut self) -> Result<Vec<String>, Error> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT DISTINCT source FROM timing WHERE source IS NOT NULL ORDER BY source",
+        )
+        .fetch_all(self)
+        .await?;
+        Ok(rows.into_iter().map(|(source,)| source).collect())
+    }
+
+    async fn get_timing_sources_by_id(
+        &mut self,
+        filters: Option<GetTimingsFilters>,
+    ) -> Result<HashMap<i64, String>, Error> {
+        let query_parts = str_split!(
+            r#"
+            SELECT timing.id, timing.source
+            FROM timing, project, client
+            WHERE timing.projectId = project.id AND project.clientId = client.id -- ?
+            AND client.name = ? -- CONDITIONAL
+            AND project.name = ? -- CONDITIONAL
+            AND timing.start >= ? -- CONDITIONAL
+            AND timing.start <= ? -- CONDITIONAL
+            AND timing.source = ? -- CONDITIONAL
+            AND timing.source IS NOT NULL;
+        "#,
+            "?"
+        );
+
+        let mut builder = QueryBuilder::<Sqlite>::new(query_parts[0]);
+        push_timings_filters(&mut builder, &query_parts, filters.unwrap_or_default());
+        builder.push(query_parts[6]);
+
+        let rows: Vec<(i64, String)> = builder.build_query_as().fetch_all(self).await?;
+        Ok(rows.into_iter().collect())
+    }
+
+    async fn get_stored_summaries(&mut self) -> Result<Vec<StoredSummary>, Error> {
+        let rows: Vec<(i64, i64, String, String, String, i32)> = sqlx::query_as(
+            r#"
+            SELECT summary.start, summary.[end], summary.text, client.name, project.name,
+                summary.archived
+            FROM summary, project, client
+            WHERE summary.projectId = project.id AND project.clientId = client.id
+            ORDER BY summary.start, client.name, project.name
+            "#,
+        )
+        .fetch_all(self)
+        .await?;
+        rows.into_iter()
+            .map(|(start, end, text, client, project, archived)| {
+                Ok(StoredSummary {
+                    start: ms_to_datetime(start)?,
+                    end: ms_to_datetime(end)?,
+                    text,
+                    client,
+                    project,
+                    archived: archived != 0,
+                })
+            })
+            .collect()
     }
 
     async fn get_timings_daily_summaries(
@@ -204,87 +1230,222 @@ impl TimingsQueries for SqliteConnection {
         client: Option<String>,
         project: Option<String>,
     ) -> Result<Vec<SummaryForDay>, Error> {
-        // Convert NaiveDate to milliseconds timestamps
-        let from_dt = timezone
-            .from_local_datetime(&from.and_hms_opt(0, 0, 0).ok_or_else(|| {
-                Error::ChronoError("Failed to create time at midnight for from date".to_string())
-            })?)
-            .single()
-            .map(|dt| dt.with_timezone(&Utc))
-            .ok_or_else(|| Error::ChronoError("Failed to convert from date to UTC".to_string()))?;
-
-        let to_dt = timezone
-            .from_local_datetime(&to.and_hms_opt(23, 59, 59).ok_or_else(|| {
-                Error::ChronoError("Failed to create time at end of day for to date".to_string())
-            })?)
-            .single()
-            .map(|dt| dt.with_timezone(&Utc))
-            .ok_or_else(|| Error::ChronoError("Failed to convert to date to UTC".to_string()))?;
-
-        let from_ms = datetime_to_ms(&from_dt);
-        let to_ms = datetime_to_ms(&to_dt);
-
-        let mut builder = QueryBuilder::<Sqlite>::new(
-            r#"
-            SELECT 
-                s.start, 
-                s.end, 
-                s.text as summary, 
-                c.name as client, 
-                p.name as project, 
-                s.archived 
-            FROM summary as s, client as c, project as p 
-            WHERE p.id = s.projectId AND p.clientId = c.id
-            "#,
-        );
+        daily_summaries(self, &timezone, from, to, |builder| {
+            if let Some(client_filter) = client {
+                builder.push(" AND c.name = ");
+                builder.push_bind(client_filter);
+            }
 
-        builder.push(" AND s.start >= ");
-        builder.push_bind(from_ms);
+            if let Some(project_filter) = project {
+                builder.push(" AND p.name = ");
+                builder.push_bind(project_filter);
+            }
+        })
+        .await
+    }
 
-        builder.push(" AND s.start <= ");
-        builder.push_bind(to_ms);
+    async fn get_timings_daily_summaries_in(
+        &mut self,
+        timezone: impl chrono::TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+        clients: Option<Vec<String>>,
+        projects: Option<Vec<String>>,
+    ) -> Result<Vec<SummaryForDay>, Error> {
+        daily_summaries(self, &timezone, from, to, |builder| {
+            push_names_in(builder, "c.name", clients);
+            push_names_in(builder, "p.name", projects);
+        })
+        .await
+    }
 
-        if let Some(client_filter) = client {
-            builder.push(" AND c.name = ");
-            builder.push_bind(client_filter);
-        }
+    async fn get_timing_app_usage(
+        &mut self,
+        filters: Option<GetTimingsFilters>,
+    ) -> Result<Vec<TimingAppUsage>, Error> {
+        let query_parts = str_split!(
+            r#"
+            SELECT client.name, project.name, timing.start, usage.app, usage.seconds
+            FROM timing_app_usage AS usage, timing, project, client
+            WHERE usage.timingId = timing.id
+            AND timing.projectId = project.id AND project.clientId = client.id -- ?
+            AND client.name = ? -- CONDITIONAL
+            AND project.name = ? -- CONDITIONAL
+            AND timing.start >= ? -- CONDITIONAL
+            AND timing.start <= ? -- CONDITIONAL
+            AND timing.source = ? -- CONDITIONAL
+            ORDER BY timing.start, client.name, project.name, usage.app;
+        "#,
+            "?"
+        );
 
-        if let Some(project_filter) = project {
-            builder.push(" AND p.name = ");
-            builder.push_bind(project_filter);
-        }
+        let mut builder = QueryBuilder::<Sqlite>::new(query_parts[0]);
+        push_timings_filters(&mut builder, &query_parts, filters.unwrap_or_default());
+        builder.push(query_parts[6]);
 
-        builder.push(" ORDER BY s.start DESC");
+        let rows: Vec<(String, String, i64, String, i64)> =
+            builder.build_query_as().fetch_all(self).await?;
+        rows.into_iter()
+            .map(|(client, project, start, app, seconds)| {
+                Ok(TimingAppUsage {
+                    client,
+                    project,
+                    start: ms_to_datetime(start)?,
+                    app,
+                    seconds,
+                })
+            })
+            .collect()
+    }
+
+    async fn get_app_usage_for_day(
+        &mut self,
+        timezone: impl chrono::TimeZone,
+        day: NaiveDate,
+    ) -> Result<Vec<AppUsageForDay>, Error> {
+        let (from_dt, to_dt) = local_dates_to_utc_range(&timezone, day, day)?;
 
         #[derive(sqlx::FromRow)]
-        struct DailySummaryRow {
-            start: i64,
-            // end: i64,
-            summary: String,
+        struct AppUsageRow {
             client: String,
             project: String,
-            archived: i32,
+            app: String,
+            seconds: i64,
         }
 
-        let rows: Vec<DailySummaryRow> = builder.build_query_as().fetch_all(self).await?;
+        let rows: Vec<AppUsageRow> = sqlx::query_as(
+            r#"
+            SELECT client.name AS client,
+                project.name AS project,
+                usage.app AS app,
+                SUM(usage.seconds) AS seconds
+            FROM timing_app_usage AS usage, timing, project, client
+            WHERE usage.timingId = timing.id
+                AND timing.projectId = project.id
+                AND project.clientId = client.id
+                AND timing.start >= ?
+                AND timing.start <= ?
+            GROUP BY client.name, project.name, usage.app
+            ORDER BY client.name, project.name, seconds DESC, usage.app
+            "#,
+        )
+        .bind(datetime_to_ms(&from_dt))
+        .bind(datetime_to_ms(&to_dt))
+        .fetch_all(self)
+        .await?;
 
         Ok(rows
             .into_iter()
-            .map(|row| -> Option<SummaryForDay> {
-                // Convert UTC timestamp to the provided timezone and extract the date
-                let start_dt = ms_to_datetime(row.start).ok()?;
-                let start_in_tz = start_dt.with_timezone(&timezone);
-                let day = start_in_tz.naive_local().date();
-
-                Some(SummaryForDay {
-                    day,
-                    project: row.project,
-                    client: row.client,
-                    summary: row.summary,
-                    archived: row.archived != 0,
-                })
+            .map(|row| AppUsageForDay {
+                day,
+                client: row.client,
+                project: row.project,
+                app: row.app,
+                hours: row.seconds as f64 / 3600.0,
             })
-            .flatten()
             .collect())
     }
+
+    async fn get_hourly_distribution(
+        &mut self,
+        timezone: impl chrono::TimeZone,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<[f64; 24], Error> {
+        let (from_dt, to_dt) = local_dates_to_utc_range(&timezone, from, to)?;
+        // Exclusive end of the range
+        let to_dt = to_dt + Duration::seconds(1);
+
+        let rows: Vec<(i64, i64)> = sqlx::query_as(
+            r#"
+            SELECT start, [end]
+            FROM timing
+            WHERE [end] > ? AND start < ?
+            "#,
+        )
+        .bind(datetime_to_ms(&from_dt))
+        .bind(datetime_to_ms(&to_dt))
+        .fetch_all(self)
+        .await?;
+
+        let mut hours = [0.0; 24];
+        for (start, end) in rows {
+            // Clip the timing to the requested range
+            let start = ms_to_datetime(start)?.max(from_dt);
+            let end = ms_to_datetime(end)?.min(to_dt);
+
+            // Apportion the timing to each hour of the day it spans
+            let mut cursor = start;
+            while cursor < end {
+                let local = cursor.with_timezone(&timezone);
+                let into_hour = Duration::minutes(local.minute() as i64)
+                    + Duration::seconds(local.second() as i64)
+                    + Duration::nanoseconds(local.nanosecond() as i64);
+                let next = (cursor - into_hour + Duration::hours(1)).min(end);
+                hours[local.hour() as usize] +=
+                    (next - cursor).num_milliseconds() as f64 / 3_600_000.0;
+                cursor = next;
+            }
+        }
+
+        Ok(hours)
+    }
+
+    async fn find_tracking_gaps(
+        &mut self,
+        day: NaiveDate,
+        timezone: impl chrono::TimeZone,
+        work_start: NaiveTime,
+        work_end: NaiveTime,
+        min_gap: Duration,
+    ) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>, Error> {
+        let to_utc = |time: NaiveTime| {
+            timezone
+                .from_local_datetime(&day.and_time(time))
+                .single()
+                .map(|dt| dt.with_timezone(&Utc))
+                .ok_or_else(|| {
+                    Error::ChronoError(format!("Failed to convert {} {} to UTC", day, time))
+                })
+        };
+        let window_start = to_utc(work_start)?;
+        let window_end = to_utc(work_end)?;
+        if window_end <= window_start {
+            return Ok(Vec::new());
+        }
+
+        let rows: Vec<(i64, i64)> = sqlx::query_as(
+            r#"
+            SELECT timing.start, timing.[end]
+            FROM timing
+            JOIN project ON timing.projectId = project.id
+            JOIN client ON project.clientId = client.id
+            WHERE timing.[end] > ? AND timing.start < ?
+            ORDER BY timing.start DESC, client.name, project.name
+            "#,
+        )
+        .bind(datetime_to_ms(&window_start))
+        .bind(datetime_to_ms(&window_end))
+        .fetch_all(self)
+        .await?;
+
+        // Sweep through the timings oldest first, overlapping timings extend
+        // the cursor
+        let mut gaps = Vec::new();
+        let mut cursor = window_start;
+        for (start, end) in rows.into_iter().rev() {
+            let start = ms_to_datetime(start)?;
+            let end = ms_to_datetime(end)?;
+            if is_tracking_gap(cursor, start, min_gap) {
+                gaps.push((cursor, start));
+            }
+            cursor = cursor.max(end);
+        }
+        if is_tracking_gap(cursor, window_end, min_gap) {
+            gaps.push((cursor, window_end));
+        }
+        gaps.reverse();
+
+        Ok(gaps)
+    }
 }
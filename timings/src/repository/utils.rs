@@ -1,6 +1,10 @@
 use crate::Error;
 use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::TimeZone;
 use chrono::Utc;
+use sqlx::Sqlite;
+use sqlx::query_builder::QueryBuilder;
 
 pub fn datetime_to_ms(dt: &DateTime<Utc>) -> i64 {
     dt.timestamp() * 1000 + (dt.timestamp_subsec_millis() as i64)
@@ -17,6 +21,78 @@ pub fn ms_to_datetime(ms: i64) -> Result<DateTime<Utc>, Error> {
     })
 }
 
+/// Pushes ` AND column IN (?, ...)` with the names bound. Nothing is pushed
+/// for `None` or no names, an empty list does not filter out everything.
+///
+/// The condition starts on a new line, the previous part may end in a
+/// comment.
+pub fn push_names_in(
+    builder: &mut QueryBuilder<'_, Sqlite>,
+    column: &str,
+    names: Option<Vec<String>>,
+) {
+    let Some(names) = names.filter(|names| !names.is_empty()) else {
+        return;
+    };
+    builder.push("\n AND ").push(column).push(" IN (");
+    let mut separated = builder.separated(", ");
+    for name in names {
+        separated.push_bind(name);
+    }
+    separated.push_unseparated(")");
+}
+
+/// Day of the timestamp in milliseconds in the timezone
+pub fn ms_to_local_day(timezone: &impl TimeZone, ms: i64) -> Result<NaiveDate, Error> {
+    Ok(ms_to_datetime(ms)?.with_timezone(timezone).date_naive())
+}
+
+/// Midnight of the day and of the next day in the timezone, as UTC
+pub fn local_day_to_utc_bounds(
+    timezone: &impl TimeZone,
+    day: NaiveDate,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    let midnight = |day: NaiveDate| {
+        timezone
+            .from_local_datetime(&day.and_time(chrono::NaiveTime::MIN))
+            .single()
+            .map(|dt| dt.with_timezone(&Utc))
+            .ok_or_else(|| {
+                Error::ChronoError(format!("Failed to convert midnight of {} to UTC", day))
+            })
+    };
+    let next_day = day
+        .succ_opt()
+        .ok_or_else(|| Error::ChronoError(format!("Failed to get the day after {}", day)))?;
+    Ok((midnight(day)?, midnight(next_day)?))
+}
+
+/// Converts local dates to a UTC range, from the midnight of `from` to the end
+/// of `to` in the given timezone
+pub fn local_dates_to_utc_range(
+    timezone: &impl TimeZone,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    let from_dt = timezone
+        .from_local_datetime(&from.and_hms_opt(0, 0, 0).ok_or_else(|| {
+            Error::ChronoError("Failed to create time at midnight for from date".to_string())
+        })?)
+        .single()
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| Error::ChronoError("Failed to convert from date to UTC".to_string()))?;
+
+    let to_dt = timezone
+        .from_local_datetime(&to.and_hms_opt(23, 59, 59).ok_or_else(|| {
+            Error::ChronoError("Failed to create time at end of day for to date".to_string())
+        })?)
+        .single()
+        .map(|dt| dt.with_timezone(&Utc))
+        .ok_or_else(|| Error::ChronoError("Failed to convert to date to UTC".to_string()))?;
+
+    Ok((from_dt, to_dt))
+}
+
 // pub fn parse_local_date(date_str: &str) -> Result<DateTime<Local>, Error> {
 //     let naivedate = NaiveDate::parse_from_str(date_str,
 // "%Y-%m-%d").map_err(|e| {         Error::ChronoError(format!("Failed to parse
@@ -1,3 +1,5 @@
+use crate::AppUsageAggregator;
+use crate::DailyTarget;
 use crate::Error;
 use crate::SummaryForDay;
 use crate::Timing;
@@ -6,11 +8,14 @@ use crate::TimingsQueries;
 use crate::Totals;
 use crate::TotalsCache;
 use crate::api::TimingsRecording;
+use crate::normalize_name;
 use chrono::DateTime;
 use chrono::Duration;
 use chrono::Local;
 use chrono::NaiveDate;
 use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
 use sqlx::Pool;
 use sqlx::Sqlite;
 use std::collections::HashMap;
@@ -18,20 +23,66 @@ use std::collections::HashMap;
 // This implementation exists in older TypeScript codebase:
 // https://github.com/Ciantic/winvd-monitoring/blob/b9e27d84a8412b0e97285f0dd869f56a57b3df4b/ui/TimingRecorder.ts#L14
 
-#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+/// Seconds without keep-alive after which the timing is considered stopped
+const KEEP_ALIVE_TIMEOUT_SECONDS: i64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, sqlx::FromRow)]
 pub struct CurrentTiming {
     pub start: DateTime<Utc>,
     pub project: String,
     pub client: String,
 }
 
+/// Running timing persisted across restarts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedTiming {
+    pub client: String,
+    pub project: String,
+    pub start: DateTime<Utc>,
+    pub last_keep_alive: DateTime<Utc>,
+}
+
+/// Unwritten recorder state, persisted periodically for crash recovery
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct RecorderState {
+    unwritten_timings: Vec<Timing>,
+    current_timing: Option<SavedTiming>,
+}
+
+/// Result of `TimingsRecorder::restore_timing`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreOutcome {
+    /// Saved timing continues with its original start
+    Resumed,
+    /// Saved timing was closed at its last keep-alive
+    Closed,
+}
+
 pub struct TimingsRecorder {
     unwritten_timings: Vec<Timing>,
     current_timing: Option<CurrentTiming>,
+    // Client and project of the paused timing, see `pause_timing`
+    paused_timing: Option<(String, String)>,
     last_keep_alive: Option<DateTime<Utc>>,
+    // Latest time seen, used to detect the clock going backwards
+    latest_now: Option<DateTime<Utc>>,
+    // Start of the latest timing segment, used to keep starts unique
+    latest_start: Option<DateTime<Utc>>,
     minimum_timing: Duration,
+    // Session tag written with the timings, e.g. the hostname
+    source: Option<String>,
     totals_cache: TotalsCache,
     summary_cache: HashMap<(NaiveDate, String, String), String>,
+    // Summaries written with the next `write_timings`
+    pending_summaries: Vec<SummaryForDay>,
+    app_usage: AppUsageAggregator,
+    // Non-idle time of the day, regardless of the project
+    presence_today: Option<(NaiveDate, Duration)>,
+    // Last keep-alive or idle event
+    presence_since: Option<DateTime<Utc>>,
+    user_idle: bool,
+    // Last successful write to the database
+    last_write: Option<DateTime<Utc>>,
     running_changed: Option<Box<dyn Fn(bool) + Send + Sync>>,
     pool: Pool<Sqlite>,
 }
@@ -46,10 +97,20 @@ impl TimingsRecorder {
         TimingsRecorder {
             unwritten_timings: Vec::new(),
             current_timing: None,
+            paused_timing: None,
             last_keep_alive: None,
+            latest_now: None,
+            latest_start: None,
             minimum_timing: min,
+            source: None,
             totals_cache: TotalsCache::new(),
             summary_cache: HashMap::new(),
+            pending_summaries: Vec::new(),
+            app_usage: AppUsageAggregator::new(),
+            presence_today: None,
+            presence_since: None,
+            user_idle: false,
+            last_write: None,
             running_changed: None,
             pool,
         }
@@ -70,28 +131,78 @@ impl TimingsRecorder {
         project: &str,
         now: DateTime<Utc>,
     ) -> Result<Totals, Error> {
-        let current_timing_start = if !self.totals_cache.has_cached_totals(client, project) {
+        let client = &normalize_name(client);
+        let project = &normalize_name(project);
+        let current_timing_start = self.current_timing.as_ref().and_then(|ct| {
+            if ct.client == *client && ct.project == *project {
+                Some(ct.start)
+            } else {
+                None
+            }
+        });
+        let mut written_current_timing_start = None;
+        if !self.totals_cache.has_cached_totals(client, project) {
             // Writing timings before getting totals to ensure up-to-date data for uncached
-            // totals. `write_timings` writes the current timing as well thus it should be
-            // empty.
+            // totals. `write_timings` writes the current timing as well if it's long
+            // enough, it must be excluded from the cached totals.
             self.write_timings(now).await?;
-            None
-        } else {
-            self.current_timing.as_ref().and_then(|ct| {
-                if ct.client == client && ct.project == project {
-                    Some(ct.start)
-                } else {
-                    None
-                }
-            })
-        };
+            written_current_timing_start = current_timing_start
+                .filter(|start| now > *start && now - *start >= self.minimum_timing);
+        }
 
         let mut conn = self.pool.acquire().await?;
         self.totals_cache
-            .get_totals(client, project, now, &mut conn, current_timing_start)
+            .get_totals(
+                client,
+                project,
+                now,
+                &mut conn,
+                current_timing_start,
+                written_current_timing_start,
+            )
             .await
     }
 
+    /// Time left today to reach the daily target of the project, including
+    /// the running timing, negative if exceeded.
+    pub async fn remaining_for_project(
+        &mut self,
+        client: &str,
+        project: &str,
+        target: &DailyTarget,
+        now: DateTime<Utc>,
+    ) -> Result<Duration, Error> {
+        let totals = self.get_totals(client, project, now).await?;
+        let today = now.with_timezone(&Local).date_naive();
+        Ok(totals.remaining_for_target(target, today))
+    }
+
+    /// Get totals for a client/project excluding the running timing, e.g. for
+    /// reports.
+    pub async fn get_committed_totals(
+        &mut self,
+        client: &str,
+        project: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Totals, Error> {
+        let totals = self.get_totals(client, project, now).await?;
+        let (client, project) = (normalize_name(client), normalize_name(project));
+        match &self.current_timing {
+            Some(current) if current.client == client && current.project == project => {
+                Ok(totals.without_current_timing(current.start, now))
+            }
+            _ => Ok(totals),
+        }
+    }
+
+    /// Drops cached totals and summaries, e.g. when the system timezone
+    /// changes, as they are bucketed by local date.
+    pub fn invalidate_caches(&mut self) {
+        log::info!("Invalidating cached totals and summaries");
+        self.totals_cache.clear();
+        self.summary_cache.clear();
+    }
+
     pub fn get_summary_if_cached(
         &self,
         day: NaiveDate,
@@ -99,7 +210,7 @@ impl TimingsRecorder {
         project: &str,
     ) -> Option<String> {
         self.summary_cache
-            .get(&(day, client.to_string(), project.to_string()))
+            .get(&(day, normalize_name(client), normalize_name(project)))
             .cloned()
     }
 
@@ -110,7 +221,9 @@ impl TimingsRecorder {
         project: &str,
         now: DateTime<Utc>,
     ) -> Result<String, Error> {
-        if client.trim().is_empty() || project.trim().is_empty() {
+        let client = &normalize_name(client);
+        let project = &normalize_name(project);
+        if client.is_empty() || project.is_empty() {
             return Ok(String::new());
         }
         if let Some(cached) =
@@ -155,45 +268,299 @@ impl TimingsRecorder {
         }
     }
 
-    pub async fn update_summary(
-        &mut self,
-        day: NaiveDate,
-        client: &str,
-        project: &str,
-        summary: &str,
-    ) -> Result<(), Error> {
-        if client.trim().is_empty() || project.trim().is_empty() {
-            return Ok(());
+    /// Queues the summary to be written in the same transaction as the
+    /// timings on the next `write_timings`, the cache is updated immediately.
+    pub fn queue_summary(&mut self, day: NaiveDate, client: &str, project: &str, summary: &str) {
+        let client = normalize_name(client);
+        let project = normalize_name(project);
+        if client.is_empty() || project.is_empty() {
+            return;
         }
-        log::trace!(
-            "Updating summary for day={}, client='{}', project='{}' to '{}'",
+        self.summary_cache
+            .insert((day, client.clone(), project.clone()), summary.to_string());
+
+        // Only the latest edit of the day's summary is written
+        self.pending_summaries.retain(|pending| {
+            (pending.day, &pending.client, &pending.project) != (day, &client, &project)
+        });
+        self.pending_summaries.push(SummaryForDay {
             day,
             client,
             project,
-            summary
-        );
+            summary: summary.to_string(),
+            archived: false,
+        });
+    }
 
-        self.summary_cache.insert(
-            (day, client.to_string(), project.to_string()),
-            summary.to_string(),
-        );
+    /// Counts the running timing towards the given foreground application
+    /// from now on, empty name if no window is active.
+    ///
+    /// The usage is written with the timings, see `TimingAppUsage`.
+    pub fn set_active_app(&mut self, app: &str, now: DateTime<Utc>) {
+        let now = self.monotonic_now(now);
+        self.app_usage.app_activated(app, now);
+    }
 
-        let mut conn = self.pool.acquire().await?;
-        conn.insert_timings_daily_summaries(
-            Local,
-            &[SummaryForDay {
-                day,
-                client: client.to_string(),
-                project: project.to_string(),
-                summary: summary.to_string(),
-                archived: false,
-            }],
-        )
-        .await?;
+    /// Marks the user idle or active, presence does not accrue while idle.
+    pub fn set_user_idle(&mut self, idle: bool, now: DateTime<Utc>) {
+        let now = self.monotonic_now(now);
+        self.accrue_presence(now);
+        self.user_idle = idle;
+    }
+
+    /// Time the user was present (not idle) today, regardless of whether a
+    /// timing was running, e.g. to compare with the tracked time.
+    pub fn presence_today(&self, now: DateTime<Utc>) -> Duration {
+        let today = now.with_timezone(&Local).date_naive();
+        match self.presence_today {
+            Some((day, presence)) if day == today => presence,
+            _ => Duration::zero(),
+        }
+    }
+
+    pub fn current_timing(&self) -> Option<&CurrentTiming> {
+        self.current_timing.as_ref()
+    }
+
+    /// Time the running timing has run at `now`, `None` if not running.
+    ///
+    /// Unlike the keep-alive, this does not change the recorder state.
+    pub fn current_elapsed(&self, now: DateTime<Utc>) -> Option<Duration> {
+        self.current_timing
+            .as_ref()
+            .map(|current| (now - current.start).max(Duration::zero()))
+    }
+
+    /// Tags the timings written from now on with the source, e.g. the
+    /// hostname, `None` writes them untagged
+    pub fn set_source(&mut self, source: Option<String>) {
+        self.source = source.filter(|source| !source.trim().is_empty());
+    }
+
+    /// Source the timings are tagged with, see `set_source`
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// Shortest timing which is recorded, shorter ones are dropped
+    pub fn minimum_timing(&self) -> Duration {
+        self.minimum_timing
+    }
+
+    /// Whether the totals of the client/project are cached, i.e. kept up to
+    /// date without the database
+    pub fn has_cached_totals(&self, client: &str, project: &str) -> bool {
+        self.totals_cache
+            .has_cached_totals(&normalize_name(client), &normalize_name(project))
+    }
+
+    /// Stops the current timing at `idle_start`, deducting the idle time
+    /// recorded since then, e.g. when the user has idled long enough.
+    ///
+    /// Timing started after `idle_start` is dropped. The timing is paused
+    /// like with `pause_timing`.
+    pub fn stop_timing_deducting_idle(&mut self, idle_start: DateTime<Utc>, now: DateTime<Utc>) {
+        let now = self.monotonic_now(now);
+        log::trace!("Stopping timing at {:?}, idle since {:?}", now, idle_start);
+
+        self.keep_alive_timing(now);
+        self.pause_current_timing();
+        if let Some(current) = &self.current_timing {
+            let end = idle_start.clamp(current.start, now);
+            self.finalize_current_timing(end);
+        }
+        self.app_usage.timing_changed(None, now);
+        if let Some(callback) = &self.running_changed {
+            callback(false);
+        }
+    }
+
+    /// Keeps the client and project of the running timing for
+    /// `resume_timing`, an earlier paused timing is kept if not running
+    fn pause_current_timing(&mut self) {
+        if let Some(current) = &self.current_timing {
+            self.paused_timing = Some((current.client.clone(), current.project.clone()));
+        }
+    }
+
+    /// Client and project `resume_timing` would start, `None` if not paused
+    pub fn paused_timing(&self) -> Option<(&str, &str)> {
+        self.paused_timing
+            .as_ref()
+            .map(|(client, project)| (client.as_str(), project.as_str()))
+    }
+
+    /// Time of the last successful `write_timings`, `None` if not written yet
+    pub fn last_write_time(&self) -> Option<DateTime<Utc>> {
+        self.last_write
+    }
+
+    /// Timings the next write at `now` would write, the running timing counts
+    /// only if it meets the minimum
+    pub fn pending_count(&self, now: DateTime<Utc>) -> usize {
+        let running = self.current_timing.as_ref().filter(|current| {
+            let duration = now - current.start;
+            duration > Duration::zero() && duration >= self.minimum_timing
+        });
+        self.unwritten_timings.len() + running.iter().count()
+    }
 
+    /// Returns the running timing to be persisted on shutdown
+    pub fn saved_timing(&self, now: DateTime<Utc>) -> Option<SavedTiming> {
+        self.current_timing.as_ref().map(|current| SavedTiming {
+            client: current.client.clone(),
+            project: current.project.clone(),
+            start: current.start,
+            last_keep_alive: now,
+        })
+    }
+
+    /// Serializes unwritten timings and the running timing, to be restored
+    /// with `restore_state` if the app crashes before writing them.
+    pub fn serialize_state(&self) -> String {
+        let state = RecorderState {
+            unwritten_timings: self.unwritten_timings.clone(),
+            current_timing: self.current_timing.as_ref().map(|current| SavedTiming {
+                client: current.client.clone(),
+                project: current.project.clone(),
+                start: current.start,
+                last_keep_alive: self.last_keep_alive.unwrap_or(current.start),
+            }),
+        };
+        serde_json::to_string(&state).expect("Recorder state is serializable")
+    }
+
+    /// Restores state serialized with `serialize_state`.
+    ///
+    /// Unwritten timings are queued for the next write, and the running timing
+    /// is closed at its last keep-alive. Timings are identified by
+    /// `(project, start)`, so restoring timings which were already written
+    /// does not duplicate them.
+    pub fn restore_state(&mut self, state: &str) -> Result<(), Error> {
+        let state: RecorderState =
+            serde_json::from_str(state).map_err(|e| Error::InvalidState(e.to_string()))?;
+        log::info!(
+            "Restoring {} unwritten timings, running timing {:?}",
+            state.unwritten_timings.len(),
+            state.current_timing
+        );
+        for timing in state.unwritten_timings {
+            self.add_timing(timing);
+        }
+        if let Some(saved) = state.current_timing {
+            self.add_timing(Timing {
+                client: saved.client,
+                project: saved.project,
+                start: saved.start,
+                end: saved.last_keep_alive,
+            });
+        }
         Ok(())
     }
 
+    /// Restores a timing saved on shutdown.
+    ///
+    /// If the saved timing is recent (within the keep-alive timeout) and the
+    /// given client and project (e.g. from the current desktop) match, the
+    /// timing resumes with its original start. Otherwise the saved timing is
+    /// closed at its last keep-alive and a new timing is started.
+    pub fn restore_timing(
+        &mut self,
+        saved: SavedTiming,
+        client: &str,
+        project: &str,
+        now: DateTime<Utc>,
+    ) -> RestoreOutcome {
+        let since_keep_alive = now - saved.last_keep_alive;
+        let recent = since_keep_alive >= Duration::zero()
+            && since_keep_alive.num_seconds() <= KEEP_ALIVE_TIMEOUT_SECONDS;
+        let matching =
+            saved.client == normalize_name(client) && saved.project == normalize_name(project);
+
+        if recent && matching && self.current_timing.is_none() {
+            log::info!("Resuming saved timing {:?}", saved);
+            self.latest_start = Some(saved.start);
+            self.current_timing = Some(CurrentTiming {
+                client: saved.client,
+                project: saved.project,
+                start: saved.start,
+            });
+            self.last_keep_alive = Some(now);
+            self.app_usage
+                .timing_changed(self.current_timing.as_ref(), now);
+            if let Some(callback) = &self.running_changed {
+                callback(true);
+            }
+            return RestoreOutcome::Resumed;
+        }
+
+        log::info!("Closing saved timing {:?} at last keep-alive", saved);
+        self.add_timing(Timing {
+            client: saved.client,
+            project: saved.project,
+            start: saved.start,
+            end: saved.last_keep_alive,
+        });
+        self.start_timing(client.to_string(), project.to_string(), now);
+        RestoreOutcome::Closed
+    }
+
+    /// Clamps `now` so that it never goes backwards.
+    ///
+    /// If the clock steps back (e.g. NTP) the latest seen time is used until
+    /// the clock catches up, so no inverted intervals are produced.
+    fn monotonic_now(&mut self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self.latest_now {
+            Some(latest) if now < latest => {
+                log::warn!(
+                    "Clock went backwards from {:?} to {:?}, using {:?}",
+                    latest,
+                    now,
+                    latest
+                );
+                latest
+            }
+            _ => {
+                self.latest_now = Some(now);
+                now
+            }
+        }
+    }
+
+    /// Start for a new timing segment, nudged by 1ms if it would collide with
+    /// the start of the previous segment.
+    ///
+    /// Timings are identified by `(project, start)` in the database, so a
+    /// colliding start would make the new segment silently extend the previous
+    /// one.
+    fn unique_start(&mut self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let start = match self.latest_start {
+            Some(latest) if now <= latest => {
+                log::warn!("Timing start {:?} collides, nudging by 1ms", now);
+                latest + Duration::milliseconds(1)
+            }
+            _ => now,
+        };
+        self.latest_start = Some(start);
+        start
+    }
+
+    /// Accrues presence since the previous keep-alive or idle event, gaps
+    /// longer than the keep-alive timeout (e.g. sleep) are not presence
+    fn accrue_presence(&mut self, now: DateTime<Utc>) {
+        if let Some(since) = self.presence_since
+            && !self.user_idle
+        {
+            let elapsed = now - since;
+            if elapsed > Duration::zero() && elapsed.num_seconds() <= KEEP_ALIVE_TIMEOUT_SECONDS {
+                let today = now.with_timezone(&Local).date_naive();
+                let presence = self.presence_today(now);
+                self.presence_today = Some((today, presence + elapsed));
+            }
+        }
+        self.presence_since = Some(now);
+    }
+
     fn add_timing(&mut self, timing: Timing) {
         let duration = timing.end - timing.start;
 
@@ -242,8 +609,9 @@ impl TimingsRecording for TimingsRecorder {
     }
 
     fn start_timing(&mut self, client: String, project: String, now: DateTime<Utc>) -> bool {
-        let client = client.trim();
-        let project = project.trim();
+        let now = self.monotonic_now(now);
+        let client = normalize_name(&client);
+        let project = normalize_name(&project);
         log::trace!(
             "Starting timing for client={}, project={} at {:?}",
             client,
@@ -252,7 +620,8 @@ impl TimingsRecording for TimingsRecorder {
         );
 
         self.keep_alive_timing(now);
-        if (client == "") || (project == "") {
+        self.paused_timing = None;
+        if client.is_empty() || project.is_empty() {
             log::warn!(
                 "Client or Project is empty (client='{}', project='{}'), not starting timing",
                 client,
@@ -272,11 +641,14 @@ impl TimingsRecording for TimingsRecorder {
         }
         self.finalize_current_timing(now);
 
+        let start = self.unique_start(now);
         self.current_timing = Some(CurrentTiming {
-            client: client.to_string(),
-            project: project.to_string(),
-            start: now,
+            client,
+            project,
+            start,
         });
+        self.app_usage
+            .timing_changed(self.current_timing.as_ref(), now);
         if let Some(callback) = &self.running_changed {
             callback(true);
         }
@@ -284,20 +656,41 @@ impl TimingsRecording for TimingsRecorder {
     }
 
     fn stop_timing(&mut self, now: DateTime<Utc>) -> () {
+        let now = self.monotonic_now(now);
         log::trace!("Stopping timing at {:?}", now);
 
         self.keep_alive_timing(now);
+        self.paused_timing = None;
         self.finalize_current_timing(now);
+        self.app_usage.timing_changed(None, now);
         if let Some(callback) = &self.running_changed {
             callback(false);
         }
     }
 
+    fn pause_timing(&mut self, now: DateTime<Utc>) -> () {
+        self.pause_current_timing();
+        let paused = self.paused_timing.take();
+        self.stop_timing(now);
+        self.paused_timing = paused;
+    }
+
+    fn resume_timing(&mut self, now: DateTime<Utc>) -> bool {
+        let Some((client, project)) = self.paused_timing.take() else {
+            return false;
+        };
+        log::trace!("Resuming timing for client={}, project={}", client, project);
+        self.start_timing(client, project, now)
+    }
+
     fn keep_alive_timing(&mut self, now: DateTime<Utc>) -> () {
-        if let Some(current) = &mut self.current_timing
-            && let Some(last_keep_alive) = self.last_keep_alive
-            && (now - last_keep_alive).num_seconds() > 60
+        let now = self.monotonic_now(now);
+        if let Some(last_keep_alive) = self.last_keep_alive
+            && (now - last_keep_alive).num_seconds() > KEEP_ALIVE_TIMEOUT_SECONDS
+            && self.current_timing.is_some()
         {
+            let start = self.unique_start(now);
+            let current = self.current_timing.as_mut().unwrap();
             log::warn!(
                 "Keep alive didn't happen in time, last at {:?}, now {:?}",
                 last_keep_alive,
@@ -310,7 +703,9 @@ impl TimingsRecording for TimingsRecorder {
                 start: current.start,
                 end: last_keep_alive,
             };
-            current.start = now;
+            current.start = start;
+            self.app_usage
+                .timing_split(last_keep_alive, Some(&*current), now);
 
             self.add_timing(timing);
         }
@@ -318,29 +713,55 @@ impl TimingsRecording for TimingsRecorder {
         log::trace!("Keep alive at {:?}", now);
 
         self.last_keep_alive = Some(now);
+        self.accrue_presence(now);
     }
 
     async fn write_timings(&mut self, now: DateTime<Utc>) -> Result<(), Error> {
+        let now = self.monotonic_now(now);
         let mut timings_to_write = self.unwritten_timings.clone();
+        let mut current_written = false;
 
         // Include current running timing if it exists and meets minimum duration
         if let Some(current) = &self.current_timing {
             let duration = now - current.start;
 
-            if duration >= self.minimum_timing {
+            if duration <= Duration::zero() {
+                log::warn!(
+                    "Running timing start {:?} is not before {:?}, not writing it",
+                    current.start,
+                    now
+                );
+            } else if duration >= self.minimum_timing {
                 timings_to_write.push(Timing {
                     client: current.client.clone(),
                     project: current.project.clone(),
                     start: current.start,
                     end: now,
                 });
+                current_written = true;
             }
         }
 
         log::trace!("Writing {} timings to database", timings_to_write.len());
         let mut conn = self.pool.acquire().await?;
-        conn.insert_timings(&timings_to_write).await?;
+        conn.insert_timings_and_summaries(
+            &timings_to_write,
+            self.source.as_deref(),
+            Local,
+            &self.pending_summaries,
+        )
+        .await?;
         self.unwritten_timings.clear();
+        self.pending_summaries.clear();
+
+        let app_usage = self.app_usage.take_usage(now, current_written);
+        if !app_usage.is_empty()
+            && let Err(e) = conn.insert_timing_app_usage(&app_usage).await
+        {
+            self.app_usage.restore_usage(app_usage);
+            return Err(e);
+        }
+        self.last_write = Some(now);
         Ok(())
     }
 }
@@ -5,6 +5,8 @@ use chrono::DateTime;
 use chrono::Datelike;
 use chrono::Duration;
 use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::TimeZone;
 use chrono::Utc;
 use sqlx::Sqlite;
 use sqlx::pool::PoolConnection;
@@ -26,16 +28,20 @@ impl DailyTotals {
         self.0.insert(date, duration);
     }
 
+    /// Adds the timing to the local days it overlaps, split at midnight
     pub fn insert_timing(&mut self, start: &DateTime<Utc>, end: &DateTime<Utc>) {
-        let (date, duration) = {
-            let local_start = start.with_timezone(&chrono::Local);
-            let local_end = end.with_timezone(&chrono::Local);
-            let date = local_start.date_naive();
-            let duration = local_end - local_start;
-            (date, duration)
-        };
-        let entry = self.0.entry(date).or_insert_with(|| Duration::zero());
-        *entry = *entry + duration;
+        for (date, duration) in split_by_local_days(&chrono::Local, start, end) {
+            let entry = self.0.entry(date).or_insert_with(|| Duration::zero());
+            *entry = *entry + duration;
+        }
+    }
+
+    pub fn remove_timing(&mut self, start: &DateTime<Utc>, end: &DateTime<Utc>) {
+        for (date, duration) in split_by_local_days(&chrono::Local, start, end) {
+            if let Some(entry) = self.0.get_mut(&date) {
+                *entry = *entry - duration;
+            }
+        }
     }
 
     pub async fn from_database(
@@ -60,7 +66,7 @@ impl DailyTotals {
         for daily_total in daily_totals {
             daily_totals_map.insert(
                 daily_total.day,
-                Duration::milliseconds((daily_total.hours * 3600.0 * 1000.0) as i64),
+                Duration::milliseconds((daily_total.hours * 3600.0 * 1000.0).round() as i64),
             );
         }
         Ok(DailyTotals(daily_totals_map))
@@ -74,6 +80,16 @@ impl DailyTotals {
         daily_totals
     }
 
+    /// Total of the last `days` local days including today, e.g. a rolling
+    /// 30 day total. Only the loaded days count, e.g. `/totals` of the HTTP
+    /// API loads the requested days.
+    pub fn get_rolling_totals(&self, days: u32, now: DateTime<Utc>) -> Duration {
+        let today = now.with_timezone(&chrono::Local).date_naive();
+        (0..days as i64)
+            .filter_map(|days_ago| self.get(&(today - Duration::days(days_ago))))
+            .fold(Duration::zero(), |total, duration| total + *duration)
+    }
+
     pub fn to_totals(&self, now: DateTime<Utc>) -> Totals {
         // Calculate totals for day, this week, last week, and eight weeks
         //
@@ -154,6 +170,63 @@ impl Totals {
             eight_weeks: self.eight_weeks + duration,
         }
     }
+
+    /// Inverse of `with_current_timing`
+    pub fn without_current_timing(&self, start: DateTime<Utc>, now: DateTime<Utc>) -> Totals {
+        let duration = now - start;
+        Totals {
+            today: self.today - duration,
+            this_week: self.this_week - duration,
+            last_week: self.last_week,
+            eight_weeks: self.eight_weeks - duration,
+        }
+    }
+
+    /// How much this week exceeds the weekly target, zero if under
+    pub fn weekly_overtime(&self, target: Duration) -> Duration {
+        (self.this_week - target).max(Duration::zero())
+    }
+
+    /// True if this week exceeds the weekly budget
+    pub fn is_over_budget(&self, budget: Duration) -> bool {
+        self.this_week > budget
+    }
+
+    /// Time left to reach the daily target today, negative if exceeded.
+    ///
+    /// Targets apply on weekdays. With rollover the unmet or exceeded time of
+    /// the earlier days of the week moves to the next day's target, which sums
+    /// up to the targets of the week so far minus this week's total.
+    pub fn remaining_for_target(&self, target: &DailyTarget, today: NaiveDate) -> Duration {
+        let weekday = today.weekday().num_days_from_monday() as i32;
+        let target_today = if weekday < 5 {
+            target.daily
+        } else {
+            Duration::zero()
+        };
+        if !target.rollover {
+            return target_today - self.today;
+        }
+        target.daily * weekday.min(5) + target_today - self.this_week
+    }
+}
+
+/// Used fraction of the weekly budget for a progress bar, clamped to 0..=1.
+/// An empty budget is full as soon as it starts.
+pub fn progress_fraction(totals: &Totals, budget: Duration) -> f32 {
+    if budget <= Duration::zero() {
+        return 1.0;
+    }
+    let fraction = totals.this_week.num_milliseconds() as f64 / budget.num_milliseconds() as f64;
+    fraction.clamp(0.0, 1.0) as f32
+}
+
+/// Hours a project should get per weekday, e.g. 2 hours a day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DailyTarget {
+    pub daily: Duration,
+    /// Carry unmet or exceeded time to the next day within the week
+    pub rollover: bool,
 }
 
 impl Add for Totals {
@@ -169,6 +242,34 @@ impl Add for Totals {
     }
 }
 
+/// Splits the interval into its parts on each local day of the timezone it
+/// overlaps
+pub fn split_by_local_days<Tz: TimeZone>(
+    timezone: &Tz,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Vec<(NaiveDate, Duration)> {
+    let mut parts = Vec::new();
+    let mut part_start = *start;
+    while part_start < *end {
+        let date = part_start.with_timezone(timezone).date_naive();
+        // Next midnight, the rest goes to this day if it does not exist
+        let part_end = date
+            .succ_opt()
+            .and_then(|next| {
+                timezone
+                    .from_local_datetime(&next.and_time(NaiveTime::MIN))
+                    .earliest()
+            })
+            .map(|midnight| midnight.with_timezone(&Utc))
+            .filter(|midnight| *midnight > part_start)
+            .map_or(*end, |midnight| midnight.min(*end));
+        parts.push((date, part_end - part_start));
+        part_start = part_end;
+    }
+    parts
+}
+
 pub(crate) struct TotalsCache {
     // Key: (client, project) -> Daily totals (NaiveDate = Local date)
     totals: HashMap<(String, String), DailyTotals>,
@@ -194,6 +295,11 @@ impl TotalsCache {
         // Do nothing if no existing totals
     }
 
+    /// Drops all cached totals, they are recalculated from the database
+    pub fn clear(&mut self) {
+        self.totals.clear();
+    }
+
     pub fn has_cached_totals(&self, client: &str, project: &str) -> bool {
         self.totals
             .contains_key(&(client.to_string(), project.to_string()))
@@ -206,13 +312,14 @@ impl TotalsCache {
         now: DateTime<Utc>,
         conn: &mut PoolConnection<Sqlite>,
         current_timing_start: Option<DateTime<Utc>>,
+        written_current_timing_start: Option<DateTime<Utc>>,
     ) -> Result<Totals, Error> {
         let totals = match self.totals.get(&(client.to_string(), project.to_string())) {
             // 1. Get cached totals if available
             Some(totals) => totals.to_totals(now),
             // 2. Calculate totals from database, and cache them
             None => {
-                let daily_totals = DailyTotals::from_database(
+                let mut daily_totals = DailyTotals::from_database(
                     conn,
                     client,
                     project,
@@ -221,6 +328,12 @@ impl TotalsCache {
                 )
                 .await?;
 
+                // Running timing written to the database is not committed yet, it's added
+                // below and to the cache when it's finalized
+                if let Some(start) = written_current_timing_start {
+                    daily_totals.remove_timing(&start, &now);
+                }
+
                 let totals = daily_totals.to_totals(now);
 
                 // Cache the daily totals
@@ -0,0 +1,30 @@
+//! Setup shared by the integration tests, each test file uses only a part
+#![allow(dead_code)]
+
+use chrono::TimeZone;
+use chrono::Utc;
+use sqlx::SqlitePool;
+use timings::Timing;
+use timings::TimingsMutations;
+
+/// In-memory database with the current schema
+pub async fn setup_test_db() -> Result<SqlitePool, Box<dyn std::error::Error>> {
+    let pool = SqlitePool::connect("sqlite::memory:").await?;
+    let mut conn = pool.acquire().await?;
+    conn.create_timings_database().await?;
+    Ok(pool)
+}
+
+/// Timing in March 2024, the start and end are `(day, hour, minute)` in UTC
+pub fn timing(client: &str, project: &str, start: (u32, u32, u32), end: (u32, u32, u32)) -> Timing {
+    Timing {
+        client: client.to_string(),
+        project: project.to_string(),
+        start: Utc
+            .with_ymd_and_hms(2024, 3, start.0, start.1, start.2, 0)
+            .unwrap(),
+        end: Utc
+            .with_ymd_and_hms(2024, 3, end.0, end.1, end.2, 0)
+            .unwrap(),
+    }
+}
@@ -0,0 +1,26 @@
+-- Tables of a database created before schema versioning, shared by the
+-- migration tests of timings and timings-app
+CREATE TABLE client (
+    id   INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name TEXT NOT NULL,
+    CONSTRAINT UQ_CLIENT_NAME UNIQUE (name)
+) STRICT;
+
+CREATE TABLE project (
+    id       INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    name     TEXT NOT NULL,
+    clientId INTEGER NOT NULL,
+    CONSTRAINT UQ_CLIENT_PROJECT_NAME UNIQUE (name, clientId)
+) STRICT;
+
+CREATE TABLE timing (
+    id        INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    start     INTEGER NOT NULL,
+    [end]     INTEGER NOT NULL,
+    projectId INTEGER NOT NULL,
+    CONSTRAINT UQ_CLIENT_PROJECT_NAME UNIQUE (projectId, start)
+) STRICT;
+
+INSERT INTO client (id, name) VALUES (1, 'Acme');
+INSERT INTO project (id, name, clientId) VALUES (1, 'Website', 1);
+INSERT INTO timing (start, [end], projectId) VALUES (1709542800000, 1709546400000, 1);
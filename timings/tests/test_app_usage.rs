@@ -0,0 +1,197 @@
+mod common;
+
+use chrono::Duration;
+use chrono::TimeZone;
+use chrono::Utc;
+use common::setup_test_db;
+use timings::AppUsageAggregator;
+use timings::AppUsageForDay;
+use timings::CurrentTiming;
+use timings::TimingAppUsage;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+use timings::TimingsRecorder;
+use timings::TimingsRecording;
+
+fn current_timing(project: &str, start: chrono::DateTime<Utc>) -> CurrentTiming {
+    CurrentTiming {
+        client: "Acme".to_string(),
+        project: project.to_string(),
+        start,
+    }
+}
+
+fn usage(project: &str, start: chrono::DateTime<Utc>, app: &str, seconds: i64) -> TimingAppUsage {
+    TimingAppUsage {
+        client: "Acme".to_string(),
+        project: project.to_string(),
+        start,
+        app: app.to_string(),
+        seconds,
+    }
+}
+
+#[test]
+fn test_aggregates_event_sequence() {
+    let t0 = Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap();
+    let at = |seconds| t0 + Duration::seconds(seconds);
+    let website = current_timing("Website", t0);
+    let backend = current_timing("Backend", at(200));
+
+    let mut aggregator = AppUsageAggregator::new();
+    aggregator.timing_changed(Some(&website), t0);
+    aggregator.app_activated("firefox", t0);
+    aggregator.app_activated("code", at(60));
+    // No window is active
+    aggregator.app_activated("", at(150));
+    aggregator.app_activated("code", at(180));
+    aggregator.timing_changed(Some(&backend), at(200));
+    aggregator.app_activated("firefox", at(230));
+    aggregator.timing_changed(None, at(260));
+    // Not counted without a running timing
+    aggregator.app_activated("code", at(300));
+
+    assert_eq!(
+        aggregator.take_usage(at(400), true),
+        vec![
+            usage("Website", t0, "code", 110),
+            usage("Website", t0, "firefox", 60),
+            usage("Backend", at(200), "code", 30),
+            usage("Backend", at(200), "firefox", 30),
+        ]
+    );
+    assert!(aggregator.take_usage(at(500), true).is_empty());
+}
+
+#[test]
+fn test_running_timing_is_kept_back() {
+    let t0 = Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap();
+    let website = current_timing("Website", t0);
+
+    let mut aggregator = AppUsageAggregator::new();
+    aggregator.timing_changed(Some(&website), t0);
+    aggregator.app_activated("firefox", t0);
+
+    assert!(
+        aggregator
+            .take_usage(t0 + Duration::seconds(2), false)
+            .is_empty()
+    );
+    // Fractions are carried over
+    assert_eq!(
+        aggregator.take_usage(t0 + Duration::milliseconds(3500), true),
+        vec![usage("Website", t0, "firefox", 3)]
+    );
+    assert_eq!(
+        aggregator.take_usage(t0 + Duration::seconds(5), true),
+        vec![usage("Website", t0, "firefox", 2)]
+    );
+}
+
+#[test]
+fn test_restored_usage_is_taken_again() {
+    let t0 = Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap();
+    let at = |seconds| t0 + Duration::seconds(seconds);
+    let website = current_timing("Website", t0);
+
+    let mut aggregator = AppUsageAggregator::new();
+    aggregator.timing_changed(Some(&website), t0);
+    aggregator.app_activated("firefox", t0);
+
+    // Writing the taken usage failed
+    let failed = aggregator.take_usage(at(30), true);
+    assert_eq!(failed, vec![usage("Website", t0, "firefox", 30)]);
+    aggregator.restore_usage(failed);
+
+    assert_eq!(
+        aggregator.take_usage(at(45), true),
+        vec![usage("Website", t0, "firefox", 45)]
+    );
+}
+
+#[test]
+fn test_split_skips_the_gap() {
+    let t0 = Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap();
+    let at = |seconds| t0 + Duration::seconds(seconds);
+    let before = current_timing("Website", t0);
+    let after = current_timing("Website", at(600));
+
+    let mut aggregator = AppUsageAggregator::new();
+    aggregator.timing_changed(Some(&before), t0);
+    aggregator.app_activated("code", t0);
+    // Last keep-alive at 60s, resumed at 600s
+    aggregator.timing_split(at(60), Some(&after), at(600));
+
+    assert_eq!(
+        aggregator.take_usage(at(630), true),
+        vec![
+            usage("Website", t0, "code", 60),
+            usage("Website", at(600), "code", 30),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_recorder_writes_app_usage() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let t0 = Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap();
+    let at = |seconds| t0 + Duration::seconds(seconds);
+
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    recorder.start_timing("Acme".to_string(), "Website".to_string(), t0);
+    recorder.set_active_app("firefox", t0);
+    recorder.keep_alive_timing(at(30));
+    recorder.set_active_app("code", at(45));
+    recorder.keep_alive_timing(at(60));
+    recorder.write_timings(at(90)).await?;
+
+    // Running timing is written again, seconds are added
+    recorder.keep_alive_timing(at(120));
+    recorder.stop_timing(at(135));
+    recorder.write_timings(at(150)).await?;
+
+    let day = t0.date_naive();
+    let usage = conn.get_app_usage_for_day(Utc, day).await?;
+    assert_eq!(
+        usage,
+        vec![
+            AppUsageForDay {
+                day,
+                client: "Acme".to_string(),
+                project: "Website".to_string(),
+                app: "code".to_string(),
+                hours: 90.0 / 3600.0,
+            },
+            AppUsageForDay {
+                day,
+                client: "Acme".to_string(),
+                project: "Website".to_string(),
+                app: "firefox".to_string(),
+                hours: 45.0 / 3600.0,
+            },
+        ]
+    );
+    assert!(
+        conn.get_app_usage_for_day(Utc, day + Duration::days(1))
+            .await?
+            .is_empty()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_usage_without_timing_is_ignored() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let t0 = Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap();
+
+    conn.insert_timing_app_usage(&[usage("Website", t0, "code", 60)])
+        .await?;
+    assert!(
+        conn.get_app_usage_for_day(Utc, t0.date_naive())
+            .await?
+            .is_empty()
+    );
+    Ok(())
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use chrono::NaiveDate;
+use chrono::Utc;
+use common::setup_test_db;
+use common::timing;
+use timings::BillableTotals;
+use timings::Locale;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+use timings::export_all_json;
+use timings::import_all_json;
+use timings::render_markdown_report;
+
+#[tokio::test]
+async fn test_toggled_billable_is_persisted_and_splits_totals()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 12, 0)),
+        timing("Acme", "Internal", (4, 13, 0), (4, 14, 0)),
+    ])
+    .await?;
+
+    // Billable by default, also a project without timings
+    assert!(conn.is_project_billable("Acme", "Internal").await?);
+    assert!(conn.is_project_billable("Acme", "Unknown").await?);
+    assert_eq!(
+        conn.get_billable_totals(Utc, day, day).await?,
+        BillableTotals {
+            billable: 4.0,
+            non_billable: 0.0,
+        }
+    );
+
+    conn.set_project_billable("Acme", "Internal", false).await?;
+    drop(conn);
+    let mut conn = pool.acquire().await?;
+    assert!(!conn.is_project_billable("Acme", "Internal").await?);
+    assert!(conn.is_project_billable("Acme", "Website").await?);
+    assert_eq!(
+        conn.get_billable_totals(Utc, day, day).await?,
+        BillableTotals {
+            billable: 3.0,
+            non_billable: 1.0,
+        }
+    );
+
+    let rows = conn.get_full_report(day, day, Utc).await?;
+    assert_eq!(
+        rows.iter()
+            .map(|row| (row.project.as_str(), row.billable))
+            .collect::<Vec<_>>(),
+        vec![("Internal", false), ("Website", true)]
+    );
+    let report = render_markdown_report("Week", &rows, &Locale::default());
+    assert!(report.contains("Billable: 3.00 h, non-billable: 1.00 h"));
+
+    // Toggled back, and set before the project has timings
+    conn.set_project_billable("Acme", "Internal", true).await?;
+    conn.set_project_billable("Acme", "Support", false).await?;
+    assert!(conn.is_project_billable("Acme", "Internal").await?);
+    assert!(!conn.is_project_billable("Acme", "Support").await?);
+    assert_eq!(
+        conn.get_billable_totals(Utc, day, day).await?.non_billable,
+        0.0
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_billable_is_exported_and_imported() -> Result<(), Box<dyn std::error::Error>> {
+    let source_pool = setup_test_db().await?;
+    let mut source = source_pool.acquire().await?;
+    source
+        .insert_timings(&[
+            timing("Acme", "Website", (4, 9, 0), (4, 12, 0)),
+            timing("Acme", "Internal", (4, 13, 0), (4, 14, 0)),
+        ])
+        .await?;
+    source
+        .set_project_billable("Acme", "Internal", false)
+        .await?;
+    let json = export_all_json(&mut *source).await?;
+
+    let target_pool = setup_test_db().await?;
+    let mut target = target_pool.acquire().await?;
+    import_all_json(&mut target, &json).await?;
+    assert!(!target.is_project_billable("Acme", "Internal").await?);
+    assert!(target.is_project_billable("Acme", "Website").await?);
+    Ok(())
+}
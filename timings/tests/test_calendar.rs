@@ -0,0 +1,118 @@
+use chrono::FixedOffset;
+use chrono::TimeZone;
+use chrono::Utc;
+use timings::Calendar;
+use timings::CalendarEvent;
+use timings::Error;
+
+const SAMPLE_ICS: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Example//Calendar//EN\r
+BEGIN:VEVENT\r
+UID:standup@example.com\r
+DTSTART:20240304T070000Z\r
+DTEND:20240304T071500Z\r
+SUMMARY:Daily standup\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:planning@example.com\r
+DTSTART;TZID=Europe/Helsinki:20240304T130000\r
+DURATION:PT1H30M\r
+SUMMARY:Sprint planning\\, Acme\r
+DESCRIPTION:Long description which is folded over\r
+  two lines\r
+BEGIN:VALARM\r
+TRIGGER:-PT10M\r
+ACTION:DISPLAY\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:review@example.com\r
+DTSTART:20240304T120000Z\r
+DTEND:20240304T123000Z\r
+SUMMARY:Design re\r
+ view\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:offsite@example.com\r
+DTSTART;VALUE=DATE:20240305\r
+SUMMARY:Offsite\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+fn helsinki_winter() -> FixedOffset {
+    FixedOffset::east_opt(2 * 3600).unwrap()
+}
+
+#[test]
+fn test_parse_ics_events() -> Result<(), Box<dyn std::error::Error>> {
+    let calendar = Calendar::parse_ics(SAMPLE_ICS, helsinki_winter())?;
+    let at = |d, h, m| Utc.with_ymd_and_hms(2024, 3, d, h, m, 0).unwrap();
+
+    assert_eq!(
+        calendar.events,
+        vec![
+            CalendarEvent {
+                summary: "Daily standup".to_string(),
+                start: at(4, 7, 0),
+                end: at(4, 7, 15),
+            },
+            // Time with TZID is in the given timezone, the escaped comma is
+            // unescaped
+            CalendarEvent {
+                summary: "Sprint planning, Acme".to_string(),
+                start: at(4, 11, 0),
+                end: at(4, 12, 30),
+            },
+            // Folded summary is joined
+            CalendarEvent {
+                summary: "Design review".to_string(),
+                start: at(4, 12, 0),
+                end: at(4, 12, 30),
+            },
+            // Date lasts the local day
+            CalendarEvent {
+                summary: "Offsite".to_string(),
+                start: at(4, 22, 0),
+                end: at(5, 22, 0),
+            },
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_current_event_covers_the_instant() -> Result<(), Box<dyn std::error::Error>> {
+    let calendar = Calendar::parse_ics(SAMPLE_ICS, helsinki_winter())?;
+    let at = |d, h, m| Utc.with_ymd_and_hms(2024, 3, d, h, m, 0).unwrap();
+    let summary = |d, h, m| {
+        calendar
+            .current_event(at(d, h, m))
+            .map(|event| event.summary.as_str())
+    };
+
+    assert_eq!(summary(4, 6, 59), None);
+    assert_eq!(summary(4, 7, 0), Some("Daily standup"));
+    // End is exclusive
+    assert_eq!(summary(4, 7, 15), None);
+    assert_eq!(summary(4, 11, 45), Some("Sprint planning, Acme"));
+    // Overlapping events, the later started one
+    assert_eq!(summary(4, 12, 10), Some("Design review"));
+    assert_eq!(summary(5, 9, 0), Some("Offsite"));
+    Ok(())
+}
+
+#[test]
+fn test_parse_ics_rejects_invalid_times() {
+    let ics = "BEGIN:VEVENT\nDTSTART:2024-03-04 09:00\nSUMMARY:Broken\nEND:VEVENT\n";
+    assert!(matches!(
+        Calendar::parse_ics(ics, Utc),
+        Err(Error::CalendarError(_))
+    ));
+    let ics = "BEGIN:VEVENT\nSUMMARY:No start\nEND:VEVENT\n";
+    assert!(matches!(
+        Calendar::parse_ics(ics, Utc),
+        Err(Error::CalendarError(_))
+    ));
+}
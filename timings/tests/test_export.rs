@@ -0,0 +1,106 @@
+mod common;
+
+use chrono::FixedOffset;
+use chrono::NaiveDate;
+use chrono::Utc;
+use common::setup_test_db;
+use common::timing;
+use timings::GetTimingsFilters;
+use timings::SummaryForDay;
+use timings::TimingsMutations;
+use timings::export_daily_totals_csv;
+use timings::export_timings_csv;
+
+fn summary(day: u32, text: &str) -> SummaryForDay {
+    SummaryForDay {
+        day: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+        client: "Acme".to_string(),
+        project: "Website".to_string(),
+        summary: text.to_string(),
+        archived: false,
+    }
+}
+
+#[tokio::test]
+async fn test_export_daily_totals_csv() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    conn.insert_timings(&[
+        timing("Acme", "Website", (12, 9, 0), (12, 11, 30)),
+        timing("Acme", "Backend", (12, 12, 0), (12, 12, 20)),
+        timing("Acme", "Website", (13, 9, 0), (13, 10, 0)),
+        // Outside of the range
+        timing("Acme", "Website", (14, 9, 0), (14, 10, 0)),
+    ])
+    .await?;
+    conn.insert_timings_daily_summaries(
+        Utc,
+        &[
+            summary(12, "Layout, styles\n\"final\""),
+            summary(13, "Fonts"),
+        ],
+    )
+    .await?;
+
+    let mut csv = Vec::new();
+    export_daily_totals_csv(
+        &mut *conn,
+        Utc,
+        NaiveDate::from_ymd_opt(2024, 3, 12).unwrap(),
+        NaiveDate::from_ymd_opt(2024, 3, 13).unwrap(),
+        &mut csv,
+    )
+    .await?;
+
+    assert_eq!(
+        String::from_utf8(csv)?,
+        "date,client,project,hours,summary\r\n2024-03-12,Acme,Backend,0.33,\r\n2024-03-12,Acme,\
+         Website,2.50,\"Layout, styles\n\"\"final\"\"\"\r\n2024-03-13,Acme,Website,1.00,Fonts\r\n"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_timings_csv() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    conn.insert_timings(&[
+        timing("Acme", "Website", (13, 9, 0), (13, 10, 0)),
+        timing("Acme", "Website", (12, 9, 0), (12, 11, 30)),
+        timing("Acme", "Website", (12, 14, 0), (12, 14, 30)),
+        // Filtered out
+        timing("Acme", "Backend", (12, 12, 0), (12, 12, 20)),
+    ])
+    .await?;
+    conn.insert_timings_daily_summaries(Utc, &[summary(12, "Layout, styles")])
+        .await?;
+
+    let mut csv = Vec::new();
+    export_timings_csv(
+        &mut *conn,
+        FixedOffset::east_opt(2 * 3600).unwrap(),
+        Some(GetTimingsFilters {
+            project: Some("Website".to_string()),
+            ..Default::default()
+        }),
+        &mut csv,
+    )
+    .await?;
+
+    assert_eq!(
+        String::from_utf8(csv)?,
+        [
+            "start,end,client,project,hours,summary",
+            "2024-03-12T11:00:00+02:00,2024-03-12T13:30:00+02:00,Acme,Website,2.50,\"Layout, \
+             styles\"",
+            // Summary is written once per day
+            "2024-03-12T16:00:00+02:00,2024-03-12T16:30:00+02:00,Acme,Website,0.50,",
+            "2024-03-13T11:00:00+02:00,2024-03-13T12:00:00+02:00,Acme,Website,1.00,",
+            "",
+        ]
+        .join("\r\n")
+    );
+
+    Ok(())
+}
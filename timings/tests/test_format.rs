@@ -0,0 +1,50 @@
+use chrono::Datelike;
+use chrono::Duration;
+use chrono::NaiveDate;
+use timings::Locale;
+
+fn comma_locale() -> Locale {
+    Locale {
+        decimal_separator: ',',
+        date_format: "%d.%m.%Y".to_string(),
+        week_format: "Week %V/%G".to_string(),
+    }
+}
+
+#[test]
+fn test_default_locale() {
+    let locale = Locale::default();
+    assert_eq!(locale.format_hours(3.25), "3.25");
+    assert_eq!(
+        locale.format_duration_hours(&(Duration::hours(1) + Duration::minutes(30))),
+        "1.50"
+    );
+    let day = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+    assert_eq!(locale.format_date(day), "2024-01-15");
+    assert_eq!(locale.format_week(day.iso_week()), "2024-W03");
+}
+
+#[test]
+fn test_comma_locale() {
+    let locale = comma_locale();
+    assert_eq!(locale.format_hours(3.25), "3,25");
+    assert_eq!(
+        locale.format_duration_hours(&(Duration::hours(1) + Duration::minutes(30))),
+        "1,50"
+    );
+    let day = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+    assert_eq!(locale.format_date(day), "15.01.2024");
+    assert_eq!(locale.format_week(day.iso_week()), "Week 03/2024");
+}
+
+#[test]
+fn test_invalid_formats_fall_back_to_iso() {
+    let locale = Locale {
+        date_format: "%H:%M".to_string(),
+        week_format: "%Q".to_string(),
+        ..Locale::default()
+    };
+    let day = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+    assert_eq!(locale.format_date(day), "2024-01-15");
+    assert_eq!(locale.format_week(day.iso_week()), "2024-W03");
+}
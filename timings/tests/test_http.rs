@@ -0,0 +1,238 @@
+#![cfg(feature = "http")]
+
+mod common;
+
+use axum::body::Body;
+use axum::http::Request;
+use axum::http::StatusCode;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use common::setup_test_db;
+use http_body_util::BodyExt;
+use sqlx::SqlitePool;
+use timings::DailyResponse;
+use timings::SummaryForDay;
+use timings::SummaryResponse;
+use timings::Timing;
+use timings::TimingsMutations;
+use timings::TotalsResponse;
+use timings::http_router;
+use tower::ServiceExt;
+
+async fn get(
+    pool: &SqlitePool,
+    token: Option<&str>,
+    uri: &str,
+    authorization: Option<&str>,
+) -> Result<(StatusCode, Vec<u8>), Box<dyn std::error::Error>> {
+    let mut request = Request::builder().uri(uri);
+    if let Some(authorization) = authorization {
+        request = request.header("Authorization", authorization);
+    }
+    let response = http_router(pool.clone(), token.map(str::to_string))
+        .oneshot(request.body(Body::empty())?)
+        .await?;
+    let status = response.status();
+    let body = response.into_body().collect().await?.to_bytes().to_vec();
+    Ok((status, body))
+}
+
+#[tokio::test]
+async fn test_http_daily() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    conn.insert_timings(&[Timing {
+        client: "Acme".to_string(),
+        project: "Website".to_string(),
+        start: Utc.with_ymd_and_hms(2024, 3, 6, 12, 0, 0).unwrap(),
+        end: Utc.with_ymd_and_hms(2024, 3, 6, 13, 30, 0).unwrap(),
+    }])
+    .await?;
+
+    let (status, body) = get(
+        &pool,
+        None,
+        "/daily?from=2024-03-04&to=2024-03-10&client=Acme",
+        None,
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    let daily: Vec<DailyResponse> = serde_json::from_slice(&body)?;
+    assert_eq!(
+        daily,
+        vec![DailyResponse {
+            day: NaiveDate::from_ymd_opt(2024, 3, 6).unwrap(),
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            hours: 1.5,
+        }]
+    );
+
+    let (_, body) = get(
+        &pool,
+        None,
+        "/daily?from=2024-03-04&to=2024-03-10&client=Other",
+        None,
+    )
+    .await?;
+    let daily: Vec<DailyResponse> = serde_json::from_slice(&body)?;
+    assert!(daily.is_empty());
+
+    // Names match exactly after normalizing, like on /summaries
+    let (_, body) = get(
+        &pool,
+        None,
+        "/daily?from=2024-03-04&to=2024-03-10&client=%20Acme&project=Website%20%20",
+        None,
+    )
+    .await?;
+    let daily: Vec<DailyResponse> = serde_json::from_slice(&body)?;
+    assert_eq!(daily.len(), 1);
+    let (_, body) = get(
+        &pool,
+        None,
+        "/daily?from=2024-03-04&to=2024-03-10&client=acme",
+        None,
+    )
+    .await?;
+    let daily: Vec<DailyResponse> = serde_json::from_slice(&body)?;
+    assert!(daily.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_summaries() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    conn.insert_timings(&[Timing {
+        client: "Acme".to_string(),
+        project: "Website".to_string(),
+        start: Utc.with_ymd_and_hms(2024, 3, 6, 12, 0, 0).unwrap(),
+        end: Utc.with_ymd_and_hms(2024, 3, 6, 14, 0, 0).unwrap(),
+    }])
+    .await?;
+    conn.insert_timings_daily_summaries(
+        chrono::Local,
+        &[SummaryForDay {
+            day: NaiveDate::from_ymd_opt(2024, 3, 6).unwrap(),
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            summary: "Layout".to_string(),
+            archived: false,
+        }],
+    )
+    .await?;
+
+    let (status, body) = get(
+        &pool,
+        None,
+        "/summaries?from=2024-03-04&to=2024-03-10",
+        None,
+    )
+    .await?;
+    assert_eq!(status, StatusCode::OK);
+    let summaries: Vec<SummaryResponse> = serde_json::from_slice(&body)?;
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].summary, "Layout");
+    assert_eq!(summaries[0].hours, 2.0);
+
+    let (_, body) = get(
+        &pool,
+        None,
+        "/summaries?from=2024-03-04&to=2024-03-10&client=%20Acme&project=Website%20%20",
+        None,
+    )
+    .await?;
+    let summaries: Vec<SummaryResponse> = serde_json::from_slice(&body)?;
+    assert_eq!(summaries.len(), 1);
+    let (_, body) = get(
+        &pool,
+        None,
+        "/summaries?from=2024-03-04&to=2024-03-10&client=acme",
+        None,
+    )
+    .await?;
+    let summaries: Vec<SummaryResponse> = serde_json::from_slice(&body)?;
+    assert!(summaries.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_totals() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let end = Utc::now() - Duration::days(1);
+    conn.insert_timings(&[Timing {
+        client: "Acme".to_string(),
+        project: "Website".to_string(),
+        start: end - Duration::minutes(45),
+        end,
+    }])
+    .await?;
+
+    let (status, body) = get(&pool, None, "/totals?client=Acme&project=Website", None).await?;
+    assert_eq!(status, StatusCode::OK);
+    let totals: TotalsResponse = serde_json::from_slice(&body)?;
+    assert_eq!(totals.client, "Acme");
+    assert_eq!(totals.eight_weeks_hours, 0.75);
+    assert_eq!(totals.rolling_hours, None);
+
+    let (_, body) = get(
+        &pool,
+        None,
+        "/totals?client=Acme&project=Website&days=30",
+        None,
+    )
+    .await?;
+    let totals: TotalsResponse = serde_json::from_slice(&body)?;
+    assert_eq!(totals.rolling_hours, Some(0.75));
+    let (_, body) = get(
+        &pool,
+        None,
+        "/totals?client=Acme&project=Website&days=1",
+        None,
+    )
+    .await?;
+    let totals: TotalsResponse = serde_json::from_slice(&body)?;
+    assert_eq!(
+        totals.rolling_hours,
+        Some(0.0),
+        "Yesterday is not in the window"
+    );
+
+    let (status, _) = get(&pool, None, "/totals?client=Acme", None).await?;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "Project is required");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_token() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+
+    let (status, _) = get(&pool, Some("secret"), "/daily", None).await?;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = get(&pool, Some("secret"), "/daily", Some("Bearer wrong")).await?;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = get(&pool, Some("secret"), "/daily", Some("Bearer secrex")).await?;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = get(&pool, Some("secret"), "/daily", Some("Bearer secret2")).await?;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = get(&pool, Some("secret"), "/daily", Some("Bearer secret")).await?;
+    assert_eq!(status, StatusCode::OK);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_error_is_generic() -> Result<(), Box<dyn std::error::Error>> {
+    // No tables, the queries fail
+    let pool = SqlitePool::connect("sqlite::memory:").await?;
+
+    let (status, body) = get(&pool, None, "/daily", None).await?;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body, b"Internal server error");
+    Ok(())
+}
@@ -0,0 +1,227 @@
+//! Drives `TimingsRecorder` with random sequences of starts, stops,
+//! keep-alives and writes, and checks the invariants of the persisted timings
+//! and the cached totals after each write.
+//!
+//! The runner is seeded deterministically, a failing sequence fails the same
+//! way on every run.
+
+mod common;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Local;
+use chrono::TimeZone;
+use chrono::Utc;
+use common::setup_test_db;
+use proptest::prelude::*;
+use proptest::test_runner::Config;
+use proptest::test_runner::RngAlgorithm;
+use proptest::test_runner::TestCaseError;
+use proptest::test_runner::TestRng;
+use proptest::test_runner::TestRunner;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use timings::DailyTotals;
+use timings::Timing;
+use timings::TimingsQueries;
+use timings::TimingsRecorder;
+use timings::TimingsRecording;
+use timings::Totals;
+
+const CLIENT: &str = "Acme";
+const PROJECTS: [&str; 3] = ["Website", "Backend", "Support"];
+
+#[derive(Debug, Clone)]
+enum Op {
+    Start(usize),
+    Stop,
+    KeepAlive,
+    /// Write after a keep-alive, the app keeps alive more often than it
+    /// writes
+    Write,
+    /// Totals of the project after a keep-alive, e.g. the overlay is shown
+    Totals(usize),
+}
+
+/// Seconds since the previous step, and the step
+fn steps() -> impl Strategy<Value = Vec<(i64, Op)>> {
+    let advance = prop_oneof![3 => 1i64..=30, 1 => 31i64..=150];
+    let op = prop_oneof![
+        3 => (0..PROJECTS.len()).prop_map(Op::Start),
+        1 => Just(Op::Stop),
+        3 => Just(Op::KeepAlive),
+        2 => Just(Op::Write),
+        1 => (0..PROJECTS.len()).prop_map(Op::Totals),
+    ];
+    prop::collection::vec((advance, op), 1..80)
+}
+
+fn minimum_seconds() -> impl Strategy<Value = i64> {
+    prop_oneof![Just(0i64), 1i64..=90]
+}
+
+fn fail(error: impl std::fmt::Display) -> TestCaseError {
+    TestCaseError::fail(error.to_string())
+}
+
+/// Totals are equal within a millisecond, the totals read from the database
+/// are rounded from hours
+fn assert_totals_eq(
+    cached: &Totals,
+    recomputed: &Totals,
+    project: &str,
+) -> Result<(), TestCaseError> {
+    let pairs = [
+        ("today", cached.today, recomputed.today),
+        ("this week", cached.this_week, recomputed.this_week),
+        ("last week", cached.last_week, recomputed.last_week),
+        ("eight weeks", cached.eight_weeks, recomputed.eight_weeks),
+    ];
+    for (name, cached, recomputed) in pairs {
+        prop_assert!(
+            (cached - recomputed).abs() <= Duration::milliseconds(1),
+            "{} total of {}: cached {:?}, recomputed {:?}",
+            name,
+            project,
+            cached,
+            recomputed
+        );
+    }
+    Ok(())
+}
+
+async fn check_invariants(
+    recorder: &mut TimingsRecorder,
+    pool: &SqlitePool,
+    first: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<(), TestCaseError> {
+    let mut conn = pool.acquire().await.map_err(fail)?;
+    let timings = conn.get_timings(None).await.map_err(fail)?;
+    let minimum = recorder.minimum_timing();
+
+    let mut by_project: HashMap<&str, Vec<&Timing>> = HashMap::new();
+    for timing in &timings {
+        prop_assert!(
+            timing.end - timing.start >= minimum,
+            "Timing shorter than {:?}: {:?}",
+            minimum,
+            timing
+        );
+        prop_assert!(
+            first <= timing.start && timing.end <= now,
+            "Timing outside of {:?} - {:?}: {:?}",
+            first,
+            now,
+            timing
+        );
+        by_project
+            .entry(timing.project.as_str())
+            .or_default()
+            .push(timing);
+    }
+    for project_timings in by_project.values_mut() {
+        project_timings.sort_by_key(|timing| timing.start);
+        for pair in project_timings.windows(2) {
+            prop_assert!(
+                pair[0].end <= pair[1].start,
+                "Overlapping timings: {:?} and {:?}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+    let total = timings.iter().fold(Duration::zero(), |total, timing| {
+        total + (timing.end - timing.start)
+    });
+    prop_assert!(
+        total <= now - first,
+        "Persisted total {:?} exceeds the span {:?}",
+        total,
+        now - first
+    );
+
+    // The running timing is written but not committed, the cached totals
+    // exclude it
+    let running_start = recorder
+        .current_timing()
+        .map(|current| (current.project.clone(), current.start));
+    for project in PROJECTS {
+        if !recorder.has_cached_totals(CLIENT, project) {
+            continue;
+        }
+        let committed: Vec<_> = timings
+            .iter()
+            .filter(|timing| timing.project == project)
+            .filter(|timing| running_start != Some((timing.project.clone(), timing.start)))
+            .map(|timing| (timing.start, timing.end))
+            .collect();
+        let recomputed = DailyTotals::from_timings(&committed).to_totals(now);
+        let cached = recorder
+            .get_committed_totals(CLIENT, project, now)
+            .await
+            .map_err(fail)?;
+        assert_totals_eq(&cached, &recomputed, project)?;
+    }
+    Ok(())
+}
+
+async fn run_steps(minimum_seconds: i64, steps: Vec<(i64, Op)>) -> Result<(), TestCaseError> {
+    let pool = setup_test_db().await.map_err(fail)?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::seconds(minimum_seconds));
+
+    // Morning of a weekday, the steps stay within the local day
+    let mut now = Local
+        .with_ymd_and_hms(2024, 3, 6, 8, 0, 0)
+        .unwrap()
+        .with_timezone(&Utc);
+    let first = now + Duration::seconds(steps[0].0);
+    for (advance, op) in steps {
+        now += Duration::seconds(advance);
+        match op {
+            Op::Start(project) => {
+                recorder.start_timing(CLIENT.to_string(), PROJECTS[project].to_string(), now);
+            }
+            Op::Stop => recorder.stop_timing(now),
+            Op::KeepAlive => recorder.keep_alive_timing(now),
+            Op::Write => {
+                recorder.keep_alive_timing(now);
+                recorder.write_timings(now).await.map_err(fail)?;
+                check_invariants(&mut recorder, &pool, first, now).await?;
+            }
+            Op::Totals(project) => {
+                recorder.keep_alive_timing(now);
+                recorder
+                    .get_totals(CLIENT, PROJECTS[project], now)
+                    .await
+                    .map_err(fail)?;
+            }
+        }
+    }
+
+    now += Duration::seconds(1);
+    recorder.stop_timing(now);
+    recorder.write_timings(now).await.map_err(fail)?;
+    check_invariants(&mut recorder, &pool, first, now).await
+}
+
+#[test]
+fn test_recorder_invariants() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let config = Config {
+        cases: 256,
+        failure_persistence: None,
+        ..Config::default()
+    };
+    let mut runner =
+        TestRunner::new_with_rng(config, TestRng::deterministic_rng(RngAlgorithm::ChaCha));
+    let result = runner.run(&(minimum_seconds(), steps()), |(minimum, steps)| {
+        runtime.block_on(run_steps(minimum, steps))
+    });
+    if let Err(error) = result {
+        panic!("{}", error);
+    }
+}
@@ -0,0 +1,88 @@
+use chrono::TimeZone;
+use chrono::Utc;
+use sqlx::Connection;
+use sqlx::Executor;
+use sqlx::SqliteConnection;
+use timings::Error;
+use timings::SCHEMA_VERSION;
+use timings::Timing;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+
+/// Tables of a database created before schema versioning
+const VERSION_0_SCHEMA: &str = include_str!("fixtures/version_0_schema.sql");
+
+#[tokio::test]
+async fn test_new_database_is_created_at_current_version() -> Result<(), Box<dyn std::error::Error>>
+{
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await?;
+    assert_eq!(conn.get_schema_version().await?, None);
+
+    conn.create_timings_database().await?;
+    assert_eq!(conn.get_schema_version().await?, Some(SCHEMA_VERSION));
+
+    // Creating again is a no-op
+    conn.create_timings_database().await?;
+    assert_eq!(conn.get_schema_version().await?, Some(SCHEMA_VERSION));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_unversioned_database_keeps_timings() -> Result<(), Box<dyn std::error::Error>>
+{
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await?;
+    conn.execute(VERSION_0_SCHEMA).await?;
+    assert_eq!(conn.get_schema_version().await?, Some(0));
+
+    conn.migrate_timings_database().await?;
+    assert_eq!(conn.get_schema_version().await?, Some(SCHEMA_VERSION));
+    conn.check_timings_database().await?;
+    // Timings from before the sources have none
+    assert!(conn.get_timing_sources().await?.is_empty());
+
+    let timings = conn.get_timings(None).await?;
+    assert_eq!(
+        timings,
+        vec![Timing {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            start: Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 4, 10, 0, 0).unwrap(),
+        }]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_newer_database_is_refused() -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await?;
+    conn.create_timings_database().await?;
+    conn.execute(format!("PRAGMA user_version = {}", SCHEMA_VERSION + 1).as_str())
+        .await?;
+
+    let result = conn.migrate_timings_database().await;
+    assert!(matches!(result, Err(Error::UnsupportedSchema(v)) if v == SCHEMA_VERSION + 1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_backup_copies_the_old_database() -> Result<(), Box<dyn std::error::Error>> {
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await?;
+    conn.execute(VERSION_0_SCHEMA).await?;
+
+    let backup = std::env::temp_dir().join(format!(
+        "timings-migrations-backup-{}.sqlite",
+        std::process::id()
+    ));
+    // An earlier backup is overwritten
+    std::fs::write(&backup, b"")?;
+    conn.backup_timings_database(&backup).await?;
+
+    let url = format!("sqlite://{}", backup.to_string_lossy());
+    let mut backup_conn = SqliteConnection::connect(&url).await?;
+    assert_eq!(backup_conn.get_schema_version().await?, Some(0));
+    assert_eq!(backup_conn.get_timings(None).await?.len(), 1);
+    backup_conn.close().await?;
+    std::fs::remove_file(&backup)?;
+    Ok(())
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use chrono::Duration;
+use chrono::TimeZone;
+use chrono::Utc;
+use common::setup_test_db;
+use timings::Timing;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+use timings::TimingsRecorder;
+use timings::TimingsRecording;
+use timings::normalize_name;
+
+#[test]
+fn test_normalize_name() {
+    // Non-breaking space and trailing unicode whitespace
+    assert_eq!(normalize_name("Acme\u{00A0}Corp\u{2003}"), "Acme Corp");
+    // Collapses internal whitespace
+    assert_eq!(normalize_name("  Acme \t  Corp "), "Acme Corp");
+    // Decomposed "e" + combining acute accent becomes precomposed "é"
+    assert_eq!(normalize_name("Cafe\u{0301}"), "Caf\u{00E9}");
+}
+
+#[tokio::test]
+async fn test_differently_encoded_names_map_to_same_client()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+
+    // Insert path
+    conn.insert_timings(&[
+        Timing {
+            client: "Caf\u{00E9} Acme".to_string(),
+            project: "Website".to_string(),
+            start: start_time,
+            end: start_time + Duration::seconds(60),
+        },
+        Timing {
+            client: "Cafe\u{0301}\u{00A0}Acme\u{00A0}".to_string(),
+            project: "Website ".to_string(),
+            start: start_time + Duration::seconds(120),
+            end: start_time + Duration::seconds(180),
+        },
+    ])
+    .await?;
+
+    // Recorder path
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    recorder.start_timing(
+        "Cafe\u{0301}  Acme".to_string(),
+        "Website\u{00A0}".to_string(),
+        start_time + Duration::seconds(240),
+    );
+    recorder.stop_timing(start_time + Duration::seconds(300));
+    recorder
+        .write_timings(start_time + Duration::seconds(310))
+        .await?;
+
+    let timings = conn.get_timings(None).await?;
+    assert_eq!(timings.len(), 3);
+    for timing in &timings {
+        assert_eq!(timing.client, "Caf\u{00E9} Acme");
+        assert_eq!(timing.project, "Website");
+    }
+
+    let clients: Vec<(String,)> = sqlx::query_as("SELECT name FROM client")
+        .fetch_all(&mut *conn)
+        .await?;
+    assert_eq!(clients.len(), 1, "Expected a single client");
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "pdf")]
+
+mod common;
+
+use chrono::NaiveDate;
+use chrono::Utc;
+use common::setup_test_db;
+use common::timing;
+use timings::Locale;
+use timings::TimingsMutations;
+use timings::pdf_invoice;
+
+fn contains(haystack: &[u8], needle: &str) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle.as_bytes())
+}
+
+#[tokio::test]
+async fn test_pdf_invoice_for_month() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 12, 0)),
+        timing("Acme", "Website", (5, 9, 0), (5, 11, 0)),
+        timing("Acme", "Backend", (6, 9, 0), (6, 10, 0)),
+        timing("Other", "Docs", (6, 9, 0), (6, 13, 0)),
+    ])
+    .await?;
+
+    let pdf = pdf_invoice(
+        &mut *conn,
+        Utc,
+        "Acme",
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+        NaiveDate::from_ymd_opt(2024, 3, 31).unwrap(),
+        &Locale::default(),
+        Some(80.0),
+    )
+    .await?;
+
+    assert!(pdf.starts_with(b"%PDF"));
+    assert!(contains(&pdf, "Acme"));
+    assert!(contains(&pdf, "Website"));
+    assert!(contains(&pdf, "5.00"), "Website hours");
+    assert!(contains(&pdf, "480.00"), "Total cost");
+    assert!(!contains(&pdf, "Docs"), "Other clients are left out");
+    Ok(())
+}
@@ -0,0 +1,417 @@
+mod common;
+
+use chrono::NaiveDate;
+use chrono::Utc;
+use common::setup_test_db;
+use common::timing;
+use sqlx::SqlitePool;
+use timings::Error;
+use timings::GetTimingsFilters;
+use timings::ImportStats;
+use timings::SummaryForDay;
+use timings::Timing;
+use timings::TimingAppUsage;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+use timings::export_all_json;
+use timings::export_json;
+use timings::import_all_json;
+use timings::import_json;
+
+#[tokio::test]
+async fn test_export_and_import_all_json() -> Result<(), Box<dyn std::error::Error>> {
+    let source_pool = setup_test_db().await?;
+    let mut source = source_pool.acquire().await?;
+    source
+        .insert_timings(&[
+            timing("Acme", "Website", (4, 9, 0), (4, 9, 45)),
+            timing("Acme", "Backend", (4, 10, 0), (4, 10, 45)),
+        ])
+        .await?;
+    source
+        .insert_timings_daily_summaries(
+            Utc,
+            &[SummaryForDay {
+                day: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+                client: "Acme".to_string(),
+                project: "Website".to_string(),
+                summary: "Layout".to_string(),
+                archived: true,
+            }],
+        )
+        .await?;
+    let json = export_all_json(&mut *source).await?;
+    assert!(json.contains("\"version\": 2"));
+
+    let target_pool = setup_test_db().await?;
+    let mut target = target_pool.acquire().await?;
+    target
+        .insert_timings(&[timing("Acme", "Website", (4, 9, 0), (4, 9, 45))])
+        .await?;
+    assert_eq!(
+        import_all_json(&mut target, &json).await?,
+        ImportStats {
+            inserted_timings: 1,
+            skipped_timings: 1,
+            inserted_summaries: 1,
+            skipped_summaries: 0,
+        }
+    );
+    assert_eq!(
+        target.get_timings(None).await?,
+        source.get_timings(None).await?
+    );
+    assert_eq!(
+        target.get_stored_summaries().await?,
+        source.get_stored_summaries().await?
+    );
+
+    // Importing again changes nothing
+    assert_eq!(
+        import_all_json(&mut target, &json).await?,
+        ImportStats {
+            inserted_timings: 0,
+            skipped_timings: 2,
+            inserted_summaries: 0,
+            skipped_summaries: 1,
+        }
+    );
+    assert_eq!(target.get_timings(None).await?.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_refuses_newer_version() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    let result = import_all_json(
+        &mut conn,
+        r#"{ "version": 3, "timings": [], "summaries": [], "sources": [] }"#,
+    )
+    .await;
+    assert!(matches!(result, Err(Error::UnsupportedExportVersion(3))));
+
+    let result = import_all_json(&mut conn, r#"{ "timings": [] }"#).await;
+    assert!(matches!(result, Err(Error::InvalidImport(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_and_import_filtered_json() -> Result<(), Box<dyn std::error::Error>> {
+    let source_pool = setup_test_db().await?;
+    let mut source = source_pool.acquire().await?;
+    source
+        .insert_timings(&[
+            timing("Acme", "Backend", (4, 10, 0), (4, 10, 45)),
+            timing("Acme", "Website", (4, 9, 0), (4, 9, 45)),
+            timing("Acme", "Website", (4, 11, 0), (4, 11, 45)),
+        ])
+        .await?;
+    let summary = |project: &str| SummaryForDay {
+        day: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+        client: "Acme".to_string(),
+        project: project.to_string(),
+        summary: format!("{} work", project),
+        archived: false,
+    };
+    source
+        .insert_timings_daily_summaries(Utc, &[summary("Website"), summary("Backend")])
+        .await?;
+
+    let document = export_json(
+        &mut *source,
+        Some(GetTimingsFilters {
+            project: Some("Website".to_string()),
+            ..Default::default()
+        }),
+    )
+    .await?;
+    assert_eq!(
+        document
+            .timings
+            .iter()
+            .map(|timing| timing.timing.clone())
+            .collect::<Vec<_>>(),
+        vec![
+            timing("Acme", "Website", (4, 9, 0), (4, 9, 45)),
+            timing("Acme", "Website", (4, 11, 0), (4, 11, 45))
+        ]
+    );
+    assert_eq!(document.summaries.len(), 1);
+    assert_eq!(document.summaries[0].text, "Website work");
+
+    let target_pool = setup_test_db().await?;
+    let mut target = target_pool.acquire().await?;
+    import_json(&mut target, &document).await?;
+    let stats = import_json(&mut target, &document).await?;
+    assert_eq!(stats.inserted_timings, 0);
+    assert_eq!(stats.inserted_summaries, 0);
+    assert_eq!(export_json(&mut *target, None).await?, document);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_filtered_export_normalizes_the_names() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    conn.insert_timings_with_source(
+        &[
+            timing("Acme", "Website", (4, 9, 0), (4, 9, 45)),
+            timing("Acme", "Backend", (4, 10, 0), (4, 10, 45)),
+        ],
+        Some("laptop"),
+    )
+    .await?;
+    let usage = |project: &str, start| TimingAppUsage {
+        client: "Acme".to_string(),
+        project: project.to_string(),
+        start,
+        app: "firefox".to_string(),
+        seconds: 600,
+    };
+    conn.insert_timing_app_usage(&[
+        usage(
+            "Website",
+            timing("Acme", "Website", (4, 9, 0), (4, 9, 45)).start,
+        ),
+        usage(
+            "Backend",
+            timing("Acme", "Backend", (4, 10, 0), (4, 10, 45)).start,
+        ),
+    ])
+    .await?;
+    conn.insert_timings_daily_summaries(
+        Utc,
+        &[SummaryForDay {
+            day: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            summary: "Layout".to_string(),
+            archived: false,
+        }],
+    )
+    .await?;
+
+    let document = export_json(
+        &mut *conn,
+        Some(GetTimingsFilters {
+            client: Some(" Acme".to_string()),
+            projects: Some(vec!["Website  ".to_string()]),
+            ..Default::default()
+        }),
+    )
+    .await?;
+    assert_eq!(document.timings.len(), 1);
+    assert_eq!(document.timings[0].source.as_deref(), Some("laptop"));
+    assert_eq!(document.timings[0].app_usage.len(), 1);
+    assert_eq!(document.summaries.len(), 1);
+    assert_eq!(document.summaries[0].text, "Layout");
+    assert_eq!(
+        conn.get_timing_app_usage(Some(GetTimingsFilters {
+            project: Some("Website".to_string()),
+            ..Default::default()
+        }))
+        .await?,
+        vec![usage(
+            "Website",
+            timing("Acme", "Website", (4, 9, 0), (4, 9, 45)).start
+        )]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_json_keeps_the_sources() -> Result<(), Box<dyn std::error::Error>> {
+    let source_pool = setup_test_db().await?;
+    let mut source = source_pool.acquire().await?;
+    source
+        .insert_timings_with_source(
+            &[timing("Acme", "Website", (4, 9, 0), (4, 9, 45))],
+            Some("laptop"),
+        )
+        .await?;
+    source
+        .insert_timings(&[timing("Acme", "Website", (4, 10, 0), (4, 10, 45))])
+        .await?;
+    let json = export_all_json(&mut *source).await?;
+
+    let target_pool = setup_test_db().await?;
+    let mut target = target_pool.acquire().await?;
+    import_all_json(&mut target, &json).await?;
+    assert_eq!(target.get_timing_sources().await?, vec!["laptop"]);
+    let laptop = target
+        .get_timings(Some(GetTimingsFilters {
+            source: Some("laptop".to_string()),
+            ..Default::default()
+        }))
+        .await?;
+    assert_eq!(
+        laptop,
+        vec![timing("Acme", "Website", (4, 9, 0), (4, 9, 45))]
+    );
+
+    // Documents of version 1 have no sources
+    let json = r#"{
+        "version": 1,
+        "timings": [{
+            "client": "Acme",
+            "project": "Backend",
+            "start": "2024-03-04T11:00:00Z",
+            "end": "2024-03-04T11:45:00Z"
+        }],
+        "summaries": []
+    }"#;
+    assert_eq!(
+        import_all_json(&mut target, json).await?.inserted_timings,
+        1
+    );
+    assert_eq!(target.get_timing_sources().await?, vec!["laptop"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_again_replaces_the_usage() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let json = |end: &str| {
+        format!(
+            r#"{{
+                "version": 2,
+                "timings": [{{
+                    "client": "Acme",
+                    "project": "Website",
+                    "start": "2024-03-04T09:00:00Z",
+                    "end": "{end}",
+                    "app_usage": {{ "firefox": 600 }}
+                }}],
+                "summaries": []
+            }}"#
+        )
+    };
+
+    import_all_json(&mut conn, &json("2024-03-04T09:30:00Z")).await?;
+    // Exported again after the running timing grew
+    let stats = import_all_json(&mut conn, &json("2024-03-04T09:45:00Z")).await?;
+    assert_eq!(stats.inserted_timings, 1);
+    assert_eq!(
+        conn.get_timings(None).await?,
+        vec![timing("Acme", "Website", (4, 9, 0), (4, 9, 45))]
+    );
+    let usage = conn.get_timing_app_usage(None).await?;
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0].seconds, 600);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_merge_database() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 9, 45)),
+        timing("Acme", "Backend", (4, 10, 0), (4, 10, 45)),
+    ])
+    .await?;
+
+    // Named in-memory database, shared by the connections of the process
+    let other_url = "sqlite:merge_other?mode=memory&cache=shared";
+    let other_pool = SqlitePool::connect(other_url).await?;
+    let mut other = other_pool.acquire().await?;
+    other.create_timings_database().await?;
+    other
+        .insert_timings(&[timing("Acme", "Website", (4, 9, 0), (4, 9, 45))])
+        .await?;
+    // Same names written differently are the same project
+    other
+        .insert_timings_with_source(
+            &[Timing {
+                client: " Acme".to_string(),
+                ..timing("Acme", "Support", (4, 11, 0), (4, 11, 45))
+            }],
+            Some("laptop"),
+        )
+        .await?;
+    other
+        .insert_timing_app_usage(&[TimingAppUsage {
+            client: "Acme".to_string(),
+            project: "Support".to_string(),
+            start: timing("Acme", "Support", (4, 11, 0), (4, 11, 45)).start,
+            app: "firefox".to_string(),
+            seconds: 600,
+        }])
+        .await?;
+    other.set_project_billable("Acme", "Support", false).await?;
+    other
+        .insert_timings_daily_summaries(
+            Utc,
+            &[SummaryForDay {
+                day: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+                client: "Acme".to_string(),
+                project: "Support".to_string(),
+                summary: "Calls".to_string(),
+                archived: false,
+            }],
+        )
+        .await?;
+
+    assert_eq!(
+        conn.merge_database(other_url).await?,
+        ImportStats {
+            inserted_timings: 1,
+            skipped_timings: 1,
+            inserted_summaries: 1,
+            skipped_summaries: 0,
+        }
+    );
+    let projects: Vec<_> = conn
+        .get_timings(None)
+        .await?
+        .into_iter()
+        .map(|timing| (timing.client, timing.project))
+        .collect();
+    assert_eq!(
+        projects,
+        vec![
+            ("Acme".to_string(), "Support".to_string()),
+            ("Acme".to_string(), "Backend".to_string()),
+            ("Acme".to_string(), "Website".to_string()),
+        ]
+    );
+
+    assert_eq!(conn.get_timing_sources().await?, vec!["laptop"]);
+    assert!(!conn.is_project_billable("Acme", "Support").await?);
+
+    // Merging again adds nothing, nor the usage twice
+    let stats = conn.merge_database(other_url).await?;
+    assert_eq!(stats.inserted_timings, 0);
+    assert_eq!(stats.inserted_summaries, 0);
+    let usage = conn.get_timing_app_usage(None).await?;
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0].seconds, 600);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_merge_database_refuses_other_versions() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    let other_url = "sqlite:merge_old?mode=memory&cache=shared";
+    let other_pool = SqlitePool::connect(other_url).await?;
+    let mut other = other_pool.acquire().await?;
+    other.create_timings_database().await?;
+    other
+        .insert_timings(&[timing("Acme", "Website", (4, 9, 0), (4, 9, 45))])
+        .await?;
+    sqlx::query("PRAGMA user_version = 4")
+        .execute(&mut *other)
+        .await?;
+
+    let result = conn.merge_database(other_url).await;
+    assert!(matches!(result, Err(Error::UnsupportedSchema(4))));
+    // The other database is only read, not migrated
+    assert_eq!(other.get_schema_version().await?, Some(4));
+    assert!(conn.get_timings(None).await?.is_empty());
+    Ok(())
+}
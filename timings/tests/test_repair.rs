@@ -0,0 +1,310 @@
+mod common;
+
+use chrono::Duration;
+use chrono::FixedOffset;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use common::setup_test_db;
+use common::timing;
+use sqlx::Executor;
+use timings::DatabaseProblem;
+use timings::Error;
+use timings::GetTimingsFilters;
+use timings::OverlapStrategy;
+use timings::Timing;
+use timings::TimingAppUsage;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+
+#[tokio::test]
+async fn test_repair_reports_and_fixes_defects() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let website = timing("Acme", "Website", (4, 9, 0), (4, 10, 0));
+    let backend = timing("Acme", "Backend", (4, 9, 30), (4, 10, 30));
+    conn.insert_timings(&[website.clone(), backend.clone()])
+        .await?;
+    // Overlap of two projects is the only problem
+    assert_eq!(conn.find_database_problems().await?.len(), 1);
+
+    // Defects of databases written without foreign keys enforced
+    (&mut *conn).execute("PRAGMA foreign_keys = OFF").await?;
+    let (website_id,): (i64,) = sqlx::query_as("SELECT id FROM project WHERE name = 'Website'")
+        .fetch_one(&mut *conn)
+        .await?;
+    let ghost_project = sqlx::query("INSERT INTO project (name, clientId) VALUES ('Ghost', 999)")
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+    sqlx::query("INSERT INTO timing (start, [end], projectId) VALUES (3000, 4000, ?)")
+        .bind(ghost_project)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query(
+        "INSERT INTO summary (archived, start, [end], text, projectId) VALUES (0, 0, 1, '', ?)",
+    )
+    .bind(ghost_project)
+    .execute(&mut *conn)
+    .await?;
+    let orphan_timing =
+        sqlx::query("INSERT INTO timing (start, [end], projectId) VALUES (5000, 6000, 998)")
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+    let orphan_summary = sqlx::query(
+        "INSERT INTO summary (archived, start, [end], text, projectId) VALUES (0, 0, 1, '', 998)",
+    )
+    .execute(&mut *conn)
+    .await?
+    .last_insert_rowid();
+    let orphan_usage =
+        sqlx::query("INSERT INTO timing_app_usage (timingId, app, seconds) VALUES (997, 'vim', 1)")
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+    let inverted =
+        sqlx::query("INSERT INTO timing (start, [end], projectId) VALUES (2000, 1000, ?)")
+            .bind(website_id)
+            .execute(&mut *conn)
+            .await?
+            .last_insert_rowid();
+    // Fixed with foreign keys enforced, like on a real database
+    (&mut *conn).execute("PRAGMA foreign_keys = ON").await?;
+
+    let overlap = DatabaseProblem::OverlappingTimings(website, backend);
+    let problems = conn.find_database_problems().await?;
+    assert_eq!(
+        problems,
+        vec![
+            DatabaseProblem::OrphanProject {
+                id: ghost_project,
+                name: "Ghost".to_string(),
+            },
+            DatabaseProblem::OrphanTiming { id: orphan_timing },
+            DatabaseProblem::OrphanSummary { id: orphan_summary },
+            DatabaseProblem::OrphanAppUsage { id: orphan_usage },
+            DatabaseProblem::InvertedTiming {
+                id: inverted,
+                start: Utc.timestamp_millis_opt(2000).unwrap(),
+                end: Utc.timestamp_millis_opt(1000).unwrap(),
+            },
+            overlap.clone(),
+        ]
+    );
+
+    // Each fix is reported, the timings and summaries of the project are
+    // deleted with it
+    for problem in &problems {
+        assert_eq!(
+            conn.fix_database_problem(problem).await?,
+            problem.is_fixable()
+        );
+    }
+    let (ghost_rows,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT (SELECT COUNT(*) FROM timing WHERE projectId = ?1)
+            + (SELECT COUNT(*) FROM summary WHERE projectId = ?1)
+        "#,
+    )
+    .bind(ghost_project)
+    .fetch_one(&mut *conn)
+    .await?;
+    assert_eq!(ghost_rows, 0);
+
+    // Overlaps are only reported
+    assert_eq!(conn.find_database_problems().await?, vec![overlap]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_resolve_overlaps_of_a_project() -> Result<(), Box<dyn std::error::Error>> {
+    let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    for (strategy, website_hours) in [
+        (OverlapStrategy::Clip, 3.0),
+        (OverlapStrategy::Merge, 3.0),
+        (OverlapStrategy::DeleteShorter, 2.0),
+    ] {
+        let pool = setup_test_db().await?;
+        let mut conn = pool.acquire().await?;
+        conn.insert_timings(&[
+            timing("Acme", "Website", (4, 9, 0), (4, 11, 0)),
+            timing("Acme", "Website", (4, 9, 15), (4, 9, 45)),
+            timing("Acme", "Website", (4, 10, 0), (4, 12, 0)),
+            // Overlap with another project is not repaired
+            timing("Acme", "Backend", (4, 9, 30), (4, 10, 30)),
+        ])
+        .await?;
+        let overlaps = conn.find_overlapping_timings().await?;
+        assert_eq!(overlaps.len(), 2);
+        assert_eq!(
+            overlaps[0].second.timing,
+            timing("Acme", "Website", (4, 9, 15), (4, 9, 45))
+        );
+
+        let totals = conn
+            .get_timings_daily_totals(Utc, day, day, None, Some("Website".to_string()))
+            .await?;
+        assert_eq!(totals[0].hours, 4.5);
+
+        let now = Utc.with_ymd_and_hms(2024, 3, 5, 8, 0, 0).unwrap();
+        assert_eq!(
+            conn.resolve_overlaps(strategy, now).await?,
+            2,
+            "{:?}",
+            strategy
+        );
+        assert!(conn.find_overlapping_timings().await?.is_empty());
+        assert_eq!(conn.resolve_overlaps(strategy, now).await?, 0);
+
+        let totals = conn
+            .get_timings_daily_totals(Utc, day, day, None, None)
+            .await?
+            .into_iter()
+            .map(|total| (total.project, total.hours))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            totals,
+            vec![
+                ("Backend".to_string(), 1.0),
+                ("Website".to_string(), website_hours),
+            ],
+            "{:?}",
+            strategy
+        );
+        // Changes are audited
+        assert!(!conn.get_timing_audit(10).await?.is_empty());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compact_timings_keeps_the_recorded_time() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 9, 30)),
+        timing("Acme", "Website", (4, 9, 31), (4, 10, 0)),
+        timing("Acme", "Website", (4, 10, 0), (4, 10, 30)),
+        // Another project in between keeps the timings apart
+        timing("Acme", "Backend", (4, 10, 30), (4, 10, 45)),
+        timing("Acme", "Website", (4, 10, 45), (4, 11, 0)),
+        // Gap longer than the maximum
+        timing("Acme", "Website", (4, 11, 10), (4, 11, 20)),
+    ])
+    .await?;
+    let usage = |start: (u32, u32), app: &str, seconds: i64| TimingAppUsage {
+        client: "Acme".to_string(),
+        project: "Website".to_string(),
+        start: Utc
+            .with_ymd_and_hms(2024, 3, 4, start.0, start.1, 0)
+            .unwrap(),
+        app: app.to_string(),
+        seconds,
+    };
+    conn.insert_timing_app_usage(&[
+        usage((9, 0), "vim", 1800),
+        usage((9, 31), "vim", 1800),
+        usage((9, 31), "firefox", 360),
+    ])
+    .await?;
+    let totals = conn
+        .get_timings_daily_totals(Utc, day, day, None, None)
+        .await?
+        .into_iter()
+        .map(|total| (total.project, total.hours))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        totals,
+        vec![("Backend".to_string(), 0.25), ("Website".to_string(), 1.9)]
+    );
+
+    let max_gap = Duration::minutes(2);
+    let filters = GetTimingsFilters {
+        project: Some("Backend".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(conn.compact_timings(Utc, max_gap, filters).await?, 0);
+    assert_eq!(
+        conn.compact_timings(Utc, max_gap, GetTimingsFilters::default())
+            .await?,
+        2
+    );
+    assert_eq!(
+        conn.compact_timings(Utc, max_gap, GetTimingsFilters::default())
+            .await?,
+        0
+    );
+
+    let mut website = conn
+        .get_timings(Some(GetTimingsFilters {
+            project: Some("Website".to_string()),
+            ..Default::default()
+        }))
+        .await?;
+    website.sort_by_key(|timing| timing.start);
+    assert_eq!(
+        website,
+        vec![
+            // 30 + 29 + 30 minutes from the start
+            timing("Acme", "Website", (4, 9, 0), (4, 10, 29)),
+            timing("Acme", "Website", (4, 10, 45), (4, 11, 0)),
+            timing("Acme", "Website", (4, 11, 10), (4, 11, 20)),
+        ]
+    );
+    let hours = conn
+        .get_timings_daily_totals(Utc, day, day, None, Some("Website".to_string()))
+        .await?[0]
+        .hours;
+    assert_eq!(hours, 1.9);
+
+    // Usage of the merged timings is kept with the first one
+    let apps = conn
+        .get_app_usage_for_day(Utc, day)
+        .await?
+        .into_iter()
+        .map(|usage| (usage.app, usage.hours))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        apps,
+        vec![("vim".to_string(), 1.0), ("firefox".to_string(), 0.1)]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compact_timings_stops_at_local_midnight() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    // 23:00 to 23:50 and 23:51 to 00:30 in UTC+2
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 21, 0), (4, 21, 50)),
+        timing("Acme", "Website", (4, 21, 51), (4, 22, 30)),
+    ])
+    .await?;
+    let max_gap = Duration::minutes(2);
+    let helsinki = FixedOffset::east_opt(2 * 3600).unwrap();
+    assert_eq!(
+        conn.compact_timings(helsinki, max_gap, GetTimingsFilters::default())
+            .await?,
+        0
+    );
+    assert_eq!(
+        conn.compact_timings(Utc, max_gap, GetTimingsFilters::default())
+            .await?,
+        1
+    );
+
+    let paged = GetTimingsFilters {
+        limit: Some(10),
+        ..Default::default()
+    };
+    assert!(matches!(
+        conn.compact_timings(Utc, max_gap, paged).await,
+        Err(Error::InvalidTiming(_))
+    ));
+    Ok(())
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use common::setup_test_db;
+use timings::Locale;
+use timings::ReportRow;
+use timings::SummaryForDay;
+use timings::Timing;
+use timings::TimingsMutations;
+use timings::UNASSIGNED_PROJECT;
+use timings::markdown_report;
+use timings::render_markdown_report;
+
+#[tokio::test]
+async fn test_markdown_report_for_week() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.insert_timings(&[
+        Timing {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            start: Utc.with_ymd_and_hms(2024, 2, 12, 9, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 2, 12, 11, 30, 0).unwrap(),
+        },
+        Timing {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            start: Utc.with_ymd_and_hms(2024, 2, 14, 9, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 2, 14, 10, 0, 0).unwrap(),
+        },
+        // Outside of the week, must not be included
+        Timing {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            start: Utc.with_ymd_and_hms(2024, 2, 19, 9, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 2, 19, 10, 0, 0).unwrap(),
+        },
+    ])
+    .await?;
+
+    conn.insert_timings_daily_summaries(
+        Utc,
+        &[SummaryForDay {
+            day: NaiveDate::from_ymd_opt(2024, 2, 12).unwrap(),
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            summary: "Layout | styles".to_string(),
+            archived: false,
+        }],
+    )
+    .await?;
+
+    let report = markdown_report(
+        &mut *conn,
+        Utc,
+        "Week 2024-W07",
+        NaiveDate::from_ymd_opt(2024, 2, 12).unwrap(),
+        NaiveDate::from_ymd_opt(2024, 2, 18).unwrap(),
+        &Locale::default(),
+        false,
+    )
+    .await?;
+
+    assert!(report.starts_with("# Week 2024-W07\n"));
+    assert!(report.contains("| 2024-02-12 | Acme | Website | 2.50 | Layout \\| styles |"));
+    assert!(report.contains("| 2024-02-14 | Acme | Website | 1.00 |  |"));
+    assert!(!report.contains("2024-02-19"));
+    assert!(report.contains("- Acme: Website — 3.50 h"));
+    assert!(report.contains("**Total: 3.50 h**"));
+
+    // Days are rendered oldest first
+    let first = report.find("2024-02-12").unwrap();
+    let second = report.find("2024-02-14").unwrap();
+    assert!(first < second);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_markdown_report_unassigned() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.insert_timings(&[
+        Timing {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            start: Utc.with_ymd_and_hms(2024, 2, 12, 9, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 2, 12, 10, 0, 0).unwrap(),
+        },
+        Timing {
+            client: "Browsing".to_string(),
+            project: UNASSIGNED_PROJECT.to_string(),
+            start: Utc.with_ymd_and_hms(2024, 2, 12, 10, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 2, 12, 10, 30, 0).unwrap(),
+        },
+    ])
+    .await?;
+
+    let from = NaiveDate::from_ymd_opt(2024, 2, 12).unwrap();
+    let to = NaiveDate::from_ymd_opt(2024, 2, 18).unwrap();
+    let report =
+        markdown_report(&mut *conn, Utc, "Week", from, to, &Locale::default(), false).await?;
+    assert!(!report.contains("(unassigned)"));
+    assert!(report.contains("**Total: 1.00 h**"));
+
+    let report =
+        markdown_report(&mut *conn, Utc, "Week", from, to, &Locale::default(), true).await?;
+    assert!(report.contains("| 2024-02-12 | Browsing | (unassigned) | 0.50 |  |"));
+    assert!(report.contains("**Total: 1.50 h**"));
+
+    Ok(())
+}
+
+#[test]
+fn test_markdown_report_with_comma_locale() {
+    let locale = Locale {
+        decimal_separator: ',',
+        date_format: "%d.%m.%Y".to_string(),
+        ..Locale::default()
+    };
+    let rows = [ReportRow {
+        day: NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(),
+        client: "Acme".to_string(),
+        project: "Website".to_string(),
+        hours: 3.25,
+        timings: 1,
+        summary: String::new(),
+        archived: false,
+        billable: true,
+    }];
+
+    let report = render_markdown_report("Week", &rows, &locale);
+    assert!(report.contains("| 15.01.2024 | Acme | Website | 3,25 |  |"));
+    assert!(report.contains("- Acme: Website — 3,25 h"));
+    assert!(report.contains("**Total: 3,25 h**"));
+}
@@ -0,0 +1,286 @@
+//! Runs every query and mutation against a freshly created schema, with all
+//! optional filters on and off, so a typo in a table or column name fails
+//! here instead of at runtime.
+
+mod common;
+
+use chrono::Duration;
+use chrono::FixedOffset;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use common::setup_test_db;
+use common::timing;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use timings::DatabaseProblem;
+use timings::GetTimingsFilters;
+use timings::SummaryForDay;
+use timings::Timing;
+use timings::TimingAppUsage;
+use timings::TimingsMockdata;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+
+fn day(d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+}
+
+fn summary(d: u32, text: &str) -> SummaryForDay {
+    SummaryForDay {
+        day: day(d),
+        client: "Acme Corp".to_string(),
+        project: "Website Redesign".to_string(),
+        summary: text.to_string(),
+        archived: false,
+    }
+}
+
+/// Optional client and project filters matching the representative data,
+/// each on and off
+fn filter_permutations(client: &str, project: &str) -> Vec<(Option<String>, Option<String>)> {
+    let clients = [None, Some(client.to_string())];
+    let projects = [None, Some(project.to_string())];
+    clients
+        .iter()
+        .flat_map(|client| {
+            projects
+                .iter()
+                .map(move |project| (client.clone(), project.clone()))
+        })
+        .collect()
+}
+
+/// Mock data of half a year, and timings, summaries and application usage
+/// after it, including a timing over midnight
+async fn setup_representative_db() -> Result<SqlitePool, Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    conn.insert_mockdata(Utc.with_ymd_and_hms(2024, 3, 15, 12, 0, 0).unwrap())
+        .await?;
+    let over_midnight = timing("Acme Corp", "Website Redesign", (16, 22, 0), (17, 2, 0));
+    conn.insert_timings_and_summaries(
+        &[
+            timing("Acme Corp", "Website Redesign", (16, 8, 0), (16, 12, 0)),
+            over_midnight.clone(),
+        ],
+        Some("laptop"),
+        Utc,
+        &[summary(16, "Layout"), summary(17, "Fonts")],
+    )
+    .await?;
+    conn.insert_timing_app_usage(&[TimingAppUsage {
+        client: over_midnight.client.clone(),
+        project: over_midnight.project.clone(),
+        start: over_midnight.start,
+        app: "firefox".to_string(),
+        seconds: 600,
+    }])
+    .await?;
+    Ok(pool)
+}
+
+#[tokio::test]
+async fn test_queries_with_filter_permutations() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_representative_db().await?;
+    let mut conn = pool.acquire().await?;
+    let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+
+    // Totals match the names with LIKE
+    for (client, project) in filter_permutations("Acme%", "Website%") {
+        let daily = conn
+            .get_timings_daily_totals(tz, day(1), day(17), client.clone(), project.clone())
+            .await?;
+        assert!(!daily.is_empty(), "{:?} {:?}", client, project);
+        let weekly = conn
+            .get_timings_weekly_totals(tz, day(1), day(17), client.clone(), project.clone())
+            .await?;
+        assert!(!weekly.is_empty(), "{:?} {:?}", client, project);
+        let monthly = conn
+            .get_timings_monthly_totals(tz, day(1), day(17), client.clone(), project.clone())
+            .await?;
+        assert!(!monthly.is_empty(), "{:?} {:?}", client, project);
+        let summaries = conn
+            .get_timings_daily_summaries(Utc, day(1), day(17), client.clone(), project.clone())
+            .await?;
+        assert!(!summaries.is_empty(), "{:?} {:?}", client, project);
+        let combined = conn
+            .get_timings_daily_totals_and_summaries(Utc, day(1), day(17), client, project)
+            .await?;
+        assert!(combined.iter().any(|row| row.summary == "Fonts"));
+    }
+
+    let from = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+    let to = Utc.with_ymd_and_hms(2024, 3, 18, 0, 0, 0).unwrap();
+    // Timings match the exact names
+    for (client, project) in filter_permutations("Acme Corp", "Website Redesign") {
+        for (from, to) in [
+            (None, None),
+            (Some(from), None),
+            (None, Some(to)),
+            (Some(from), Some(to)),
+        ] {
+            let timings = conn
+                .get_timings(Some(GetTimingsFilters {
+                    from,
+                    to,
+                    client: client.clone(),
+                    project: project.clone(),
+                    clients: None,
+                    projects: None,
+                    source: None,
+                    limit: None,
+                    offset: None,
+                }))
+                .await?;
+            assert!(
+                !timings.is_empty(),
+                "{:?} {:?} {:?} {:?}",
+                from,
+                to,
+                client,
+                project
+            );
+        }
+    }
+    assert!(!conn.get_timings(None).await?.is_empty());
+    assert!(!conn.get_timings_with_ids(None).await?.is_empty());
+    let laptop = GetTimingsFilters {
+        source: Some("laptop".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(conn.get_timings(Some(laptop)).await?.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_queries_without_filters() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_representative_db().await?;
+    let mut conn = pool.acquire().await?;
+    let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+
+    assert!(conn.get_schema_version().await?.is_some());
+    conn.check_timings_database().await?;
+    conn.find_database_problems().await?;
+    assert!(
+        !conn
+            .get_timings_for_day(tz, day(17), "Acme Corp", "Website Redesign")
+            .await?
+            .is_empty()
+    );
+    let hourly = conn.get_hourly_distribution(tz, day(1), day(17)).await?;
+    assert!(hourly.iter().sum::<f64>() > 0.0);
+    conn.find_tracking_gaps(
+        day(16),
+        Utc,
+        NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+        NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        Duration::minutes(15),
+    )
+    .await?;
+    assert!(
+        !conn
+            .get_projects_by_total(day(1), day(17), tz, 5)
+            .await?
+            .is_empty()
+    );
+    conn.get_implausible_days(tz, day(1), day(17), 1.0).await?;
+    conn.get_timings_spanning_midnights(Utc, day(1), day(17))
+        .await?;
+    assert_eq!(
+        conn.get_recent_distinct_summaries("Acme Corp", "Website Redesign", 10)
+            .await?,
+        vec!["Fonts".to_string(), "Layout".to_string()]
+    );
+    assert!(!conn.get_full_report(day(1), day(17), tz).await?.is_empty());
+    conn.get_timing_audit(10).await?;
+    assert!(conn.get_grand_total().await? > Duration::zero());
+    assert_eq!(conn.get_counts().await?, (2, 8));
+    assert_eq!(conn.get_timing_sources().await?, vec!["laptop".to_string()]);
+    assert!(!conn.get_app_usage_for_day(Utc, day(16)).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_mutations() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_representative_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.migrate_timings_database().await?;
+    conn.insert_timings(&[timing(
+        "Acme Corp",
+        "Website Redesign",
+        (18, 8, 0),
+        (18, 9, 0),
+    )])
+    .await?;
+    conn.insert_timings_daily_summaries(Utc, &[summary(18, "Review")])
+        .await?;
+
+    let now = Utc.with_ymd_and_hms(2024, 3, 19, 12, 0, 0).unwrap();
+    conn.update_timing(
+        &timing("Acme Corp", "Website Redesign", (18, 8, 0), (18, 9, 0)),
+        &timing("Acme Corp", "Website Redesign", (18, 8, 0), (18, 10, 0)),
+        now,
+    )
+    .await?;
+    conn.delete_timing(
+        &timing("Acme Corp", "Website Redesign", (18, 8, 0), (18, 10, 0)),
+        now,
+    )
+    .await?;
+    let start = timing("Acme Corp", "Website Redesign", (16, 8, 0), (16, 12, 0)).start;
+    assert!(
+        conn.delete_timing_at("Acme Corp", "Website Redesign", start, now)
+            .await?
+    );
+    assert_eq!(conn.get_timing_audit(10).await?.len(), 3);
+
+    let problems = [
+        DatabaseProblem::OrphanProject {
+            id: 0,
+            name: "Ghost".to_string(),
+        },
+        DatabaseProblem::OrphanTiming { id: 0 },
+        DatabaseProblem::OrphanSummary { id: 0 },
+        DatabaseProblem::OrphanAppUsage { id: 0 },
+    ];
+    for problem in &problems {
+        assert!(conn.fix_database_problem(problem).await?);
+    }
+
+    let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+    for dry_run in [true, false] {
+        conn.shift_day_attribution(day(1), day(18), Utc, tz, dry_run)
+            .await?;
+    }
+
+    conn.apply_rename_map(
+        HashMap::from([("Oma".to_string(), "Own".to_string())]),
+        HashMap::from([(
+            ("Acme Corp".to_string(), "Website Redesign".to_string()),
+            ("Acme Corp".to_string(), "Website".to_string()),
+        )]),
+    )
+    .await?;
+    assert!(
+        !conn
+            .get_timings_daily_totals(Utc, day(1), day(17), Some("Own".to_string()), None)
+            .await?
+            .is_empty()
+    );
+
+    let backup = std::env::temp_dir().join(format!(
+        "timings-schema-drift-{}.sqlite",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&backup);
+    conn.backup_timings_database(&backup).await?;
+    std::fs::remove_file(&backup)?;
+
+    conn.check_timings_database().await?;
+    Ok(())
+}
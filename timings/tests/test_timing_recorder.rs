@@ -1,20 +1,19 @@
+mod common;
+
 use chrono::DateTime;
 use chrono::Duration;
+use chrono::Local;
 use chrono::TimeZone;
 use chrono::Utc;
+use common::setup_test_db;
 use sqlx::SqlitePool;
+use timings::RestoreOutcome;
+use timings::SavedTiming;
 use timings::TimingsMutations;
 use timings::TimingsQueries;
 use timings::TimingsRecorder;
 use timings::TimingsRecording;
 
-async fn setup_test_db() -> Result<SqlitePool, Box<dyn std::error::Error>> {
-    let pool = SqlitePool::connect("sqlite::memory:").await?;
-    let mut conn = pool.acquire().await?;
-    conn.create_timings_database().await?;
-    Ok(pool)
-}
-
 fn call_keep_alives(recorder: &mut TimingsRecorder, start: DateTime<Utc>, end: DateTime<Utc>) {
     let duration = (end - start).num_seconds() as usize;
     let keep_alive_intervals = duration / 30;
@@ -375,3 +374,610 @@ async fn test_start_timing_with_empty_project_stops_current_timing()
 
     Ok(())
 }
+
+/// Runs a recorder until shutdown and returns the saved timing
+async fn run_until_shutdown(
+    pool: &SqlitePool,
+    start_time: DateTime<Utc>,
+    shutdown_time: DateTime<Utc>,
+) -> Result<SavedTiming, Box<dyn std::error::Error>> {
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+    call_keep_alives(&mut recorder, start_time, shutdown_time);
+    let saved = recorder.saved_timing(shutdown_time).unwrap();
+    recorder.write_timings(shutdown_time).await?;
+    Ok(saved)
+}
+
+#[tokio::test]
+async fn test_restore_recent_and_matching_timing_resumes() -> Result<(), Box<dyn std::error::Error>>
+{
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let shutdown_time = start_time + Duration::seconds(120);
+    let saved = run_until_shutdown(&pool, start_time, shutdown_time).await?;
+
+    // Restart 20 seconds later on the same desktop
+    let restart_time = shutdown_time + Duration::seconds(20);
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let outcome = recorder.restore_timing(saved, "client1", "project1", restart_time);
+    assert_eq!(outcome, RestoreOutcome::Resumed);
+    assert!(recorder.is_running());
+
+    let write_time = restart_time + Duration::seconds(30);
+    recorder.keep_alive_timing(write_time);
+    recorder.write_timings(write_time).await?;
+
+    let timings = conn.get_timings(None).await?;
+    assert_eq!(timings.len(), 1, "Resumed timing continues the same row");
+    assert_eq!(timings[0].start, start_time);
+    assert_eq!(timings[0].end, write_time);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_recent_timing_on_different_desktop_closes()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let shutdown_time = start_time + Duration::seconds(120);
+    let saved = run_until_shutdown(&pool, start_time, shutdown_time).await?;
+
+    let restart_time = shutdown_time + Duration::seconds(20);
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let outcome = recorder.restore_timing(saved, "client2", "project2", restart_time);
+    assert_eq!(outcome, RestoreOutcome::Closed);
+
+    let write_time = restart_time + Duration::seconds(30);
+    recorder.write_timings(write_time).await?;
+
+    let mut timings = conn.get_timings(None).await?;
+    timings.sort_by_key(|t| t.start);
+    assert_eq!(timings.len(), 2);
+    assert_eq!(timings[0].client, "client1");
+    assert_eq!(timings[0].start, start_time);
+    assert_eq!(timings[0].end, shutdown_time);
+    assert_eq!(timings[1].client, "client2");
+    assert_eq!(timings[1].start, restart_time);
+    assert_eq!(timings[1].end, write_time);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restore_stale_timing_closes_at_last_keep_alive()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let shutdown_time = start_time + Duration::seconds(120);
+    let saved = run_until_shutdown(&pool, start_time, shutdown_time).await?;
+
+    // Restart an hour later on the same desktop
+    let restart_time = shutdown_time + Duration::hours(1);
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let outcome = recorder.restore_timing(saved, "client1", "project1", restart_time);
+    assert_eq!(outcome, RestoreOutcome::Closed);
+    assert!(recorder.is_running());
+
+    let write_time = restart_time + Duration::seconds(30);
+    recorder.write_timings(write_time).await?;
+
+    let mut timings = conn.get_timings(None).await?;
+    timings.sort_by_key(|t| t.start);
+    assert_eq!(timings.len(), 2);
+    assert_eq!(timings[0].start, start_time);
+    assert_eq!(timings[0].end, shutdown_time);
+    assert_eq!(timings[1].start, restart_time);
+    assert_eq!(timings[1].end, write_time);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_clock_backwards_keep_alive_does_not_shrink_timing()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let write_time = start_time + Duration::minutes(5);
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+    call_keep_alives(&mut recorder, start_time, write_time);
+    recorder.write_timings(write_time).await?;
+
+    // NTP steps the clock back 2 minutes
+    let stepped_back = write_time - Duration::minutes(2);
+    recorder.keep_alive_timing(stepped_back);
+    recorder.write_timings(stepped_back).await?;
+
+    let timings = conn.get_timings(None).await?;
+    assert_eq!(timings.len(), 1);
+    assert_eq!(timings[0].start, start_time);
+    assert_eq!(timings[0].end, write_time, "End must not move backwards");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_clock_backwards_before_start_does_not_write_inverted_timing()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+
+    // Clock steps back before the start of the running timing
+    recorder
+        .write_timings(start_time - Duration::minutes(2))
+        .await?;
+
+    let timings = conn.get_timings(None).await?;
+    assert!(timings.is_empty(), "Inverted timing must not be written");
+
+    // Tracking continues once the clock catches up
+    let write_time = start_time + Duration::minutes(1);
+    recorder.keep_alive_timing(write_time);
+    recorder.write_timings(write_time).await?;
+    let timings = conn.get_timings(None).await?;
+    assert_eq!(timings.len(), 1);
+    assert_eq!(timings[0].start, start_time);
+    assert_eq!(timings[0].end, write_time);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_clock_backwards_stop_timing_closes_at_latest_time()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let last_keep_alive = start_time + Duration::minutes(5);
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+    call_keep_alives(&mut recorder, start_time, last_keep_alive);
+
+    recorder.stop_timing(last_keep_alive - Duration::minutes(2));
+    recorder
+        .write_timings(last_keep_alive - Duration::minutes(2))
+        .await?;
+
+    let timings = conn.get_timings(None).await?;
+    assert_eq!(timings.len(), 1);
+    assert_eq!(timings[0].start, start_time);
+    assert_eq!(timings[0].end, last_keep_alive);
+    assert!(timings[0].end > timings[0].start);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_committed_totals_exclude_running_timing() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+    recorder.stop_timing(start_time + Duration::minutes(30));
+
+    let running_start = start_time + Duration::minutes(40);
+    let now = running_start + Duration::minutes(10);
+    recorder.start_timing("client1".to_string(), "project1".to_string(), running_start);
+    call_keep_alives(&mut recorder, running_start, now);
+
+    // Both uncached and cached totals
+    for _ in 0..2 {
+        let committed = recorder
+            .get_committed_totals("client1", "project1", now)
+            .await?;
+        let totals = recorder.get_totals("client1", "project1", now).await?;
+        let elapsed = now - running_start;
+        assert_eq!(totals.today - committed.today, elapsed);
+        assert_eq!(totals.this_week - committed.this_week, elapsed);
+        assert_eq!(totals.eight_weeks - committed.eight_weeks, elapsed);
+        assert_eq!(committed.today, Duration::minutes(30));
+    }
+
+    Ok(())
+}
+
+// Persistence identity: timings are identified by `(project, start)`, the
+// running timing is UPSERTed on each write and a new segment must always get
+// a new start.
+
+#[tokio::test]
+async fn test_keep_alive_split_after_write_keeps_written_row()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let first_write = start_time + Duration::minutes(2);
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+    call_keep_alives(&mut recorder, start_time, first_write);
+    recorder.write_timings(first_write).await?;
+
+    // Keep-alive gap splits the running timing, the new segment starts at the
+    // late keep-alive
+    let late_keep_alive = first_write + Duration::minutes(3);
+    recorder.keep_alive_timing(late_keep_alive);
+    let second_write = late_keep_alive + Duration::minutes(1);
+    recorder.write_timings(second_write).await?;
+
+    let mut timings = conn.get_timings(None).await?;
+    timings.sort_by_key(|t| t.start);
+    assert_eq!(timings.len(), 2);
+    assert_eq!(timings[0].start, start_time);
+    assert_eq!(timings[0].end, first_write, "Written row stays as is");
+    assert_eq!(timings[1].start, late_keep_alive);
+    assert_eq!(timings[1].end, second_write);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restart_at_same_instant_nudges_start() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+
+    // Start, stop and start again within the clock resolution
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+    recorder.stop_timing(start_time);
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+
+    let restarted = recorder.current_timing().unwrap().start;
+    assert_eq!(restarted, start_time + Duration::milliseconds(1));
+
+    let end_time = start_time + Duration::minutes(1);
+    recorder.stop_timing(end_time);
+    recorder.write_timings(end_time).await?;
+
+    let timings = conn.get_timings(None).await?;
+    assert_eq!(timings.len(), 1);
+    assert_eq!(timings[0].start, restarted);
+    assert_eq!(timings[0].end, end_time);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_switching_back_and_forth_at_same_instant_keeps_segments_apart()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let switch_time = start_time + Duration::minutes(1);
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+    recorder.keep_alive_timing(start_time + Duration::seconds(30));
+    recorder.write_timings(switch_time).await?;
+
+    // Switch away and back at the same instant, then keep working
+    recorder.start_timing("client2".to_string(), "project2".to_string(), switch_time);
+    recorder.start_timing("client1".to_string(), "project1".to_string(), switch_time);
+    let end_time = switch_time + Duration::minutes(1);
+    recorder.keep_alive_timing(switch_time + Duration::seconds(30));
+    recorder.stop_timing(end_time);
+    recorder.write_timings(end_time).await?;
+
+    let mut timings = conn.get_timings(None).await?;
+    timings.sort_by_key(|t| t.start);
+    assert_eq!(timings.len(), 2, "Second segment must not extend the first");
+    assert_eq!(timings[0].start, start_time);
+    assert_eq!(timings[0].end, switch_time);
+    assert_eq!(timings[1].client, "client1");
+    assert!(timings[1].start > switch_time);
+    assert_eq!(timings[1].end, end_time);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_serialize_and_restore_state_writes_pending_timings()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let start = Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap();
+
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    recorder.start_timing("Acme".to_string(), "Website".to_string(), start);
+    call_keep_alives(&mut recorder, start, start + Duration::minutes(10));
+    recorder.start_timing(
+        "Acme".to_string(),
+        "Backend".to_string(),
+        start + Duration::minutes(10),
+    );
+    call_keep_alives(
+        &mut recorder,
+        start + Duration::minutes(10),
+        start + Duration::minutes(15),
+    );
+    let state = recorder.serialize_state();
+
+    // Crash, nothing was written
+    drop(recorder);
+    let mut conn = pool.acquire().await?;
+    assert!(conn.get_timings(None).await?.is_empty());
+
+    let mut recovered = TimingsRecorder::new(pool.clone(), Duration::zero());
+    recovered.restore_state(&state)?;
+    assert!(!recovered.is_running(), "Running timing is closed");
+    recovered
+        .write_timings(start + Duration::minutes(30))
+        .await?;
+
+    let mut timings = conn.get_timings(None).await?;
+    timings.sort_by_key(|t| t.start);
+    assert_eq!(timings.len(), 2);
+    assert_eq!(timings[0].project, "Website");
+    assert_eq!(timings[0].end, start + Duration::minutes(10));
+    assert_eq!(timings[1].project, "Backend");
+    assert_eq!(timings[1].start, start + Duration::minutes(10));
+    assert_eq!(timings[1].end, start + Duration::minutes(15));
+
+    assert!(recovered.restore_state("not json").is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalidate_caches_recalculates_totals() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let now = Utc::now();
+
+    let totals = recorder.get_totals("client1", "project1", now).await?;
+    assert_eq!(totals.eight_weeks, Duration::zero());
+
+    // Written behind the cache, e.g. bucketed differently in another timezone
+    conn.insert_timings(&[timings::Timing {
+        client: "client1".to_string(),
+        project: "project1".to_string(),
+        start: now - Duration::days(1) - Duration::hours(1),
+        end: now - Duration::days(1),
+    }])
+    .await?;
+    let totals = recorder.get_totals("client1", "project1", now).await?;
+    assert_eq!(totals.eight_weeks, Duration::zero(), "Cached");
+
+    recorder.invalidate_caches();
+    let totals = recorder.get_totals("client1", "project1", now).await?;
+    assert_eq!(totals.eight_weeks, Duration::hours(1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_presence_accrues_while_not_idle() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let t0 = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let at = |minutes| t0 + Duration::minutes(minutes);
+
+    // Present without a timing running
+    recorder.keep_alive_timing(t0);
+    call_keep_alives(&mut recorder, t0, at(10));
+    assert_eq!(recorder.presence_today(at(10)), Duration::minutes(10));
+
+    // Idle time is not presence, even though keep-alives continue
+    recorder.set_user_idle(true, at(10));
+    call_keep_alives(&mut recorder, at(10), at(20));
+    assert_eq!(recorder.presence_today(at(20)), Duration::minutes(10));
+
+    recorder.set_user_idle(false, at(20));
+    recorder.start_timing("client1".to_string(), "project1".to_string(), at(20));
+    call_keep_alives(&mut recorder, at(20), at(25));
+    assert_eq!(recorder.presence_today(at(25)), Duration::minutes(15));
+
+    // Gaps without keep-alives, e.g. sleep, are not presence
+    recorder.keep_alive_timing(at(60));
+    assert_eq!(recorder.presence_today(at(60)), Duration::minutes(15));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_last_write_time_and_pending() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let t0 = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    assert_eq!(recorder.last_write_time(), None);
+    assert_eq!(recorder.pending_count(t0), 0);
+
+    recorder.start_timing("client1".to_string(), "project1".to_string(), t0);
+    recorder.start_timing(
+        "client1".to_string(),
+        "project2".to_string(),
+        t0 + Duration::minutes(5),
+    );
+    assert_eq!(recorder.pending_count(t0 + Duration::minutes(6)), 2);
+
+    let written_at = t0 + Duration::minutes(10);
+    recorder.write_timings(written_at).await?;
+    assert_eq!(recorder.last_write_time(), Some(written_at));
+    // The running timing is still pending
+    assert_eq!(recorder.pending_count(written_at), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pending_count_before_and_after_write() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::seconds(30));
+    let t0 = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    assert_eq!(recorder.pending_count(t0), 0);
+
+    recorder.start_timing("client1".to_string(), "project1".to_string(), t0);
+    call_keep_alives(&mut recorder, t0, t0 + Duration::minutes(5));
+    let switch = t0 + Duration::minutes(5);
+    recorder.start_timing("client1".to_string(), "project2".to_string(), switch);
+    // The new running timing is shorter than the minimum
+    assert_eq!(recorder.pending_count(switch + Duration::seconds(10)), 1);
+    assert_eq!(recorder.pending_count(switch + Duration::seconds(30)), 2);
+
+    let written_at = switch + Duration::minutes(1);
+    recorder.keep_alive_timing(written_at);
+    recorder.write_timings(written_at).await?;
+    // The running timing is written again on the next write
+    assert_eq!(recorder.pending_count(written_at), 1);
+    recorder.stop_timing(written_at);
+    recorder.write_timings(written_at).await?;
+    assert_eq!(recorder.pending_count(written_at), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_queued_summary_is_written_with_timings() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let start_time = Utc::now() - Duration::minutes(10);
+    let day = start_time.with_timezone(&Local).date_naive();
+
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+    recorder.queue_summary(day, "client1", "project1", "Draft");
+    recorder.queue_summary(day, "client1", "project1", "Layout");
+    assert_eq!(
+        recorder.get_summary_if_cached(day, "client1", "project1"),
+        Some("Layout".to_string())
+    );
+
+    let mut conn = pool.acquire().await?;
+    let summaries = conn
+        .get_timings_daily_summaries(Local, day, day, None, None)
+        .await?;
+    assert!(summaries.is_empty());
+
+    recorder
+        .write_timings(start_time + Duration::minutes(5))
+        .await?;
+    let summaries = conn
+        .get_timings_daily_summaries(Local, day, day, None, None)
+        .await?;
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].summary, "Layout");
+    assert_eq!(conn.get_timings(None).await?.len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stop_timing_deducting_idle() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let t0 = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let at = |minutes| t0 + Duration::minutes(minutes);
+
+    recorder.start_timing("client1".to_string(), "project1".to_string(), t0);
+    // Idle from 20 minutes, keep-alives and writes continue meanwhile
+    call_keep_alives(&mut recorder, t0, at(30));
+    recorder.write_timings(at(30)).await?;
+    recorder.stop_timing_deducting_idle(at(20), at(30));
+    assert!(!recorder.is_running());
+
+    recorder.write_timings(at(31)).await?;
+    let timings = conn.get_timings(None).await?;
+    assert_eq!(timings.len(), 1);
+    assert_eq!(timings[0].start, t0);
+    assert_eq!(timings[0].end, at(20), "Idle time is deducted");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pause_and_resume_records_two_timings() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let t0 = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let at = |minutes| t0 + Duration::minutes(minutes);
+
+    assert!(!recorder.resume_timing(t0), "Nothing is paused");
+    recorder.start_timing("client1".to_string(), "project1".to_string(), t0);
+    call_keep_alives(&mut recorder, t0, at(10));
+    recorder.pause_timing(at(10));
+    assert!(!recorder.is_running());
+    assert_eq!(recorder.paused_timing(), Some(("client1", "project1")));
+
+    // Pausing again keeps the paused timing
+    recorder.pause_timing(at(12));
+    assert!(recorder.resume_timing(at(15)));
+    assert!(recorder.is_running());
+    assert_eq!(recorder.paused_timing(), None);
+    call_keep_alives(&mut recorder, at(15), at(25));
+    recorder.stop_timing(at(25));
+    recorder.write_timings(at(26)).await?;
+
+    let timings = conn.get_timings(None).await?;
+    assert_eq!(
+        timings
+            .iter()
+            .map(|t| (t.client.as_str(), t.project.as_str(), t.start, t.end))
+            .collect::<Vec<_>>(),
+        vec![
+            ("client1", "project1", at(15), at(25)),
+            ("client1", "project1", t0, at(10)),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stop_forgets_the_paused_timing() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let t0 = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+
+    recorder.start_timing("client1".to_string(), "project1".to_string(), t0);
+    recorder.pause_timing(t0 + Duration::seconds(30));
+    recorder.stop_timing(t0 + Duration::seconds(40));
+    assert!(!recorder.resume_timing(t0 + Duration::seconds(50)));
+
+    // Paused by deducting idle time, a new start forgets it
+    recorder.start_timing(
+        "client1".to_string(),
+        "project1".to_string(),
+        t0 + Duration::seconds(60),
+    );
+    recorder.stop_timing_deducting_idle(t0 + Duration::seconds(70), t0 + Duration::seconds(80));
+    assert_eq!(recorder.paused_timing(), Some(("client1", "project1")));
+    recorder.start_timing(
+        "client2".to_string(),
+        "project2".to_string(),
+        t0 + Duration::seconds(90),
+    );
+    assert_eq!(recorder.paused_timing(), None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_current_elapsed() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut recorder = TimingsRecorder::new(pool.clone(), Duration::zero());
+    let start_time = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    assert_eq!(recorder.current_elapsed(start_time), None);
+
+    recorder.start_timing("client1".to_string(), "project1".to_string(), start_time);
+    call_keep_alives(&mut recorder, start_time, start_time + Duration::minutes(5));
+    assert_eq!(
+        recorder.current_elapsed(start_time + Duration::minutes(5)),
+        Some(Duration::minutes(5))
+    );
+
+    // Reading past the keep-alive timeout does not split the timing
+    let late = start_time + Duration::minutes(10);
+    assert_eq!(recorder.current_elapsed(late), Some(Duration::minutes(10)));
+    assert_eq!(recorder.current_timing().unwrap().start, start_time);
+
+    recorder.stop_timing(start_time + Duration::minutes(6));
+    assert_eq!(recorder.current_elapsed(late), None);
+
+    Ok(())
+}
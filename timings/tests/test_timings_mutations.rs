@@ -0,0 +1,786 @@
+mod common;
+
+use chrono::Duration;
+use chrono::FixedOffset;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use common::setup_test_db;
+use sqlx::SqliteConnection;
+use std::collections::HashMap;
+use timings::Error;
+use timings::GetTimingsFilters;
+use timings::SummaryForDay;
+use timings::Timing;
+use timings::TimingChange;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+
+#[tokio::test]
+async fn test_insert_timings_refuses_inverted_timing() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+
+    let result = conn
+        .insert_timings(&[Timing {
+            client: "client1".to_string(),
+            project: "project1".to_string(),
+            start,
+            end: start - Duration::minutes(2),
+        }])
+        .await;
+    assert!(matches!(result, Err(Error::InvalidTiming(_))));
+    assert!(conn.get_timings(None).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_insert_timings_and_summaries_is_atomic() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let day = NaiveDate::from_ymd_opt(2020, 5, 5).unwrap();
+    let summary = SummaryForDay {
+        day,
+        client: "client1".to_string(),
+        project: "project1".to_string(),
+        summary: "Layout".to_string(),
+        archived: false,
+    };
+
+    // Summaries are inserted first, the failing timing rolls them back
+    let result = conn
+        .insert_timings_and_summaries(
+            &[Timing {
+                client: "client1".to_string(),
+                project: "project1".to_string(),
+                start,
+                end: start - Duration::minutes(2),
+            }],
+            None,
+            tz,
+            &[summary.clone()],
+        )
+        .await;
+    assert!(matches!(result, Err(Error::InvalidTiming(_))));
+    assert!(conn.get_timings(None).await?.is_empty());
+    assert!(
+        conn.get_timings_daily_summaries(tz, day, day, None, None)
+            .await?
+            .is_empty()
+    );
+
+    conn.insert_timings_and_summaries(
+        &[Timing {
+            client: "client1".to_string(),
+            project: "project1".to_string(),
+            start,
+            end: start + Duration::minutes(2),
+        }],
+        None,
+        tz,
+        &[summary],
+    )
+    .await?;
+    assert_eq!(conn.get_timings(None).await?.len(), 1);
+    let summaries = conn
+        .get_timings_daily_summaries(tz, day, day, None, None)
+        .await?;
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].summary, "Layout");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_insert_timings_same_project_and_start_updates_end()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let timing = |end: Duration| Timing {
+        client: "client1".to_string(),
+        project: "project1".to_string(),
+        start,
+        end: start + end,
+    };
+
+    // Same `(project, start)` is the same timing, the running timing is written
+    // this way repeatedly
+    conn.insert_timings(&[timing(Duration::minutes(1))]).await?;
+    conn.insert_timings(&[timing(Duration::minutes(5))]).await?;
+
+    let timings = conn.get_timings(None).await?;
+    assert_eq!(timings.len(), 1);
+    assert_eq!(timings[0].end, start + Duration::minutes(5));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_insert_manual_timing_writes_audit_entry() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let now = Utc.with_ymd_and_hms(2020, 5, 5, 14, 0, 0).unwrap();
+    let timing = Timing {
+        client: "client1".to_string(),
+        project: "project1".to_string(),
+        start,
+        end: start + Duration::minutes(90),
+    };
+
+    conn.insert_manual_timing(&timing, Some("laptop"), now)
+        .await?;
+    assert_eq!(conn.get_timings(None).await?, vec![timing.clone()]);
+    assert_eq!(conn.get_timing_sources().await?, vec!["laptop"]);
+
+    let audit = conn.get_timing_audit(10).await?;
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].change, TimingChange::Insert);
+    assert_eq!(audit[0].changed_at, now);
+    assert_eq!(audit[0].old, None);
+    assert_eq!(audit[0].new, Some(timing.clone()));
+
+    // Inserting onto the timing updates it, with the normalized names
+    let longer = Timing {
+        client: " client1".to_string(),
+        end: start + Duration::hours(2),
+        ..timing.clone()
+    };
+    conn.insert_manual_timing(&longer, None, now).await?;
+    let longer = Timing {
+        client: "client1".to_string(),
+        ..longer
+    };
+    assert_eq!(conn.get_timings(None).await?, vec![longer.clone()]);
+    let audit = conn.get_timing_audit(10).await?;
+    assert_eq!(audit.len(), 2);
+    assert_eq!(audit[0].change, TimingChange::Update);
+    assert_eq!(audit[0].old, Some(timing));
+    assert_eq!(audit[0].new, Some(longer));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_delete_timing_writes_audit_entry() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let now = Utc.with_ymd_and_hms(2020, 5, 6, 9, 0, 0).unwrap();
+    let timing = Timing {
+        client: "client1".to_string(),
+        project: "project1".to_string(),
+        start,
+        end: start + Duration::minutes(30),
+    };
+    conn.insert_timings(std::slice::from_ref(&timing)).await?;
+
+    // Only the client, project and start identify the timing
+    conn.delete_timing(
+        &Timing {
+            end: start,
+            ..timing.clone()
+        },
+        now,
+    )
+    .await?;
+    assert!(conn.get_timings(None).await?.is_empty());
+
+    let audit = conn.get_timing_audit(10).await?;
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].change, TimingChange::Delete);
+    assert_eq!(audit[0].changed_at, now);
+    assert_eq!(audit[0].old, Some(timing.clone()));
+    assert_eq!(audit[0].new, None);
+
+    // Deleting again is refused and not audited
+    let result = conn.delete_timing(&timing, now).await;
+    assert!(matches!(result, Err(Error::InvalidTiming(_))));
+    assert_eq!(conn.get_timing_audit(10).await?.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_delete_timing_at_start() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let now = Utc.with_ymd_and_hms(2020, 5, 6, 9, 0, 0).unwrap();
+    let timing = |start| Timing {
+        client: "client1".to_string(),
+        project: "project1".to_string(),
+        start,
+        end: start + Duration::minutes(30),
+    };
+    let later = timing(start + Duration::hours(1));
+    conn.insert_timings(&[timing(start), later.clone()]).await?;
+
+    assert!(
+        conn.delete_timing_at("client1", "project1", start, now)
+            .await?
+    );
+    assert_eq!(conn.get_timings(None).await?, vec![later]);
+    assert_eq!(conn.get_timing_audit(10).await?.len(), 1);
+
+    // Nothing to delete
+    assert!(
+        !conn
+            .delete_timing_at("client1", "project1", start, now)
+            .await?
+    );
+    assert!(
+        !conn
+            .delete_timing_at("client1", "unknown", start, now)
+            .await?
+    );
+    assert_eq!(conn.get_timing_audit(10).await?.len(), 1);
+
+    // Deleting the last timing keeps the client and project, no project is
+    // created for the unknown name
+    assert!(
+        conn.delete_timing_at("client1", "project1", start + Duration::hours(1), now)
+            .await?
+    );
+    assert!(conn.get_timings(None).await?.is_empty());
+    let (clients, projects): (i64, i64) =
+        sqlx::query_as("SELECT (SELECT COUNT(*) FROM client), (SELECT COUNT(*) FROM project)")
+            .fetch_one(&mut *conn)
+            .await?;
+    assert_eq!((clients, projects), (1, 1));
+    assert!(conn.find_database_problems().await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_delete_timings_by_filters() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let day = Utc.with_ymd_and_hms(2020, 5, 5, 0, 0, 0).unwrap();
+    let now = Utc.with_ymd_and_hms(2020, 5, 6, 9, 0, 0).unwrap();
+    let timing = |client: &str, project: &str, hour| Timing {
+        client: client.to_string(),
+        project: project.to_string(),
+        start: day + Duration::hours(hour),
+        end: day + Duration::hours(hour) + Duration::minutes(30),
+    };
+    conn.insert_timings(&[
+        timing("Wrong", "Website", 9),
+        timing("Wrong", "Website", 10),
+        timing("Wrong", "Backend", 11),
+        timing("Acme", "Website", 12),
+    ])
+    .await?;
+    conn.insert_timings_daily_summaries(
+        Utc,
+        &[SummaryForDay {
+            day: NaiveDate::from_ymd_opt(2020, 5, 5).unwrap(),
+            client: "Wrong".to_string(),
+            project: "Backend".to_string(),
+            summary: "Kept".to_string(),
+            archived: false,
+        }],
+    )
+    .await?;
+
+    // Nothing matches
+    let filters = |client: &str, from_hour| GetTimingsFilters {
+        client: Some(client.to_string()),
+        from: Some(day + Duration::hours(from_hour)),
+        ..Default::default()
+    };
+    assert_eq!(
+        conn.delete_timings(filters("Nobody", 0), true, now).await?,
+        0
+    );
+
+    // Timings starting from 10 of the wrong client
+    assert_eq!(
+        conn.delete_timings(filters("Wrong", 10), false, now)
+            .await?,
+        2
+    );
+    assert_eq!(
+        conn.get_timings(None).await?,
+        vec![timing("Acme", "Website", 12), timing("Wrong", "Website", 9)]
+    );
+    assert_eq!(conn.get_timing_audit(10).await?.len(), 2);
+    assert_eq!(conn.get_counts().await?, (2, 3));
+
+    // Unused projects and clients are removed, the project with a summary
+    // and the client of it are kept
+    assert_eq!(
+        conn.delete_timings(filters("Wrong", 0), true, now).await?,
+        1
+    );
+    assert_eq!(
+        conn.get_timings(None).await?,
+        vec![timing("Acme", "Website", 12)]
+    );
+    assert_eq!(conn.get_counts().await?, (2, 2));
+    assert!(conn.find_database_problems().await?.is_empty());
+
+    // Last timings of the client
+    conn.insert_timings(&[timing("Gone", "Docs", 14)]).await?;
+    assert_eq!(conn.delete_timings(filters("Gone", 0), true, now).await?, 1);
+    assert_eq!(conn.get_counts().await?, (2, 2));
+
+    // Page of timings, the same as listed with the filters
+    conn.insert_timings(&[
+        timing("Acme", "Website", 13),
+        timing("Acme", "Website", 14),
+        timing("Acme", "Website", 15),
+    ])
+    .await?;
+    let page = GetTimingsFilters {
+        limit: Some(2),
+        offset: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(
+        conn.get_timings(Some(page.clone())).await?,
+        vec![timing("Acme", "Website", 14), timing("Acme", "Website", 13)]
+    );
+    assert_eq!(conn.delete_timings(page, false, now).await?, 2);
+    assert_eq!(
+        conn.get_timings(None).await?,
+        vec![timing("Acme", "Website", 15), timing("Acme", "Website", 12)]
+    );
+
+    // Names are normalized when listing and deleting alike
+    let unnormalized = GetTimingsFilters {
+        client: Some(" Acme ".to_string()),
+        projects: Some(vec!["Web site".to_string(), "Website  ".to_string()]),
+        ..Default::default()
+    };
+    assert_eq!(
+        conn.get_timings(Some(unnormalized.clone())).await?,
+        vec![timing("Acme", "Website", 15), timing("Acme", "Website", 12)]
+    );
+    assert_eq!(conn.delete_timings(unnormalized, false, now).await?, 2);
+    assert!(conn.get_timings(None).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_timing_writes_audit_entry() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let now = Utc.with_ymd_and_hms(2020, 5, 6, 9, 0, 0).unwrap();
+    let old = Timing {
+        client: "client1".to_string(),
+        project: "project1".to_string(),
+        start,
+        end: start + Duration::minutes(30),
+    };
+    let new = Timing {
+        client: "client1".to_string(),
+        project: "project2".to_string(),
+        start: start - Duration::minutes(15),
+        end: start + Duration::minutes(20),
+    };
+    conn.insert_timings(std::slice::from_ref(&old)).await?;
+
+    let inverted = Timing {
+        end: new.start,
+        ..new.clone()
+    };
+    let result = conn.update_timing(&old, &inverted, now).await;
+    assert!(matches!(result, Err(Error::InvalidTiming(_))));
+
+    conn.update_timing(&old, &new, now).await?;
+    assert_eq!(conn.get_timings(None).await?, vec![new.clone()]);
+
+    let audit = conn.get_timing_audit(10).await?;
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].change, TimingChange::Update);
+    assert_eq!(audit[0].old, Some(old));
+    assert_eq!(audit[0].new, Some(new));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_update_timing_by_id() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let now = Utc.with_ymd_and_hms(2020, 5, 6, 9, 0, 0).unwrap();
+    let timing = |project: &str, minutes: i64| Timing {
+        client: "client1".to_string(),
+        project: project.to_string(),
+        start: start + Duration::minutes(minutes),
+        end: start + Duration::minutes(minutes + 30),
+    };
+    conn.insert_timings(&[timing("project1", 0), timing("project1", 60)])
+        .await?;
+
+    let rows = conn.get_timings_with_ids(None).await?;
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].timing, timing("project1", 60));
+    let id = rows[1].id;
+
+    // Moving onto the start of another timing of the project is refused
+    let result = conn
+        .update_timing_by_id(id, &timing("project1", 60), now)
+        .await;
+    assert!(matches!(result, Err(Error::TimingConflict(t)) if t == timing("project1", 60)));
+    let result = conn
+        .update_timing(&timing("project1", 0), &timing("project1", 60), now)
+        .await;
+    assert!(matches!(result, Err(Error::TimingConflict(_))));
+    let result = conn
+        .update_timing_by_id(id + 100, &timing("project1", 10), now)
+        .await;
+    assert!(matches!(result, Err(Error::InvalidTiming(_))));
+    assert!(conn.get_timing_audit(10).await?.is_empty());
+
+    // Same start in another project is fine, the id stays
+    conn.update_timing_by_id(id, &timing("project2", 60), now)
+        .await?;
+    let rows = conn.get_timings_with_ids(None).await?;
+    assert!(
+        rows.iter()
+            .any(|row| row.id == id && row.timing == timing("project2", 60))
+    );
+
+    let audit = conn.get_timing_audit(10).await?;
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].old, Some(timing("project1", 0)));
+    assert_eq!(audit[0].new, Some(timing("project2", 60)));
+
+    Ok(())
+}
+
+/// Hours of the Acme clients on the day, of all projects and of the website
+async fn acme_hours(
+    conn: &mut SqliteConnection,
+    day: NaiveDate,
+) -> Result<(f64, f64), Box<dyn std::error::Error>> {
+    let rows = conn
+        .get_timings_daily_totals(Utc, day, day, Some("Acme%".to_string()), None)
+        .await?;
+    let total = rows.iter().map(|row| row.hours).sum();
+    let website = rows
+        .iter()
+        .filter(|row| row.project == "Website")
+        .map(|row| row.hours)
+        .sum();
+    Ok((total, website))
+}
+
+#[tokio::test]
+async fn test_rename_client_and_project_keep_totals() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let day = NaiveDate::from_ymd_opt(2020, 5, 5).unwrap();
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 9, 0, 0).unwrap();
+    let timing = |client: &str, project: &str, hours, minutes| Timing {
+        client: client.to_string(),
+        project: project.to_string(),
+        start: start + Duration::hours(hours),
+        end: start + Duration::hours(hours) + Duration::minutes(minutes),
+    };
+    let summary = |client: &str, text: &str| SummaryForDay {
+        day,
+        client: client.to_string(),
+        project: "Website".to_string(),
+        summary: text.to_string(),
+        archived: false,
+    };
+    conn.insert_timings(&[
+        timing("Acme", "Website", 0, 60),
+        timing("Acme Corp", "Website", 2, 30),
+        timing("Acme", "Backend", 3, 15),
+        timing("Acme Corp", "Web", 4, 60),
+    ])
+    .await?;
+    conn.insert_timings_daily_summaries(
+        Utc,
+        &[summary("Acme", "Layout"), summary("Acme Corp", "Fonts")],
+    )
+    .await?;
+
+    assert_eq!(acme_hours(&mut conn, day).await?, (2.75, 1.5));
+
+    conn.rename_client("Acme", "Acme Corp").await?;
+    assert_eq!(acme_hours(&mut conn, day).await?, (2.75, 1.5));
+
+    conn.rename_project("Acme Corp", "Web", "Website").await?;
+    assert_eq!(acme_hours(&mut conn, day).await?, (2.75, 2.5));
+
+    let summaries = conn
+        .get_timings_daily_summaries(Utc, day, day, None, None)
+        .await?;
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].client, "Acme Corp");
+    assert_eq!(summaries[0].summary, "Fonts\nLayout", "Texts are joined");
+    assert_eq!(conn.get_counts().await?, (1, 2));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_rename_map_merges() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let timing = |client: &str, project: &str, start, minutes| Timing {
+        client: client.to_string(),
+        project: project.to_string(),
+        start,
+        end: start + Duration::minutes(minutes),
+    };
+    conn.insert_timings(&[
+        timing("ACME Inc", "Website", start, 30),
+        timing("Acme", "Website", start, 45),
+        timing("Acme", "Web site", start + Duration::hours(2), 15),
+        timing("Other", "Support", start + Duration::hours(4), 10),
+    ])
+    .await?;
+    let summary = |client: &str, project: &str, text: &str| SummaryForDay {
+        day: NaiveDate::from_ymd_opt(2020, 5, 5).unwrap(),
+        client: client.to_string(),
+        project: project.to_string(),
+        summary: text.to_string(),
+        archived: false,
+    };
+    conn.insert_timings_daily_summaries(
+        Utc,
+        &[
+            summary("ACME Inc", "Website", "Layout"),
+            summary("Acme", "Website", "Fonts"),
+        ],
+    )
+    .await?;
+
+    conn.apply_rename_map(
+        HashMap::from([("ACME Inc".to_string(), "Acme".to_string())]),
+        HashMap::from([(
+            ("Acme".to_string(), "Web site".to_string()),
+            ("Acme".to_string(), "Website".to_string()),
+        )]),
+    )
+    .await?;
+
+    // Timings of the same start are combined keeping the later end
+    let mut timings = conn.get_timings(None).await?;
+    timings.sort_by_key(|timing| timing.start);
+    assert_eq!(
+        timings,
+        vec![
+            timing("Acme", "Website", start, 45),
+            timing("Acme", "Website", start + Duration::hours(2), 15),
+            timing("Other", "Support", start + Duration::hours(4), 10),
+        ]
+    );
+
+    let day = NaiveDate::from_ymd_opt(2020, 5, 5).unwrap();
+    let summaries = conn
+        .get_timings_daily_summaries(Utc, day, day, None, None)
+        .await?;
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].client, "Acme");
+    assert_eq!(summaries[0].summary, "Fonts\nLayout");
+
+    let clients: Vec<(String,)> = sqlx::query_as("SELECT name FROM client ORDER BY name")
+        .fetch_all(&mut *conn)
+        .await?;
+    assert_eq!(
+        clients,
+        vec![("Acme".to_string(),), ("Other".to_string(),)],
+        "Merged client is deleted"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_rename_map_refuses_chains() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start = Utc.with_ymd_and_hms(2020, 5, 5, 12, 0, 0).unwrap();
+    let timing = |client: &str, project: &str, start| Timing {
+        client: client.to_string(),
+        project: project.to_string(),
+        start,
+        end: start + Duration::minutes(30),
+    };
+    conn.insert_timings(&[
+        timing("Acme", "Website", start),
+        timing("Beta", "Website", start + Duration::hours(1)),
+    ])
+    .await?;
+
+    let result = conn
+        .apply_rename_map(
+            HashMap::from([
+                ("Acme".to_string(), "Beta".to_string()),
+                ("Beta".to_string(), "Gamma".to_string()),
+            ]),
+            HashMap::new(),
+        )
+        .await;
+    assert!(matches!(result, Err(Error::InvalidRename(_))));
+
+    let result = conn
+        .apply_rename_map(
+            HashMap::new(),
+            HashMap::from([
+                (
+                    ("Acme".to_string(), "Website".to_string()),
+                    ("Acme".to_string(), "Web site".to_string()),
+                ),
+                (
+                    ("Acme".to_string(), "Web site".to_string()),
+                    ("Acme".to_string(), "Website".to_string()),
+                ),
+            ]),
+        )
+        .await;
+    assert!(matches!(result, Err(Error::InvalidRename(_))));
+
+    // Nothing was renamed
+    let mut timings = conn.get_timings(None).await?;
+    timings.sort_by_key(|timing| timing.start);
+    assert_eq!(
+        timings,
+        vec![
+            timing("Acme", "Website", start),
+            timing("Beta", "Website", start + Duration::hours(1)),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shift_day_attribution_moves_summaries() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let helsinki = FixedOffset::east_opt(2 * 3600).unwrap();
+    let new_york = FixedOffset::west_opt(5 * 3600).unwrap();
+    let day = |d| NaiveDate::from_ymd_opt(2020, 5, d).unwrap();
+    let summary = |d, text: &str| SummaryForDay {
+        day: day(d),
+        client: "Acme".to_string(),
+        project: "Website".to_string(),
+        summary: text.to_string(),
+        archived: false,
+    };
+
+    // Written while the machine ran in the old timezone
+    conn.insert_timings_daily_summaries(helsinki, &[summary(4, "Layout"), summary(5, "Fonts")])
+        .await?;
+    // Written after moving, collides with the shifted summary of the 5th
+    conn.insert_timings_daily_summaries(new_york, &[summary(5, "Images")])
+        .await?;
+
+    let dry_run = conn
+        .shift_day_attribution(day(4), day(5), helsinki, new_york, true)
+        .await?;
+    assert_eq!(dry_run.len(), 2);
+    assert_eq!(dry_run[0].day, day(4));
+    assert_eq!(dry_run[0].summary, "Layout");
+    assert_eq!(
+        dry_run[0].new_start,
+        Utc.with_ymd_and_hms(2020, 5, 4, 5, 0, 0).unwrap()
+    );
+    let mut summaries = conn
+        .get_timings_daily_summaries(new_york, day(1), day(10), None, None)
+        .await?;
+    summaries.sort_by_key(|summary| summary.day);
+    assert_eq!(summaries.len(), 3, "Dry run changes nothing");
+    assert_eq!(
+        summaries[0].day,
+        day(3),
+        "Old midnight is the evening before"
+    );
+
+    let shifted = conn
+        .shift_day_attribution(day(4), day(5), helsinki, new_york, false)
+        .await?;
+    assert_eq!(shifted, dry_run);
+
+    let mut summaries = conn
+        .get_timings_daily_summaries(new_york, day(1), day(10), None, None)
+        .await?;
+    summaries.sort_by_key(|summary| summary.day);
+    let days_and_texts: Vec<_> = summaries
+        .iter()
+        .map(|summary| (summary.day, summary.summary.as_str()))
+        .collect();
+    assert_eq!(
+        days_and_texts,
+        vec![(day(4), "Layout"), (day(5), "Images\nFonts")]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_insert_timings_with_source() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let start = Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap();
+    let timing = |hour: i64| Timing {
+        client: "Acme".to_string(),
+        project: "Website".to_string(),
+        start: start + Duration::hours(hour),
+        end: start + Duration::hours(hour) + Duration::minutes(30),
+    };
+    let by_source = |source: Option<&str>| {
+        Some(GetTimingsFilters {
+            source: source.map(str::to_string),
+            ..Default::default()
+        })
+    };
+
+    conn.insert_timings_with_source(&[timing(0), timing(1)], Some("laptop"))
+        .await?;
+    conn.insert_timings_with_source(&[timing(2)], Some("desktop"))
+        .await?;
+    conn.insert_timings(&[timing(3)]).await?;
+    assert_eq!(
+        conn.get_timing_sources().await?,
+        vec!["desktop".to_string(), "laptop".to_string()]
+    );
+
+    assert_eq!(
+        conn.get_timings(by_source(Some("laptop"))).await?,
+        vec![timing(1), timing(0)]
+    );
+    assert_eq!(
+        conn.get_timings(by_source(Some("desktop"))).await?,
+        vec![timing(2)]
+    );
+    assert!(conn.get_timings(by_source(Some("phone"))).await?.is_empty());
+    assert_eq!(conn.get_timings(by_source(None)).await?.len(), 4);
+
+    // Updating the end without a source keeps the source
+    let mut extended = timing(2);
+    extended.end += Duration::minutes(10);
+    conn.insert_timings(&[extended.clone()]).await?;
+    assert_eq!(
+        conn.get_timings(by_source(Some("desktop"))).await?,
+        vec![extended]
+    );
+
+    let now = start + Duration::days(1);
+    let laptop = by_source(Some("laptop")).unwrap();
+    assert_eq!(conn.delete_timings(laptop, false, now).await?, 2);
+    assert_eq!(
+        conn.get_timing_sources().await?,
+        vec!["desktop".to_string()]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,958 @@
+mod common;
+
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use common::setup_test_db;
+use common::timing;
+use timings::GetTimingsFilters;
+use timings::ReportRow;
+use timings::SummaryForDay;
+use timings::Timing;
+use timings::TimingOfDay;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+
+#[tokio::test]
+async fn test_hourly_distribution_splits_timing_across_hours()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.insert_timings(&[
+        // 09:30 - 10:15 spans two hour buckets
+        timing("Acme", "Website", (4, 9, 30), (4, 10, 15)),
+        timing("Acme", "Backend", (5, 14, 0), (5, 15, 0)),
+    ])
+    .await?;
+
+    let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    let hours = conn.get_hourly_distribution(Utc, day, day).await?;
+    assert!((hours[9] - 0.5).abs() < 1e-9);
+    assert!((hours[10] - 0.25).abs() < 1e-9);
+    assert_eq!(hours[14], 0.0, "Other day must not be included");
+
+    let hours = conn
+        .get_hourly_distribution(Utc, day, day.succ_opt().unwrap())
+        .await?;
+    assert!((hours[14] - 1.0).abs() < 1e-9);
+    assert!((hours.iter().sum::<f64>() - 1.75).abs() < 1e-9);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_get_timings_pages_newest_first() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 10, 0)),
+        timing("Acme", "Backend", (4, 11, 0), (4, 12, 0)),
+        // Same start, ordered by project
+        timing("Acme", "Website", (4, 13, 0), (4, 14, 0)),
+        timing("Acme", "Backend", (4, 13, 0), (4, 14, 0)),
+        timing("Other", "Support", (4, 15, 0), (4, 16, 0)),
+    ])
+    .await?;
+
+    let page = |limit, offset| {
+        Some(GetTimingsFilters {
+            client: Some("Acme".to_string()),
+            limit: Some(limit),
+            offset,
+            ..Default::default()
+        })
+    };
+    assert_eq!(
+        conn.get_timings(page(2, None)).await?,
+        vec![
+            timing("Acme", "Backend", (4, 13, 0), (4, 14, 0)),
+            timing("Acme", "Website", (4, 13, 0), (4, 14, 0)),
+        ]
+    );
+    assert_eq!(
+        conn.get_timings(page(2, Some(2))).await?,
+        vec![
+            timing("Acme", "Backend", (4, 11, 0), (4, 12, 0)),
+            timing("Acme", "Website", (4, 9, 0), (4, 10, 0)),
+        ]
+    );
+    assert!(conn.get_timings(page(2, Some(4))).await?.is_empty());
+
+    // Offset without a limit skips the newest
+    let rest = GetTimingsFilters {
+        offset: Some(4),
+        ..Default::default()
+    };
+    assert_eq!(
+        conn.get_timings(Some(rest)).await?,
+        vec![timing("Acme", "Website", (4, 9, 0), (4, 10, 0))]
+    );
+
+    // Counted without the limit and the offset
+    assert_eq!(conn.count_timings(page(2, Some(2))).await?, 4);
+    assert_eq!(conn.count_timings(None).await?, 5);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_filters_by_any_of_the_names() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 10, 0)),
+        timing("Globex", "Website", (4, 10, 0), (4, 11, 0)),
+        timing("Initech", "Backend", (4, 11, 0), (4, 12, 0)),
+        timing("Umbrella", "Website", (4, 12, 0), (4, 13, 0)),
+    ])
+    .await?;
+    let summary = |client: &str, project: &str| SummaryForDay {
+        day: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap(),
+        client: client.to_string(),
+        project: project.to_string(),
+        summary: "Work".to_string(),
+        archived: false,
+    };
+    conn.insert_timings_daily_summaries(
+        Utc,
+        &[
+            summary("Acme", "Website"),
+            summary("Globex", "Website"),
+            summary("Umbrella", "Website"),
+        ],
+    )
+    .await?;
+
+    let names = |names: &[&str]| -> Option<Vec<String>> {
+        Some(names.iter().map(|name| name.to_string()).collect())
+    };
+    let umbrella = names(&["Acme", "Globex", "Initech"]);
+    let filters = |clients, projects| {
+        Some(GetTimingsFilters {
+            clients,
+            projects,
+            ..Default::default()
+        })
+    };
+    let clients_of = |timings: Vec<Timing>| -> Vec<String> {
+        timings.into_iter().map(|timing| timing.client).collect()
+    };
+
+    assert_eq!(
+        clients_of(conn.get_timings(filters(umbrella.clone(), None)).await?),
+        vec!["Initech", "Globex", "Acme"]
+    );
+    assert_eq!(
+        clients_of(
+            conn.get_timings(filters(umbrella.clone(), names(&["Website"])))
+                .await?
+        ),
+        vec!["Globex", "Acme"]
+    );
+    assert_eq!(
+        conn.count_timings(filters(umbrella.clone(), None)).await?,
+        3
+    );
+
+    // Empty lists are like no filter, not like matching nothing
+    assert_eq!(
+        conn.count_timings(filters(names(&[]), names(&[]))).await?,
+        4
+    );
+
+    let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    let totals = conn
+        .get_timings_daily_totals_in(Utc, day, day, umbrella.clone(), None)
+        .await?;
+    assert_eq!(
+        totals.iter().map(|t| t.client.as_str()).collect::<Vec<_>>(),
+        vec!["Acme", "Globex", "Initech"]
+    );
+    assert!((totals.iter().map(|t| t.hours).sum::<f64>() - 3.0).abs() < 1e-9);
+    let totals = conn
+        .get_timings_daily_totals_in(Utc, day, day, names(&[]), None)
+        .await?;
+    assert_eq!(totals.len(), 4);
+
+    let summaries = conn
+        .get_timings_daily_summaries_in(Utc, day, day, umbrella, None)
+        .await?;
+    assert_eq!(
+        summaries
+            .iter()
+            .map(|s| s.client.as_str())
+            .collect::<Vec<_>>(),
+        vec!["Acme", "Globex"]
+    );
+    let summaries = conn
+        .get_timings_daily_summaries_in(Utc, day, day, None, names(&[]))
+        .await?;
+    assert_eq!(summaries.len(), 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_tracking_gaps() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.insert_timings(&[
+        // Started before work hours
+        timing("Acme", "Website", (4, 7, 30), (4, 10, 0)),
+        // Short 5 minute gap is ignored
+        timing("Acme", "Website", (4, 10, 5), (4, 12, 0)),
+        // Gap 12:00 - 13:30
+        timing("Acme", "Backend", (4, 13, 30), (4, 16, 0)),
+        // Overlapping timing within the previous one
+        timing("Acme", "Website", (4, 15, 0), (4, 15, 30)),
+        timing("Acme", "Website", (4, 15, 50), (4, 17, 0)),
+    ])
+    .await?;
+
+    let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    let gaps = conn
+        .find_tracking_gaps(
+            day,
+            Utc,
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            Duration::minutes(15),
+        )
+        .await?;
+    assert_eq!(
+        gaps,
+        vec![(
+            Utc.with_ymd_and_hms(2024, 3, 4, 12, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 3, 4, 13, 30, 0).unwrap(),
+        )]
+    );
+
+    // Untracked end of the work day
+    let gaps = conn
+        .find_tracking_gaps(
+            day,
+            Utc,
+            NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+            Duration::minutes(15),
+        )
+        .await?;
+    assert_eq!(gaps.len(), 2);
+    assert_eq!(
+        gaps[0],
+        (
+            Utc.with_ymd_and_hms(2024, 3, 4, 17, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 3, 4, 18, 0, 0).unwrap(),
+        )
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_latest_tracking_gap() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 8, 0), (4, 9, 0)),
+        // Gap 9:00 - 10:00
+        timing("Acme", "Website", (4, 10, 0), (4, 11, 0)),
+        // Gap 11:00 - 12:00
+        timing("Acme", "Backend", (4, 12, 0), (4, 12, 30)),
+    ])
+    .await?;
+
+    let work_start = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+    let work_end = NaiveTime::from_hms_opt(17, 0, 0).unwrap();
+    let latest = |hour, minute| Utc.with_ymd_and_hms(2024, 3, 4, hour, minute, 0).unwrap();
+
+    // Running timing started at 13:10, the gap after the last timing is cut
+    let gap = conn
+        .find_latest_tracking_gap(
+            latest(13, 10),
+            Utc,
+            work_start,
+            work_end,
+            Duration::minutes(15),
+        )
+        .await?;
+    assert_eq!(gap, Some((latest(12, 30), latest(13, 10))));
+
+    // Too short gap after the last timing, the previous gap is the latest
+    let gap = conn
+        .find_latest_tracking_gap(
+            latest(12, 40),
+            Utc,
+            work_start,
+            work_end,
+            Duration::minutes(15),
+        )
+        .await?;
+    assert_eq!(gap, Some((latest(11, 0), latest(12, 0))));
+
+    // Gap starting after the given time is not yet a gap
+    let gap = conn
+        .find_latest_tracking_gap(
+            latest(9, 10),
+            Utc,
+            work_start,
+            work_end,
+            Duration::minutes(15),
+        )
+        .await?;
+    assert_eq!(gap, None);
+
+    // Gap of exactly the minimum is not a gap, whether cut or not
+    let gap = conn
+        .find_latest_tracking_gap(
+            latest(12, 45),
+            Utc,
+            work_start,
+            work_end,
+            Duration::minutes(15),
+        )
+        .await?;
+    assert_eq!(gap, Some((latest(11, 0), latest(12, 0))));
+    let gap = conn
+        .find_latest_tracking_gap(
+            latest(13, 10),
+            Utc,
+            work_start,
+            work_end,
+            Duration::minutes(60),
+        )
+        .await?;
+    assert_eq!(gap, None);
+    let gaps = conn
+        .find_tracking_gaps(
+            latest(0, 0).date_naive(),
+            Utc,
+            work_start,
+            NaiveTime::from_hms_opt(12, 30, 0).unwrap(),
+            Duration::minutes(60),
+        )
+        .await?;
+    assert!(gaps.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_projects_by_total_descending_and_limited() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 10, 0)),
+        timing("Acme", "Website", (5, 9, 0), (5, 10, 30)),
+        timing("Acme", "Backend", (4, 12, 0), (4, 15, 0)),
+        timing("Other", "Docs", (5, 12, 0), (5, 12, 30)),
+        // Outside the range
+        timing("Other", "Docs", (12, 9, 0), (12, 17, 0)),
+    ])
+    .await?;
+
+    let from = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    let to = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+    let top = conn.get_projects_by_total(from, to, Utc, 2).await?;
+    assert_eq!(
+        top,
+        vec![
+            ("Acme".to_string(), "Backend".to_string(), 3.0),
+            ("Acme".to_string(), "Website".to_string(), 2.5),
+        ]
+    );
+
+    let all = conn.get_projects_by_total(from, to, Utc, 10).await?;
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[2], ("Other".to_string(), "Docs".to_string(), 0.5));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_grand_total_sums_all_timings() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    assert_eq!(conn.get_grand_total().await?, Duration::zero());
+
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 10, 0)),
+        timing("Acme", "Backend", (4, 12, 0), (4, 15, 30)),
+        timing("Other", "Docs", (12, 9, 0), (12, 9, 15)),
+    ])
+    .await?;
+    assert_eq!(
+        conn.get_grand_total().await?,
+        Duration::hours(4) + Duration::minutes(45)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_counts_of_clients_and_projects() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    assert_eq!(conn.get_counts().await?, (0, 0));
+
+    // Acme is shared, Website is a separate project of both clients
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 10, 0)),
+        timing("Acme", "Backend", (4, 12, 0), (4, 15, 30)),
+        timing("Acme", "Website", (5, 9, 0), (5, 10, 0)),
+        timing("Other", "Website", (12, 9, 0), (12, 9, 15)),
+    ])
+    .await?;
+    assert_eq!(conn.get_counts().await?, (2, 3));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_implausible_days_detects_broken_day() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 17, 0)),
+        // Clock jumped while tracking, the day sums to 27 hours
+        timing("Acme", "Website", (5, 1, 0), (5, 20, 0)),
+        timing("Acme", "Backend", (5, 8, 0), (5, 16, 0)),
+        timing("Other", "Docs", (6, 9, 0), (6, 12, 0)),
+    ])
+    .await?;
+
+    let from = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    let to = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+    let days = conn.get_implausible_days(Utc, from, to, 16.0).await?;
+    assert_eq!(days.len(), 1);
+    assert_eq!(days[0].day, NaiveDate::from_ymd_opt(2024, 3, 5).unwrap());
+    assert_eq!(days[0].hours, 27.0);
+    assert_eq!(
+        days[0].timings,
+        vec![
+            timing("Acme", "Website", (5, 1, 0), (5, 20, 0)),
+            timing("Acme", "Backend", (5, 8, 0), (5, 16, 0)),
+        ]
+    );
+
+    assert!(
+        conn.get_implausible_days(Utc, from, to, 27.0)
+            .await?
+            .is_empty()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_timings_spanning_midnights() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.insert_timings(&[
+        // Over one midnight is plausible, e.g. working late
+        timing("Acme", "Website", (4, 22, 0), (5, 1, 0)),
+        timing("Acme", "Backend", (6, 23, 0), (8, 2, 0)),
+    ])
+    .await?;
+
+    let from = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    let to = NaiveDate::from_ymd_opt(2024, 3, 6).unwrap();
+    assert_eq!(
+        conn.get_timings_spanning_midnights(Utc, from, to).await?,
+        vec![timing("Acme", "Backend", (6, 23, 0), (8, 2, 0))]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_timings_for_day_clips_midnight_spanner() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 22, 0), (5, 1, 30)),
+        timing("Acme", "Website", (5, 9, 0), (5, 10, 0)),
+        // Other project and day
+        timing("Acme", "Backend", (5, 11, 0), (5, 12, 0)),
+        timing("Acme", "Website", (6, 9, 0), (6, 10, 0)),
+    ])
+    .await?;
+
+    let day = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+    assert_eq!(
+        conn.get_timings_for_day(Utc, day, "Acme", "Website")
+            .await?,
+        vec![
+            TimingOfDay {
+                timing: timing("Acme", "Website", (5, 9, 0), (5, 10, 0)),
+                duration: Duration::hours(1),
+            },
+            TimingOfDay {
+                timing: timing("Acme", "Website", (4, 22, 0), (5, 1, 30)),
+                duration: Duration::minutes(90),
+            },
+        ]
+    );
+
+    // Names are normalized like when inserting
+    assert_eq!(
+        conn.get_timings_for_day(Utc, day, " Acme", "Website  ")
+            .await?
+            .len(),
+        2
+    );
+
+    // Parts sum up to the daily total of the cell
+    let totals = conn
+        .get_timings_daily_totals(
+            Utc,
+            day,
+            day,
+            Some("Acme".to_string()),
+            Some("Website".to_string()),
+        )
+        .await?;
+    assert_eq!(totals.len(), 1);
+    assert_eq!(totals[0].hours, 2.5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_recent_distinct_summaries() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let summary = |day, project: &str, text: &str| SummaryForDay {
+        day: NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+        client: "Acme".to_string(),
+        project: project.to_string(),
+        summary: text.to_string(),
+        archived: false,
+    };
+    conn.insert_timings_daily_summaries(
+        Utc,
+        &[
+            summary(4, "Website", "Layout"),
+            summary(5, "Website", "Fonts"),
+            summary(6, "Website", "Layout"),
+            summary(7, "Website", "Review"),
+            summary(8, "Website", "Fonts"),
+            summary(9, "Backend", "Database"),
+        ],
+    )
+    .await?;
+
+    assert_eq!(
+        conn.get_recent_distinct_summaries("Acme", "Website", 10)
+            .await?,
+        vec!["Fonts", "Review", "Layout"]
+    );
+    assert_eq!(
+        conn.get_recent_distinct_summaries("Acme", "Website", 2)
+            .await?,
+        vec!["Fonts", "Review"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_full_report_combines_hours_and_summaries() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 10, 0)),
+        timing("Acme", "Website", (4, 13, 0), (4, 13, 30)),
+        timing("Acme", "Backend", (4, 14, 0), (4, 16, 0)),
+        timing("Acme", "Website", (5, 9, 0), (5, 9, 45)),
+        // Outside of the range
+        timing("Acme", "Website", (8, 9, 0), (8, 10, 0)),
+    ])
+    .await?;
+    conn.insert_timings_daily_summaries(
+        Utc,
+        &[
+            SummaryForDay {
+                day: day(4),
+                client: "Acme".to_string(),
+                project: "Website".to_string(),
+                summary: "Layout".to_string(),
+                archived: true,
+            },
+            // Day without timings of the project is not a row
+            SummaryForDay {
+                day: day(6),
+                client: "Acme".to_string(),
+                project: "Website".to_string(),
+                summary: "Fonts".to_string(),
+                archived: false,
+            },
+        ],
+    )
+    .await?;
+
+    let row = |d, project: &str, hours, timings, summary: &str, archived| ReportRow {
+        day: day(d),
+        client: "Acme".to_string(),
+        project: project.to_string(),
+        hours,
+        timings,
+        summary: summary.to_string(),
+        archived,
+        billable: true,
+    };
+    assert_eq!(
+        conn.get_full_report(day(4), day(6), Utc).await?,
+        vec![
+            row(4, "Backend", 2.0, 1, "", false),
+            row(4, "Website", 1.5, 2, "Layout", true),
+            row(5, "Website", 0.75, 1, "", false),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_queries_order_does_not_depend_on_insert_order()
+-> Result<(), Box<dyn std::error::Error>> {
+    let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+    let timings = vec![
+        timing("Zeta", "Api", (4, 9, 0), (4, 10, 0)),
+        timing("Acme", "Website", (4, 9, 0), (4, 9, 30)),
+        timing("Acme", "Backend", (4, 9, 0), (4, 11, 0)),
+        timing("Acme", "Website", (5, 13, 0), (5, 14, 0)),
+        timing("Zeta", "Api", (5, 8, 0), (5, 9, 0)),
+    ];
+    let summaries: Vec<SummaryForDay> = timings
+        .iter()
+        .map(|t| SummaryForDay {
+            day: t.start.date_naive(),
+            client: t.client.clone(),
+            project: t.project.clone(),
+            summary: format!("{} notes", t.project),
+            archived: false,
+        })
+        .collect();
+
+    // Same rows inserted in two different orders
+    let mut results = Vec::new();
+    for reverse in [false, true] {
+        let pool = setup_test_db().await?;
+        let mut conn = pool.acquire().await?;
+        let mut timings = timings.clone();
+        let mut summaries = summaries.clone();
+        if reverse {
+            timings.reverse();
+            summaries.reverse();
+        }
+        for (timing, summary) in timings.iter().zip(&summaries) {
+            conn.insert_timings(std::slice::from_ref(timing)).await?;
+            conn.insert_timings_daily_summaries(Utc, std::slice::from_ref(summary))
+                .await?;
+        }
+
+        let timings = conn
+            .get_timings(None)
+            .await?
+            .into_iter()
+            .map(|t| (t.start, t.client, t.project))
+            .collect::<Vec<_>>();
+        let totals = conn
+            .get_timings_daily_totals(Utc, day(4), day(5), None, None)
+            .await?
+            .into_iter()
+            .map(|t| (t.day, t.client, t.project))
+            .collect::<Vec<_>>();
+        let summaries = conn
+            .get_timings_daily_summaries(Utc, day(4), day(5), None, None)
+            .await?
+            .into_iter()
+            .map(|s| (s.day, s.client, s.project))
+            .collect::<Vec<_>>();
+        let report = conn
+            .get_full_report(day(4), day(5), Utc)
+            .await?
+            .into_iter()
+            .map(|r| (r.day, r.client, r.project))
+            .collect::<Vec<_>>();
+        results.push((timings, totals, summaries, report));
+    }
+    assert_eq!(results[0], results[1]);
+
+    let (timings, totals, summaries, report) = &results[0];
+    let row = |d, client: &str, project: &str| (day(d), client.to_string(), project.to_string());
+    let newest_first = vec![
+        row(5, "Acme", "Website"),
+        row(5, "Zeta", "Api"),
+        row(4, "Acme", "Backend"),
+        row(4, "Acme", "Website"),
+        row(4, "Zeta", "Api"),
+    ];
+    assert_eq!(totals, &newest_first);
+    assert_eq!(summaries, &newest_first);
+    assert_eq!(
+        timings
+            .iter()
+            .map(|(start, client, project)| (start.date_naive(), client.clone(), project.clone()))
+            .collect::<Vec<_>>(),
+        newest_first
+    );
+    // Reports are read oldest day first
+    assert_eq!(
+        report,
+        &vec![
+            row(4, "Acme", "Backend"),
+            row(4, "Acme", "Website"),
+            row(4, "Zeta", "Api"),
+            row(5, "Acme", "Website"),
+            row(5, "Zeta", "Api"),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_weekly_totals_split_at_monday_in_timezone() -> Result<(), Box<dyn std::error::Error>>
+{
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+    let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+
+    // 2024-03-10 is a Sunday, the timing is from 23:00 to 01:00 in UTC+2
+    conn.insert_timings(&[
+        timing("Acme", "Website", (10, 21, 0), (10, 23, 0)),
+        timing("Acme", "Backend", (11, 8, 0), (11, 9, 30)),
+        timing("Zeta", "Api", (12, 8, 0), (12, 9, 0)),
+    ])
+    .await?;
+
+    let weekly = conn
+        .get_timings_weekly_totals(tz, day(4), day(17), None, None)
+        .await?
+        .into_iter()
+        .map(|t| (t.year_week, t.hours, t.client, t.project))
+        .collect::<Vec<_>>();
+    let row = |week: &str, hours, client: &str, project: &str| {
+        (
+            week.to_string(),
+            hours,
+            client.to_string(),
+            project.to_string(),
+        )
+    };
+    assert_eq!(
+        weekly,
+        vec![
+            row("2024-W11", 1.5, "Acme", "Backend"),
+            row("2024-W11", 1.0, "Acme", "Website"),
+            row("2024-W11", 1.0, "Zeta", "Api"),
+            row("2024-W10", 1.0, "Acme", "Website"),
+        ]
+    );
+
+    // In UTC the whole timing is on Sunday
+    let weekly = conn
+        .get_timings_weekly_totals(
+            Utc,
+            day(4),
+            day(17),
+            Some("Acme".to_string()),
+            Some("Web%".to_string()),
+        )
+        .await?
+        .into_iter()
+        .map(|t| (t.year_week, t.hours))
+        .collect::<Vec<_>>();
+    assert_eq!(weekly, vec![("2024-W10".to_string(), 2.0)]);
+
+    // Weeks cut by the range, only Sunday of the first and Monday to
+    // Tuesday of the second
+    let weekly = conn
+        .get_timings_weekly_totals(tz, day(10), day(12), Some("Acme".to_string()), None)
+        .await?
+        .into_iter()
+        .map(|t| (t.year_week, t.hours, t.project))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        weekly,
+        vec![
+            ("2024-W11".to_string(), 1.5, "Backend".to_string()),
+            ("2024-W11".to_string(), 1.0, "Website".to_string()),
+            ("2024-W10".to_string(), 1.0, "Website".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_weekly_timesheet_of_two_weeks() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+
+    // Weeks 2024-W10 from 4th and 2024-W11 from 11th of March
+    conn.insert_timings(&[
+        timing("Acme", "Website", (4, 9, 0), (4, 11, 0)),
+        timing("Acme", "Website", (6, 9, 0), (6, 10, 30)),
+        timing("Acme", "Backend", (7, 13, 0), (7, 14, 0)),
+        timing("Acme", "Website", (12, 9, 0), (12, 12, 0)),
+        timing("Zeta", "Api", (15, 8, 0), (15, 9, 0)),
+    ])
+    .await?;
+    let summary = |d, project: &str, text: &str| SummaryForDay {
+        day: day(d),
+        client: "Acme".to_string(),
+        project: project.to_string(),
+        summary: text.to_string(),
+        archived: false,
+    };
+    conn.insert_timings_daily_summaries(
+        Utc,
+        &[
+            summary(4, "Website", "Layout"),
+            summary(6, "Website", "Fonts"),
+            summary(7, "Backend", "Database"),
+            summary(12, "Website", "Layout"),
+            // No hours in the week
+            summary(14, "Backend", "Planning"),
+        ],
+    )
+    .await?;
+
+    let rows = conn
+        .get_weekly_timesheet(Utc, day(4), day(17))
+        .await?
+        .into_iter()
+        .map(|row| {
+            (
+                row.year_week,
+                row.client,
+                row.project,
+                row.hours,
+                row.summaries,
+            )
+        })
+        .collect::<Vec<_>>();
+    let row = |week: &str, client: &str, project: &str, hours, summaries: &str| {
+        (
+            week.to_string(),
+            client.to_string(),
+            project.to_string(),
+            hours,
+            summaries.to_string(),
+        )
+    };
+    assert_eq!(
+        rows,
+        vec![
+            row("2024-W11", "Acme", "Website", 3.0, "Layout"),
+            row("2024-W11", "Zeta", "Api", 1.0, ""),
+            row("2024-W10", "Acme", "Backend", 1.0, "Database"),
+            row("2024-W10", "Acme", "Website", 3.5, "Layout; Fonts"),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_weekly_totals_use_iso_week_year() -> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let at = |y, m, d, h| Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap();
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+    // Monday 2024-12-30 belongs to the first week of 2025, Sunday 2024-12-29
+    // to the last week of 2024
+    conn.insert_timings(&[
+        Timing {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            start: at(2024, 12, 29, 8),
+            end: at(2024, 12, 29, 10),
+        },
+        Timing {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            start: at(2024, 12, 30, 8),
+            end: at(2024, 12, 30, 9),
+        },
+        Timing {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            start: at(2025, 1, 2, 8),
+            end: at(2025, 1, 2, 11),
+        },
+    ])
+    .await?;
+
+    let weekly = conn
+        .get_timings_weekly_totals(Utc, date(2024, 12, 23), date(2025, 1, 5), None, None)
+        .await?
+        .into_iter()
+        .map(|t| (t.year_week, t.hours))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        weekly,
+        vec![("2025-W01".to_string(), 4.0), ("2024-W52".to_string(), 2.0),]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_monthly_totals_split_at_month_end_in_timezone()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let tz = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+    let date = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+
+    // From 23:00 on March 31 to 03:00 on April 1 in UTC+2
+    conn.insert_timings(&[
+        Timing {
+            client: "Acme".to_string(),
+            project: "Website".to_string(),
+            start: Utc.with_ymd_and_hms(2024, 3, 31, 21, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 4, 1, 1, 0, 0).unwrap(),
+        },
+        timing("Zeta", "Api", (4, 8, 0), (4, 10, 0)),
+    ])
+    .await?;
+
+    let monthly = conn
+        .get_timings_monthly_totals(tz, date(3, 1), date(4, 30), None, None)
+        .await?
+        .into_iter()
+        .map(|t| (t.year, t.month, t.hours, t.client, t.project))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        monthly,
+        vec![
+            (2024, 4, 3.0, "Acme".to_string(), "Website".to_string()),
+            (2024, 3, 1.0, "Acme".to_string(), "Website".to_string()),
+            (2024, 3, 2.0, "Zeta".to_string(), "Api".to_string()),
+        ]
+    );
+
+    // In UTC most of the timing is in March
+    let monthly = conn
+        .get_timings_monthly_totals(
+            Utc,
+            date(3, 1),
+            date(4, 30),
+            Some("Acme".to_string()),
+            Some("Web%".to_string()),
+        )
+        .await?
+        .into_iter()
+        .map(|t| (t.month, t.hours))
+        .collect::<Vec<_>>();
+    assert_eq!(monthly, vec![(4, 1.0), (3, 3.0)]);
+    Ok(())
+}
@@ -0,0 +1,222 @@
+mod common;
+
+use chrono::Duration;
+use chrono::FixedOffset;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use common::setup_test_db;
+use timings::DailyTarget;
+use timings::DailyTotals;
+use timings::Timing;
+use timings::TimingsMutations;
+use timings::TimingsQueries;
+use timings::Totals;
+use timings::progress_fraction;
+use timings::split_by_local_days;
+
+fn totals_with_this_week(this_week: Duration) -> Totals {
+    Totals {
+        today: Duration::zero(),
+        this_week,
+        last_week: Duration::zero(),
+        eight_weeks: this_week,
+    }
+}
+
+#[test]
+fn test_split_by_local_days() {
+    let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+    let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+    // Local midnight is at 22:00 UTC
+    let start = Utc.with_ymd_and_hms(2024, 3, 4, 21, 0, 0).unwrap();
+    let end = Utc.with_ymd_and_hms(2024, 3, 5, 23, 30, 0).unwrap();
+
+    assert_eq!(
+        split_by_local_days(&tz, &start, &end),
+        vec![
+            (day(4), Duration::hours(1)),
+            (day(5), Duration::hours(24)),
+            (day(6), Duration::minutes(90)),
+        ]
+    );
+    assert!(split_by_local_days(&tz, &end, &start).is_empty());
+}
+
+#[test]
+fn test_weekly_overtime_under_target() {
+    let totals = totals_with_this_week(Duration::hours(30));
+    assert_eq!(
+        totals.weekly_overtime(Duration::hours(40)),
+        Duration::zero()
+    );
+}
+
+#[test]
+fn test_weekly_overtime_at_target() {
+    let totals = totals_with_this_week(Duration::hours(40));
+    assert_eq!(
+        totals.weekly_overtime(Duration::hours(40)),
+        Duration::zero()
+    );
+}
+
+#[test]
+fn test_weekly_overtime_over_target() {
+    let totals = totals_with_this_week(Duration::hours(42) + Duration::minutes(30));
+    assert_eq!(
+        totals.weekly_overtime(Duration::hours(40)),
+        Duration::hours(2) + Duration::minutes(30)
+    );
+}
+
+#[test]
+fn test_progress_fraction_under_budget() {
+    let totals = totals_with_this_week(Duration::hours(10));
+    assert_eq!(progress_fraction(&totals, Duration::hours(40)), 0.25);
+    assert!(!totals.is_over_budget(Duration::hours(40)));
+}
+
+#[test]
+fn test_progress_fraction_over_budget() {
+    let totals = totals_with_this_week(Duration::hours(45));
+    assert_eq!(progress_fraction(&totals, Duration::hours(40)), 1.0);
+    assert!(totals.is_over_budget(Duration::hours(40)));
+    assert_eq!(progress_fraction(&totals, Duration::zero()), 1.0);
+}
+
+#[test]
+fn test_rolling_totals_include_today_and_exclude_older_days() {
+    let today = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+    let now = Local
+        .from_local_datetime(&today.and_hms_opt(12, 0, 0).unwrap())
+        .unwrap()
+        .with_timezone(&Utc);
+    let mut daily_totals = DailyTotals::new();
+    for days_ago in 0..8 {
+        daily_totals.insert(today - Duration::days(days_ago), Duration::hours(1));
+    }
+    // Tomorrow is not in the window
+    daily_totals.insert(today + Duration::days(1), Duration::hours(5));
+
+    assert_eq!(daily_totals.get_rolling_totals(7, now), Duration::hours(7));
+    assert_eq!(daily_totals.get_rolling_totals(8, now), Duration::hours(8));
+    assert_eq!(daily_totals.get_rolling_totals(1, now), Duration::hours(1));
+    assert_eq!(daily_totals.get_rolling_totals(0, now), Duration::zero());
+}
+
+#[test]
+fn test_remaining_for_target_rolls_over_to_next_day() {
+    let monday = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    let tuesday = monday.succ_opt().unwrap();
+    let target = |rollover| DailyTarget {
+        daily: Duration::hours(2),
+        rollover,
+    };
+    let totals = |today, this_week| Totals {
+        today,
+        this_week,
+        last_week: Duration::zero(),
+        eight_weeks: this_week,
+    };
+
+    // Monday 1 hour short of the target
+    let monday_totals = totals(Duration::hours(1), Duration::hours(1));
+    assert_eq!(
+        monday_totals.remaining_for_target(&target(true), monday),
+        Duration::hours(1)
+    );
+
+    // Unmet hour is added to Tuesday's target with rollover
+    let tuesday_totals = totals(Duration::minutes(30), Duration::minutes(90));
+    assert_eq!(
+        tuesday_totals.remaining_for_target(&target(true), tuesday),
+        Duration::minutes(150)
+    );
+    assert_eq!(
+        tuesday_totals.remaining_for_target(&target(false), tuesday),
+        Duration::minutes(90)
+    );
+
+    // Exceeded hours reduce the next day's target
+    let tuesday_totals = totals(Duration::zero(), Duration::hours(5));
+    assert_eq!(
+        tuesday_totals.remaining_for_target(&target(true), tuesday),
+        Duration::hours(-1)
+    );
+
+    // Weekends have no target, the rest of the week is left
+    let saturday = NaiveDate::from_ymd_opt(2024, 3, 9).unwrap();
+    let saturday_totals = totals(Duration::zero(), Duration::hours(9));
+    assert_eq!(
+        saturday_totals.remaining_for_target(&target(true), saturday),
+        Duration::hours(1)
+    );
+    assert_eq!(
+        saturday_totals.remaining_for_target(&target(false), saturday),
+        Duration::zero()
+    );
+}
+
+#[tokio::test]
+async fn test_timing_over_midnight_is_split_in_database_and_cache()
+-> Result<(), Box<dyn std::error::Error>> {
+    let pool = setup_test_db().await?;
+    let mut conn = pool.acquire().await?;
+    let day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    let next_day = day.succ_opt().unwrap();
+    // 23:00 - 02:00 local time
+    let start = Local
+        .from_local_datetime(&day.and_hms_opt(23, 0, 0).unwrap())
+        .single()
+        .unwrap()
+        .with_timezone(&Utc);
+    let end = start + Duration::hours(3);
+    conn.insert_timings(&[Timing {
+        client: "Acme".to_string(),
+        project: "Website".to_string(),
+        start,
+        end,
+    }])
+    .await?;
+
+    let mut totals = conn
+        .get_timings_daily_totals(Local, day, next_day, None, None)
+        .await?;
+    totals.sort_by_key(|total| total.day);
+    let hours = totals
+        .iter()
+        .map(|total| (total.day, total.hours))
+        .collect::<Vec<_>>();
+    assert_eq!(hours, vec![(day, 1.0), (next_day, 2.0)]);
+
+    // Range of the second day only still includes its part
+    let totals = conn
+        .get_timings_daily_totals(Local, next_day, next_day, None, None)
+        .await?;
+    assert_eq!(totals.len(), 1);
+    assert_eq!(totals[0].hours, 2.0);
+
+    // Cache agrees with the database
+    let cached = DailyTotals::from_timings(&[(start, end)]);
+    assert_eq!(cached.get(&day), Some(&Duration::hours(1)));
+    assert_eq!(cached.get(&next_day), Some(&Duration::hours(2)));
+
+    let from_database = DailyTotals::from_database(
+        &mut conn,
+        "Acme",
+        "Website",
+        start - Duration::days(1),
+        end + Duration::days(1),
+    )
+    .await?;
+    assert_eq!(from_database.get(&day), Some(&Duration::hours(1)));
+    assert_eq!(from_database.get(&next_day), Some(&Duration::hours(2)));
+
+    let mut removed = cached;
+    removed.remove_timing(&start, &end);
+    assert_eq!(removed.get(&day), Some(&Duration::zero()));
+    assert_eq!(removed.get(&next_day), Some(&Duration::zero()));
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use futures::stream::StreamExt;
+use virtual_desktops::*;
+
+// Although we use `tokio` here, you can use any async runtime of choice.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut source = KDEActiveApplicationSource::new().await?;
+    let mut stream = source.listen().await?;
+
+    println!("Listening for active application changes...");
+    println!("Switch to a different window to see its class.");
+
+    while let Some(application) = stream.next().await {
+        if application.is_empty() {
+            println!("No active window");
+        } else {
+            println!("Active application: {}", application);
+        }
+    }
+
+    println!("Stream ended.");
+    source.unload().await?;
+    Ok(())
+}
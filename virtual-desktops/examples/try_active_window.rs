@@ -0,0 +1,24 @@
+use futures::stream::StreamExt;
+use virtual_desktops::*;
+
+// Although we use `tokio` here, you can use any async runtime of choice.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut source = KDEActiveWindowSource::new().await?;
+    let mut stream = source.listen().await?;
+
+    println!("Listening for active window changes...");
+    println!("Switch to a different window to see the title.");
+
+    while let Some(title) = stream.next().await {
+        if title.is_empty() {
+            println!("No active window");
+        } else {
+            println!("Active window: {}", title);
+        }
+    }
+
+    println!("Stream ended.");
+    source.unload().await?;
+    Ok(())
+}
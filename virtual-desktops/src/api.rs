@@ -69,3 +69,19 @@ pub trait VirtualDesktopController {
         futures::executor::block_on(self.get_desktops())
     }
 }
+
+/// Source of active window titles, e.g. to suggest the project
+#[allow(async_fn_in_trait)]
+pub trait ActiveWindowSource {
+    /// Streams the title of the active window whenever it changes, empty title
+    /// means no window is active.
+    async fn listen(&mut self) -> Result<impl Stream<Item = String>, Error>;
+}
+
+/// Source of the active application, e.g. to break down time per application
+#[allow(async_fn_in_trait)]
+pub trait ActiveApplicationSource {
+    /// Streams the application (window class) of the active window whenever it
+    /// changes, empty name means no window is active.
+    async fn listen(&mut self) -> Result<impl Stream<Item = String>, Error>;
+}
@@ -0,0 +1,83 @@
+use super::kwin_scripting::load_script;
+use super::kwin_scripting::unload_script;
+use crate::api::*;
+use futures::Stream;
+use futures::channel::mpsc::UnboundedSender;
+use futures::channel::mpsc::unbounded;
+use zbus::Connection;
+use zbus::interface;
+
+const RECEIVER_PATH: &str = "/io/github/ciantic/VirtualDesktops/ActiveApplication";
+const RECEIVER_INTERFACE: &str = "io.github.ciantic.VirtualDesktops.ActiveApplication";
+
+/// Streams the window class of the active window on KDE
+///
+/// Like `KDEActiveWindowSource`, a KWin script is loaded which calls back to
+/// this process. Only activations are reported, title changes within the same
+/// application are not.
+#[derive(Debug, Clone)]
+pub struct KDEActiveApplicationSource {
+    connection: Connection,
+    plugin_name: String,
+}
+
+impl KDEActiveApplicationSource {
+    pub async fn new() -> Result<Self, Error> {
+        let connection = Connection::session().await?;
+        let plugin_name = format!("virtual-desktops-active-application-{}", std::process::id());
+        Ok(Self {
+            connection,
+            plugin_name,
+        })
+    }
+
+    /// Unloads the KWin script, ending the stream of applications.
+    pub async fn unload(&self) -> Result<(), Error> {
+        unload_script(&self.connection, &self.plugin_name).await
+    }
+
+    fn script(&self) -> Result<String, Error> {
+        let unique_name = self
+            .connection
+            .unique_name()
+            .ok_or_else(|| Error::SysError("D-Bus connection has no unique name".to_string()))?;
+
+        // KWin 6 uses windows, KWin 5 uses clients
+        Ok(format!(
+            r#"
+function activated(window) {{
+    callDBus("{service}", "{path}", "{interface}", "Changed", window ? window.resourceClass : "");
+}}
+(workspace.windowActivated || workspace.clientActivated).connect(activated);
+activated(workspace.activeWindow || workspace.activeClient);
+"#,
+            service = unique_name,
+            path = RECEIVER_PATH,
+            interface = RECEIVER_INTERFACE,
+        ))
+    }
+}
+
+struct ActiveApplicationReceiver {
+    sender: UnboundedSender<String>,
+}
+
+#[interface(name = "io.github.ciantic.VirtualDesktops.ActiveApplication")]
+impl ActiveApplicationReceiver {
+    /// Called by the KWin script, application is empty if no window is active
+    fn changed(&self, application: String) {
+        let _ = self.sender.unbounded_send(application);
+    }
+}
+
+impl ActiveApplicationSource for KDEActiveApplicationSource {
+    async fn listen(&mut self) -> Result<impl Stream<Item = String>, Error> {
+        let (sender, receiver) = unbounded();
+        self.connection
+            .object_server()
+            .at(RECEIVER_PATH, ActiveApplicationReceiver { sender })
+            .await?;
+        load_script(&self.connection, &self.plugin_name, &self.script()?).await?;
+        Ok(receiver)
+    }
+}
@@ -0,0 +1,90 @@
+use super::kwin_scripting::load_script;
+use super::kwin_scripting::unload_script;
+use crate::api::*;
+use futures::Stream;
+use futures::channel::mpsc::UnboundedSender;
+use futures::channel::mpsc::unbounded;
+use zbus::Connection;
+use zbus::interface;
+
+const RECEIVER_PATH: &str = "/io/github/ciantic/VirtualDesktops/ActiveWindow";
+const RECEIVER_INTERFACE: &str = "io.github.ciantic.VirtualDesktops.ActiveWindow";
+
+/// Streams active window titles on KDE
+///
+/// KWin does not expose the active window over D-Bus, so a KWin script is
+/// loaded which calls back to this process whenever the active window or its
+/// title changes.
+#[derive(Debug, Clone)]
+pub struct KDEActiveWindowSource {
+    connection: Connection,
+    plugin_name: String,
+}
+
+impl KDEActiveWindowSource {
+    pub async fn new() -> Result<Self, Error> {
+        let connection = Connection::session().await?;
+        let plugin_name = format!("virtual-desktops-active-window-{}", std::process::id());
+        Ok(Self {
+            connection,
+            plugin_name,
+        })
+    }
+
+    /// Unloads the KWin script, ending the stream of titles.
+    pub async fn unload(&self) -> Result<(), Error> {
+        unload_script(&self.connection, &self.plugin_name).await
+    }
+
+    fn script(&self) -> Result<String, Error> {
+        let unique_name = self
+            .connection
+            .unique_name()
+            .ok_or_else(|| Error::SysError("D-Bus connection has no unique name".to_string()))?;
+
+        // KWin 6 uses windows, KWin 5 uses clients
+        Ok(format!(
+            r#"
+let current = null;
+function report() {{
+    callDBus("{service}", "{path}", "{interface}", "Changed", current ? current.caption : "");
+}}
+function activated(window) {{
+    if (current) current.captionChanged.disconnect(report);
+    current = window;
+    if (current) current.captionChanged.connect(report);
+    report();
+}}
+(workspace.windowActivated || workspace.clientActivated).connect(activated);
+activated(workspace.activeWindow || workspace.activeClient);
+"#,
+            service = unique_name,
+            path = RECEIVER_PATH,
+            interface = RECEIVER_INTERFACE,
+        ))
+    }
+}
+
+struct ActiveWindowReceiver {
+    sender: UnboundedSender<String>,
+}
+
+#[interface(name = "io.github.ciantic.VirtualDesktops.ActiveWindow")]
+impl ActiveWindowReceiver {
+    /// Called by the KWin script, title is empty if no window is active
+    fn changed(&self, title: String) {
+        let _ = self.sender.unbounded_send(title);
+    }
+}
+
+impl ActiveWindowSource for KDEActiveWindowSource {
+    async fn listen(&mut self) -> Result<impl Stream<Item = String>, Error> {
+        let (sender, receiver) = unbounded();
+        self.connection
+            .object_server()
+            .at(RECEIVER_PATH, ActiveWindowReceiver { sender })
+            .await?;
+        load_script(&self.connection, &self.plugin_name, &self.script()?).await?;
+        Ok(receiver)
+    }
+}
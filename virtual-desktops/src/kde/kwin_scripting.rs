@@ -0,0 +1,79 @@
+//! # D-Bus interface proxies for: `org.kde.kwin.Scripting` and
+//! `org.kde.kwin.Script`
+//!
+//! Used to load a KWin script which reports window events back over D-Bus.
+
+use zbus::proxy;
+
+#[proxy(
+    interface = "org.kde.kwin.Scripting",
+    default_service = "org.kde.KWin",
+    default_path = "/Scripting"
+)]
+pub trait Scripting {
+    /// loadScript method, returns the script ID
+    #[zbus(name = "loadScript")]
+    fn load_script(&self, file_path: &str, plugin_name: &str) -> zbus::Result<i32>;
+
+    /// unloadScript method
+    #[zbus(name = "unloadScript")]
+    fn unload_script(&self, plugin_name: &str) -> zbus::Result<bool>;
+
+    /// isScriptLoaded method
+    #[zbus(name = "isScriptLoaded")]
+    fn is_script_loaded(&self, plugin_name: &str) -> zbus::Result<bool>;
+}
+
+#[proxy(interface = "org.kde.kwin.Script", default_service = "org.kde.KWin")]
+pub trait Script {
+    /// run method
+    fn run(&self) -> zbus::Result<()>;
+
+    /// stop method
+    fn stop(&self) -> zbus::Result<()>;
+}
+
+/// Loads and runs a KWin script, replacing a script already loaded with the
+/// same plugin name.
+///
+/// The script is written to a file in the temporary directory, as KWin loads
+/// scripts from files only.
+pub(crate) async fn load_script(
+    connection: &zbus::Connection,
+    plugin_name: &str,
+    source: &str,
+) -> Result<(), crate::Error> {
+    let script_path = script_path(plugin_name);
+    std::fs::write(&script_path, source)
+        .map_err(|e| crate::Error::SysError(format!("Failed to write KWin script: {}", e)))?;
+
+    let scripting = ScriptingProxy::new(connection).await?;
+    if scripting.is_script_loaded(plugin_name).await? {
+        scripting.unload_script(plugin_name).await?;
+    }
+    let script_id = scripting
+        .load_script(&script_path.to_string_lossy(), plugin_name)
+        .await?;
+
+    let script = ScriptProxy::builder(connection)
+        .path(format!("/Scripting/Script{}", script_id))?
+        .build()
+        .await?;
+    script.run().await?;
+    Ok(())
+}
+
+/// Unloads a script loaded with `load_script` and removes its file
+pub(crate) async fn unload_script(
+    connection: &zbus::Connection,
+    plugin_name: &str,
+) -> Result<(), crate::Error> {
+    let scripting = ScriptingProxy::new(connection).await?;
+    scripting.unload_script(plugin_name).await?;
+    let _ = std::fs::remove_file(script_path(plugin_name));
+    Ok(())
+}
+
+fn script_path(plugin_name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("{}.js", plugin_name))
+}
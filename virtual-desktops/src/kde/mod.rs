@@ -1,5 +1,10 @@
+mod active_application;
+mod active_window;
+pub mod kwin_scripting;
 pub mod virtual_desktop_manager;
 use crate::api::*;
+pub use active_application::*;
+pub use active_window::*;
 use futures::Stream;
 use futures::StreamExt;
 use std::pin::Pin;
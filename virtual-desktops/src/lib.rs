@@ -1,4 +1,6 @@
 mod api;
 mod kde;
+mod manual;
 pub use api::*;
 pub use kde::*;
+pub use manual::*;
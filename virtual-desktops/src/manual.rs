@@ -0,0 +1,74 @@
+use crate::api::*;
+use futures::Stream;
+use futures::channel::mpsc::UnboundedSender;
+use futures::channel::mpsc::unbounded;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+const MANUAL_DESKTOP_ID: &str = "manual";
+
+/// Single in-memory desktop whose name is set by the application
+///
+/// Used where virtual desktops are not available or not used for projects,
+/// the name is set with `update_desktop_name` (e.g. from a GUI) and changes
+/// are streamed to listeners like with real virtual desktops.
+#[derive(Debug, Clone, Default)]
+pub struct ManualDesktopController {
+    name: Arc<Mutex<String>>,
+    listeners: Arc<Mutex<Vec<UnboundedSender<VirtualDesktopMessage>>>>,
+}
+
+impl ManualDesktopController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn desktop_id() -> DesktopId {
+        DesktopId(MANUAL_DESKTOP_ID.to_string())
+    }
+}
+
+impl VirtualDesktopController for ManualDesktopController {
+    async fn listen(&mut self) -> Result<impl Stream<Item = VirtualDesktopMessage>, Error> {
+        let (sender, receiver) = unbounded();
+        self.listeners.lock().unwrap().push(sender);
+        Ok(receiver)
+    }
+
+    async fn update_desktop_name(
+        &mut self,
+        desktop_id: DesktopId,
+        desktop_name: &str,
+    ) -> Result<(), Error> {
+        if desktop_id != Self::desktop_id() {
+            return Err(Error::DesktopNotFound(desktop_id));
+        }
+        *self.name.lock().unwrap() = desktop_name.to_string();
+
+        let message =
+            VirtualDesktopMessage::DesktopNameChanged(desktop_id, desktop_name.to_string());
+        self.listeners
+            .lock()
+            .unwrap()
+            .retain(|listener| listener.unbounded_send(message.clone()).is_ok());
+        Ok(())
+    }
+
+    async fn get_desktop_name(&self, desktop_id: &DesktopId) -> Result<String, Error> {
+        if *desktop_id != Self::desktop_id() {
+            return Err(Error::DesktopNotFound(desktop_id.clone()));
+        }
+        Ok(self.name.lock().unwrap().clone())
+    }
+
+    async fn get_current_desktop(&self) -> Result<DesktopId, Error> {
+        Ok(Self::desktop_id())
+    }
+
+    async fn get_desktops(&self) -> Result<Vec<(DesktopId, String)>, Error> {
+        Ok(vec![(
+            Self::desktop_id(),
+            self.name.lock().unwrap().clone(),
+        )])
+    }
+}